@@ -0,0 +1,349 @@
+use std::fmt::{self, Display, Formatter};
+
+use serde::Serialize;
+
+use crate::config::{Method, Metric, SolverOptions};
+use crate::data::{Coord, MapCell, Pos};
+use crate::level::Level;
+use crate::map::MapType;
+use crate::solver::{self, SolverErr};
+use crate::vec2d::Vec2d;
+use crate::Solve;
+
+/// How many states the probe search in `estimate_difficulty` visits at most
+pub const PROBE_STATES: i32 = 20_000;
+
+/// Static properties of a level found by the solver's preprocessing
+#[derive(Debug, Clone)]
+pub struct Analysis {
+    pub rows: usize,
+    pub cols: usize,
+    pub box_cnt: usize,
+    /// `None` for remover maps
+    pub goal_cnt: Option<usize>,
+    /// Squares from which a box can't be pushed to any goal (or the remover)
+    pub dead_square_cnt: usize,
+    /// The level in XSB with dead squares marked by `x`
+    map: String,
+}
+
+/// A rough estimate of how hard a level is, only meaningful for comparing levels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Difficulty {
+    /// Higher is harder, 0 for solved levels
+    pub score: f64,
+    pub box_cnt: usize,
+    /// Size of the area reachable by the player
+    pub playable_squares: usize,
+    /// Lower bound on pushes
+    pub lower_bound: u32,
+    /// The most goals in one room - filling a goal room is harder than goals spread around the level
+    pub max_room_goals: usize,
+    /// Whether the probe search found a solution within `PROBE_STATES`
+    pub probe_solved: bool,
+    /// States visited by the probe search
+    pub probe_visited: i32,
+}
+
+/// Counts describing a level's map and initial state, all of them cheap to compute
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct LevelReport {
+    pub rows: usize,
+    pub cols: usize,
+    /// Squares reachable by the player when ignoring boxes
+    pub playable_squares: usize,
+    pub walls: usize,
+    pub boxes: usize,
+    /// `None` for remover maps
+    pub goals: Option<usize>,
+    /// Squares from which a box can't be pushed to any goal (or the remover)
+    pub dead_squares: usize,
+    /// Squares with walls on both sides (corridors in `MapAnalysis`)
+    pub tunnels: usize,
+    pub rooms: usize,
+    /// Squares which split the playable area when blocked
+    pub articulation_squares: usize,
+    pub lower_bound_pushes: u32,
+    pub lower_bound_moves: u32,
+    /// Boxes which start on a goal
+    pub boxes_on_goals: usize,
+}
+
+impl Level {
+    /// Combines `analyze`, `map_analysis` and `lower_bound` into one set of numbers
+    pub fn report(&self) -> Result<LevelReport, SolverErr> {
+        let analysis = self.analyze()?;
+        let map_analysis = self.map_analysis()?;
+        let grid = self.map().grid();
+        let walls = grid
+            .positions()
+            .filter(|&pos| grid[pos] == MapCell::Wall)
+            .count();
+        let goals = self.goal_positions();
+        let boxes_on_goals = self
+            .box_positions()
+            .iter()
+            .filter(|pos| goals.contains(pos))
+            .count();
+        // analysis succeeded so the level is valid and bounds exist
+        let lower_bound = |metric| self.lower_bound(metric).ok_or(SolverErr::IncompleteBorder);
+
+        Ok(LevelReport {
+            rows: analysis.rows,
+            cols: analysis.cols,
+            playable_squares: map_analysis.rooms.iter().map(Vec::len).sum::<usize>()
+                + map_analysis.corridors.len(),
+            walls,
+            boxes: analysis.box_cnt,
+            goals: analysis.goal_cnt,
+            dead_squares: analysis.dead_square_cnt,
+            tunnels: map_analysis.corridors.len(),
+            rooms: map_analysis.rooms.len(),
+            articulation_squares: map_analysis.articulation_squares.len(),
+            lower_bound_pushes: lower_bound(Metric::Pushes)?,
+            lower_bound_moves: lower_bound(Metric::Moves)?,
+            boxes_on_goals,
+        })
+    }
+
+    /// Estimates difficulty without fully solving the level
+    /// by combining static properties and a short push-optimal search.
+    ///
+    /// The probe search dominates the score: each order of magnitude of visited states adds 10
+    /// and not finding a solution adds another 10.
+    /// Boxes, lower bound (divided by 10) and goals in the fullest room (divided by 2) are added to that.
+    pub fn estimate_difficulty(&self) -> Result<Difficulty, SolverErr> {
+        let map_analysis = self.map_analysis()?;
+        let goals = self.goal_positions();
+        let max_room_goals = map_analysis
+            .rooms
+            .iter()
+            .map(|room| room.iter().filter(|pos| goals.contains(pos)).count())
+            .max()
+            .unwrap_or(0);
+        let playable_squares =
+            map_analysis.rooms.iter().map(Vec::len).sum::<usize>() + map_analysis.corridors.len();
+
+        let mut progress = |stats: &solver::Stats| stats.total_unique_visited() < PROBE_STATES;
+        let probe =
+            self.solve_with_progress(Method::Pushes, &SolverOptions::default(), &mut progress)?;
+        let probe_solved = probe.moves.is_some();
+        let probe_visited = probe.stats.total_unique_visited();
+        // the probe returns the real error if the level is invalid, this doesn't fail after it
+        let lower_bound = self
+            .lower_bound(Metric::Pushes)
+            .ok_or(SolverErr::IncompleteBorder)?;
+
+        let score = if lower_bound == 0 {
+            0.0
+        } else {
+            let search = 10.0 * f64::from(probe_visited).log10().max(0.0);
+            let unsolved = if probe_solved { 0.0 } else { 10.0 };
+            // box counts fit into f64 exactly
+            #[allow(clippy::cast_precision_loss)]
+            let statics = self.state.boxes.len() as f64
+                + f64::from(lower_bound) / 10.0
+                + max_room_goals as f64 / 2.0;
+            search + unsolved + statics
+        };
+
+        Ok(Difficulty {
+            score,
+            box_cnt: self.state.boxes.len(),
+            playable_squares,
+            lower_bound,
+            max_room_goals,
+            probe_solved,
+            probe_visited,
+        })
+    }
+
+    pub fn analyze(&self) -> Result<Analysis, SolverErr> {
+        let dead_squares = solver::dead_squares(self)?;
+        let grid = self.map().grid();
+
+        let mut map = String::new();
+        for (r, line) in self.xsb().to_string().lines().enumerate() {
+            for (c, ch) in line.chars().enumerate() {
+                let pos = Pos::new(r as Coord, c as Coord);
+                if ch == ' ' && dead_squares[pos] {
+                    map.push('x');
+                } else {
+                    map.push(ch);
+                }
+            }
+            map.push('\n');
+        }
+
+        Ok(Analysis {
+            rows: grid.rows().into(),
+            cols: grid.cols().into(),
+            box_cnt: self.state.boxes.len(),
+            goal_cnt: match self.map {
+                MapType::Goals(ref goal_map) => Some(goal_map.goals.len()),
+                MapType::Hybrid(ref hybrid_map) => Some(hybrid_map.goals.len()),
+                MapType::Remover(_) => None,
+            },
+            dead_square_cnt: count(&dead_squares),
+            map,
+        })
+    }
+}
+
+fn count(dead_squares: &Vec2d<bool>) -> usize {
+    dead_squares
+        .positions()
+        .filter(|&pos| dead_squares[pos])
+        .count()
+}
+
+impl Display for Analysis {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Size: {}x{}", self.cols, self.rows)?;
+        writeln!(f, "Boxes: {}", self.box_cnt)?;
+        match self.goal_cnt {
+            Some(goal_cnt) => writeln!(f, "Goals: {goal_cnt}")?,
+            None => writeln!(f, "Remover")?,
+        }
+        writeln!(f, "Dead squares: {}", self.dead_square_cnt)?;
+        write!(f, "{}", self.map)
+    }
+}
+
+impl Display for LevelReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Size: {}x{}", self.cols, self.rows)?;
+        writeln!(f, "Playable squares: {}", self.playable_squares)?;
+        writeln!(f, "Walls: {}", self.walls)?;
+        writeln!(f, "Boxes: {}", self.boxes)?;
+        match self.goals {
+            Some(goals) => writeln!(f, "Goals: {goals}")?,
+            None => writeln!(f, "Remover")?,
+        }
+        writeln!(f, "Boxes on goals: {}", self.boxes_on_goals)?;
+        writeln!(f, "Dead squares: {}", self.dead_squares)?;
+        writeln!(f, "Tunnels: {}", self.tunnels)?;
+        writeln!(f, "Rooms: {}", self.rooms)?;
+        writeln!(f, "Articulation squares: {}", self.articulation_squares)?;
+        writeln!(
+            f,
+            "Lower bound: {} pushes, {} moves",
+            self.lower_bound_pushes, self.lower_bound_moves
+        )
+    }
+}
+
+impl Display for Difficulty {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Difficulty: {:.1}", self.score)?;
+        writeln!(f, "Playable squares: {}", self.playable_squares)?;
+        writeln!(f, "Most goals in a room: {}", self.max_room_goals)?;
+        let result = if self.probe_solved {
+            "solved"
+        } else {
+            "not solved"
+        };
+        writeln!(
+            f,
+            "Probe search: {result} after visiting {} states",
+            self.probe_visited
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dead_squares() {
+        let level: Level = r"
+######
+#    #
+#@$ .#
+######"
+            .parse()
+            .unwrap();
+        let analysis = level.analyze().unwrap();
+        assert_eq!(analysis.box_cnt, 1);
+        assert_eq!(analysis.goal_cnt, Some(1));
+        assert_eq!(analysis.dead_square_cnt, 5);
+        assert_eq!(
+            analysis.to_string(),
+            r"Size: 6x4
+Boxes: 1
+Goals: 1
+Dead squares: 5
+######
+#xxxx#
+#@$ .#
+######
+"
+        );
+    }
+
+    #[test]
+    fn report() {
+        let level: Level = r"
+#######
+#@  * #
+## ## #
+#  $ .#
+#######"
+            .parse()
+            .unwrap();
+        let report = level.report().unwrap();
+        assert_eq!(
+            report.to_string(),
+            r"Size: 7x5
+Playable squares: 12
+Walls: 23
+Boxes: 2
+Goals: 2
+Boxes on goals: 1
+Dead squares: 5
+Tunnels: 8
+Rooms: 4
+Articulation squares: 2
+Lower bound: 2 pushes, 4 moves
+"
+        );
+        assert_eq!(
+            report.playable_squares + report.walls,
+            report.rows * report.cols
+        );
+    }
+
+    #[test]
+    fn difficulty() {
+        let solved: Level = "#####\n#@*##\n#####".parse().unwrap();
+        let solved = solved.estimate_difficulty().unwrap();
+        assert!(solved.score.abs() < f64::EPSILON);
+        assert!(solved.probe_solved);
+
+        let easy: Level = "######\n#@$ .#\n######".parse().unwrap();
+        let easy = easy.estimate_difficulty().unwrap();
+        assert!(easy.probe_solved);
+        assert_eq!(easy.lower_bound, 2);
+        assert_eq!(easy.max_room_goals, 0);
+
+        let harder: Level = r"
+########
+#@ $  .#
+#  ##  #
+# $  $.#
+#.     #
+########"
+            .parse()
+            .unwrap();
+        let harder = harder.estimate_difficulty().unwrap();
+        assert!(harder.probe_solved);
+        assert_eq!(harder.box_cnt, 3);
+        assert!(harder.score > easy.score);
+
+        let unsolvable: Level = "########\n#@$$ ..#\n########".parse().unwrap();
+        let unsolvable = unsolvable.estimate_difficulty().unwrap();
+        assert!(!unsolvable.probe_solved);
+        assert!(unsolvable.score > 10.0);
+    }
+}
@@ -0,0 +1,141 @@
+//! Anytime search - a fast suboptimal solution first, then better ones for as long as allowed.
+
+use crate::config::{AnyProfile, Method, SolverOptions};
+use crate::level::Level;
+use crate::moves::Moves;
+use crate::optimizer;
+use crate::solver::{SolverErr, SolverOk, Stats};
+use crate::Solve;
+
+/// Suboptimality percentages of the searches run by `Level::solve_anytime`.
+/// The first one is close to greedy, the last one is optimal.
+pub const SCHEDULE: [u16; 6] = [500, 200, 100, 50, 20, 0];
+
+impl Level {
+    /// Runs searches with decreasing suboptimality (see `SCHEDULE`)
+    /// and calls `improved` with every solution shorter than the previous ones
+    /// along with how many percent longer than optimal it can be at most.
+    ///
+    /// Stops when a solution is known to be optimal, the level is found unsolvable
+    /// or `progress` (shared by all the searches) returns `false`.
+    /// The result has the best solution found, the tightest known bound on its suboptimality
+    /// and stats of the last search. `options.suboptimality_percent` is ignored.
+    pub fn solve_anytime(
+        &self,
+        method: Method,
+        options: &SolverOptions,
+        improved: &mut dyn FnMut(&Moves, u16),
+        progress: &mut dyn FnMut(&Stats) -> bool,
+    ) -> Result<SolverOk, SolverErr> {
+        let metric = method.metric();
+        let lower_bound = self.lower_bound(metric);
+        let with_percent = |suboptimality_percent| SolverOptions {
+            suboptimality_percent,
+            // the schedule decides the suboptimality, not the profile
+            any_profile: AnyProfile::Thorough,
+            ..*options
+        };
+
+        let mut best = self.solve_with_progress(method, &with_percent(SCHEDULE[0]), progress)?;
+        if let Some(ref moves) = best.moves {
+            improved(moves, SCHEDULE[0]);
+        }
+        for &percent in &SCHEDULE[1..] {
+            // interrupted or unsolvable - every search is complete so the first one finds out
+            let Some(ref best_moves) = best.moves else {
+                break;
+            };
+            let best_cost = optimizer::cost(best_moves, metric);
+            if lower_bound.is_some_and(|lower_bound| best_cost.0 == lower_bound as usize) {
+                best.suboptimality_percent = 0;
+            }
+            if best.suboptimality_percent == 0 {
+                break;
+            }
+
+            let result = self.solve_with_progress(method, &with_percent(percent), progress)?;
+            match result.moves {
+                None => {
+                    best.interrupted = result.interrupted;
+                    best.stats = result.stats;
+                    break;
+                }
+                Some(ref moves) if optimizer::cost(moves, metric) < best_cost => {
+                    improved(moves, percent);
+                    best = result;
+                }
+                Some(_) => {
+                    // the best solution is at most as long as this one so the new bound holds for it
+                    best.suboptimality_percent = percent;
+                    best.stats = result.stats;
+                }
+            }
+        }
+        Ok(best)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn anytime() {
+        let level: Level = r"
+########
+#@ $  .#
+#  ##  #
+# $  $.#
+#.     #
+########
+"
+        .parse()
+        .unwrap();
+
+        for &method in &[Method::Moves, Method::Pushes] {
+            let optimal = level.solve(method, false).unwrap().moves.unwrap();
+
+            let mut solutions = Vec::new();
+            let result = level
+                .solve_anytime(
+                    method,
+                    &SolverOptions::default(),
+                    &mut |moves, percent| solutions.push((moves.clone(), percent)),
+                    &mut |_| true,
+                )
+                .unwrap();
+            assert!(!result.interrupted);
+            assert_eq!(result.suboptimality_percent, 0);
+            let best = result.moves.unwrap();
+            assert_eq!(
+                optimizer::cost(&best, method.metric()).0,
+                optimizer::cost(&optimal, method.metric()).0
+            );
+
+            assert!(!solutions.is_empty());
+            assert_eq!(solutions.last().unwrap().0, best);
+            for window in solutions.windows(2) {
+                let (ref prev, prev_percent) = window[0];
+                let (ref next, next_percent) = window[1];
+                assert!(
+                    optimizer::cost(next, method.metric()) < optimizer::cost(prev, method.metric())
+                );
+                assert!(next_percent < prev_percent);
+            }
+            for (moves, _) in &solutions {
+                assert!(level.verify_solution(moves).is_ok());
+            }
+        }
+
+        let result = level
+            .solve_anytime(
+                Method::Pushes,
+                &SolverOptions::default(),
+                &mut |_, _| panic!("Interrupted before finding anything"),
+                &mut |_| false,
+            )
+            .unwrap();
+        assert!(result.interrupted);
+        assert!(result.moves.is_none());
+    }
+}
@@ -0,0 +1,225 @@
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+use crate::config::{ParseOptions, Variant};
+use crate::data::{Coord, MapCell, Pos, MAX_SIZE};
+use crate::level::Level;
+use crate::parser::{self, ParserErr};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildErr {
+    /// Position (row, column) is outside the size given to the builder
+    OutOfBounds(usize, usize),
+    /// A box or player at this position is on a wall or another box or player
+    Occupied(usize, usize),
+    /// The same checks as when parsing failed
+    Invalid(ParserErr),
+}
+
+impl Display for BuildErr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildErr::OutOfBounds(r, c) => write!(f, "Position out of bounds: [{r}, {c}]"),
+            BuildErr::Occupied(r, c) => write!(f, "Position already occupied: [{r}, {c}]"),
+            BuildErr::Invalid(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl Error for BuildErr {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            BuildErr::Invalid(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Creates a level cell by cell without going through the text formats.
+///
+/// All cells start empty, later calls overwrite the cell set by earlier calls.
+/// Errors are reported by `build`.
+#[derive(Debug, Clone)]
+pub struct LevelBuilder {
+    grid: Vec<Vec<MapCell>>,
+    boxes: Vec<Pos>,
+    players: Vec<Pos>,
+    options: ParseOptions,
+    err: Option<BuildErr>,
+}
+
+impl LevelBuilder {
+    pub fn new(rows: usize, cols: usize) -> Self {
+        let mut builder = LevelBuilder {
+            grid: vec![vec![MapCell::Empty; cols]; rows],
+            boxes: Vec::new(),
+            players: Vec::new(),
+            options: ParseOptions::default(),
+            err: None,
+        };
+        if rows > MAX_SIZE || cols > MAX_SIZE {
+            builder.grid.clear();
+            builder.err = Some(BuildErr::Invalid(ParserErr::TooLarge));
+        }
+        builder
+    }
+
+    pub fn wall(&mut self, r: usize, c: usize) -> &mut Self {
+        self.set(r, c, MapCell::Wall)
+    }
+
+    pub fn goal(&mut self, r: usize, c: usize) -> &mut Self {
+        self.set(r, c, MapCell::Goal)
+    }
+
+    pub fn remover(&mut self, r: usize, c: usize) -> &mut Self {
+        self.set(r, c, MapCell::Remover)
+    }
+
+    /// Box (`box` is a keyword)
+    pub fn box_(&mut self, r: usize, c: usize) -> &mut Self {
+        if let Some(pos) = self.pos(r, c) {
+            self.boxes.push(pos);
+        }
+        self
+    }
+
+    pub fn player(&mut self, r: usize, c: usize) -> &mut Self {
+        if let Some(pos) = self.pos(r, c) {
+            self.players.push(pos);
+        }
+        self
+    }
+
+    /// The rules of the level, only `Variant::Multiban` allows multiple players
+    pub fn variant(&mut self, variant: Variant) -> &mut Self {
+        self.options.variant = variant;
+        self
+    }
+
+    /// Allow more goals than boxes, see `ParseOptions::extra_goals`
+    pub fn extra_goals(&mut self, extra_goals: bool) -> &mut Self {
+        self.options.extra_goals = extra_goals;
+        self
+    }
+
+    pub fn build(&self) -> Result<Level, BuildErr> {
+        if let Some(ref err) = self.err {
+            return Err(err.clone());
+        }
+
+        let mut goals = Vec::new();
+        let mut removers = Vec::new();
+        for (r, row) in self.grid.iter().enumerate() {
+            for (c, &cell) in row.iter().enumerate() {
+                let pos = Pos::new(r as Coord, c as Coord);
+                match cell {
+                    MapCell::Goal => goals.push(pos),
+                    MapCell::Remover => removers.push(pos),
+                    MapCell::Empty | MapCell::Wall => {}
+                }
+            }
+        }
+
+        let mut occupied = Vec::new();
+        for &pos in self.boxes.iter().chain(&self.players) {
+            let cell = self.grid[usize::from(pos.r)][usize::from(pos.c)];
+            if cell == MapCell::Wall || occupied.contains(&pos) {
+                return Err(BuildErr::Occupied(pos.r.into(), pos.c.into()));
+            }
+            occupied.push(pos);
+        }
+        parser::level_from_parts(
+            &self.grid,
+            goals,
+            &removers,
+            self.boxes.clone(),
+            &self.players,
+            self.options,
+        )
+        .map_err(BuildErr::Invalid)
+    }
+
+    fn set(&mut self, r: usize, c: usize, cell: MapCell) -> &mut Self {
+        if let Some(pos) = self.pos(r, c) {
+            self.grid[usize::from(pos.r)][usize::from(pos.c)] = cell;
+        }
+        self
+    }
+
+    /// Records the first error so calls can be chained
+    fn pos(&mut self, r: usize, c: usize) -> Option<Pos> {
+        if r < self.grid.len() && c < self.grid[r].len() {
+            Some(Pos::new(r as Coord, c as Coord))
+        } else {
+            if self.err.is_none() {
+                self.err = Some(BuildErr::OutOfBounds(r, c));
+            }
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Surrounds the level with walls
+    fn walled(rows: usize, cols: usize) -> LevelBuilder {
+        let mut builder = LevelBuilder::new(rows, cols);
+        for r in 0..rows {
+            builder.wall(r, 0).wall(r, cols - 1);
+        }
+        for c in 0..cols {
+            builder.wall(0, c).wall(rows - 1, c);
+        }
+        builder
+    }
+
+    #[test]
+    fn building() {
+        let level = walled(3, 6)
+            .player(1, 1)
+            .box_(1, 2)
+            .goal(1, 4)
+            .box_(1, 4)
+            .goal(1, 3)
+            .build()
+            .unwrap();
+        assert_eq!(level.to_string(), "######\n#@$.*#\n######\n");
+
+        let level = walled(3, 5).player(1, 1).remover(1, 3).build().unwrap();
+        assert_eq!(level.to_string(), "#####\n#@ r#\n#####\n");
+    }
+
+    #[test]
+    fn building_fail() {
+        assert_eq!(
+            walled(3, 5).player(1, 1).box_(3, 1).build().unwrap_err(),
+            BuildErr::OutOfBounds(3, 1)
+        );
+        assert_eq!(
+            walled(3, 5).player(1, 1).box_(0, 1).build().unwrap_err(),
+            BuildErr::Occupied(0, 1)
+        );
+        assert_eq!(
+            walled(3, 5).player(1, 1).box_(1, 1).build().unwrap_err(),
+            BuildErr::Occupied(1, 1)
+        );
+        assert_eq!(
+            walled(3, 5).build().unwrap_err(),
+            BuildErr::Invalid(ParserErr::NoPlayer)
+        );
+        assert_eq!(
+            walled(3, 5).player(1, 1).player(1, 2).build().unwrap_err(),
+            BuildErr::Invalid(ParserErr::MultiplePlayers)
+        );
+        assert_eq!(
+            LevelBuilder::new(MAX_SIZE + 1, 5)
+                .player(1, 1)
+                .build()
+                .unwrap_err(),
+            BuildErr::Invalid(ParserErr::TooLarge)
+        );
+    }
+}
@@ -0,0 +1,934 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::path::Path;
+
+use crate::config::{Format, Method, SolverOptions, Variant};
+use crate::level::{Level, LevelMetadata};
+use crate::moves::Moves;
+use crate::parser::{self, ParserErr};
+use crate::portfolio::{self, PortfolioOk, Stage};
+use crate::solver::{IncrementalSolver, SolverErr, SolverOk};
+use crate::verify::SolutionInfo;
+use crate::{LoadLevelCollection, SokobanError};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CollectionErr {
+    /// Index of the level in the collection (starting at 0)
+    pub index: usize,
+    /// Line where the level's board starts (starting at 0)
+    pub line: usize,
+    pub err: ParserErr,
+}
+
+impl Display for CollectionErr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Level {} (line {}): {}",
+            self.index + 1,
+            self.line + 1,
+            self.err
+        )
+    }
+}
+
+impl Error for CollectionErr {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.err)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SlcErr {
+    Xml(roxmltree::Error),
+    /// The document is valid XML but doesn't have the expected structure
+    Structure(String),
+    Level(CollectionErr),
+}
+
+impl Display for SlcErr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            SlcErr::Xml(err) => write!(f, "Invalid XML: {err}"),
+            SlcErr::Structure(msg) => write!(f, "Invalid SLC file: {msg}"),
+            SlcErr::Level(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl Error for SlcErr {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SlcErr::Xml(err) => Some(err),
+            SlcErr::Structure(_) => None,
+            SlcErr::Level(err) => Some(err),
+        }
+    }
+}
+
+/// Levels which belong together, usually loaded from one file
+#[derive(Debug, Clone, Default)]
+pub struct Collection {
+    pub levels: Vec<(LevelMetadata, Level)>,
+}
+
+/// The outcome of solving one level of a `Collection`
+#[derive(Debug)]
+pub struct LevelResult {
+    /// Index of the level in the collection (starting at 0)
+    pub index: usize,
+    pub result: Result<SolverOk, SolverErr>,
+}
+
+/// The outcome of solving one level of a `Collection` with `Collection::solve_portfolio`
+#[derive(Debug)]
+pub struct PortfolioResult {
+    /// Index of the level in the collection (starting at 0)
+    pub index: usize,
+    pub result: Result<PortfolioOk, SolverErr>,
+}
+
+/// A `Solution` property of a level checked by `Collection::verify_solutions`
+#[derive(Debug)]
+pub struct SolutionCheck {
+    /// Index of the level in the collection (starting at 0)
+    pub index: usize,
+    /// The property's key, e.g. `Solution/Pushes (2/1)`
+    pub key: String,
+    /// Moves and pushes written in the key (like the `(2/1)` above) if there are any
+    pub claimed: Option<(usize, usize)>,
+    /// The recomputed counts or why the solution doesn't solve the level
+    pub result: Result<SolutionInfo, SokobanError>,
+}
+
+impl SolutionCheck {
+    /// The solution is invalid or its counts differ from the claimed ones
+    pub fn is_discrepancy(&self) -> bool {
+        match (&self.result, self.claimed) {
+            (Err(_), _) => true,
+            (Ok(info), Some(claimed)) => claimed != (info.move_cnt, info.push_cnt),
+            (Ok(_), None) => false,
+        }
+    }
+}
+
+impl Display for SolutionCheck {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self.result {
+            Err(err) => write!(f, "invalid: {err}"),
+            Ok(info) => {
+                write!(
+                    f,
+                    "{} moves, {} pushes, {} box lines, {} box changes",
+                    info.move_cnt, info.push_cnt, info.box_lines, info.box_changes
+                )?;
+                match self.claimed {
+                    Some((moves, pushes)) if self.is_discrepancy() => {
+                        write!(f, " (claims {moves} moves, {pushes} pushes)")
+                    }
+                    _ => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+impl Collection {
+    pub fn new(levels: Vec<(LevelMetadata, Level)>) -> Self {
+        Collection { levels }
+    }
+
+    /// Solves the levels one by one in order
+    pub fn solve_all(&self, method: Method, options: &SolverOptions) -> Vec<LevelResult> {
+        self.solve_all_with_callback(method, options, &mut |_| true)
+    }
+
+    /// Like `solve_all` but `level_done` is called after each level,
+    /// returning `false` skips the rest of the levels.
+    ///
+    /// Preprocessing is shared between the levels as much as possible
+    /// (see `IncrementalSolver`) so packs with many variations of the same map are faster to solve
+    /// than the individual levels.
+    pub fn solve_all_with_callback(
+        &self,
+        method: Method,
+        options: &SolverOptions,
+        level_done: &mut dyn FnMut(&LevelResult) -> bool,
+    ) -> Vec<LevelResult> {
+        let mut solver = IncrementalSolver::new();
+        let mut results = Vec::new();
+        for (index, (_, level)) in self.levels.iter().enumerate() {
+            let result = LevelResult {
+                index,
+                result: solver.solve(level, method, options),
+            };
+            let go_on = level_done(&result);
+            results.push(result);
+            if !go_on {
+                break;
+            }
+        }
+        results
+    }
+
+    /// Solves each level with the first of `stages` that can (see `Level::solve_portfolio`),
+    /// usually cheap configurations first (e.g. `Stage::escalating`) so the easy levels
+    /// of a pack don't take as long as the hard ones. `level_done` works like in
+    /// `solve_all_with_callback`.
+    ///
+    /// # Panics
+    ///
+    /// If `stages` is empty.
+    pub fn solve_portfolio(
+        &self,
+        stages: &[Stage],
+        level_done: &mut dyn FnMut(&PortfolioResult) -> bool,
+    ) -> Vec<PortfolioResult> {
+        let mut solver = IncrementalSolver::new();
+        let mut results = Vec::new();
+        for (index, (_, level)) in self.levels.iter().enumerate() {
+            let result = PortfolioResult {
+                index,
+                result: portfolio::solve_stages(&mut solver, level, stages, &mut |_| true),
+            };
+            let go_on = level_done(&result);
+            results.push(result);
+            if !go_on {
+                break;
+            }
+        }
+        results
+    }
+}
+
+impl Collection {
+    /// Checks every `Solution` property (with any suffix, e.g. `Solution/Moves (10/2)`)
+    /// of all levels, e.g. of solution databases from other solvers or websites.
+    /// Levels without solutions are skipped.
+    pub fn verify_solutions(&self) -> Vec<SolutionCheck> {
+        let mut checks = Vec::new();
+        for (index, (metadata, level)) in self.levels.iter().enumerate() {
+            for (key, lurd) in &metadata.properties {
+                if !key.starts_with("Solution") {
+                    continue;
+                }
+                let result = Moves::from_lurd(lurd)
+                    .map_err(SokobanError::from)
+                    .and_then(|moves| Ok(level.verify_solution(&moves)?));
+                checks.push(SolutionCheck {
+                    index,
+                    key: key.clone(),
+                    claimed: claimed_counts(key),
+                    result,
+                });
+            }
+        }
+        checks
+    }
+}
+
+/// Parses the `(moves/pushes)` at the end of keys like `Solution/Pushes (10/2)`
+fn claimed_counts(key: &str) -> Option<(usize, usize)> {
+    let (_, counts) = key.trim_end().strip_suffix(')')?.rsplit_once('(')?;
+    let (moves, pushes) = counts.split_once('/')?;
+    Some((moves.trim().parse().ok()?, pushes.trim().parse().ok()?))
+}
+
+impl From<Vec<(LevelMetadata, Level)>> for Collection {
+    fn from(levels: Vec<(LevelMetadata, Level)>) -> Self {
+        Collection::new(levels)
+    }
+}
+
+impl<P> LoadLevelCollection for P
+where
+    P: AsRef<Path>,
+{
+    fn load_level_collection_variant(
+        &self,
+        variant: Variant,
+    ) -> Result<Vec<(LevelMetadata, Level)>, SokobanError> {
+        if is_slc(self) {
+            let text = decode_slc(&fs::read(self)?);
+            Ok(parse_slc_variant(&text, variant)?)
+        } else {
+            let text = fs::read_to_string(self)?;
+            Ok(parse_collection_variant(&text, variant)?)
+        }
+    }
+}
+
+/// What bytes 0x80-0x9F mean in Windows-1252, the rest is the same as Latin-1.
+/// The 5 unused bytes are kept as the control characters like browsers do.
+const WINDOWS_1252_80_9F: [char; 32] = [
+    '\u{20AC}', '\u{81}', '\u{201A}', '\u{192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{2C6}', '\u{2030}', '\u{160}', '\u{2039}', '\u{152}', '\u{8D}', '\u{17D}', '\u{8F}',
+    '\u{90}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{2DC}', '\u{2122}', '\u{161}', '\u{203A}', '\u{153}', '\u{9D}', '\u{17E}', '\u{178}',
+];
+
+/// Decodes an SLC file according to the encoding in its XML declaration.
+///
+/// YASC saves them as ISO-8859-1 (Latin-1), Windows-1252 only differs in bytes 0x80-0x9F.
+/// Other encodings are read as UTF-8 with Latin-1 as a fallback
+/// for files which declare the wrong encoding (or none).
+fn decode_slc(bytes: &[u8]) -> String {
+    let latin1 = |bytes: &[u8]| bytes.iter().copied().map(char::from).collect();
+    let windows_1252 = |bytes: &[u8]| {
+        bytes
+            .iter()
+            .map(|&byte| match byte {
+                0x80..=0x9F => WINDOWS_1252_80_9F[usize::from(byte - 0x80)],
+                _ => char::from(byte),
+            })
+            .collect()
+    };
+
+    // the declaration can only contain ASCII
+    let declaration_end = bytes
+        .windows(2)
+        .position(|window| window == b"?>")
+        .unwrap_or(0);
+    let declaration = String::from_utf8_lossy(&bytes[..declaration_end]).to_ascii_lowercase();
+    let declared_latin1 = ["iso-8859-1", "latin1", "latin-1"]
+        .iter()
+        .any(|encoding| declaration.contains(encoding));
+
+    if declaration.contains("windows-1252") {
+        windows_1252(bytes)
+    } else if declared_latin1 {
+        latin1(bytes)
+    } else {
+        String::from_utf8(bytes.to_vec()).unwrap_or_else(|_| latin1(bytes))
+    }
+}
+
+/// Whether the file should be read as an SLC (XML) collection based on its extension
+pub fn is_slc<P: AsRef<Path>>(path: P) -> bool {
+    path.as_ref()
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("slc"))
+}
+
+/// Parses a file with multiple levels in the XSB format (usually called .sok or .xsb).
+///
+/// Levels are separated by blank lines or title lines. `Key: value` lines after a level
+/// are its metadata. A free-standing line (or a `;` comment) right before a level
+/// is used as its title if it doesn't have a `Title:` line.
+/// Text before the first level which is separated from it by a blank line is ignored.
+pub fn parse_collection(text: &str) -> Result<Vec<(LevelMetadata, Level)>, CollectionErr> {
+    parse_collection_variant(text, Variant::Push)
+}
+
+/// Like `parse_collection` but all levels get `variant`
+pub fn parse_collection_variant(
+    text: &str,
+    variant: Variant,
+) -> Result<Vec<(LevelMetadata, Level)>, CollectionErr> {
+    let text = parser::normalize(text);
+    let lines: Vec<&str> = text.lines().map(str::trim_end).collect();
+
+    // ranges of lines containing boards
+    let mut boards = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        if is_board_line(lines[i]) {
+            let start = i;
+            while i < lines.len() && is_board_line(lines[i]) {
+                i += 1;
+            }
+            boards.push((start, i));
+        } else {
+            i += 1;
+        }
+    }
+
+    let mut levels = Vec::new();
+    for (index, &(start, end)) in boards.iter().enumerate() {
+        let next_start = boards.get(index + 1).map_or(lines.len(), |&(s, _)| s);
+        let prev_end = if index == 0 { 0 } else { boards[index - 1].1 };
+
+        let board = lines[start..end].join("\n");
+        let mut level =
+            parser::parse_format(&board, Format::Xsb, variant.into()).map_err(|mut err| {
+                // lines of the whole file instead of the board
+                if let ParserErr::Syntax(ref mut syntax) = err {
+                    syntax.line += start;
+                }
+                CollectionErr {
+                    index,
+                    line: start,
+                    err,
+                }
+            })?;
+
+        // the title line for the next level must not be mistaken for this level's metadata
+        let mut after = &lines[end..next_start];
+        if index + 1 < boards.len() && title_line(after).is_some() {
+            after = &after[..after.len() - 1];
+        }
+        let mut metadata = parse_metadata(after);
+        if metadata.title.is_none() {
+            metadata.title = title_line(&lines[prev_end..start]).map(str::to_owned);
+        }
+
+        level.set_metadata(metadata.clone());
+        levels.push((metadata, level));
+    }
+
+    Ok(levels)
+}
+
+/// Parses the XML collection format used by YASC and letslogic (usually called .slc).
+///
+/// The level's `Id` is used as its title and its `Copyright` (or the collection's)
+/// as its author. Other attributes and `Comment` elements are kept as properties.
+pub fn parse_slc(text: &str) -> Result<Vec<(LevelMetadata, Level)>, SlcErr> {
+    parse_slc_variant(text, Variant::Push)
+}
+
+/// Like `parse_slc` but all levels get `variant`
+pub fn parse_slc_variant(
+    text: &str,
+    variant: Variant,
+) -> Result<Vec<(LevelMetadata, Level)>, SlcErr> {
+    let doc = roxmltree::Document::parse(text).map_err(SlcErr::Xml)?;
+    let root = doc.root_element();
+    if !root.has_tag_name("SokobanLevels") {
+        return Err(SlcErr::Structure(format!(
+            "root element is <{}>, expected <SokobanLevels>",
+            root.tag_name().name()
+        )));
+    }
+
+    let mut levels = Vec::new();
+    for collection in root
+        .children()
+        .filter(|node| node.has_tag_name("LevelCollection"))
+    {
+        let collection_author = collection.attribute("Copyright");
+
+        for level_node in collection
+            .children()
+            .filter(|node| node.has_tag_name("Level"))
+        {
+            let index = levels.len();
+            let line = doc.text_pos_at(level_node.range().start).row as usize - 1;
+
+            let mut metadata = LevelMetadata {
+                title: level_node.attribute("Id").map(str::to_owned),
+                author: level_node
+                    .attribute("Copyright")
+                    .or(collection_author)
+                    .map(str::to_owned),
+                properties: Vec::new(),
+            };
+            for attr in level_node.attributes() {
+                if !matches!(attr.name(), "Id" | "Copyright") {
+                    metadata
+                        .properties
+                        .push((attr.name().to_owned(), attr.value().to_owned()));
+                }
+            }
+
+            let mut rows = Vec::new();
+            for child in level_node.children().filter(roxmltree::Node::is_element) {
+                let text = child.text().unwrap_or("");
+                if child.has_tag_name("L") {
+                    rows.push(text);
+                } else {
+                    metadata
+                        .properties
+                        .push((child.tag_name().name().to_owned(), text.trim().to_owned()));
+                }
+            }
+
+            let mut level = parser::parse_format(&rows.join("\n"), Format::Xsb, variant.into())
+                .map_err(|err| SlcErr::Level(CollectionErr { index, line, err }))?;
+            level.set_metadata(metadata.clone());
+            levels.push((metadata, level));
+        }
+    }
+
+    Ok(levels)
+}
+
+/// Groups of indices of levels which are the same puzzle (see `Level::is_equivalent`).
+///
+/// Only groups with more than one level are returned, ordered by their first level.
+pub fn find_duplicates<'a, I>(levels: I) -> Vec<Vec<usize>>
+where
+    I: IntoIterator<Item = &'a Level>,
+{
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    let mut group_indices = HashMap::new();
+    for (i, level) in levels.into_iter().enumerate() {
+        // invalid levels can't be normalized so they're only compared as they are
+        let key = match level.normalize() {
+            Ok(normalized) => (true, normalized.xsb().to_string()),
+            Err(_) => (false, level.xsb().to_string()),
+        };
+        let group = *group_indices.entry(key).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[group].push(i);
+    }
+    groups.retain(|group| group.len() > 1);
+    groups
+}
+
+fn is_board_line(line: &str) -> bool {
+    // walls are required so that text lines made of valid characters (e.g. `pp`) are not mistaken for boards
+    line.contains('#')
+        && line
+            .chars()
+            .all(|c| "#@+$*. -_pPbBrR|".contains(c) || c.is_ascii_digit())
+}
+
+/// Returns the title if the last line before a board is a free-standing text line.
+pub(crate) fn title_line<'a>(lines: &[&'a str]) -> Option<&'a str> {
+    let (&last, rest) = lines.split_last()?;
+    let last = last.trim();
+    if last.is_empty() || key_value(last).is_some() {
+        return None;
+    }
+    if rest.last().is_some_and(|line| !line.trim().is_empty()) {
+        // part of a longer block of text
+        return None;
+    }
+    let title = last.trim_start_matches(';').trim();
+    if title.is_empty() {
+        None
+    } else {
+        Some(title)
+    }
+}
+
+pub(crate) fn parse_metadata(lines: &[&str]) -> LevelMetadata {
+    let mut metadata = LevelMetadata::default();
+    let mut iter = lines.iter().map(|line| line.trim()).peekable();
+    while let Some(line) = iter.next() {
+        let Some((key, value)) = key_value(line) else {
+            continue;
+        };
+        let mut value = value.to_owned();
+
+        if key.eq_ignore_ascii_case("comment") && value.is_empty() {
+            // multi-line comment
+            let mut comment = Vec::new();
+            for line in iter.by_ref() {
+                if line.eq_ignore_ascii_case("comment-end:")
+                    || line.eq_ignore_ascii_case("comment_end:")
+                {
+                    break;
+                }
+                comment.push(line);
+            }
+            value = comment.join("\n");
+        } else if key.starts_with("Solution") {
+            // solutions are often split into multiple lines
+            while let Some(line) = iter.next_if(|line| !line.is_empty() && is_lurd(line)) {
+                value.push_str(line);
+            }
+        }
+
+        if key.eq_ignore_ascii_case("title") {
+            metadata.title = Some(value);
+        } else if key.eq_ignore_ascii_case("author") {
+            metadata.author = Some(value);
+        } else {
+            metadata.properties.push((key.to_owned(), value));
+        }
+    }
+    metadata
+}
+
+pub(crate) fn key_value(line: &str) -> Option<(&str, &str)> {
+    let (key, value) = line.split_once(':')?;
+    let key = key.trim();
+    if key.is_empty() || key.starts_with(';') {
+        return None;
+    }
+    Some((key, value.trim()))
+}
+
+fn is_lurd(line: &str) -> bool {
+    line.chars().all(|c| "lurdLURD".contains(c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::moves::Moves;
+
+    #[test]
+    fn solve_all() {
+        let text = r"
+#####
+#@$.#
+#####
+
+######
+#@$ .#
+######
+
+######
+#@$ ##
+######
+";
+        let collection = Collection::from(parse_collection(text).unwrap());
+        let results = collection.solve_all(Method::Pushes, &SolverOptions::default());
+        assert_eq!(results.len(), 3);
+        for (i, result) in results.iter().enumerate() {
+            assert_eq!(result.index, i);
+        }
+        let push_cnt = |result: &LevelResult| {
+            let solver_ok = result.result.as_ref().unwrap();
+            solver_ok.moves.as_ref().map(Moves::push_cnt)
+        };
+        assert_eq!(push_cnt(&results[0]), Some(1));
+        assert_eq!(push_cnt(&results[1]), Some(2));
+        assert_eq!(
+            results[2].result.as_ref().unwrap_err(),
+            &SolverErr::DiffBoxesGoals
+        );
+
+        // stopping after the first level
+        let mut seen = Vec::new();
+        let results = collection.solve_all_with_callback(
+            Method::Pushes,
+            &SolverOptions::default(),
+            &mut |result| {
+                seen.push(result.index);
+                false
+            },
+        );
+        assert_eq!(results.len(), 1);
+        assert_eq!(seen, [0]);
+    }
+
+    #[test]
+    fn solve_portfolio() {
+        let text = "
+#####
+#@$.#
+#####
+
+######
+#@$ .#
+######
+
+#######
+#@$ $.#
+#.    #
+#######
+";
+        let collection = Collection::from(parse_collection(text).unwrap());
+        let stages = Stage::escalating(Method::Pushes, &SolverOptions::default());
+        let results = collection.solve_portfolio(&stages, &mut |_| true);
+        assert_eq!(results.len(), 3);
+        for result in &results {
+            let portfolio_ok = result.result.as_ref().unwrap();
+            assert_eq!(portfolio_ok.stage, Some(0));
+        }
+        assert!(results[2]
+            .result
+            .as_ref()
+            .unwrap()
+            .solver_ok
+            .moves
+            .is_none());
+    }
+
+    #[test]
+    fn verify_solutions() {
+        let text = r"
+#####
+#@$.#
+#####
+Solution: R
+
+######
+#@$ .#
+######
+Solution/Pushes (3/2): RR
+Solution/Moves (2/2): RR
+Solution (1/1): L
+Solution: x
+
+######
+#@ $.#
+######
+";
+        let collection = Collection::from(parse_collection(text).unwrap());
+        let checks = collection.verify_solutions();
+        let summary: Vec<_> = checks
+            .iter()
+            .map(|check| (check.index, check.key.as_str(), check.is_discrepancy()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (0, "Solution", false),
+                (1, "Solution/Pushes (3/2)", true),
+                (1, "Solution/Moves (2/2)", false),
+                (1, "Solution (1/1)", true),
+                (1, "Solution", true),
+            ]
+        );
+        assert_eq!(checks[1].claimed, Some((3, 2)));
+        assert_eq!(
+            checks[1].to_string(),
+            "2 moves, 2 pushes, 1 box lines, 1 box changes (claims 3 moves, 2 pushes)"
+        );
+        assert!(matches!(checks[3].result, Err(SokobanError::Verify(_))));
+        assert!(matches!(checks[4].result, Err(SokobanError::Lurd(_))));
+    }
+
+    #[test]
+    fn sok_collection() {
+        let text = r"
+Some collection
+by someone
+
+Level 1
+#####
+#@$.#
+#####
+Author: Me
+Comment: first
+
+; 2
+#####
+#.$@#
+#####
+
+######
+#@$ .#
+######
+Title: Third
+Solution (2/1): rR
+Comment:
+Multi
+line
+Comment-End:
+";
+        let levels = parse_collection(text).unwrap();
+        assert_eq!(levels.len(), 3);
+
+        assert_eq!(levels[0].0.title.as_deref(), Some("Level 1"));
+        assert_eq!(levels[0].0.author.as_deref(), Some("Me"));
+        assert_eq!(
+            levels[0].0.properties,
+            vec![("Comment".to_owned(), "first".to_owned())]
+        );
+        assert_eq!(levels[0].1.to_string(), "#####\n#@$.#\n#####\n");
+        assert_eq!(levels[0].1.metadata(), &levels[0].0);
+
+        assert_eq!(levels[1].0.title.as_deref(), Some("2"));
+        assert_eq!(levels[1].0.author, None);
+        assert_eq!(levels[1].1.to_string(), "#####\n#.$@#\n#####\n");
+
+        assert_eq!(levels[2].0.title.as_deref(), Some("Third"));
+        assert_eq!(
+            levels[2].0.properties,
+            vec![
+                ("Solution (2/1)".to_owned(), "rR".to_owned()),
+                ("Comment".to_owned(), "Multi\nline".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn single_level() {
+        let levels = "levels/boxxle1/1.txt".load_level_collection().unwrap();
+        assert_eq!(levels.len(), 1);
+        assert_eq!(levels[0].0, LevelMetadata::default());
+    }
+
+    #[test]
+    fn windows_collection() {
+        let text =
+            "\u{feff}; First\r\n#####\r\n#@$.#\r\n#####\r\n\r\n\t#####\r\n\t#@$.#\r\n\t#####\r\n";
+        let levels = parse_collection(text).unwrap();
+        assert_eq!(levels.len(), 2);
+        assert_eq!(levels[0].0.title.as_deref(), Some("First"));
+        assert_eq!(levels[1].1.box_positions(), vec![(1, 10)]);
+    }
+
+    #[test]
+    fn invalid_level() {
+        let text = r"
+#####
+#@$.#
+#####
+
+#####
+#@$.@#
+#####
+";
+        let err = parse_collection(text).unwrap_err();
+        assert_eq!(
+            err,
+            CollectionErr {
+                index: 1,
+                line: 5,
+                err: ParserErr::MultiplePlayers,
+            }
+        );
+        assert_eq!(err.to_string(), "Level 2 (line 6): More than one player");
+
+        let err = parse_collection(&text.replace("#@$.@#", "#@$.#2")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Level 2 (line 6): Count with nothing to repeat at line 7, column 6:\n#@$.#2\n     ^"
+        );
+    }
+
+    #[test]
+    fn slc_collection() {
+        let text = r#"<?xml version="1.0" encoding="ISO-8859-1"?>
+<SokobanLevels xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">
+  <Title>Test</Title>
+  <Description>Two levels</Description>
+  <LevelCollection Copyright="Someone" MaxWidth="6" MaxHeight="3">
+    <Level Id="First" Width="5" Height="3">
+      <L>#####</L>
+      <L>#@$.#</L>
+      <L>#####</L>
+    </Level>
+    <Level Id="Second" Copyright="Me" Width="6" Height="3">
+      <L>######</L>
+      <L>#.$ @#</L>
+      <L>######</L>
+      <Comment>Push left</Comment>
+    </Level>
+  </LevelCollection>
+</SokobanLevels>
+"#;
+        let levels = parse_slc(text).unwrap();
+        assert_eq!(levels.len(), 2);
+
+        assert_eq!(levels[0].0.title.as_deref(), Some("First"));
+        assert_eq!(levels[0].0.author.as_deref(), Some("Someone"));
+        assert_eq!(levels[0].1.to_string(), "#####\n#@$.#\n#####\n");
+
+        assert_eq!(levels[1].0.title.as_deref(), Some("Second"));
+        assert_eq!(levels[1].0.author.as_deref(), Some("Me"));
+        assert_eq!(
+            levels[1].0.properties,
+            vec![
+                ("Width".to_owned(), "6".to_owned()),
+                ("Height".to_owned(), "3".to_owned()),
+                ("Comment".to_owned(), "Push left".to_owned()),
+            ]
+        );
+        assert_eq!(levels[1].1.to_string(), "######\n#.$ @#\n######\n");
+    }
+
+    #[test]
+    fn slc_latin1() {
+        let slc = |declaration: &str, author: &[u8]| {
+            let mut bytes =
+                format!("{declaration}<SokobanLevels><LevelCollection Copyright=\"").into_bytes();
+            bytes.extend_from_slice(author);
+            bytes.extend_from_slice(
+                b"\"><Level Id=\"1\"><L>#####</L><L>#@$.#</L><L>#####</L></Level>\
+                  </LevelCollection></SokobanLevels>",
+            );
+            bytes
+        };
+
+        let cases: [(&str, &[u8]); 3] = [
+            // e with an acute accent is 0xE9 in Latin-1
+            (r#"<?xml version="1.0" encoding="ISO-8859-1"?>"#, b"Ren\xE9"),
+            // wrong or missing declarations
+            (r#"<?xml version="1.0" encoding="UTF-8"?>"#, b"Ren\xE9"),
+            ("", b"Ren\xE9"),
+        ];
+        for (declaration, author) in &cases {
+            let levels = parse_slc(&decode_slc(&slc(declaration, author))).unwrap();
+            assert_eq!(levels[0].0.author.as_deref(), Some("Ren\u{e9}"));
+        }
+
+        let levels = parse_slc(&decode_slc(&slc("", "Ren\u{e9}".as_bytes()))).unwrap();
+        assert_eq!(levels[0].0.author.as_deref(), Some("Ren\u{e9}"));
+
+        // 0x80-0x9F differ from Latin-1
+        let declaration = r#"<?xml version="1.0" encoding="windows-1252"?>"#;
+        let levels = parse_slc(&decode_slc(&slc(declaration, b"\x93Ren\xE9\x94 \x80"))).unwrap();
+        assert_eq!(
+            levels[0].0.author.as_deref(),
+            Some("\u{201C}Ren\u{e9}\u{201D} \u{20AC}")
+        );
+    }
+
+    #[test]
+    fn slc_invalid() {
+        assert!(matches!(parse_slc("<SokobanLevels>"), Err(SlcErr::Xml(_))));
+        assert!(matches!(
+            parse_slc("<Levels></Levels>"),
+            Err(SlcErr::Structure(_))
+        ));
+
+        let text = r#"<SokobanLevels>
+  <LevelCollection>
+    <Level Id="1">
+      <L>#####</L>
+      <L>#@$.@#</L>
+      <L>#####</L>
+    </Level>
+  </LevelCollection>
+</SokobanLevels>"#;
+        assert_eq!(
+            parse_slc(text).unwrap_err(),
+            SlcErr::Level(CollectionErr {
+                index: 0,
+                line: 2,
+                err: ParserErr::MultiplePlayers,
+            })
+        );
+    }
+
+    #[test]
+    fn duplicates() {
+        let text = r"
+#####
+#@$.#
+#####
+
+######
+#@ $.#
+######
+
+#####
+#.$@#
+#####
+
+######
+#@ $.#
+######
+
+#####
+#@$.
+#####
+
+#####
+#@$.
+#####
+";
+        let levels: Vec<_> = parse_collection(text)
+            .unwrap()
+            .into_iter()
+            .map(|(_, level)| level)
+            .collect();
+        assert_eq!(
+            find_duplicates(&levels),
+            vec![vec![0, 2], vec![1, 3], vec![4, 5]]
+        );
+    }
+}
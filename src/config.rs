@@ -1,28 +1,361 @@
+use std::error::Error;
 use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+use std::time::Duration;
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Format {
     Custom,
     Xsb,
+    /// Run-length encoded XSB - e.g. `3#` instead of `###` and `|` instead of newlines
+    XsbRle,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum Method {
     MovesPushes,
     Moves,
     PushesMoves,
+    /// Push-optimal with the fewest box lines (see `SolutionInfo::box_lines`) among those
+    PushesBoxLines,
     Pushes,
+    /// Any solution - how much optimality is traded for speed is chosen by `SolverOptions::any_profile`
     Any,
 }
 
+impl Method {
+    /// All methods which guarantee an optimal solution in moves or pushes.
+    /// `Method::PushesBoxLines` is left out since it's only a different tie-break for `Method::Pushes`.
+    pub const OPTIMAL: [Method; 4] = [
+        Method::MovesPushes,
+        Method::Moves,
+        Method::PushesMoves,
+        Method::Pushes,
+    ];
+
+    /// The metric in which solutions found by this method are optimal (or the closest to it)
+    pub fn metric(self) -> Metric {
+        match self {
+            Method::MovesPushes | Method::Moves => Metric::Moves,
+            Method::PushesMoves | Method::PushesBoxLines | Method::Pushes | Method::Any => {
+                Metric::Pushes
+            }
+        }
+    }
+}
+
+impl FromStr for Method {
+    type Err = UnknownMethod;
+
+    /// The inverse of `Display`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "moves-pushes" => Ok(Method::MovesPushes),
+            "moves" => Ok(Method::Moves),
+            "pushes-moves" => Ok(Method::PushesMoves),
+            "pushes-box-lines" => Ok(Method::PushesBoxLines),
+            "pushes" => Ok(Method::Pushes),
+            "any" => Ok(Method::Any),
+            _ => Err(UnknownMethod(s.to_owned())),
+        }
+    }
+}
+
 impl Display for Method {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match *self {
             Method::MovesPushes => write!(f, "moves-pushes"),
             Method::Moves => write!(f, "moves"),
             Method::PushesMoves => write!(f, "pushes-moves"),
+            Method::PushesBoxLines => write!(f, "pushes-box-lines"),
             Method::Pushes => write!(f, "pushes"),
             Method::Any => write!(f, "any"),
         }
     }
 }
+
+/// The rules for moving boxes, levels use the same formats for all of them
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Variant {
+    /// Standard Sokoban
+    #[default]
+    Push,
+    /// The player pulls boxes instead of pushing them - stepping away from an adjacent box
+    /// can drag it along. Pulls are written as uppercase letters in LURD.
+    Pull,
+    /// Pushing with any number of players which block each other like boxes.
+    /// In LURD, `[n]` switches to the `n`th player (counted from 1 in row-major order).
+    Multiban,
+}
+
+impl FromStr for Variant {
+    type Err = UnknownVariant;
+
+    /// The inverse of `Display`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "push" => Ok(Variant::Push),
+            "pull" => Ok(Variant::Pull),
+            "multiban" => Ok(Variant::Multiban),
+            _ => Err(UnknownVariant(s.to_owned())),
+        }
+    }
+}
+
+impl Display for Variant {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match *self {
+            Variant::Push => write!(f, "push"),
+            Variant::Pull => write!(f, "pull"),
+            Variant::Multiban => write!(f, "multiban"),
+        }
+    }
+}
+
+/// How hard `Method::Any` tries to find a short solution
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum AnyProfile {
+    /// Close to greedy - the heuristic is weighted so much that the search mostly just
+    /// pushes boxes towards goals. Solutions can be several times longer than optimal.
+    Fast,
+    /// Weighted A* - solutions are at most twice as long as optimal
+    Balanced,
+    /// Weighted like `Balanced` plus macro moves - a box pushed into a tunnel is pushed
+    /// all the way through and a box pushed into a room with empty goals is pushed
+    /// on to the furthest one it can reach, each as a single step of the search
+    Macro,
+    /// Push optimal, the same as `Method::Pushes` so `Method::Any` stays optimal by default
+    #[default]
+    Thorough,
+}
+
+impl AnyProfile {
+    /// How many percent longer than optimal the solution can be,
+    /// `SolverOptions::suboptimality_percent` is used instead if it's higher
+    pub fn suboptimality_percent(self) -> u16 {
+        match self {
+            AnyProfile::Fast => 400,
+            AnyProfile::Balanced | AnyProfile::Macro => 100,
+            AnyProfile::Thorough => 0,
+        }
+    }
+}
+
+impl FromStr for AnyProfile {
+    type Err = UnknownAnyProfile;
+
+    /// The inverse of `Display`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fast" => Ok(AnyProfile::Fast),
+            "balanced" => Ok(AnyProfile::Balanced),
+            "macro" => Ok(AnyProfile::Macro),
+            "thorough" => Ok(AnyProfile::Thorough),
+            _ => Err(UnknownAnyProfile(s.to_owned())),
+        }
+    }
+}
+
+impl Display for AnyProfile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match *self {
+            AnyProfile::Fast => write!(f, "fast"),
+            AnyProfile::Balanced => write!(f, "balanced"),
+            AnyProfile::Macro => write!(f, "macro"),
+            AnyProfile::Thorough => write!(f, "thorough"),
+        }
+    }
+}
+
+/// Which of the states with the same cost (distance plus heuristic) the search expands first
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum TieBreak {
+    /// The one furthest from the start - closest to a solution according to the heuristic.
+    /// The search dives towards a solution like DFS which usually finds the first one much sooner.
+    #[default]
+    Deeper,
+    /// The one closest to the start - each cost is explored breadth-first
+    Shallower,
+}
+
+impl FromStr for TieBreak {
+    type Err = UnknownTieBreak;
+
+    /// The inverse of `Display`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "deeper" => Ok(TieBreak::Deeper),
+            "shallower" => Ok(TieBreak::Shallower),
+            _ => Err(UnknownTieBreak(s.to_owned())),
+        }
+    }
+}
+
+impl Display for TieBreak {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match *self {
+            TieBreak::Deeper => write!(f, "deeper"),
+            TieBreak::Shallower => write!(f, "shallower"),
+        }
+    }
+}
+
+/// Settings for parsing levels which the level formats can't express
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ParseOptions {
+    pub variant: Variant,
+    /// Allow more goals than boxes - the level is solved when every box is on a goal
+    /// no matter which goals stay empty (only for levels without removers)
+    pub extra_goals: bool,
+    /// `None` detects the format - levels containing `<` are in the custom format.
+    /// `Format::Xsb` and `Format::XsbRle` are parsed the same way.
+    pub format: Option<Format>,
+}
+
+impl From<Variant> for ParseOptions {
+    fn from(variant: Variant) -> Self {
+        ParseOptions {
+            variant,
+            ..ParseOptions::default()
+        }
+    }
+}
+
+/// What to count when measuring solution length
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Metric {
+    Moves,
+    Pushes,
+}
+
+/// What form the solver returns solutions in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum SolveOutput {
+    /// All moves in `SolverOk::moves`
+    #[default]
+    Moves,
+    /// Only the pushes in `SolverOk::pushes`, `SolverOk::moves` is `None`.
+    /// The player's walking between pushes is never searched for which makes long solutions
+    /// much faster to return when their moves aren't needed. Disables `reorder_pushes`.
+    Pushes,
+}
+
+/// One metric of a solution for `Level::solve_lexicographic`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Criterion {
+    Moves,
+    Pushes,
+    /// See `SolutionInfo::box_lines`
+    BoxLines,
+    /// See `SolutionInfo::box_changes`
+    BoxChanges,
+}
+
+impl Criterion {
+    /// Each criterion can be used at most once so there are never more than this many
+    pub const MAX: usize = 4;
+}
+
+/// The solver's estimate of the remaining distance, all are admissible so they give solutions of the same length
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Heuristic {
+    /// Sum of the distances of boxes to their closest goals
+    #[default]
+    PushDists,
+    /// Distances found by pulling boxes backwards from each goal, with each box assigned
+    /// a different goal (a minimum cost matching). Often higher than `PushDists` when several boxes
+    /// are closest to the same goal but it ignores whether the player can get around boxes
+    /// and the matching is slower to compute. Levels with removers use the closest goal or remover.
+    GoalPullDists,
+    /// Number of boxes not on goals - much weaker and slower,
+    /// only useful for checking the results of the other one
+    MisplacedBoxes,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownMethod(pub String);
+
+impl Display for UnknownMethod {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Unknown method: {}", self.0)
+    }
+}
+
+impl Error for UnknownMethod {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownVariant(pub String);
+
+impl Display for UnknownVariant {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Unknown variant: {}", self.0)
+    }
+}
+
+impl Error for UnknownVariant {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownAnyProfile(pub String);
+
+impl Display for UnknownAnyProfile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Unknown profile: {}", self.0)
+    }
+}
+
+impl Error for UnknownAnyProfile {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownTieBreak(pub String);
+
+impl Display for UnknownTieBreak {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Unknown tie-break: {}", self.0)
+    }
+}
+
+impl Error for UnknownTieBreak {}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+// independent switches, not states
+#[allow(clippy::struct_excessive_bools)]
+pub struct SolverOptions {
+    /// Print the stats every time the search reaches a new depth. The output goes to stdout
+    /// unless the search is started with `Level::solve_with_output`.
+    pub print_status: bool,
+    /// Run independent searches on separate threads.
+    /// Status output of different searches will be interleaved.
+    pub parallel: bool,
+    /// Accept solutions up to this many percent longer than optimal
+    /// in exchange for (usually much) faster search. 0 means optimal.
+    pub suboptimality_percent: u16,
+    /// Only used by `Method::Any`
+    pub any_profile: AnyProfile,
+    /// After a search with `Method::Pushes`, reorder the pushes of the solution to reduce moves.
+    /// Much cheaper than `Method::PushesMoves` but not guaranteed to be move-optimal.
+    pub reorder_pushes: bool,
+    /// What form the solution is returned in
+    pub output: SolveOutput,
+    /// When the search is interrupted, save its state into `SolverOk::checkpoint`
+    /// so it can be resumed later. Uses about as much memory as the search itself.
+    pub checkpoint: bool,
+    /// Don't create states further from the start than this
+    /// (in the metric minimized first by the method)
+    pub max_depth: Option<u16>,
+    /// Stop after visiting this many unique states
+    pub max_nodes: Option<u32>,
+    /// Print the search speed, open list size and current f-value this often.
+    /// Independent of `print_status`.
+    pub status_interval: Option<Duration>,
+    pub heuristic: Heuristic,
+    /// Which of the nodes with the same cost is expanded first
+    pub tie_break: TieBreak,
+    /// Nodes with the same cost and distance are normally expanded in the order they were created.
+    /// With a seed, the order is random (but reproducible) so different seeds can find
+    /// different solutions of the same length.
+    pub seed: Option<u64>,
+}
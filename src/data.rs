@@ -2,9 +2,24 @@ use std::fmt;
 use std::fmt::{Display, Formatter};
 use std::ops::{Add, Sub};
 
+/// Type of row and column indices, limits the size of levels
+#[cfg(not(feature = "large-levels"))]
+pub(crate) type Coord = u8;
+#[cfg(feature = "large-levels")]
+pub(crate) type Coord = u16;
+
+/// Type of indices into `State::boxes`
+#[cfg(not(feature = "large-levels"))]
+pub(crate) type BoxIndex = u8;
+#[cfg(feature = "large-levels")]
+pub(crate) type BoxIndex = u16;
+
 // TODO this is fishy - add tests that test both limits
-pub(crate) const MAX_SIZE: usize = 255;
-pub(crate) const MAX_BOXES: usize = 255;
+pub(crate) const MAX_SIZE: usize = Coord::MAX as usize;
+// The max value (index of one past the last box) is used to represent empty in expand_{bfs,dfs}
+pub(crate) const MAX_BOXES: usize = BoxIndex::MAX as usize;
+/// Players are numbered from 1 in LURD so the number of the last one still has to fit into `Move::player`
+pub(crate) const MAX_PLAYERS: usize = u8::MAX as usize;
 
 // TODO considering i made a mistake once already it might be worth
 // trying to split this into two types - one for remover and one for goals
@@ -44,20 +59,19 @@ pub(crate) enum Contents {
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub(crate) struct Pos {
-    pub(crate) r: u8,
-    pub(crate) c: u8,
+    pub(crate) r: Coord,
+    pub(crate) c: Coord,
 }
 
 impl Pos {
-    pub(crate) fn new(r: u8, c: u8) -> Pos {
+    pub(crate) fn new(r: Coord, c: Coord) -> Pos {
         Pos { r, c }
     }
 
-    #[cfg(test)]
     #[allow(clippy::cast_sign_loss)] // LATER https://github.com/rust-lang/rust/issues/62111
     pub(crate) fn dist(self, other: Pos) -> u16 {
-        ((i16::from(self.r) - i16::from(other.r)).abs()
-            + (i16::from(self.c) - i16::from(other.c)).abs()) as u16
+        ((i32::from(self.r) - i32::from(other.r)).abs()
+            + (i32::from(self.c) - i32::from(other.c)).abs()) as u16
     }
 
     pub(crate) fn neighbors(self) -> [Pos; 4] {
@@ -100,6 +114,12 @@ impl Pos {
     }
 }
 
+impl From<Pos> for (usize, usize) {
+    fn from(pos: Pos) -> Self {
+        (pos.r.into(), pos.c.into())
+    }
+}
+
 impl Add<Dir> for Pos {
     type Output = Pos;
 
@@ -137,8 +157,8 @@ impl Sub<Dir> for Pos {
 
 pub(crate) const DIRECTIONS: [Dir; 4] = [Dir::Up, Dir::Right, Dir::Down, Dir::Left];
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub(crate) enum Dir {
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Dir {
     Up,
     Right,
     Down,
@@ -0,0 +1,144 @@
+//! One error type for everything that can go wrong when loading, parsing and solving levels.
+
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::io;
+
+use crate::collection::{CollectionErr, SlcErr};
+use crate::moves::LurdErr;
+use crate::parser::ParserErr;
+use crate::solver::SolverErr;
+use crate::verify::VerifyErr;
+
+/// Any of the library's errors, the more specific error types convert into it.
+///
+/// More kinds might be added so matching has to include a wildcard arm.
+/// It's displayed the same as the wrapped error.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum SokobanError {
+    /// Reading a file failed
+    Io(io::Error),
+    /// A single level is invalid
+    Parse(ParserErr),
+    /// A level in a collection is invalid
+    Collection(CollectionErr),
+    /// An SLC (XML) collection is invalid
+    Slc(SlcErr),
+    /// A solution in the LURD format is invalid
+    Lurd(LurdErr),
+    /// A solution doesn't solve its level
+    Verify(VerifyErr),
+    /// The level can't be solved by the solver
+    Solver(SolverErr),
+}
+
+impl Display for SokobanError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            SokobanError::Io(err) => write!(f, "{err}"),
+            SokobanError::Parse(err) => write!(f, "{err}"),
+            SokobanError::Collection(err) => write!(f, "{err}"),
+            SokobanError::Slc(err) => write!(f, "{err}"),
+            SokobanError::Lurd(err) => write!(f, "{err}"),
+            SokobanError::Verify(err) => write!(f, "{err}"),
+            SokobanError::Solver(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl Error for SokobanError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SokobanError::Io(err) => Some(err),
+            SokobanError::Parse(err) => Some(err),
+            SokobanError::Collection(err) => Some(err),
+            SokobanError::Slc(err) => Some(err),
+            SokobanError::Lurd(err) => Some(err),
+            SokobanError::Verify(err) => Some(err),
+            SokobanError::Solver(err) => Some(err),
+        }
+    }
+}
+
+impl From<io::Error> for SokobanError {
+    fn from(err: io::Error) -> Self {
+        SokobanError::Io(err)
+    }
+}
+
+impl From<ParserErr> for SokobanError {
+    fn from(err: ParserErr) -> Self {
+        SokobanError::Parse(err)
+    }
+}
+
+impl From<CollectionErr> for SokobanError {
+    fn from(err: CollectionErr) -> Self {
+        SokobanError::Collection(err)
+    }
+}
+
+impl From<SlcErr> for SokobanError {
+    fn from(err: SlcErr) -> Self {
+        SokobanError::Slc(err)
+    }
+}
+
+impl From<LurdErr> for SokobanError {
+    fn from(err: LurdErr) -> Self {
+        SokobanError::Lurd(err)
+    }
+}
+
+impl From<VerifyErr> for SokobanError {
+    fn from(err: VerifyErr) -> Self {
+        SokobanError::Verify(err)
+    }
+}
+
+impl From<SolverErr> for SokobanError {
+    fn from(err: SolverErr) -> Self {
+        SokobanError::Solver(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::config::Method;
+    use crate::level::Level;
+    use crate::moves::Moves;
+    use crate::{LoadLevel, LoadLevelCollection, Solve};
+
+    #[test]
+    fn error_kinds() {
+        let err = "levels/does-not-exist.txt".load_level().unwrap_err();
+        assert!(matches!(err, SokobanError::Io(ref err) if err.kind() == io::ErrorKind::NotFound));
+        let err = "levels/does-not-exist.sok"
+            .load_level_collection()
+            .unwrap_err();
+        assert!(matches!(err, SokobanError::Io(_)));
+
+        let parse = |level: &str| -> Result<Level, SokobanError> { Ok(level.parse()?) };
+        let err = parse("#####\n# $.#\n#####").unwrap_err();
+        assert!(matches!(err, SokobanError::Parse(ParserErr::NoPlayer)));
+        assert_eq!(err.to_string(), "No player");
+
+        let lurd = |lurd: &str| -> Result<Moves, SokobanError> { Ok(Moves::from_lurd(lurd)?) };
+        assert!(matches!(lurd("RX"), Err(SokobanError::Lurd(_))));
+
+        let solve = |level: &Level| -> Result<(), SokobanError> {
+            level.solve(Method::Pushes, false)?;
+            Ok(())
+        };
+        let level = parse("#####\n#@$ #\n#####").unwrap();
+        let err = solve(&level).unwrap_err();
+        assert!(matches!(
+            err,
+            SokobanError::Solver(SolverErr::DiffBoxesGoals)
+        ));
+        assert!(err.source().is_some());
+    }
+}
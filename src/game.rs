@@ -0,0 +1,281 @@
+//! Playing a level move by move - used by the interactive `play` mode.
+
+use crate::config::Variant;
+use crate::data::{Coord, Dir, MapCell, Pos};
+use crate::level::{CellKind, Level};
+use crate::moves::{Move, Moves};
+use crate::state::State;
+use crate::verify::{self, MoveErr};
+
+/// What happened after trying to move the player
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveOutcome {
+    /// The player moved without touching a box
+    Moved,
+    Pushed,
+    Pulled,
+    /// Nothing changed
+    Blocked(MoveErr),
+}
+
+/// A level being played with the history needed for undo
+#[derive(Debug, Clone)]
+pub struct Board {
+    /// The current position
+    level: Level,
+    /// States before each move
+    history: Vec<State>,
+    moves: Moves,
+    /// Which player moves in `Variant::Multiban` levels
+    player: usize,
+}
+
+impl Board {
+    pub fn new(level: Level) -> Self {
+        Board {
+            level,
+            history: Vec::new(),
+            moves: Moves::default(),
+            player: 0,
+        }
+    }
+
+    /// The level with the current position as its initial state - can be displayed or solved
+    pub fn level(&self) -> &Level {
+        &self.level
+    }
+
+    /// All moves made so far (undone moves are removed).
+    ///
+    /// Pulls are recorded as uppercase letters like pushes (the convention of reverse mode
+    /// in other players) so moves containing both can't be replayed.
+    pub fn moves(&self) -> &Moves {
+        &self.moves
+    }
+
+    /// Index of the player which moves, always 0 except in `Variant::Multiban` levels
+    pub fn player(&self) -> usize {
+        self.player
+    }
+
+    pub fn player_cnt(&self) -> usize {
+        self.level.state.player_cnt()
+    }
+
+    /// Selects the next player to move, wrapping around to the first one
+    pub fn next_player(&mut self) {
+        self.player = (self.player + 1) % self.player_cnt();
+    }
+
+    /// Moves the player, pushing a box if there is one in the way.
+    /// In `Variant::Pull` levels, boxes can't be pushed so this is only a step.
+    pub fn try_move(&mut self, dir: Dir) -> MoveOutcome {
+        let state = &self.level.state;
+        let is_push = self.level.variant != Variant::Pull
+            && state.boxes.contains(&(state.player(self.player) + dir));
+        let mov = Move::new(dir, is_push).with_player(self.player as u8);
+        match self.make_move(mov, self.level.variant) {
+            Ok(()) if is_push => MoveOutcome::Pushed,
+            Ok(()) => MoveOutcome::Moved,
+            Err(err) => MoveOutcome::Blocked(err),
+        }
+    }
+
+    /// Moves the player, pulling the box behind it if there is one (in any variant)
+    pub fn try_pull(&mut self, dir: Dir) -> MoveOutcome {
+        let state = &self.level.state;
+        let is_pull = state.boxes.contains(&(state.player(self.player) - dir));
+        let mov = Move::new(dir, is_pull).with_player(self.player as u8);
+        match self.make_move(mov, Variant::Pull) {
+            Ok(()) if is_pull => MoveOutcome::Pulled,
+            Ok(()) => MoveOutcome::Moved,
+            Err(err) => MoveOutcome::Blocked(err),
+        }
+    }
+
+    /// Makes all the moves or none of them if any is illegal
+    pub fn play(&mut self, moves: &Moves) -> Result<(), MoveErr> {
+        let mut board = self.clone();
+        for &mov in moves {
+            board.make_move(mov, board.level.variant)?;
+        }
+        *self = board;
+        Ok(())
+    }
+
+    /// Makes the moves up to and including the `push_cnt`th push,
+    /// again all or none of them
+    pub fn play_pushes(&mut self, moves: &Moves, push_cnt: usize) -> Result<(), MoveErr> {
+        let mut moves = moves.clone();
+        moves.truncate_to_push(push_cnt);
+        self.play(&moves)
+    }
+
+    /// Returns `false` if there's nothing to undo
+    pub fn undo(&mut self) -> bool {
+        match self.history.pop() {
+            Some(state) => {
+                self.level.state = state;
+                self.moves.pop();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn is_solved(&self) -> bool {
+        self.level.map().is_solved(&self.level.state.boxes)
+    }
+
+    /// Whether there's a box at row `r` and column `c`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the position is outside the level.
+    pub fn has_box(&self, r: usize, c: usize) -> bool {
+        assert!(
+            r < self.level.rows() && c < self.level.cols(),
+            "Position [{}, {}] is outside the level",
+            r,
+            c
+        );
+        self.level
+            .state
+            .boxes
+            .contains(&Pos::new(r as Coord, c as Coord))
+    }
+
+    /// Whether there's a goal at row `r` and column `c`, with or without a box
+    ///
+    /// # Panics
+    ///
+    /// Panics if the position is outside the level.
+    pub fn is_goal(&self, r: usize, c: usize) -> bool {
+        self.level.cell(r, c) == CellKind::Goal
+    }
+
+    pub fn boxes_on_goals(&self) -> usize {
+        let grid = self.level.map().grid();
+        self.level
+            .state
+            .boxes
+            .iter()
+            .filter(|&&b| grid[b] == MapCell::Goal)
+            .count()
+    }
+
+    fn make_move(&mut self, mov: Move, variant: Variant) -> Result<(), MoveErr> {
+        let new_state = verify::apply_move(self.level.map(), &self.level.state, variant, mov)?;
+        let old_state = std::mem::replace(&mut self.level.state, new_state);
+        self.history.push(old_state);
+        self.moves.add(mov);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn playing() {
+        let level: Level = r"
+#######
+#@ $ .#
+#######"
+            .parse()
+            .unwrap();
+        let mut board = Board::new(level);
+        assert!(!board.is_solved());
+        assert!(!board.undo());
+
+        assert_eq!(
+            board.try_move(Dir::Left),
+            MoveOutcome::Blocked(MoveErr::Wall)
+        );
+        assert_eq!(board.try_move(Dir::Right), MoveOutcome::Moved);
+        assert_eq!(board.try_move(Dir::Right), MoveOutcome::Pushed);
+        assert_eq!(board.moves().to_string(), "rR");
+        assert_eq!(board.level().player_pos(), (1, 3));
+        assert_eq!(board.level().box_positions(), vec![(1, 4)]);
+        assert!(board.has_box(1, 4));
+        assert!(!board.has_box(1, 3));
+
+        assert!(board.undo());
+        assert_eq!(board.moves().to_string(), "r");
+        assert_eq!(board.level().box_positions(), vec![(1, 3)]);
+
+        // illegal moves leave the board unchanged
+        let moves = Moves::from_lurd("RRR").unwrap();
+        assert_eq!(board.play(&moves), Err(MoveErr::Blocked));
+        assert_eq!(board.moves().to_string(), "r");
+
+        board
+            .play_pushes(&Moves::from_lurd("RlrR").unwrap(), 1)
+            .unwrap();
+        assert_eq!(board.moves().to_string(), "rR");
+        assert_eq!(board.boxes_on_goals(), 0);
+        board.play(&Moves::from_lurd("R").unwrap()).unwrap();
+        assert!(board.is_solved());
+        assert!(board.is_goal(1, 5));
+        assert_eq!(board.boxes_on_goals(), 1);
+        assert_eq!(board.moves().to_string(), "rRR");
+        assert_eq!(board.level().to_string(), "#######\n#   @*#\n#######\n");
+    }
+
+    #[test]
+    fn pulling() {
+        let level: Level = r"
+#######
+#  $@.#
+#######"
+            .parse()
+            .unwrap();
+        let mut board = Board::new(level);
+
+        assert_eq!(board.try_pull(Dir::Right), MoveOutcome::Pulled);
+        assert_eq!(board.level().to_string(), "#######\n#   $+#\n#######\n");
+        assert_eq!(
+            board.try_pull(Dir::Right),
+            MoveOutcome::Blocked(MoveErr::Wall)
+        );
+        assert_eq!(
+            board.try_pull(Dir::Left),
+            MoveOutcome::Blocked(MoveErr::Blocked)
+        );
+        assert!(board.undo());
+        assert_eq!(
+            board.try_pull(Dir::Left),
+            MoveOutcome::Blocked(MoveErr::Blocked)
+        );
+
+        // without a box behind, pulling is just a move
+        let mut board = Board::new("#####\n#@ .#\n#$  #\n#####".parse().unwrap());
+        assert_eq!(board.try_pull(Dir::Right), MoveOutcome::Moved);
+        assert_eq!(board.moves().to_string(), "r");
+    }
+
+    #[test]
+    fn multiple_players() {
+        let level = Level::parse_variant("#######\n#@$@ .#\n#######", Variant::Multiban).unwrap();
+        let mut board = Board::new(level);
+        assert_eq!(board.player_cnt(), 2);
+        assert_eq!(
+            board.try_move(Dir::Right),
+            MoveOutcome::Blocked(MoveErr::Blocked)
+        );
+
+        board.next_player();
+        assert_eq!(board.player(), 1);
+        assert_eq!(board.try_move(Dir::Right), MoveOutcome::Moved);
+        board.next_player();
+        assert_eq!(board.player(), 0);
+        assert_eq!(board.try_move(Dir::Right), MoveOutcome::Pushed);
+        assert_eq!(board.moves().to_string(), "[2]r[1]R");
+        assert_eq!(board.level().player_positions(), vec![(1, 2), (1, 4)]);
+        assert_eq!(
+            board.try_move(Dir::Right),
+            MoveOutcome::Blocked(MoveErr::Blocked)
+        );
+    }
+}
@@ -0,0 +1,440 @@
+//! Random level generation - random maps are filled with goals and boxes
+//! and kept only if the solver proves them solvable.
+
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+use fastrand::Rng;
+#[cfg(not(feature = "tracing"))]
+use log::debug;
+#[cfg(feature = "tracing")]
+use tracing::debug;
+
+use crate::builder::LevelBuilder;
+use crate::config::{Method, SolverOptions};
+use crate::data::{Coord, Dir, MapCell, Pos, DIRECTIONS, MAX_SIZE};
+use crate::level::Level;
+use crate::moves::Moves;
+use crate::solver::{preprocessing, Stats};
+use crate::vec2d::Vec2d;
+use crate::Solve;
+
+/// Chance of each square inside the border to become a wall
+const WALL_PERCENT: u32 = 25;
+
+/// Chance of `Mode::Reverse` repeating the previous pull if possible
+const MOMENTUM_PERCENT: u32 = 75;
+
+/// How many pulls `Mode::Reverse` makes by default
+pub const DEFAULT_PULLS: usize = 300;
+
+/// How boxes are placed once the map is generated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Boxes are placed randomly (but not on dead squares),
+    /// many of the resulting levels are unsolvable and get rejected.
+    Random,
+    /// Boxes start on goals and are pulled randomly,
+    /// the result is always solvable so the solver is only used to measure it.
+    Reverse { pulls: usize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GeneratorOptions {
+    pub mode: Mode,
+    /// Including the border walls
+    pub rows: usize,
+    /// Including the border walls
+    pub cols: usize,
+    pub boxes: usize,
+    /// Only accept levels whose push-optimal solution has at least this many pushes
+    pub min_pushes: usize,
+    /// How many random levels to try before giving up
+    pub attempts: usize,
+    /// Levels which the solver can't solve after visiting this many states are rejected
+    pub max_states: i32,
+}
+
+impl Default for GeneratorOptions {
+    fn default() -> Self {
+        GeneratorOptions {
+            mode: Mode::Random,
+            rows: 10,
+            cols: 10,
+            boxes: 3,
+            min_pushes: 0,
+            attempts: 1000,
+            max_states: 100_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GenerateErr {
+    NoBoxes,
+    /// Not enough space inside the border for the player, boxes and goals
+    TooSmall,
+    TooLarge,
+    /// None of the attempts produced a level satisfying the constraints
+    NotFound,
+}
+
+impl Display for GenerateErr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match *self {
+            GenerateErr::NoBoxes => write!(f, "At least one box is required"),
+            GenerateErr::TooSmall => write!(f, "Level is too small for so many boxes"),
+            GenerateErr::TooLarge => write!(f, "Level is too large"),
+            GenerateErr::NotFound => write!(f, "No suitable level found"),
+        }
+    }
+}
+
+impl Error for GenerateErr {}
+
+#[derive(Debug, Clone)]
+pub struct GeneratedLevel {
+    pub level: Level,
+    /// A push-optimal solution
+    pub solution: Moves,
+}
+
+/// Creates a random solvable level - the same options and seed always give the same level.
+pub fn generate(options: &GeneratorOptions, seed: u64) -> Result<GeneratedLevel, GenerateErr> {
+    if options.boxes == 0 {
+        return Err(GenerateErr::NoBoxes);
+    }
+    if options.rows > MAX_SIZE || options.cols > MAX_SIZE {
+        return Err(GenerateErr::TooLarge);
+    }
+    let inner = options.rows.saturating_sub(2) * options.cols.saturating_sub(2);
+    if inner < 2 * options.boxes + 1 {
+        return Err(GenerateErr::TooSmall);
+    }
+
+    let mut rng = Rng::with_seed(seed);
+    for attempt in 0..options.attempts {
+        if let Some(generated) = try_generate(options, &mut rng) {
+            debug!("Generated a level after {} attempts", attempt + 1);
+            return Ok(generated);
+        }
+    }
+    Err(GenerateErr::NotFound)
+}
+
+fn try_generate(options: &GeneratorOptions, rng: &mut Rng) -> Option<GeneratedLevel> {
+    let (grid, player_pos, mut squares) = random_map(options, rng)?;
+    if squares.len() < 2 * options.boxes + 1 {
+        return None;
+    }
+
+    rng.shuffle(&mut squares);
+    let (goals, rest) = squares.split_at(options.boxes);
+    let (boxes, player_pos) = match options.mode {
+        Mode::Random => (random_boxes(&grid, goals, rest, player_pos)?, player_pos),
+        Mode::Reverse { pulls } => pull_boxes(&grid, goals, player_pos, pulls, rng),
+    };
+
+    let mut builder = LevelBuilder::new(options.rows, options.cols);
+    for pos in grid.positions() {
+        let (r, c) = pos.into();
+        // walls which don't touch the playable area only make the level look cluttered
+        if grid[pos] == MapCell::Wall && touches_playable(&grid, pos) {
+            builder.wall(r, c);
+        }
+    }
+    for &goal in goals {
+        let (r, c) = goal.into();
+        builder.goal(r, c);
+    }
+    for &b in &boxes {
+        let (r, c) = b.into();
+        builder.box_(r, c);
+    }
+    let (r, c) = player_pos.into();
+    builder.player(r, c);
+    let level = builder.build().ok()?;
+
+    let max_states = options.max_states;
+    let mut progress = |stats: &Stats| stats.total_unique_visited() < max_states;
+    let solver_ok = level
+        .solve_with_progress(Method::Pushes, &SolverOptions::default(), &mut progress)
+        .ok()?;
+    let solution = solver_ok.moves?;
+    if solution.push_cnt() < options.min_pushes.max(1) {
+        return None;
+    }
+    Some(GeneratedLevel { level, solution })
+}
+
+/// Picks boxes from `squares` in order, skipping dead squares
+fn random_boxes(
+    grid: &Vec2d<MapCell>,
+    goals: &[Pos],
+    squares: &[Pos],
+    player_pos: Pos,
+) -> Option<Vec<Pos>> {
+    let mut map = grid.clone();
+    for &goal in goals {
+        map[goal] = MapCell::Goal;
+    }
+    // boxes on dead squares can never reach a goal
+    let dead = preprocessing::simple_dead_squares(&map, &[player_pos]);
+    let boxes: Vec<_> = squares
+        .iter()
+        .copied()
+        .filter(|&pos| !dead[pos])
+        .take(goals.len())
+        .collect();
+    if boxes.len() < goals.len() {
+        return None;
+    }
+    Some(boxes)
+}
+
+/// Starts with all boxes on goals and makes random pulls,
+/// the level is then solvable by pushing the boxes back the same way.
+///
+/// Random pulls tend to wander back and forth so the result is the position along the way
+/// where the boxes are farthest from where they started,
+/// with the player on a random square reachable from there.
+fn pull_boxes(
+    grid: &Vec2d<MapCell>,
+    goals: &[Pos],
+    mut player_pos: Pos,
+    pulls: usize,
+    rng: &mut Rng,
+) -> (Vec<Pos>, Pos) {
+    let mut boxes = goals.to_vec();
+    let mut occupied = grid.scratchpad();
+    for &b in &boxes {
+        occupied[b] = true;
+    }
+    let mut farthest = (0, boxes.clone(), player_pos);
+    let mut prev = None;
+
+    for _ in 0..pulls {
+        let mut possible = possible_pulls(grid, &occupied, &boxes, player_pos);
+        rng.shuffle(&mut possible);
+        // keep pulling the same way most of the time so boxes travel further
+        if let Some(prev) = prev {
+            if let Some(index) = possible.iter().position(|&p| p == prev) {
+                if rng.u32(0..100) < MOMENTUM_PERCENT {
+                    possible.swap(0, index);
+                }
+            }
+        }
+        // prefer pulls after which the player isn't trapped
+        let chosen = possible
+            .iter()
+            .copied()
+            .find(|&(i, dir)| {
+                let new_player_pos = pull(&mut occupied, &mut boxes, i, dir);
+                let trapped = possible_pulls(grid, &occupied, &boxes, new_player_pos).is_empty();
+                pull(&mut occupied, &mut boxes, i, dir.inverse());
+                !trapped
+            })
+            .or_else(|| possible.first().copied());
+        let Some((i, dir)) = chosen else {
+            break;
+        };
+        player_pos = pull(&mut occupied, &mut boxes, i, dir);
+        prev = Some((i, dir));
+
+        // the solution doesn't have to return each box to the goal it started on
+        let dist: u32 = boxes
+            .iter()
+            .map(|&b| {
+                goals
+                    .iter()
+                    .map(|&goal| u32::from(b.dist(goal)))
+                    .min()
+                    .unwrap_or(0)
+            })
+            .sum();
+        if dist >= farthest.0 {
+            farthest = (dist, boxes.clone(), player_pos);
+        }
+    }
+
+    let (_, boxes, player_pos) = farthest;
+    let mut occupied = grid.scratchpad();
+    for &b in &boxes {
+        occupied[b] = true;
+    }
+    let reachable = player_reachable(grid, &occupied, player_pos);
+    let starts: Vec<_> = reachable
+        .positions()
+        .filter(|&pos| reachable[pos])
+        .collect();
+    (boxes, starts[rng.usize(..starts.len())])
+}
+
+/// Box index and the direction the player moves while pulling it
+fn possible_pulls(
+    grid: &Vec2d<MapCell>,
+    occupied: &Vec2d<bool>,
+    boxes: &[Pos],
+    player_pos: Pos,
+) -> Vec<(usize, Dir)> {
+    let reachable = player_reachable(grid, occupied, player_pos);
+    let mut possible = Vec::new();
+    for (i, &b) in boxes.iter().enumerate() {
+        for &dir in &DIRECTIONS {
+            // the player stands next to the box and steps away from it,
+            // squares next to reachable ones are never outside the map
+            let player_from = b + dir;
+            if !reachable[player_from] {
+                continue;
+            }
+            let player_to = player_from + dir;
+            if grid[player_to] != MapCell::Wall && !occupied[player_to] {
+                possible.push((i, dir));
+            }
+        }
+    }
+    possible
+}
+
+/// Moves the box and returns the player's new position,
+/// pulling in the opposite direction undoes it.
+fn pull(occupied: &mut Vec2d<bool>, boxes: &mut [Pos], i: usize, dir: Dir) -> Pos {
+    occupied[boxes[i]] = false;
+    boxes[i] = boxes[i] + dir;
+    occupied[boxes[i]] = true;
+    boxes[i] + dir
+}
+
+fn player_reachable(grid: &Vec2d<MapCell>, occupied: &Vec2d<bool>, player_pos: Pos) -> Vec2d<bool> {
+    let mut reachable = grid.scratchpad();
+    reachable[player_pos] = true;
+    let mut to_visit = vec![player_pos];
+    while let Some(pos) = to_visit.pop() {
+        for &dir in &DIRECTIONS {
+            let new_pos = pos + dir;
+            if grid[new_pos] != MapCell::Wall && !occupied[new_pos] && !reachable[new_pos] {
+                reachable[new_pos] = true;
+                to_visit.push(new_pos);
+            }
+        }
+    }
+    reachable
+}
+
+/// Walls with random empty squares inside the border,
+/// squares not reachable by the player are turned into walls.
+///
+/// Returns the map, the player's position and the empty squares other than the player's.
+fn random_map(
+    options: &GeneratorOptions,
+    rng: &mut Rng,
+) -> Option<(Vec2d<MapCell>, Pos, Vec<Pos>)> {
+    let mut cells = vec![vec![MapCell::Wall; options.cols]; options.rows];
+    let mut empty = Vec::new();
+    for (r, row) in cells.iter_mut().enumerate().skip(1).take(options.rows - 2) {
+        for (c, cell) in row.iter_mut().enumerate().skip(1).take(options.cols - 2) {
+            if rng.u32(0..100) >= WALL_PERCENT {
+                *cell = MapCell::Empty;
+                empty.push(Pos::new(r as Coord, c as Coord));
+            }
+        }
+    }
+    if empty.is_empty() {
+        return None;
+    }
+    let player_pos = empty[rng.usize(..empty.len())];
+
+    let mut grid = Vec2d::new(&cells);
+    let mut reachable = grid.scratchpad();
+    reachable[player_pos] = true;
+    let mut to_visit = vec![player_pos];
+    let mut squares = Vec::new();
+    while let Some(pos) = to_visit.pop() {
+        for &dir in &DIRECTIONS {
+            let new_pos = pos + dir;
+            if grid[new_pos] != MapCell::Wall && !reachable[new_pos] {
+                reachable[new_pos] = true;
+                squares.push(new_pos);
+                to_visit.push(new_pos);
+            }
+        }
+    }
+    for pos in empty {
+        if !reachable[pos] {
+            grid[pos] = MapCell::Wall;
+        }
+    }
+    // sorted so the result only depends on the RNG, not the order of the search
+    squares.sort();
+    Some((grid, player_pos, squares))
+}
+
+/// Whether any of the 8 surrounding squares is not a wall
+fn touches_playable(grid: &Vec2d<MapCell>, pos: Pos) -> bool {
+    let (r, c) = pos.into();
+    (r.saturating_sub(1)..=r + 1).any(|nr| {
+        (c.saturating_sub(1)..=c + 1)
+            .any(|nc| grid.get(nr, nc).is_some_and(|&cell| cell != MapCell::Wall))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generating() {
+        let options = GeneratorOptions {
+            boxes: 2,
+            min_pushes: 5,
+            ..GeneratorOptions::default()
+        };
+        let generated = generate(&options, 42).unwrap();
+        assert_eq!(generated.level.box_positions().len(), 2);
+        assert!(generated.solution.push_cnt() >= 5);
+        assert!(generated.level.verify_solution(&generated.solution).is_ok());
+
+        // deterministic for a given seed
+        let again = generate(&options, 42).unwrap();
+        assert_eq!(generated.level.to_string(), again.level.to_string());
+    }
+
+    #[test]
+    fn generating_reverse() {
+        let options = GeneratorOptions {
+            mode: Mode::Reverse {
+                pulls: DEFAULT_PULLS,
+            },
+            boxes: 3,
+            min_pushes: 10,
+            ..GeneratorOptions::default()
+        };
+        let generated = generate(&options, 7).unwrap();
+        assert_eq!(generated.level.box_positions().len(), 3);
+        assert!(generated.solution.push_cnt() >= 10);
+        assert!(generated.level.verify_solution(&generated.solution).is_ok());
+    }
+
+    #[test]
+    fn invalid_options() {
+        let options = GeneratorOptions {
+            boxes: 0,
+            ..GeneratorOptions::default()
+        };
+        assert_eq!(generate(&options, 0).unwrap_err(), GenerateErr::NoBoxes);
+
+        let options = GeneratorOptions {
+            rows: 4,
+            cols: 4,
+            ..GeneratorOptions::default()
+        };
+        assert_eq!(generate(&options, 0).unwrap_err(), GenerateErr::TooSmall);
+
+        let options = GeneratorOptions {
+            min_pushes: 1000,
+            attempts: 3,
+            ..GeneratorOptions::default()
+        };
+        assert_eq!(generate(&options, 0).unwrap_err(), GenerateErr::NotFound);
+    }
+}
@@ -0,0 +1,110 @@
+//! Submitting solutions to [Letslogic](https://letslogic.com) through its API.
+//!
+//! Levels are identified by their Letslogic ID which has to be in the level's metadata
+//! as an `ID: 1234` line. The API key of the account to submit to is read
+//! from the `LETSLOGIC_API_KEY` environment variable by the CLI.
+
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+use crate::level::LevelMetadata;
+use crate::moves::Moves;
+
+/// The environment variable with the API key (found in the Letslogic account settings)
+pub const API_KEY_VAR: &str = "LETSLOGIC_API_KEY";
+
+const API_URL: &str = "https://letslogic.com/api/v1";
+
+#[derive(Debug)]
+pub enum LetslogicErr {
+    /// Connecting failed or the server returned an error status
+    Http(ureq::Error),
+    /// The server refused the solution, e.g. because it's invalid or the key is wrong
+    Rejected(String),
+}
+
+impl Display for LetslogicErr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            LetslogicErr::Http(err) => write!(f, "Request failed: {err}"),
+            LetslogicErr::Rejected(msg) => write!(f, "Rejected: {msg}"),
+        }
+    }
+}
+
+impl Error for LetslogicErr {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LetslogicErr::Http(err) => Some(err),
+            LetslogicErr::Rejected(_) => None,
+        }
+    }
+}
+
+/// The level's ID on Letslogic from its `ID` property
+pub fn level_id(metadata: &LevelMetadata) -> Option<u32> {
+    metadata
+        .properties
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case("id"))
+        .and_then(|(_, value)| value.trim().parse().ok())
+}
+
+/// Submits the solution of the level with `level_id`,
+/// returns the server's message (e.g. whether it's a new best solution)
+pub fn submit(api_key: &str, level_id: u32, moves: &Moves) -> Result<String, LetslogicErr> {
+    let solution = moves.to_string();
+    let body = ureq::post(format!("{API_URL}/level/{level_id}"))
+        .send_form([("key", api_key), ("solution", &solution)])
+        .and_then(|response| response.into_body().read_to_string())
+        .map_err(LetslogicErr::Http)?;
+    parse_response(&body)
+}
+
+/// The API answers with JSON with either a `result` or an `error` message
+fn parse_response(body: &str) -> Result<String, LetslogicErr> {
+    let json: serde_json::Value = serde_json::from_str(body)
+        .map_err(|_| LetslogicErr::Rejected(format!("Unexpected response: {body}")))?;
+    let message = |key| {
+        json.get(key).map(|value| match value.as_str() {
+            Some(msg) => msg.to_owned(),
+            None => value.to_string(),
+        })
+    };
+    match (message("error"), message("result")) {
+        (Some(err), _) => Err(LetslogicErr::Rejected(err)),
+        (None, Some(result)) => Ok(result),
+        (None, None) => Ok(body.trim().to_owned()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids() {
+        let mut metadata = LevelMetadata::default();
+        assert_eq!(level_id(&metadata), None);
+        metadata
+            .properties
+            .push(("Id".to_owned(), " 1234 ".to_owned()));
+        assert_eq!(level_id(&metadata), Some(1234));
+    }
+
+    #[test]
+    fn responses() {
+        assert_eq!(
+            parse_response(r#"{"result":"Solution Accepted"}"#).unwrap(),
+            "Solution Accepted"
+        );
+        assert!(matches!(
+            parse_response(r#"{"error":"Invalid key"}"#),
+            Err(LetslogicErr::Rejected(msg)) if msg == "Invalid key"
+        ));
+        assert!(matches!(
+            parse_response("<html>"),
+            Err(LetslogicErr::Rejected(_))
+        ));
+    }
+}
@@ -1,25 +1,101 @@
 use std::fmt;
 use std::fmt::{Debug, Display, Formatter};
 
-use crate::config::Format;
+use crate::config::{Format, Method, Variant};
+use crate::data::{Coord, MapCell, Pos};
 use crate::map::{Map, MapType};
 use crate::map_formatter::MapFormatter;
 use crate::moves::Moves;
-use crate::solution_formatter::SolutionFormatter;
+use crate::solution_formatter::{
+    AssignmentFormatter, PushSummary, SokFormatter, SolutionFormatter,
+};
 use crate::state::State;
+use crate::verify::{self, BoxAssignment, SolutionInfo, VerifyErr};
 
 #[cfg(test)]
 use crate::map::{GoalMap, RemoverMap};
 
+/// Information about a level which doesn't affect solving
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LevelMetadata {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    /// Other `Key: value` pairs in the order they appeared
+    pub properties: Vec<(String, String)>,
+}
+
+/// The static part of a square - boxes and the player are not included
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CellKind {
+    Empty,
+    Wall,
+    Goal,
+    Remover,
+}
+
+impl From<MapCell> for CellKind {
+    fn from(cell: MapCell) -> Self {
+        match cell {
+            MapCell::Empty => CellKind::Empty,
+            MapCell::Wall => CellKind::Wall,
+            MapCell::Goal => CellKind::Goal,
+            MapCell::Remover => CellKind::Remover,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct Level {
     pub(crate) map: MapType,
     pub(crate) state: State,
+    pub(crate) variant: Variant,
+    pub(crate) metadata: LevelMetadata,
 }
 
 impl Level {
     pub(crate) fn new(map: MapType, state: State) -> Self {
-        Level { map, state }
+        Level {
+            map,
+            state,
+            variant: Variant::Push,
+            metadata: LevelMetadata::default(),
+        }
+    }
+
+    /// Title, author and other information from the level's file - comments and `Key: value` lines
+    /// around an XSB level or the metadata of a level in a collection
+    pub fn metadata(&self) -> &LevelMetadata {
+        &self.metadata
+    }
+
+    pub fn set_metadata(&mut self, metadata: LevelMetadata) {
+        self.metadata = metadata;
+    }
+
+    /// Which rules apply when solving, verifying and formatting solutions - `Variant::Push` by default.
+    /// The level formats don't contain it so it has to be set after parsing
+    /// (or passed to `Level::parse_variant` to allow multiple players).
+    pub fn variant(&self) -> Variant {
+        self.variant
+    }
+
+    /// # Panics
+    ///
+    /// Panics if the level has more than one player and the variant is not `Variant::Multiban`.
+    pub fn set_variant(&mut self, variant: Variant) {
+        assert!(
+            variant == Variant::Multiban || self.state.player_cnt() == 1,
+            "Only Multiban levels can have more than one player"
+        );
+        self.variant = variant;
+    }
+
+    /// Whether the level can have more goals than boxes, see `ParseOptions::extra_goals`
+    pub fn extra_goals(&self) -> bool {
+        match self.map {
+            MapType::Goals(ref goal_map) => goal_map.extra_goals,
+            _ => false,
+        }
     }
 
     pub(crate) fn map(&self) -> &dyn Map {
@@ -30,18 +106,81 @@ impl Level {
     pub(crate) fn goal_map(&self) -> &GoalMap {
         match self.map {
             MapType::Goals(ref goal_map) => goal_map,
-            MapType::Remover(_) => panic!(),
+            _ => panic!(),
         }
     }
 
     #[cfg(test)]
     pub(crate) fn remover_map(&self) -> &RemoverMap {
         match self.map {
-            MapType::Goals(_) => panic!(),
             MapType::Remover(ref remover_map) => remover_map,
+            _ => panic!(),
         }
     }
 
+    pub fn rows(&self) -> usize {
+        self.map().grid().rows().into()
+    }
+
+    pub fn cols(&self) -> usize {
+        self.map().grid().cols().into()
+    }
+
+    /// Position as `(row, column)`, the first player in Multiban levels
+    pub fn player_pos(&self) -> (usize, usize) {
+        self.state.player_pos.into()
+    }
+
+    /// Positions as `(row, column)` in the order moves refer to them,
+    /// only Multiban levels have more than one
+    pub fn player_positions(&self) -> Vec<(usize, usize)> {
+        self.state.players().map(Into::into).collect()
+    }
+
+    /// Positions as `(row, column)` - sorted but otherwise in no particular order
+    pub fn box_positions(&self) -> Vec<(usize, usize)> {
+        self.state.boxes.iter().map(|&pos| pos.into()).collect()
+    }
+
+    /// Positions as `(row, column)`, empty for remover levels
+    pub fn goal_positions(&self) -> Vec<(usize, usize)> {
+        match self.map {
+            MapType::Goals(ref goal_map) => goal_map.goals.iter().map(|&pos| pos.into()).collect(),
+            MapType::Hybrid(ref hybrid_map) => {
+                hybrid_map.goals.iter().map(|&pos| pos.into()).collect()
+            }
+            MapType::Remover(_) => Vec::new(),
+        }
+    }
+
+    /// Positions as `(row, column)`, empty for levels with only goals
+    pub fn remover_positions(&self) -> Vec<(usize, usize)> {
+        match self.map {
+            MapType::Goals(_) => Vec::new(),
+            MapType::Remover(ref remover_map) => {
+                remover_map.removers.iter().map(|&pos| pos.into()).collect()
+            }
+            MapType::Hybrid(ref hybrid_map) => {
+                hybrid_map.removers.iter().map(|&pos| pos.into()).collect()
+            }
+        }
+    }
+
+    /// The static part of the square at row `r` and column `c`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the position is outside the level.
+    pub fn cell(&self, r: usize, c: usize) -> CellKind {
+        assert!(
+            r < self.rows() && c < self.cols(),
+            "Position [{}, {}] is outside the level",
+            r,
+            c
+        );
+        self.map().grid()[Pos::new(r as Coord, c as Coord)].into()
+    }
+
     pub fn xsb(&self) -> MapFormatter<'_> {
         self.format(Format::Xsb)
     }
@@ -58,7 +197,7 @@ impl Level {
         &'a self,
         moves: &'a Moves,
         include_steps: bool,
-    ) -> SolutionFormatter<'_> {
+    ) -> SolutionFormatter<'a> {
         self.format_solution(Format::Xsb, moves, include_steps)
     }
 
@@ -66,7 +205,7 @@ impl Level {
         &'a self,
         moves: &'a Moves,
         include_steps: bool,
-    ) -> SolutionFormatter<'_> {
+    ) -> SolutionFormatter<'a> {
         self.format_solution(Format::Custom, moves, include_steps)
     }
 
@@ -76,10 +215,87 @@ impl Level {
         moves: &'a Moves,
         include_steps: bool,
     ) -> SolutionFormatter<'a> {
-        SolutionFormatter::new(self.map(), &self.state, moves, include_steps, format)
+        SolutionFormatter::new(
+            self.map(),
+            &self.state,
+            self.variant,
+            moves,
+            include_steps,
+            format,
+        )
+    }
+
+    /// Replays the moves and checks they solve the level
+    pub fn verify_solution(&self, moves: &Moves) -> Result<SolutionInfo, VerifyErr> {
+        verify::verify(self.map(), &self.state, self.variant, moves)
+    }
+
+    /// Which goal each box ends up on or which push removed it,
+    /// in the same order as `box_positions`. Level designers can see the packing order this way.
+    ///
+    /// Returns the same errors as `verify_solution`.
+    pub fn box_assignment(&self, moves: &Moves) -> Result<Vec<BoxAssignment>, VerifyErr> {
+        verify::box_assignment(self.map(), &self.state, self.variant, moves)
+    }
+
+    /// Only the pushes, grouped by box like `b2:R,R,U b1:L`.
+    /// Boxes are numbered from 1 in the order of `box_positions`.
+    ///
+    /// Returns `VerifyErr::IllegalMove` if any move can't be made, the level doesn't have to be solved.
+    pub fn push_summary(&self, moves: &Moves) -> Result<String, VerifyErr> {
+        let pushes = verify::push_sequence(self.map(), &self.state, self.variant, moves)?;
+        Ok(PushSummary(&pushes).to_string())
+    }
+
+    /// The initial level annotated with the result of `box_assignment`
+    pub fn format_assignment<'a>(
+        &'a self,
+        assignment: &'a [BoxAssignment],
+    ) -> AssignmentFormatter<'a> {
+        AssignmentFormatter::new(self.map(), &self.state, assignment)
+    }
+
+    /// The level after making the moves - the player and boxes start where the moves left them.
+    ///
+    /// Returns `VerifyErr::IllegalMove` if any move can't be made, the level doesn't have to be solved.
+    /// Combined with the solver this allows solving from any position reached while playing.
+    pub fn apply(&self, moves: &Moves) -> Result<Level, VerifyErr> {
+        let state = verify::apply_moves(self.map(), &self.state, self.variant, moves)?;
+        Ok(Level {
+            map: self.map.clone(),
+            state,
+            variant: self.variant,
+            metadata: self.metadata.clone(),
+        })
+    }
+
+    /// The level, its metadata and the solution found by `method` in the .sok format
+    pub fn sok_solution<'a>(
+        &'a self,
+        metadata: &'a LevelMetadata,
+        method: Method,
+        moves: &'a Moves,
+    ) -> SokFormatter<'a> {
+        SokFormatter::new(self.map(), &self.state, metadata, method, moves)
+    }
+}
+
+/// Two levels are equal if they're the same puzzle - the same squares, players, boxes and variant.
+/// Metadata isn't compared since the level formats can't all keep it.
+impl PartialEq for Level {
+    fn eq(&self, other: &Self) -> bool {
+        self.map().grid() == other.map().grid()
+            && self.state == other.state
+            && self.variant == other.variant
+            && self.extra_goals() == other.extra_goals()
     }
 }
 
+impl Eq for Level {}
+
+/// The board in XSB. Parsing it gives an equal level as long as the level was parsed
+/// with the default `ParseOptions` - the variant and extra goals aren't part of the text.
+/// The same holds for `Level::custom` and `Level::from_custom_str`.
 impl Display for Level {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.xsb())
@@ -98,6 +314,63 @@ mod tests {
 
     use crate::data::Dir;
     use crate::moves::Move;
+    use crate::Solve;
+
+    #[test]
+    fn inspecting() {
+        let level: Level = r"
+#####
+#@$.#
+# *$##
+#.  #
+#####"
+            .parse()
+            .unwrap();
+        assert_eq!(level.rows(), 5);
+        assert_eq!(level.cols(), 6);
+        assert_eq!(level.player_pos(), (1, 1));
+        assert_eq!(level.box_positions(), vec![(1, 2), (2, 2), (2, 3)]);
+        assert_eq!(level.goal_positions(), vec![(1, 3), (2, 2), (3, 1)]);
+        assert_eq!(level.remover_positions(), vec![]);
+        assert_eq!(level.cell(0, 0), CellKind::Wall);
+        assert_eq!(level.cell(1, 1), CellKind::Empty);
+        assert_eq!(level.cell(2, 2), CellKind::Goal);
+        assert_eq!(level.cell(4, 5), CellKind::Empty);
+
+        let level: Level = "#####\n#@$r#\n#####".parse().unwrap();
+        assert_eq!(level.goal_positions(), vec![]);
+        assert_eq!(level.remover_positions(), vec![(1, 3)]);
+        assert_eq!(level.cell(1, 3), CellKind::Remover);
+    }
+
+    #[test]
+    fn applying_moves() {
+        let level: Level = r"
+#######
+#@ $ .#
+#######"
+            .parse()
+            .unwrap();
+
+        let moved = level.apply(&Moves::from_lurd("rR").unwrap()).unwrap();
+        assert_eq!(moved.to_string(), "#######\n#  @$.#\n#######\n");
+        // the original is unchanged
+        assert_eq!(level.player_pos(), (1, 1));
+
+        let rest = moved.solve(Method::Moves, false).unwrap().moves.unwrap();
+        assert_eq!(rest.to_string(), "R");
+        let mut all = Moves::from_lurd("rR").unwrap();
+        all.extend(&rest);
+        assert!(level.verify_solution(&all).is_ok());
+
+        assert_eq!(
+            level.apply(&Moves::from_lurd("rRRR").unwrap()).unwrap_err(),
+            VerifyErr::IllegalMove {
+                index: 3,
+                err: verify::MoveErr::Blocked
+            }
+        );
+    }
 
     #[test]
     fn formatting_level() {
@@ -136,6 +409,22 @@ B_<><><><>B_<>
         }
     }
 
+    #[test]
+    fn equality() {
+        let xsb = "; Title\n#######\n#@$ r.#\n#######";
+        let level: Level = xsb.parse().unwrap();
+        let custom = Level::from_custom_str(&level.custom().to_string()).unwrap();
+        // the title is lost but it's still the same puzzle
+        assert_eq!(custom.metadata().title, None);
+        assert_eq!(custom, level);
+        assert_eq!(level.to_string().parse::<Level>().unwrap(), level);
+
+        let pull = Level::parse_variant(xsb, Variant::Pull).unwrap();
+        assert_ne!(pull, level);
+        let moved = level.apply(&Moves::from_lurd("R").unwrap()).unwrap();
+        assert_ne!(moved, level);
+    }
+
     #[test]
     fn formatting_solution() {
         let level = r"
@@ -184,4 +473,128 @@ B_<><><><>B_<>
             expected_without_steps
         );
     }
+
+    #[test]
+    fn formatting_remover_solution() {
+        let custom = r"
+<><><><><><>
+<>P B    R<>
+<><><><><><>
+"
+        .trim_start_matches('\n');
+        let expected = r"
+<><><><><><>
+<>P B    R<>
+<><><><><><>
+
+<><><><><><>
+<>  P B  R<>
+<><><><><><>
+
+<><><><><><>
+<>    P  R<>
+<><><><><><>
+
+"
+        .trim_start_matches('\n');
+
+        let level: Level = custom.parse().unwrap();
+        assert_eq!(level.custom().to_string(), custom);
+        let moves = level.solve(Method::Moves, false).unwrap().moves.unwrap();
+        assert_eq!(moves.to_string(), "RR");
+        assert_eq!(level.custom_solution(&moves, true).to_string(), expected);
+        assert!(level.verify_solution(&moves).is_ok());
+    }
+
+    #[test]
+    fn formatting_annotated_solution() {
+        let level: Level = "#######\n#@ $ .#\n#  $ .#\n#######".parse().unwrap();
+        let moves = Moves::from_lurd("rRRlldRR").unwrap();
+        let expected = r"
+Start
+#######
+#@ $ .#
+#  $ .#
+#######
+
+Move 2/8, push 1/4: b1 R
+#######
+#  @$.#
+#  $ .#
+#######
+
+Move 3/8, push 2/4: b1 R
+#######
+#   @*#
+#  $ .#
+#######
+
+Move 7/8, push 3/4: b2 R
+#######
+#    *#
+#  @$.#
+#######
+
+Move 8/8, push 4/4: b2 R
+#######
+#    *#
+#   @*#
+#######
+
+Pushes: b1:R,R b2:R,R
+"
+        .trim_start_matches('\n');
+        assert_eq!(
+            level
+                .xsb_solution(&moves, false)
+                .annotated(true)
+                .to_string(),
+            expected
+        );
+        assert_eq!(level.push_summary(&moves).unwrap(), "b1:R,R b2:R,R");
+
+        let steps = level.xsb_solution(&moves, true).annotated(true).to_string();
+        assert!(steps.contains("Move 1/8: r\n"));
+        assert!(steps.contains("Move 6/8: d\n"));
+    }
+
+    #[test]
+    fn formatting_sok_solution() {
+        let level: Level = "######\n#@ $.#\n######".parse().unwrap();
+        let moves = Moves::new(vec![
+            Move::new(Dir::Right, false),
+            Move::new(Dir::Right, true),
+        ]);
+        let metadata = LevelMetadata {
+            title: Some("Short".to_owned()),
+            author: Some("Me".to_owned()),
+            properties: vec![
+                ("Comment".to_owned(), "Two\nlines".to_owned()),
+                ("Solution".to_owned(), "rrr".to_owned()),
+            ],
+        };
+        let expected = r"
+######
+#@ $.#
+######
+Title: Short
+Author: Me
+Comment:
+Two
+lines
+Comment-End:
+Solution/Pushes (2/1): rR
+"
+        .trim_start_matches('\n');
+
+        let sok = level.sok_solution(&metadata, Method::Pushes, &moves);
+        assert_eq!(sok.to_string(), expected);
+
+        let parsed = crate::collection::parse_collection(expected).unwrap();
+        assert_eq!(parsed[0].0.title, metadata.title);
+        assert_eq!(
+            parsed[0].0.properties[1],
+            ("Solution/Pushes (2/1)".to_owned(), "rR".to_owned())
+        );
+    }
 }
@@ -1,9 +1,5 @@
-// https://github.com/rust-lang/rust/issues/31844
-#![feature(min_specialization)]
-// https://github.com/rust-lang/rust/issues/15701
-#![feature(stmt_expr_attributes)]
-// Stuff for testing
-#![cfg_attr(test, feature(test))]
+// The old libtest benches
+#![cfg_attr(all(test, feature = "nightly"), feature(test))]
 // Additional warnings that are allow by default (`rustc -W help`)
 #![warn(missing_copy_implementations)]
 #![warn(missing_debug_implementations)]
@@ -28,52 +24,140 @@
 #![allow(clippy::struct_field_names)]
 // ^ End of pedantic overrides
 
+/// Enters a `tracing` span until the end of the current scope.
+/// Does nothing without the `tracing` feature.
+macro_rules! span {
+    ($($args:tt)*) => {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!($($args)*).entered();
+    };
+}
+
+pub mod analysis;
+pub mod anytime;
+pub mod builder;
+pub mod collection;
 pub mod config;
+pub mod error;
+pub mod game;
+pub mod generator;
+#[cfg(feature = "letslogic")]
+pub mod letslogic;
 pub mod level;
+pub mod map_analysis;
 pub mod map_formatter;
 pub mod moves;
+pub mod optimizer;
+#[cfg(feature = "plugin")]
+pub mod plugin;
+pub mod portfolio;
+pub mod push_distances;
+pub mod results;
+pub mod self_check;
 pub mod solution_formatter;
 pub mod solver;
+pub mod verify;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
 mod data;
 mod map;
 mod parser;
 mod state;
+mod transform;
 mod vec2d;
 
-use std::error::Error;
+use crate::config::{Method, ParseOptions, SolverOptions, Variant};
+use crate::level::{Level, LevelMetadata};
+use crate::solver::{SolverErr, SolverOk, Stats};
 
-use crate::config::Method;
-use crate::level::Level;
-use crate::solver::{SolverErr, SolverOk};
+pub use crate::data::Dir;
+pub use crate::error::SokobanError;
+pub use crate::parser::{ParserErr, SyntaxErr, SyntaxErrKind};
+pub use crate::transform::Axis;
+pub use crate::vec2d::{CellFormat, Vec2d};
 
+/// Sources of single levels - files given by path (`&str`, `Path`, `OsStr` and their owned versions)
+/// or already opened `File`s. Other sources only have to implement `level_text`.
+/// The format is detected like in `FromStr` unless `ParseOptions::format` is set.
 pub trait LoadLevel {
-    fn load_level(&self) -> Result<Level, Box<dyn Error>>;
+    /// The whole text of the level
+    fn level_text(&self) -> Result<String, SokobanError>;
+
+    fn load_level(&self) -> Result<Level, SokobanError> {
+        self.load_level_variant(Variant::Push)
+    }
+
+    /// Multiple players are only allowed in `Variant::Multiban`
+    fn load_level_variant(&self, variant: Variant) -> Result<Level, SokobanError> {
+        self.load_level_with_options(variant.into())
+    }
+
+    fn load_level_with_options(&self, options: ParseOptions) -> Result<Level, SokobanError> {
+        Ok(Level::parse_with_options(&self.level_text()?, options)?)
+    }
+}
+
+pub trait LoadLevelCollection {
+    fn load_level_collection(&self) -> Result<Vec<(LevelMetadata, Level)>, SokobanError> {
+        self.load_level_collection_variant(Variant::Push)
+    }
+
+    /// Multiple players are only allowed in `Variant::Multiban`
+    fn load_level_collection_variant(
+        &self,
+        variant: Variant,
+    ) -> Result<Vec<(LevelMetadata, Level)>, SokobanError>;
 }
 
 pub trait Solve {
-    fn solve(&self, method: Method, print_status: bool) -> Result<SolverOk, SolverErr>;
+    fn solve(&self, method: Method, print_status: bool) -> Result<SolverOk, SolverErr> {
+        let options = SolverOptions {
+            print_status,
+            ..SolverOptions::default()
+        };
+        self.solve_with_options(method, &options)
+    }
+
+    fn solve_with_options(
+        &self,
+        method: Method,
+        options: &SolverOptions,
+    ) -> Result<SolverOk, SolverErr> {
+        self.solve_with_progress(method, options, &mut |_| true)
+    }
+
+    /// `progress` is called every time the search reaches a new depth
+    /// and every `solver::PROGRESS_INTERVAL` visited states.
+    /// Returning `false` from it stops the search.
+    fn solve_with_progress(
+        &self,
+        method: Method,
+        options: &SolverOptions,
+        progress: &mut dyn FnMut(&Stats) -> bool,
+    ) -> Result<SolverOk, SolverErr>;
 }
 
 #[cfg(test)]
 mod tests {
-    extern crate test;
-    use self::test::Bencher;
-
+    use std::collections::BTreeMap;
+    use std::convert::TryFrom;
     use std::fmt::{Display, Write};
     use std::fs;
     use std::path::Path;
+    use std::sync::Mutex;
     use std::time::Instant;
 
     use separator::Separatable;
 
     use crate::config::Method;
+    use crate::results::{LevelRecord, Results, RESULTS_VERSION};
 
     use super::*;
 
     #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     struct TestResult {
-        /// move_cnt, push_cnt
+        /// `move_cnt`, `push_cnt`
         counts: Option<(i32, i32)>,
         comparison: TestComparison,
     }
@@ -88,7 +172,7 @@ mod tests {
 
     #[test]
     fn test_levels() {
-        // Note: this test (and the other level tests) will likely break if implementation details of the containers used in the solver change.
+        // Note: this test (and the other level tests) will likely break if the order in which the solver expands states changes (e.g. its tie-breaking).
 
         const UNSOLVED: i32 = 3;
         const SLOW: i32 = 2; // slow even in release
@@ -131,7 +215,7 @@ mod tests {
             ("custom", "remover-02-one-way.txt", ALL_OK),
             ("custom", "remover-03-long-way.txt", ALL_OK),
             ("custom", "remover-04-two-boxes.txt", ALL_OK),
-            ("custom", "remover-original-01.txt", [SLOW, SLOW, SLOW, OK]),
+            ("custom", "remover-original-01.txt", [SLOW, SLOW, RELEASE, OK]),
             ("custom", "remover-original-02.txt", [UNSOLVED, UNSOLVED, SLOW, SLOW]),
             ("custom", "remover-original-03.txt", [UNSOLVED, UNSOLVED, SLOW, SLOW]),
             ("custom", "remover-original-04.txt", ALL_UNSOLVED),
@@ -252,7 +336,7 @@ mod tests {
             ("boxxle1", "14.txt", ALL_UNSOLVED),
             ("boxxle1", "15.txt", ALL_OK),
             ("boxxle1", "16.txt", ALL_UNSOLVED),
-            ("boxxle1", "17.txt", [SLOW, SLOW, SLOW, RELEASE]),
+            ("boxxle1", "17.txt", ALL_RELEASE),
             ("boxxle1", "18.txt", ALL_RELEASE),
             ("boxxle1", "19.txt", ALL_OK),
             ("boxxle1", "20.txt", ALL_OK),
@@ -272,7 +356,7 @@ mod tests {
             ("boxxle2", "3.txt", [RELEASE, RELEASE, OK, OK]),
             ("boxxle2", "4.txt", [UNSOLVED, UNSOLVED, SLOW, RELEASE]),
             ("boxxle2", "5.txt", ALL_UNSOLVED),
-            ("boxxle2", "6.txt", [SLOW, SLOW, SLOW, RELEASE]),
+            ("boxxle2", "6.txt", [SLOW, SLOW, RELEASE, RELEASE]),
             ("boxxle2", "7.txt", [UNSOLVED, UNSOLVED, SLOW, SLOW]),
             ("boxxle2", "8.txt", ALL_UNSOLVED),
             ("boxxle2", "9.txt", ALL_UNSOLVED),
@@ -294,7 +378,7 @@ mod tests {
     }
 
     #[test]
-    #[ignore]
+    #[ignore = "takes too long"]
     fn test_more_levels() {
         let levels: Vec<_> = (100..=696) // most are simple but there's so many of them that testing all of them takes too long
             .filter(|&i| i != 250 && i != 693) // currently can't solve only these two
@@ -554,6 +638,45 @@ mod tests {
         assert!(all_levels_passed);
     }
 
+    /// The saved results of the level tests, by file
+    static SAVED_RESULTS: Mutex<BTreeMap<String, Results>> = Mutex::new(BTreeMap::new());
+
+    /// The saved result of the level or `None` if there's none yet.
+    /// Results are saved in one file per method and pack.
+    fn saved_result(results_file: &str, method: Method, name: &str) -> Option<LevelRecord> {
+        let mut saved = SAVED_RESULTS.lock().unwrap();
+        let results = saved.entry(results_file.to_owned()).or_insert_with(|| {
+            fs::read_to_string(results_file).map_or_else(
+                |_| Results::new(method),
+                |json| Results::from_json(&json).unwrap(),
+            )
+        });
+        results
+            .levels
+            .iter()
+            .find(|record| record.name == name)
+            .cloned()
+    }
+
+    /// Replaces the saved result of the same level or adds it
+    fn save_result(results_file: &str, record: LevelRecord) {
+        let mut saved = SAVED_RESULTS.lock().unwrap();
+        let results = saved
+            .get_mut(results_file)
+            .expect("Results are loaded before saving");
+        results.version = RESULTS_VERSION;
+        match results
+            .levels
+            .iter_mut()
+            .find(|saved| saved.name == record.name)
+        {
+            Some(saved) => *saved = record,
+            None => results.levels.push(record),
+        }
+        fs::create_dir_all(Path::new(results_file).parent().unwrap()).unwrap();
+        fs::write(results_file, results.to_json()).unwrap();
+    }
+
     #[must_use]
     #[allow(clippy::comparison_chain)]
     fn test_level<L: AsRef<str> + Display>(
@@ -563,8 +686,7 @@ mod tests {
     ) -> TestResult {
         let method_name = method.to_string();
         let level_path = format!("levels/{level_pack}/{level_name}");
-        let result_dir = format!("solutions/{method_name}/{level_pack}");
-        let result_file = format!("{result_dir}/{level_name}");
+        let results_file = format!("solutions/{method_name}/{level_pack}.json");
 
         println!("Solving level {level_path} using method {method_name}");
         let started = Instant::now();
@@ -578,46 +700,31 @@ mod tests {
             started.elapsed().as_millis().separated_string(),
         );
 
-        let mut out = String::new();
-        match solution.moves {
-            None => writeln!(out, "No solution").unwrap(),
-            Some(ref moves) => {
-                writeln!(out, "{moves}").unwrap();
-                writeln!(out, "Moves: {}", moves.move_cnt()).unwrap();
-                writeln!(out, "Pushes: {}", moves.push_cnt()).unwrap();
-            }
-        }
-        writeln!(out, "{}", solution.stats).unwrap();
-        if let Some(ref moves) = solution.moves {
-            let include_steps = method == Method::Moves;
-            write!(out, "{}", level.xsb_solution(moves, include_steps)).unwrap();
-        }
-
-        if !Path::new(&result_dir).exists() {
-            fs::create_dir_all(&result_dir).unwrap();
-        }
-
-        if !Path::new(&result_file).exists() {
-            fs::write(&result_file, &out).unwrap();
-            print!("Solution:\n{out}");
+        let include_steps = method == Method::Moves;
+        let out = LevelRecord::new(level_path.clone(), &solution).with_steps(
+            &level,
+            solution.moves.as_ref(),
+            include_steps,
+        );
+        let expected = saved_result(&results_file, method, &level_path).unwrap_or_else(|| {
+            save_result(&results_file, out.clone());
+            print!("Solution:\n{}", record_to_string(&out));
             println!("\t>>> SAVED NEW SOLUTION <<<\n\n");
-            // we could return here but let's parse the output as a sanity check
-        }
+            out.clone()
+        });
 
-        let expected = fs::read_to_string(&result_file).unwrap();
-
-        let (maybe_out_lens, out_created, out_visited) = parse_stats(&out);
-        let (maybe_expected_lens, expected_created, expected_visited) = parse_stats(&expected);
+        let out_lens = lengths(&out);
+        let expected_lens = lengths(&expected);
 
         if out == expected {
             return TestResult {
-                counts: maybe_out_lens,
+                counts: out_lens,
                 comparison: TestComparison::Ok,
             };
         }
 
-        print!("\t>>> Expected:\n{expected}");
-        print!("\t>>> Got:\n{out}");
+        print!("\t>>> Expected:\n{}", record_to_string(&expected));
+        print!("\t>>> Got:\n{}", record_to_string(&out));
         // This is commented out because the `difference` crate in unmaintained
         // (https://rustsec.org/advisories/RUSTSEC-2020-0095.html).
         // Difference has this nice and simple API which prints the changes with colors.
@@ -626,21 +733,21 @@ mod tests {
         // though at least `similar` has an example with colors.
         //print!("{}", Changeset::new(&expected, &out, "\n"));
 
-        if maybe_out_lens.is_some() != maybe_expected_lens.is_some() {
+        if out_lens.is_some() != expected_lens.is_some() {
             println!("\t>>> SOLVABILITY CHANGED <<<\n\n");
             return TestResult {
-                counts: maybe_out_lens,
+                counts: out_lens,
                 comparison: TestComparison::SolvabilityChanged,
             };
         }
 
-        let (out_moves, out_pushes) = maybe_out_lens.unwrap_or((-1, -1));
-        let (expected_moves, expected_pushes) = maybe_expected_lens.unwrap_or((-1, -1));
+        let (out_moves, out_pushes) = out_lens.unwrap_or((-1, -1));
+        let (expected_moves, expected_pushes) = expected_lens.unwrap_or((-1, -1));
 
         let m = (out_moves, expected_moves);
         let p = (out_pushes, expected_pushes);
-        let c = (out_created, expected_created);
-        let v = (out_visited, expected_visited);
+        let c = (out.created, expected.created);
+        let v = (out.visited, expected.visited);
 
         let stats = ["MOVES", "PUSHES", "CREATED", "VISITED"];
         let coefs = [m, p, c, v];
@@ -659,84 +766,100 @@ mod tests {
         println!();
 
         // uncomment to update results (might also wanna run with higher difficulty to update all levels)
-        //fs::write(&result_file, &out).unwrap();
+        //save_result(&results_file, out.clone());
 
         TestResult {
-            counts: maybe_out_lens,
+            counts: out_lens,
             comparison: TestComparison::Changed(m, p, c, v),
         }
     }
 
-    fn parse_stats(stats: &str) -> (Option<(i32, i32)>, i32, i32) {
-        let mut lines = stats.lines();
-
-        let first = lines.next().unwrap();
-        let maybe_lengths = if first == "No solution" {
-            None
-        } else {
-            let moves = parse_number_from_line(lines.next().unwrap());
-            let pushes = parse_number_from_line(lines.next().unwrap());
-            Some((moves, pushes))
-        };
-
-        // created and visited
-        let created = parse_number_from_line(lines.next().unwrap());
-        let visited = parse_number_from_line(lines.next().unwrap());
-
-        // other stats can go up with a better solution
-        (maybe_lengths, created, visited)
+    /// Moves and pushes, `None` if there's no solution
+    fn lengths(record: &LevelRecord) -> Option<(i32, i32)> {
+        let len = |len: Option<usize>| i32::try_from(len.unwrap()).unwrap();
+        record
+            .solution
+            .as_ref()
+            .map(|_| (len(record.moves), len(record.pushes)))
     }
 
-    fn parse_number_from_line(line: &str) -> i32 {
-        line.split_whitespace()
-            .last()
-            .unwrap()
-            .split(',')
-            .collect::<Vec<_>>()
-            .join("")
-            .parse()
-            .unwrap()
+    /// Readable text for comparing the results in the test output
+    fn record_to_string(record: &LevelRecord) -> String {
+        let mut out = String::new();
+        match record.solution {
+            None => writeln!(out, "No solution").unwrap(),
+            Some(ref solution) => {
+                writeln!(out, "{solution}").unwrap();
+                writeln!(out, "Moves: {}", record.moves.unwrap()).unwrap();
+                writeln!(out, "Pushes: {}", record.pushes.unwrap()).unwrap();
+            }
+        }
+        writeln!(out, "States created total: {}", record.created).unwrap();
+        writeln!(out, "Unique visited total: {}", record.visited).unwrap();
+        writeln!(out).unwrap();
+        for depth in &record.depths {
+            writeln!(
+                out,
+                "{}: {} created, {} unique, {} duplicates",
+                depth.depth, depth.created, depth.unique_visited, depth.reached_duplicates
+            )
+            .unwrap();
+        }
+        writeln!(out).unwrap();
+        if let Some(ref steps) = record.steps {
+            write!(out, "{steps}").unwrap();
+        }
+        out
     }
 
     // old benches using the default bencher - all ignored since moving to criterion
+    #[cfg(feature = "nightly")]
+    mod benches {
+        extern crate test;
+        use self::test::Bencher;
+
+        use super::*;
+
+        #[bench]
+        #[ignore = "replaced by criterion"]
+        fn bench_boxxle1_001(b: &mut Bencher) {
+            // 3 goals in a row
+            bench_level("levels/boxxle1/1.txt", Method::Pushes, b);
+        }
 
-    #[bench]
-    #[ignore]
-    fn bench_boxxle1_001(b: &mut Bencher) {
-        // 3 goals in a row
-        bench_level("levels/boxxle1/1.txt", Method::Pushes, b);
-    }
-
-    #[bench]
-    #[ignore]
-    fn bench_boxxle1_005(b: &mut Bencher) {
-        // 4 boxes goal room
-        bench_level("levels/boxxle1/5.txt", Method::Pushes, b);
-    }
+        #[bench]
+        #[ignore = "replaced by criterion"]
+        fn bench_boxxle1_005(b: &mut Bencher) {
+            // 4 boxes goal room
+            bench_level("levels/boxxle1/5.txt", Method::Pushes, b);
+        }
 
-    #[bench]
-    #[ignore]
-    fn bench_boxxle1_018(b: &mut Bencher) {
-        // 6 boxes - tiny goalroom
-        bench_level("levels/boxxle1/18.txt", Method::Pushes, b);
-    }
+        #[bench]
+        #[ignore = "replaced by criterion"]
+        fn bench_boxxle1_018(b: &mut Bencher) {
+            // 6 boxes - tiny goalroom
+            bench_level("levels/boxxle1/18.txt", Method::Pushes, b);
+        }
 
-    #[bench]
-    #[ignore]
-    fn bench_boxxle1_108(b: &mut Bencher) {
-        // 6 boxes in the middle
-        bench_level("levels/boxxle1/108.txt", Method::Pushes, b);
-    }
+        #[bench]
+        #[ignore = "replaced by criterion"]
+        fn bench_boxxle1_108(b: &mut Bencher) {
+            // 6 boxes in the middle
+            bench_level("levels/boxxle1/108.txt", Method::Pushes, b);
+        }
 
-    #[bench]
-    #[ignore]
-    fn bench_boxxle1_001_moves(b: &mut Bencher) {
-        bench_level("levels/boxxle1/1.txt", Method::Moves, b);
-    }
+        #[bench]
+        #[ignore = "replaced by criterion"]
+        fn bench_boxxle1_001_moves(b: &mut Bencher) {
+            bench_level("levels/boxxle1/1.txt", Method::Moves, b);
+        }
 
-    fn bench_level(level_path: &str, method: Method, b: &mut Bencher) {
-        let level = level_path.load_level().unwrap();
+        fn bench_level(level_path: &str, method: Method, b: &mut Bencher) {
+            let level = level_path.load_level().unwrap();
 
-        b.iter(|| test::black_box(level.solve(test::black_box(method), test::black_box(false))));
+            b.iter(|| {
+                test::black_box(level.solve(test::black_box(method), test::black_box(false)))
+            });
+        }
     }
 }
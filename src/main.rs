@@ -13,112 +13,945 @@
 // ^ End of pedantic overrides
 
 use std::ffi::OsString;
-#[cfg(unix)]
-use std::{fs, process};
+use std::hash::Hasher;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use std::{env, fs, process, thread};
 
-use clap::{crate_authors, crate_version, value_parser, Arg, ArgAction, ArgGroup, Command};
+use clap::{
+    crate_authors, crate_version, value_parser, Arg, ArgAction, ArgGroup, ArgMatches, Command,
+};
+use crossterm::cursor::{self, MoveTo, MoveToNextLine};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::style::Print;
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+use fnv::FnvHasher;
+use serde::{Deserialize, Serialize};
 
 use sokoban_solver::{
-    config::{Format, Method},
-    LoadLevel, Solve,
+    analysis::LevelReport,
+    collection::{self, Collection},
+    config::{Format, Heuristic, Method, Metric, ParseOptions, SolverOptions, TieBreak, Variant},
+    game::{Board, MoveOutcome},
+    generator::{self, GeneratorOptions, Mode},
+    level::{Level, LevelMetadata},
+    moves::Moves,
+    portfolio::Stage,
+    results::{LevelRecord, Results},
+    solver::{Checkpoint, EventWriter, SearchOutcome, SolverErr, SolverOk, Stats},
+    Axis, Dir, LoadLevel, LoadLevelCollection, Solve,
 };
 
-fn main() {
-    // Use consts for strings which appear in multiple places.
-    // If anybody thinks this is overkill, i made a typo twice already.
-    const CUSTOM: &str = "custom";
-    const XSB: &str = "xsb";
-    const MOVES_PUSHES: &str = "moves-pushes";
-    const MOVES: &str = "moves";
-    const PUSHES_MOVES: &str = "pushes-moves";
-    const PUSHES: &str = "pushes";
-    const ANY: &str = "any";
-    const LEVEL_FILE: &str = "level-file";
-    #[cfg(debug_assertions)]
-    const VERBOSE: &str = "verbose";
+#[cfg(feature = "letslogic")]
+use sokoban_solver::letslogic;
+#[cfg(feature = "graph")]
+use sokoban_solver::solver::SearchTrace;
+
+// Use consts for strings which appear in multiple places.
+// If anybody thinks this is overkill, i made a typo twice already.
+const SOLVE: &str = "solve";
+const VERIFY: &str = "verify";
+const OPTIMIZE: &str = "optimize";
+const ANALYZE: &str = "analyze";
+const FMT: &str = "fmt";
+const PLAY: &str = "play";
+const GENERATE: &str = "generate";
+const COMPARE: &str = "compare";
+const VERIFY_SOLUTIONS: &str = "verify-solutions";
+const DEDUPE: &str = "dedupe";
+const SERVE: &str = "serve";
+const LISTEN: &str = "listen";
+const CUSTOM: &str = "custom";
+const XSB: &str = "xsb";
+const RLE: &str = "rle";
+const MOVES_PUSHES: &str = "moves-pushes";
+const MOVES: &str = "moves";
+const PUSHES_MOVES: &str = "pushes-moves";
+const PUSHES_BOX_LINES: &str = "pushes-box-lines";
+const PUSHES: &str = "pushes";
+const ANY: &str = "any";
+const SUBOPTIMALITY: &str = "suboptimality";
+const ANY_PROFILE: &str = "any-profile";
+const REORDER_PUSHES: &str = "reorder-pushes";
+const ASSIGNMENT: &str = "assignment";
+const ANNOTATE: &str = "annotate";
+const SELF_CHECK: &str = "self-check";
+const METRIC: &str = "metric";
+const WINDOW: &str = "window";
+const LEVEL: &str = "level";
+const ALL: &str = "all";
+const SAVE_SOLUTIONS: &str = "save-solutions";
+const SUMMARY_CSV: &str = "summary-csv";
+const SUMMARY_JSON: &str = "summary-json";
+const STATS_CSV: &str = "stats-csv";
+const RECORD: &str = "record";
+const LETSLOGIC: &str = "letslogic";
+const OLD: &str = "old";
+const NEW: &str = "new";
+const NO_CACHE: &str = "no-cache";
+const TIMEOUT: &str = "timeout";
+const CHECKPOINT: &str = "checkpoint";
+const RESUME: &str = "resume";
+const ANYTIME: &str = "anytime";
+const RACE: &str = "race";
+const PORTFOLIO: &str = "portfolio";
+const GRAPH: &str = "graph";
+const EVENTS: &str = "events";
+const MAX_DEPTH: &str = "max-depth";
+const MAX_NODES: &str = "max-nodes";
+const LOWER_BOUND: &str = "lower-bound";
+const ROOMS: &str = "rooms";
+const DIFFICULTY: &str = "difficulty";
+const REPORT: &str = "report";
+const JSON: &str = "json";
+const COUNT_SOLUTIONS: &str = "count-solutions";
+const BOXES: &str = "boxes";
+const MIN_PUSHES: &str = "min-pushes";
+const ROWS: &str = "rows";
+const COLS: &str = "cols";
+const SEED: &str = "seed";
+const TIE_BREAK: &str = "tie-break";
+const REVERSE: &str = "reverse";
+const ROTATE: &str = "rotate";
+const MIRROR: &str = "mirror";
+const LEVEL_FILE: &str = "level-file";
+const SOLUTION_FILE: &str = "solution-file";
+const VARIANT: &str = "variant";
+const INPUT_FORMAT: &str = "input-format";
+const VERBOSE: &str = "verbose";
+const QUIET: &str = "quiet";
+const LOG_JSON: &str = "log-json";
+const STATUS_INTERVAL: &str = "status-interval";
+
+/// Set by Ctrl-C, the search stops at the next progress check
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+struct LoadedLevel {
+    /// For printing
+    name: String,
+    /// For saving the solution
+    file_stem: String,
+    metadata: LevelMetadata,
+    level: Level,
+}
+
+/// A solver result saved to disk so re-running on the same levels is fast
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheEntry {
+    /// The full key in case of hash collisions
+    key: String,
+    /// LURD, `None` if there's no solution
+    solution: Option<String>,
+    unsolvable: Option<String>,
+    suboptimality_percent: u16,
+    created: i32,
+    visited: i32,
+    /// Seconds
+    time: f64,
+    memory: usize,
+}
+
+/// The result of solving one level for the final summary
+#[derive(Debug, Serialize)]
+struct LevelSummary {
+    name: String,
+    solved: bool,
+    /// Stopped by Ctrl-C or timeout before finding a solution
+    interrupted: bool,
+    moves: Option<usize>,
+    pushes: Option<usize>,
+    created: i32,
+    visited: i32,
+    /// Seconds
+    time: f64,
+    /// Approximate bytes used by the search
+    memory: usize,
+}
 
+fn main() {
     let app = Command::new("sokoban-solver")
         .author(crate_authors!())
         .version(crate_version!())
-        .arg(
-            Arg::new(CUSTOM)
-                .short('c')
-                .long(CUSTOM)
-                .help("Output in the custom format")
-                .action(ArgAction::SetTrue)
-                .conflicts_with(XSB),
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(
+            Command::new(SOLVE)
+                .about("Solve levels")
+                .args(format_args())
+                .group(ArgGroup::new("format").args([CUSTOM, XSB, RLE]))
+                .arg(
+                    Arg::new(MOVES_PUSHES)
+                        .short('M')
+                        .long(MOVES_PUSHES)
+                        .help("Search for a move-optimal solution with minimal pushes")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with_all([MOVES, PUSHES_MOVES, PUSHES, ANY]),
+                )
+                .arg(
+                    Arg::new(MOVES)
+                        .short('m')
+                        .long(MOVES)
+                        .help("Search for a move-optimal solution")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with_all([PUSHES_MOVES, PUSHES, ANY]),
+                )
+                .arg(
+                    Arg::new(PUSHES_MOVES)
+                        .short('P')
+                        .long(PUSHES_MOVES)
+                        .help("Search for a push-optimal solution with minimal moves")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with_all([PUSHES, ANY]),
+                )
+                .arg(
+                    Arg::new(PUSHES_BOX_LINES)
+                        .long(PUSHES_BOX_LINES)
+                        .help("Search for a push-optimal solution with minimal box lines")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new(PUSHES)
+                        .short('p')
+                        .long(PUSHES)
+                        .help("Search for a push-optimal solution")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with_all([ANY]),
+                )
+                .arg(
+                    Arg::new(ANY)
+                        .short('a')
+                        .long(ANY)
+                        .help("Search for any solution (default, see --any-profile)")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new(ANY_PROFILE)
+                        .long(ANY_PROFILE)
+                        .value_name("PROFILE")
+                        .help(
+                            "How hard --any tries to find a short solution - fast is close to greedy, \
+                             balanced is at most twice as long as optimal, macro is balanced with \
+                             tunnel and goal room macro moves, thorough is push optimal",
+                        )
+                        .value_parser(["fast", "balanced", "macro", "thorough"])
+                        .default_value("thorough")
+                        .conflicts_with_all([
+                            MOVES_PUSHES,
+                            MOVES,
+                            PUSHES_MOVES,
+                            PUSHES_BOX_LINES,
+                            PUSHES,
+                        ]),
+                )
+                .group(ArgGroup::new("method").args([
+                    MOVES_PUSHES,
+                    MOVES,
+                    PUSHES_MOVES,
+                    PUSHES_BOX_LINES,
+                    PUSHES,
+                    ANY,
+                ]))
+                .arg(
+                    Arg::new(SUBOPTIMALITY)
+                        .short('s')
+                        .long(SUBOPTIMALITY)
+                        .value_name("PERCENT")
+                        .help(
+                            "Accept solutions at most PERCENT longer than optimal to search faster",
+                        )
+                        .value_parser(value_parser!(u16))
+                        .default_value("0"),
+                )
+                .arg(
+                    Arg::new(REORDER_PUSHES)
+                        .long(REORDER_PUSHES)
+                        .help("Reorder pushes of the solution to reduce moves (cheap but not move-optimal)")
+                        .action(ArgAction::SetTrue)
+                        .requires(PUSHES),
+                )
+                .arg(
+                    Arg::new(ANNOTATE)
+                        .long(ANNOTATE)
+                        .help("Label each printed step with the move and push number and the pushed box")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new(SELF_CHECK)
+                        .long(SELF_CHECK)
+                        .help("Replay each solution and solve again with a different heuristic to check it's optimal")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new(ASSIGNMENT)
+                        .long(ASSIGNMENT)
+                        .help("Show which goal each box ends up on")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new(SAVE_SOLUTIONS)
+                        .long(SAVE_SOLUTIONS)
+                        .value_name("DIR")
+                        .help("Save each level with its solution to DIR in the .sok format")
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new(SUMMARY_CSV)
+                        .long(SUMMARY_CSV)
+                        .value_name("FILE")
+                        .help("Write a summary of all solved levels to FILE as CSV")
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new(SUMMARY_JSON)
+                        .long(SUMMARY_JSON)
+                        .value_name("FILE")
+                        .help("Write a summary of all solved levels to FILE as JSON")
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new(STATS_CSV)
+                        .long(STATS_CSV)
+                        .value_name("FILE")
+                        .help("Write the states created and visited at each depth to FILE as CSV (disables the cache)")
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new(RECORD)
+                        .long(RECORD)
+                        .value_name("FILE")
+                        .help("Write the solutions and stats of all finished levels to FILE for the compare command")
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new(LETSLOGIC)
+                        .long(LETSLOGIC)
+                        .help("Submit solutions of levels with an ID property to letslogic.com \
+                               using the API key in LETSLOGIC_API_KEY, needs a build with the letslogic feature")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new(NO_CACHE)
+                        .long(NO_CACHE)
+                        .help("Always solve levels instead of reusing results cached by previous runs")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new(TIMEOUT)
+                        .long(TIMEOUT)
+                        .value_name("SECONDS")
+                        .help("Stop searching each level after SECONDS")
+                        .value_parser(value_parser!(f64)),
+                )
+                .arg(
+                    Arg::new(TIE_BREAK)
+                        .long(TIE_BREAK)
+                        .value_name("ORDER")
+                        .help(
+                            "Which of equally good states to expand first - deeper dives towards \
+                             a solution like DFS, shallower explores them breadth-first",
+                        )
+                        .value_parser(["deeper", "shallower"])
+                        .default_value("deeper"),
+                )
+                .arg(
+                    Arg::new(SEED)
+                        .long(SEED)
+                        .value_name("N")
+                        .help("Break ties between equally good states randomly using seed N")
+                        .value_parser(value_parser!(u64)),
+                )
+                .arg(
+                    Arg::new(STATUS_INTERVAL)
+                        .long(STATUS_INTERVAL)
+                        .value_name("SECONDS")
+                        .help("Print search speed, open list size and current f-value every SECONDS")
+                        .value_parser(value_parser!(f64)),
+                )
+                .arg(
+                    Arg::new(MAX_DEPTH)
+                        .long(MAX_DEPTH)
+                        .value_name("N")
+                        .help("Only search for solutions at most N moves or pushes long (whichever the method minimizes first)")
+                        .value_parser(value_parser!(u16)),
+                )
+                .arg(
+                    Arg::new(MAX_NODES)
+                        .long(MAX_NODES)
+                        .value_name("N")
+                        .help("Stop each level after visiting N unique states")
+                        .value_parser(value_parser!(u32)),
+                )
+                .arg(
+                    Arg::new(CHECKPOINT)
+                        .long(CHECKPOINT)
+                        .value_name("FILE")
+                        .help("When interrupted by Ctrl-C or timeout, save the search to FILE")
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new(RESUME)
+                        .long(RESUME)
+                        .value_name("FILE")
+                        .help("Continue a search saved with --checkpoint (with the same method)")
+                        .value_parser(value_parser!(PathBuf))
+                        .conflicts_with_all(["method", SUBOPTIMALITY]),
+                )
+                .arg(
+                    Arg::new(ANYTIME)
+                        .long(ANYTIME)
+                        .help("Print a fast solution first, then keep searching for shorter ones until optimal or interrupted")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with_all([SUBOPTIMALITY, CHECKPOINT, RESUME]),
+                )
+                .arg(
+                    Arg::new(RACE)
+                        .long(RACE)
+                        .help("Race the method with both heuristics (and the any method) on separate threads, keep the first solution")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with_all([ANYTIME, CHECKPOINT, RESUME, SELF_CHECK, EVENTS]),
+                )
+                .arg(
+                    Arg::new(PORTFOLIO)
+                        .long(PORTFOLIO)
+                        .help("Try cheap suboptimal searches with few states first and only run the method on levels they fail on")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with_all([ANYTIME, RACE, CHECKPOINT, RESUME, SELF_CHECK, EVENTS]),
+                )
+                .arg(
+                    Arg::new(GRAPH)
+                        .long(GRAPH)
+                        .value_name("FILE")
+                        .help("Write the searched state space to FILE as DOT, GraphML or JSON (by extension), \
+                               needs a build with the graph feature")
+                        .value_parser(value_parser!(PathBuf))
+                        .conflicts_with(ANYTIME),
+                )
+                .arg(
+                    Arg::new(EVENTS)
+                        .long(EVENTS)
+                        .value_name("FILE")
+                        .help("Stream every node the search creates or visits to FILE in a compact binary format")
+                        .value_parser(value_parser!(PathBuf))
+                        .conflicts_with_all([ANYTIME, RESUME]),
+                )
+                .args(level_args()),
         )
-        .arg(
-            Arg::new(XSB)
-                .short('x')
-                .long(XSB)
-                .help("Output in the XSB format (default)")
-                .action(ArgAction::SetTrue),
+        .subcommand(
+            Command::new(VERIFY)
+                .about("Check that a LURD solution solves a level")
+                .arg(level_num_arg())
+                .arg(variant_arg())
+                .arg(input_format_arg())
+                .arg(
+                    Arg::new(LEVEL_FILE)
+                        .value_parser(value_parser!(OsString))
+                        .required(true),
+                )
+                .arg(
+                    Arg::new(SOLUTION_FILE)
+                        .value_parser(value_parser!(PathBuf))
+                        .required(true),
+                ),
         )
-        .group(ArgGroup::new("format").args([CUSTOM, XSB]))
-        .arg(
-            Arg::new(MOVES_PUSHES)
-                .short('M')
-                .long(MOVES_PUSHES)
-                .help("Search for a move-optimal solution with minimal pushes")
-                .action(ArgAction::SetTrue)
-                .conflicts_with_all([MOVES, PUSHES_MOVES, PUSHES, ANY]),
+        .subcommand(
+            Command::new(OPTIMIZE)
+                .about("Improve a LURD solution by re-solving parts of it optimally")
+                .arg(level_num_arg())
+                .arg(variant_arg())
+                .arg(input_format_arg())
+                .arg(
+                    Arg::new(METRIC)
+                        .long(METRIC)
+                        .help("What to minimize, the other metric is used to break ties")
+                        .value_parser([MOVES, PUSHES])
+                        .default_value(MOVES),
+                )
+                .arg(
+                    Arg::new(WINDOW)
+                        .long(WINDOW)
+                        .value_name("N")
+                        .help("Re-solve N consecutive pushes at a time, larger is slower but finds more")
+                        .value_parser(value_parser!(u32).range(1..))
+                        .default_value("10"),
+                )
+                .arg(
+                    Arg::new(LEVEL_FILE)
+                        .value_parser(value_parser!(OsString))
+                        .required(true),
+                )
+                .arg(
+                    Arg::new(SOLUTION_FILE)
+                        .value_parser(value_parser!(PathBuf))
+                        .required(true),
+                ),
         )
-        .arg(
-            Arg::new(MOVES)
-                .short('m')
-                .long(MOVES)
-                .help("Search for a move-optimal solution")
-                .action(ArgAction::SetTrue)
-                .conflicts_with_all([PUSHES_MOVES, PUSHES, ANY]),
+        .subcommand(
+            Command::new(ANALYZE)
+                .about("Print static properties of levels such as dead squares")
+                .arg(
+                    Arg::new(LOWER_BOUND)
+                        .long(LOWER_BOUND)
+                        .help("Also print lower bounds on the number of pushes and moves")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new(ROOMS)
+                        .long(ROOMS)
+                        .help("Also print rooms, corridors and articulation squares")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new(DIFFICULTY)
+                        .long(DIFFICULTY)
+                        .help("Also estimate difficulty using a short probe search")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new(REPORT)
+                        .long(REPORT)
+                        .help("Print counts of squares, boxes, tunnels, rooms and the lower bounds instead of the map")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new(JSON)
+                        .long(JSON)
+                        .help("Print the report of each level as a line of JSON and nothing else")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with_all([LOWER_BOUND, ROOMS, DIFFICULTY, COUNT_SOLUTIONS]),
+                )
+                .arg(
+                    Arg::new(COUNT_SOLUTIONS)
+                        .long(COUNT_SOLUTIONS)
+                        .value_name("N")
+                        .help("Also count the push-optimal solutions and print up to N of them")
+                        .value_parser(value_parser!(usize)),
+                )
+                .args(level_args()),
         )
-        .arg(
-            Arg::new(PUSHES_MOVES)
-                .short('P')
-                .long(PUSHES_MOVES)
-                .help("Search for a push-optimal solution with minimal moves")
-                .action(ArgAction::SetTrue)
-                .conflicts_with_all([PUSHES, ANY]),
+        .subcommand(
+            Command::new(FMT)
+                .about("Convert levels between formats")
+                .args(format_args())
+                .group(ArgGroup::new("format").args([CUSTOM, XSB, RLE]))
+                .arg(
+                    Arg::new(ROTATE)
+                        .long(ROTATE)
+                        .value_name("DEGREES")
+                        .help("Rotate the levels clockwise")
+                        .value_parser(["90", "180", "270"]),
+                )
+                .arg(
+                    Arg::new(MIRROR)
+                        .long(MIRROR)
+                        .value_name("AXIS")
+                        .help(
+                            "Mirror the levels - horizontal swaps top and bottom, \
+                             vertical left and right (done after rotating)",
+                        )
+                        .value_parser(["horizontal", "vertical"]),
+                )
+                .args(level_args()),
         )
+        .subcommand(
+            Command::new(PLAY)
+                .about("Play a level in the terminal with hints from the solver")
+                .arg(level_num_arg())
+                .arg(variant_arg())
+                .arg(input_format_arg())
+                .arg(
+                    Arg::new(LEVEL_FILE)
+                        .value_parser(value_parser!(OsString))
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            Command::new(COMPARE)
+                .about("Compare results saved with solve --record, exits with 1 if they differ")
+                .arg(
+                    Arg::new(OLD)
+                        .value_parser(value_parser!(PathBuf))
+                        .required(true),
+                )
+                .arg(
+                    Arg::new(NEW)
+                        .value_parser(value_parser!(PathBuf))
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            Command::new(VERIFY_SOLUTIONS)
+                .about(
+                    "Check the Solution entries of levels (e.g. from other solvers or websites), \
+                     exits with 1 if any are invalid or their counts are wrong",
+                )
+                .args(level_args()),
+        )
+        .subcommand(
+            Command::new(DEDUPE)
+                .about("Find levels which are the same puzzle, possibly rotated or mirrored")
+                .arg(
+                    Arg::new(LEVEL_FILE)
+                        .help("Uses all levels of each file")
+                        .value_parser(value_parser!(OsString))
+                        .required(true)
+                        .action(ArgAction::Append),
+                ),
+        )
+        .subcommand(
+            Command::new(GENERATE)
+                .about("Generate a random solvable level and print it as XSB")
+                .arg(
+                    Arg::new(BOXES)
+                        .long(BOXES)
+                        .value_name("N")
+                        .value_parser(value_parser!(usize))
+                        .default_value("3"),
+                )
+                .arg(
+                    Arg::new(MIN_PUSHES)
+                        .long(MIN_PUSHES)
+                        .value_name("N")
+                        .help("Only accept levels whose optimal solution needs at least N pushes")
+                        .value_parser(value_parser!(usize))
+                        .default_value("0"),
+                )
+                .arg(
+                    Arg::new(ROWS)
+                        .long(ROWS)
+                        .value_name("N")
+                        .help("Height including the border walls")
+                        .value_parser(value_parser!(usize))
+                        .default_value("10"),
+                )
+                .arg(
+                    Arg::new(COLS)
+                        .long(COLS)
+                        .value_name("N")
+                        .help("Width including the border walls")
+                        .value_parser(value_parser!(usize))
+                        .default_value("10"),
+                )
+                .arg(
+                    Arg::new(SEED)
+                        .long(SEED)
+                        .value_name("N")
+                        .help("Seed for the random number generator, random by default")
+                        .value_parser(value_parser!(u64)),
+                )
+                .arg(
+                    Arg::new(REVERSE)
+                        .long(REVERSE)
+                        .help("Place boxes on goals and pull them randomly instead of placing them randomly")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new(SERVE)
+                .about("Solve levels sent as newline-delimited JSON on stdin or over TCP")
+                .long_about(
+                    "Solve levels sent as newline-delimited JSON on stdin or over TCP.\n\n\
+                     Each request is an object like \
+                     {\"id\": 1, \"level\": \"...\", \"method\": \"pushes\", \"timeout\": 30} \
+                     where only level is required, method defaults to any \
+                     and timeout is in seconds.\n\
+                     Each request is answered by zero or more progress objects \
+                     followed by a result or an error object, one per line.",
+                )
+                .arg(
+                    Arg::new(LISTEN)
+                        .long(LISTEN)
+                        .value_name("ADDRESS")
+                        .help("Listen for TCP connections on ADDRESS (e.g. 127.0.0.1:7777) instead of using stdio"),
+                ),
+        );
+
+    let app = app
         .arg(
-            Arg::new(PUSHES)
-                .short('p')
-                .long(PUSHES)
-                .help("Search for a push-optimal solution")
-                .action(ArgAction::SetTrue)
-                .conflicts_with_all([ANY]),
+            Arg::new(VERBOSE)
+                .short('v')
+                .long(VERBOSE)
+                .help("Print debug log messages, repeat (-vv) to also print trace messages")
+                .action(ArgAction::Count)
+                .global(true),
         )
         .arg(
-            Arg::new(ANY)
-                .short('a')
-                .long(ANY)
-                .help("Search for any solution (default, currently push optimal)")
-                .action(ArgAction::SetTrue),
+            Arg::new(QUIET)
+                .short('q')
+                .long(QUIET)
+                .help("Only print warnings and errors from the log and no search status")
+                .action(ArgAction::SetTrue)
+                .conflicts_with(VERBOSE)
+                .global(true),
         )
-        .group(ArgGroup::new("method").args([MOVES_PUSHES, MOVES, PUSHES_MOVES, PUSHES, ANY]))
         .arg(
-            Arg::new(LEVEL_FILE)
-                .value_parser(value_parser!(OsString))
-                .required(true)
-                .action(ArgAction::Append),
+            Arg::new(LOG_JSON)
+                .long(LOG_JSON)
+                .help("Print the log as JSON lines (needs a build with the tracing feature)")
+                .action(ArgAction::SetTrue)
+                .global(true),
         );
 
-    #[cfg(debug_assertions)]
-    let app = app.arg(
-        Arg::new(VERBOSE)
-            .short('v')
-            .long(VERBOSE)
-            .help("Print all log levels (only available in debug builds)")
+    let args = args_with_default_subcommand(&app);
+    let matches = app.get_matches_from(args);
+
+    let log_level = match matches.get_count(VERBOSE) {
+        _ if matches.get_flag(QUIET) => log::LevelFilter::Warn,
+        0 => log::LevelFilter::Info,
+        1 => log::LevelFilter::Debug,
+        _ => log::LevelFilter::Trace,
+    };
+    init_logging(log_level, matches.get_flag(LOG_JSON));
+
+    match matches.subcommand() {
+        Some((SOLVE, matches)) => solve(matches),
+        Some((VERIFY, matches)) => verify(matches),
+        Some((OPTIMIZE, matches)) => optimize(matches),
+        Some((ANALYZE, matches)) => analyze(matches),
+        Some((FMT, matches)) => fmt(matches),
+        Some((PLAY, matches)) => play(matches),
+        Some((COMPARE, matches)) => compare(matches),
+        Some((VERIFY_SOLUTIONS, matches)) => verify_solutions(matches),
+        Some((DEDUPE, matches)) => dedupe(matches),
+        Some((GENERATE, matches)) => generate(matches),
+        Some((SERVE, matches)) => serve(matches),
+        _ => unreachable!("Subcommand is required"),
+    }
+}
+
+/// `sokoban-solver [options] <level>...` from before there were subcommands still works and means `solve`
+fn args_with_default_subcommand(app: &Command) -> Vec<OsString> {
+    let mut args: Vec<OsString> = env::args_os().collect();
+    let first_positional = args
+        .iter()
+        .skip(1)
+        .map(|arg| arg.to_string_lossy())
+        .find(|arg| !arg.starts_with('-'));
+    if let Some(arg) = first_positional {
+        if arg != "help" && app.find_subcommand(&*arg).is_none() {
+            args.insert(1, SOLVE.into());
+        }
+    }
+    args
+}
+
+#[cfg(not(feature = "tracing"))]
+fn init_logging(level: log::LevelFilter, json: bool) {
+    if json {
+        eprintln!("--{LOG_JSON} needs a build with the tracing feature");
+        process::exit(1);
+    }
+    env_logger::Builder::from_default_env()
+        .filter_level(level)
+        .init();
+}
+
+/// Spans print their duration when they close so the log shows how long each phase took
+#[cfg(feature = "tracing")]
+fn init_logging(level: log::LevelFilter, json: bool) {
+    use tracing_subscriber::filter::LevelFilter;
+    use tracing_subscriber::fmt::format::FmtSpan;
+
+    let level = match level {
+        log::LevelFilter::Off => LevelFilter::OFF,
+        log::LevelFilter::Error => LevelFilter::ERROR,
+        log::LevelFilter::Warn => LevelFilter::WARN,
+        log::LevelFilter::Info => LevelFilter::INFO,
+        log::LevelFilter::Debug => LevelFilter::DEBUG,
+        log::LevelFilter::Trace => LevelFilter::TRACE,
+    };
+    // status output goes to stdout, keep the log separate
+    let builder = tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_writer(io::stderr);
+    if json {
+        builder.json().init();
+    } else {
+        builder.init();
+    }
+}
+
+fn format_args() -> [Arg; 3] {
+    [
+        Arg::new(CUSTOM)
+            .short('c')
+            .long(CUSTOM)
+            .help("Output in the custom format")
+            .action(ArgAction::SetTrue)
+            .conflicts_with_all([XSB, RLE]),
+        Arg::new(XSB)
+            .short('x')
+            .long(XSB)
+            .help("Output in the XSB format (default)")
+            .action(ArgAction::SetTrue)
+            .conflicts_with(RLE),
+        Arg::new(RLE)
+            .short('r')
+            .long(RLE)
+            .help("Output in the run-length encoded XSB format")
             .action(ArgAction::SetTrue),
-    );
+    ]
+}
+
+fn level_num_arg() -> Arg {
+    Arg::new(LEVEL)
+        .short('l')
+        .long(LEVEL)
+        .value_name("N")
+        .help("Use only level N (starting at 1) of each file containing multiple levels")
+        .value_parser(value_parser!(usize))
+}
+
+fn variant_arg() -> Arg {
+    Arg::new(VARIANT)
+        .long(VARIANT)
+        .help(
+            "Game rules - in the pull variant the player pulls boxes instead of pushing them, \
+             in multiban there can be multiple players",
+        )
+        .value_parser(["push", "pull", "multiban"])
+        .default_value("push")
+}
+
+fn input_format_arg() -> Arg {
+    Arg::new(INPUT_FORMAT)
+        .long(INPUT_FORMAT)
+        .help(
+            "Format of files with a single level, auto treats levels containing '<' as custom \
+             (collections are always XSB)",
+        )
+        .value_parser(["auto", XSB, CUSTOM])
+        .default_value("auto")
+}
 
-    let matches = app.get_matches();
+fn level_args() -> [Arg; 5] {
+    [
+        variant_arg(),
+        input_format_arg(),
+        level_num_arg().conflicts_with(ALL),
+        Arg::new(ALL)
+            .long(ALL)
+            .help("Use all levels of each file containing multiple levels (default for .slc files)")
+            .action(ArgAction::SetTrue),
+        Arg::new(LEVEL_FILE)
+            .value_parser(value_parser!(OsString))
+            .required(true)
+            .action(ArgAction::Append),
+    ]
+}
 
-    let format = if matches.get_flag(CUSTOM) {
+fn get_format(matches: &ArgMatches) -> Format {
+    if matches.get_flag(CUSTOM) {
         Format::Custom
+    } else if matches.get_flag(RLE) {
+        Format::XsbRle
     } else {
         Format::Xsb
+    }
+}
+
+fn load_levels<'a, I>(
+    paths: I,
+    level_num: Option<usize>,
+    all: bool,
+    options: ParseOptions,
+) -> Vec<LoadedLevel>
+where
+    I: IntoIterator<Item = &'a OsString>,
+{
+    let mut levels = Vec::new();
+    for path in paths {
+        let path_name = path.to_string_lossy();
+        let file_stem = Path::new(path)
+            .file_stem()
+            .map_or_else(|| "level".into(), |stem| stem.to_string_lossy());
+        // SLC files are always collections so use all levels unless told otherwise
+        if level_num.is_none() && !all && !collection::is_slc(path) {
+            let level = path.load_level_with_options(options).unwrap_or_else(|err| {
+                eprintln!("Can't load level: {err}");
+                process::exit(1);
+            });
+            let name = match level.metadata().title {
+                Some(ref title) => format!("{path_name} ({title})"),
+                None => path_name.into_owned(),
+            };
+            levels.push(LoadedLevel {
+                name,
+                file_stem: file_stem.into_owned(),
+                metadata: level.metadata().clone(),
+                level,
+            });
+            continue;
+        }
+
+        let collection = path
+            .load_level_collection_variant(options.variant)
+            .unwrap_or_else(|err| {
+                eprintln!("Can't load levels: {err}");
+                process::exit(1);
+            });
+        let prev_len = levels.len();
+        for (i, (metadata, level)) in collection.into_iter().enumerate() {
+            if level_num.is_some_and(|num| num != i + 1) {
+                continue;
+            }
+            let name = match metadata.title {
+                Some(ref title) => format!("{path_name} level {} ({title})", i + 1),
+                None => format!("{path_name} level {}", i + 1),
+            };
+            levels.push(LoadedLevel {
+                name,
+                file_stem: format!("{file_stem}-{}", i + 1),
+                metadata,
+                level,
+            });
+        }
+        if let Some(num) = level_num {
+            if levels.len() == prev_len {
+                eprintln!("Can't load level: {path_name} doesn't contain level {num}");
+                process::exit(1);
+            }
+        }
+    }
+    levels
+}
+
+fn load_levels_from_args(matches: &ArgMatches) -> Vec<LoadedLevel> {
+    load_levels(
+        matches
+            .get_many::<OsString>(LEVEL_FILE)
+            .expect("Level path is required"),
+        matches.get_one::<usize>(LEVEL).copied(),
+        matches.get_flag(ALL),
+        get_parse_options(matches),
+    )
+}
+
+fn get_variant(matches: &ArgMatches) -> Variant {
+    matches
+        .get_one::<String>(VARIANT)
+        .and_then(|variant| variant.parse().ok())
+        .unwrap_or_default()
+}
+
+fn get_parse_options(matches: &ArgMatches) -> ParseOptions {
+    let format = match matches.get_one::<String>(INPUT_FORMAT).map(String::as_str) {
+        Some(XSB) => Some(Format::Xsb),
+        Some(CUSTOM) => Some(Format::Custom),
+        _ => None,
     };
+    ParseOptions {
+        format,
+        ..get_variant(matches).into()
+    }
+}
+
+fn solve(matches: &ArgMatches) {
+    let format = get_format(matches);
 
     let method = if matches.get_flag(MOVES_PUSHES) {
         Method::MovesPushes
@@ -126,63 +959,1291 @@ fn main() {
         Method::Moves
     } else if matches.get_flag(PUSHES_MOVES) {
         Method::PushesMoves
+    } else if matches.get_flag(PUSHES_BOX_LINES) {
+        Method::PushesBoxLines
     } else if matches.get_flag(PUSHES) {
         Method::Pushes
     } else {
         Method::Any
     };
 
-    #[cfg(debug_assertions)]
-    let verbose = matches.get_flag(VERBOSE);
-    #[cfg(not(debug_assertions))]
-    let verbose = false;
+    let checkpoint_path = matches.get_one::<PathBuf>(CHECKPOINT);
+    let resume = matches
+        .get_one::<PathBuf>(RESUME)
+        .map(|path| load_checkpoint(path));
+    let method = resume.as_ref().map_or(method, Checkpoint::method);
+    let timeout = matches
+        .get_one::<f64>(TIMEOUT)
+        .map(|&secs| Duration::from_secs_f64(secs));
 
-    let log_level = if verbose {
-        log::LevelFilter::Trace
-    } else {
-        log::LevelFilter::Info
+    let options = SolverOptions {
+        print_status: !matches.get_flag(QUIET),
+        suboptimality_percent: *matches
+            .get_one::<u16>(SUBOPTIMALITY)
+            .expect("Suboptimality has a default"),
+        any_profile: matches
+            .get_one::<String>(ANY_PROFILE)
+            .and_then(|profile| profile.parse().ok())
+            .unwrap_or_default(),
+        reorder_pushes: matches.get_flag(REORDER_PUSHES),
+        checkpoint: checkpoint_path.is_some(),
+        max_depth: matches.get_one::<u16>(MAX_DEPTH).copied(),
+        max_nodes: matches.get_one::<u32>(MAX_NODES).copied(),
+        tie_break: matches
+            .get_one::<String>(TIE_BREAK)
+            .and_then(|tie_break| tie_break.parse().ok())
+            .unwrap_or_default(),
+        seed: matches.get_one::<u64>(SEED).copied(),
+        status_interval: matches
+            .get_one::<f64>(STATUS_INTERVAL)
+            .map(|&secs| Duration::from_secs_f64(secs)),
+        ..SolverOptions::default()
     };
-    env_logger::Builder::from_default_env()
-        .filter_level(log_level)
-        .init();
+
+    ctrlc::set_handler(|| {
+        // a second Ctrl-C quits immediately in case the search doesn't stop soon enough
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            process::exit(130);
+        }
+    })
+    .unwrap_or_else(|err| eprintln!("Couldn't set Ctrl-C handler: {err}"));
 
     // Chrome uses 300 (which means vscode does too) and gets killed when trying to solve hard levels.
     #[cfg(unix)]
     fs::write(
-        &format!("/proc/{}/oom_score_adj", process::id()),
+        format!("/proc/{}/oom_score_adj", process::id()),
         500.to_string(),
     )
     .unwrap_or_else(|_| eprintln!("Couldn't change oom_score_adj"));
 
-    for path in matches
-        .get_many::<OsString>(LEVEL_FILE)
-        .expect("Level path is required")
-    {
-        let level = path.load_level().unwrap_or_else(|err| {
-            eprintln!("Can't load level: {err}");
+    let save_dir = matches.get_one::<PathBuf>(SAVE_SOLUTIONS);
+    if let Some(dir) = save_dir {
+        fs::create_dir_all(dir).unwrap_or_else(|err| {
+            eprintln!("Can't create {}: {err}", dir.display());
             process::exit(1);
         });
+    }
 
-        println!("Solving {}...", path.to_string_lossy());
-        let solver_ok = level.solve(method, true).unwrap_or_else(|err| {
-            eprintln!("Invalid level: {err}");
+    let graph_path = matches.get_one::<PathBuf>(GRAPH);
+    if graph_path.is_some() && !cfg!(feature = "graph") {
+        eprintln!("--{GRAPH} needs a build with the graph feature");
+        process::exit(1);
+    }
+    if matches.get_flag(LETSLOGIC) && !cfg!(feature = "letslogic") {
+        eprintln!("--{LETSLOGIC} needs a build with the letslogic feature");
+        process::exit(1);
+    }
+    #[cfg(feature = "letslogic")]
+    let letslogic_key = matches.get_flag(LETSLOGIC).then(|| {
+        env::var(letslogic::API_KEY_VAR).unwrap_or_else(|_| {
+            eprintln!(
+                "--{LETSLOGIC} needs the API key in {}",
+                letslogic::API_KEY_VAR
+            );
             process::exit(1);
+        })
+    });
+
+    // results of resumed, anytime, randomized and reordered searches are not cached to keep the cache key simple
+    // and the graph is only collected when actually searching
+    let anytime = matches.get_flag(ANYTIME);
+    let race = matches.get_flag(RACE);
+    let portfolio = matches.get_flag(PORTFOLIO);
+    let events_path = matches.get_one::<PathBuf>(EVENTS);
+    let stats_csv_path = matches.get_one::<PathBuf>(STATS_CSV);
+    // a cached solution might be longer than the limit and "not found" shouldn't be cached
+    let use_cache = !matches.get_flag(NO_CACHE)
+        && options.seed.is_none()
+        && options.tie_break == TieBreak::default()
+        && options.max_depth.is_none()
+        && options.max_nodes.is_none()
+        && resume.is_none()
+        && !anytime
+        && !race
+        && !portfolio
+        && graph_path.is_none()
+        && events_path.is_none()
+        && stats_csv_path.is_none();
+
+    let levels = load_levels_from_args(matches);
+    let level_cnt = levels.len();
+    if (checkpoint_path.is_some() || resume.is_some()) && level_cnt != 1 {
+        eprintln!("--{CHECKPOINT} and --{RESUME} only work with a single level");
+        process::exit(1);
+    }
+    if graph_path.is_some() && level_cnt != 1 {
+        eprintln!("--{GRAPH} only works with a single level");
+        process::exit(1);
+    }
+    if events_path.is_some() && level_cnt != 1 {
+        eprintln!("--{EVENTS} only works with a single level");
+        process::exit(1);
+    }
+    let mut summaries = Vec::new();
+    let mut stats_csv = String::from(STATS_CSV_HEADER);
+    let mut results = Results::new(method);
+    let mut self_check_failed = false;
+    for LoadedLevel {
+        name,
+        file_stem,
+        metadata,
+        level,
+    } in levels
+    {
+        println!("Solving {name}...");
+        let cache_key = if use_cache {
+            cache_key(&level, method, options)
+        } else {
+            None
+        };
+        // stats are only printed when actually solving
+        let cached = cache_key
+            .as_deref()
+            .and_then(|key| load_cached(key, &level));
+        let (entry, stats, depths, interrupted, checkpoint, limit_reached) = if let Some(entry) =
+            cached
+        {
+            // the cache doesn't keep the stats by depth
+            (entry, None, Vec::new(), false, None, None)
+        } else {
+            let started = Instant::now();
+            let mut progress = |_: &Stats| {
+                !INTERRUPTED.load(Ordering::SeqCst)
+                    && timeout.is_none_or(|timeout| started.elapsed() <= timeout)
+            };
+            let solver_ok = if let Some(ref checkpoint) = resume {
+                level.resume_with_progress(checkpoint, &options, &mut progress)
+            } else if anytime {
+                let mut improved = |moves: &Moves, percent| {
+                    println!(
+                        "Found solution with {} moves, {} pushes (at most {percent}% longer than optimal):",
+                        moves.move_cnt(),
+                        moves.push_cnt()
+                    );
+                    println!("{moves}");
+                };
+                level.solve_anytime(method, &options, &mut improved, &mut progress)
+            } else if race {
+                let stop = || {
+                    INTERRUPTED.load(Ordering::SeqCst)
+                        || timeout.is_some_and(|timeout| started.elapsed() > timeout)
+                };
+                solve_race(&level, method, options, &stop)
+            } else if portfolio {
+                solve_portfolio(&level, method, options, &mut progress)
+            } else if let Some(path) = events_path {
+                solve_with_events(&level, method, options, &mut progress, path)
+            } else {
+                level.solve_with_progress(method, &options, &mut progress)
+            }
+            .unwrap_or_else(|err| {
+                eprintln!("Invalid level: {err}");
+                process::exit(1);
+            });
+            let entry = CacheEntry {
+                key: cache_key.clone().unwrap_or_default(),
+                solution: solver_ok.moves.as_ref().map(ToString::to_string),
+                unsolvable: solver_ok.unsolvable.as_ref().map(ToString::to_string),
+                suboptimality_percent: solver_ok.suboptimality_percent,
+                created: solver_ok.stats.total_created(),
+                visited: solver_ok.stats.total_unique_visited(),
+                time: started.elapsed().as_secs_f64(),
+                memory: solver_ok.stats.memory().total(),
+            };
+            if cache_key.is_some() && !solver_ok.interrupted {
+                save_cached(&entry);
+            }
+            #[cfg(feature = "graph")]
+            if let (Some(path), Some(trace)) = (graph_path, &solver_ok.trace) {
+                save_trace(path, trace);
+            }
+            if stats_csv_path.is_some() {
+                stats_csv += &stats_csv_rows(&name, &solver_ok.stats);
+            }
+            let heuristic = if matches.get_count(VERBOSE) > 0 {
+                format!("\n{}", solver_ok.stats.heuristic())
+            } else {
+                String::new()
+            };
+            let stats = format!(
+                "{}\n{}{}{}",
+                solver_ok.stats,
+                solver_ok.stats.pruned(),
+                solver_ok.stats.memory(),
+                heuristic
+            );
+            (
+                entry,
+                Some(stats),
+                solver_ok.stats.by_depth().collect(),
+                solver_ok.interrupted,
+                solver_ok.checkpoint,
+                solver_ok.limit_reached,
+            )
+        };
+        let moves = entry
+            .solution
+            .as_deref()
+            .map(|lurd| Moves::from_lurd(lurd).expect("Solutions are valid LURD"));
+
+        if !interrupted {
+            let mut record =
+                LevelRecord::from_parts(name.clone(), moves.as_ref(), entry.created, entry.visited);
+            record.depths = depths;
+            results.levels.push(record);
+        }
+        summaries.push(LevelSummary {
+            name,
+            solved: moves.is_some(),
+            interrupted,
+            moves: moves.as_ref().map(Moves::move_cnt),
+            pushes: moves.as_ref().map(Moves::push_cnt),
+            created: entry.created,
+            visited: entry.visited,
+            time: entry.time,
+            memory: entry.memory,
+        });
+
+        // searches which didn't finish prove nothing
+        let self_check = (matches.get_flag(SELF_CHECK)
+            && (moves.is_some() || entry.unsolvable.is_some()))
+        .then(|| {
+            let options = SolverOptions {
+                suboptimality_percent: entry.suboptimality_percent,
+                ..options
+            };
+            level.self_check(method, &options, moves.as_ref())
         });
 
-        match solver_ok.moves {
+        match moves {
             None => {
-                println!("No solution");
-                println!("{}", solver_ok.stats);
+                if interrupted {
+                    println!("Search interrupted");
+                } else if let Some(limit) = limit_reached {
+                    println!("Not solved within limit ({limit})");
+                } else {
+                    match entry.unsolvable {
+                        Some(reason) => println!("No solution: {reason}"),
+                        None => println!("No solution"),
+                    }
+                }
+                if let Some(stats) = stats {
+                    println!("{stats}");
+                }
+                if let (Some(path), Some(checkpoint)) = (checkpoint_path, checkpoint) {
+                    save_checkpoint(path, &checkpoint);
+                    println!("Saved checkpoint to {}", path.display());
+                }
             }
             Some(moves) => {
                 let include_steps = method == Method::Moves;
                 println!("Found solution:");
-                print!("{}", level.format_solution(format, &moves, include_steps));
-                println!("{}", solver_ok.stats);
+                if entry.suboptimality_percent > 0 {
+                    println!(
+                        "(at most {}% longer than optimal)",
+                        entry.suboptimality_percent
+                    );
+                }
+                print!(
+                    "{}",
+                    level
+                        .format_solution(format, &moves, include_steps)
+                        .annotated(matches.get_flag(ANNOTATE))
+                );
+                if let Some(stats) = stats {
+                    println!("{stats}");
+                }
                 println!("{moves}");
                 println!("Moves: {}", moves.move_cnt());
                 println!("Pushes: {}", moves.push_cnt());
+                let info = level
+                    .verify_solution(&moves)
+                    .expect("Found solutions are valid");
+                println!("Box lines: {}", info.box_lines);
+                println!("Box changes: {}", info.box_changes);
+                if matches.get_flag(ASSIGNMENT) {
+                    let assignment = level
+                        .box_assignment(&moves)
+                        .expect("Found solutions are valid");
+                    println!("Box assignment:");
+                    print!("{}", level.format_assignment(&assignment));
+                }
+
+                if let Some(dir) = save_dir {
+                    let path = dir.join(format!("{file_stem}.sok"));
+                    let sok = level.sok_solution(&metadata, method, &moves).to_string();
+                    fs::write(&path, sok).unwrap_or_else(|err| {
+                        eprintln!("Can't save solution to {}: {err}", path.display());
+                        process::exit(1);
+                    });
+                    println!("Saved to {}", path.display());
+                }
+                #[cfg(feature = "letslogic")]
+                if let Some(ref api_key) = letslogic_key {
+                    submit_to_letslogic(api_key, &metadata, &moves);
+                }
+            }
+        }
+
+        match self_check {
+            Some(Ok(())) => println!("Self-check passed"),
+            Some(Err(err)) => {
+                println!("Self-check failed: {err}");
+                self_check_failed = true;
+            }
+            None => {}
+        }
+
+        // Ctrl-C stops the whole run, a timeout only the current level
+        if INTERRUPTED.load(Ordering::SeqCst) {
+            break;
+        }
+    }
+
+    if level_cnt > 1 {
+        println!();
+        print_summary(&summaries);
+    }
+    if let Some(path) = matches.get_one::<PathBuf>(SUMMARY_CSV) {
+        write_file(path, &summary_csv(&summaries));
+    }
+    if let Some(path) = matches.get_one::<PathBuf>(SUMMARY_JSON) {
+        let json = serde_json::to_string_pretty(&summaries).expect("Summary is always valid JSON");
+        write_file(path, &(json + "\n"));
+    }
+    if let Some(path) = stats_csv_path {
+        write_file(path, &stats_csv);
+    }
+    if let Some(path) = matches.get_one::<PathBuf>(RECORD) {
+        write_file(path, &results.to_json());
+    }
+    if self_check_failed {
+        process::exit(1);
+    }
+}
+
+#[cfg(feature = "letslogic")]
+fn submit_to_letslogic(api_key: &str, metadata: &LevelMetadata, moves: &Moves) {
+    let Some(id) = letslogic::level_id(metadata) else {
+        println!("Not submitted to Letslogic: the level has no ID");
+        return;
+    };
+    match letslogic::submit(api_key, id, moves) {
+        Ok(msg) => println!("Letslogic: {msg}"),
+        Err(err) => eprintln!("Letslogic: {err}"),
+    }
+}
+
+/// Solutions of levels which only differ in decoration or player position within the same area
+/// share the key. Unsolvable results apply to all of them but solutions are checked before use.
+/// The heuristic is part of the key because it changes the stats and what `-v` prints.
+fn cache_key(level: &Level, method: Method, options: SolverOptions) -> Option<String> {
+    let normalized = level.normalize().ok()?;
+    // the profile changes what any means
+    let method = if method == Method::Any {
+        format!("{method}-{}", options.any_profile)
+    } else {
+        method.to_string()
+    };
+    Some(format!(
+        "{method} {} {:?} {} {}\n{}",
+        level.variant(),
+        options.heuristic,
+        options.suboptimality_percent,
+        options.reorder_pushes,
+        normalized.xsb()
+    ))
+}
+
+/// `$SOKOBAN_SOLVER_CACHE` or the usual per-user cache location
+fn cache_dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os("SOKOBAN_SOLVER_CACHE") {
+        return Some(dir.into());
+    }
+    let base = env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("LOCALAPPDATA").map(PathBuf::from))
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))?;
+    Some(base.join("sokoban-solver"))
+}
+
+fn cache_path(key: &str) -> Option<PathBuf> {
+    // FNV because it's stable across Rust versions unlike the std hasher
+    let mut hasher = FnvHasher::default();
+    hasher.write(key.as_bytes());
+    Some(cache_dir()?.join(format!("{:016x}.json.zst", hasher.finish())))
+}
+
+/// Missing, corrupted or outdated entries are all treated the same - as if not cached
+fn load_cached(key: &str, level: &Level) -> Option<CacheEntry> {
+    let compressed = fs::read(cache_path(key)?).ok()?;
+    let json = zstd::decode_all(compressed.as_slice()).ok()?;
+    let entry: CacheEntry = serde_json::from_slice(&json).ok()?;
+    if entry.key != key {
+        return None;
+    }
+    if let Some(ref lurd) = entry.solution {
+        // the solution might be for a rotated version or a different starting position
+        let moves = Moves::from_lurd(lurd).ok()?;
+        level.verify_solution(&moves).ok()?;
+    }
+    println!("Using cached result");
+    Some(entry)
+}
+
+/// Failing to cache is not an error, the level will just be solved again next time
+fn save_cached(entry: &CacheEntry) {
+    let Some(path) = cache_path(&entry.key) else {
+        return;
+    };
+    let json = serde_json::to_vec(entry).expect("Cache entry is always valid JSON");
+    let result = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|()| zstd::encode_all(json.as_slice(), 0))
+        .and_then(|compressed| fs::write(&path, compressed));
+    if let Err(err) = result {
+        eprintln!("Can't write cache {}: {err}", path.display());
+    }
+}
+
+/// Same format as the cache but a checkpoint is needed to continue so errors are fatal
+fn save_checkpoint(path: &Path, checkpoint: &Checkpoint) {
+    let json = serde_json::to_vec(checkpoint).expect("Checkpoint is always valid JSON");
+    let result =
+        zstd::encode_all(json.as_slice(), 0).and_then(|compressed| fs::write(path, compressed));
+    if let Err(err) = result {
+        eprintln!("Can't save checkpoint to {}: {err}", path.display());
+        process::exit(1);
+    }
+}
+
+/// The method with each heuristic, the any method finds a (usually) worse solution much faster
+fn race_entrants(method: Method, options: SolverOptions) -> Vec<(Method, SolverOptions)> {
+    let mut entrants: Vec<_> = [Heuristic::PushDists, Heuristic::GoalPullDists]
+        .iter()
+        .map(|&heuristic| {
+            let options = SolverOptions {
+                heuristic,
+                ..options
+            };
+            (method, options)
+        })
+        .collect();
+    if method != Method::Any {
+        entrants.push((Method::Any, options));
+    }
+    entrants
+}
+
+fn solve_race(
+    level: &Level,
+    method: Method,
+    options: SolverOptions,
+    stop: &(dyn Fn() -> bool + Sync),
+) -> Result<SolverOk, SolverErr> {
+    let entrants = race_entrants(method, options);
+    let winner = level.race(&entrants, stop)?;
+    if winner.solver_ok.outcome() != SearchOutcome::LimitReached {
+        let (method, options) = entrants[winner.index];
+        println!("Race won by {method} with {:?}", options.heuristic);
+    }
+    Ok(winner.solver_ok)
+}
+
+fn solve_portfolio(
+    level: &Level,
+    method: Method,
+    options: SolverOptions,
+    progress: &mut dyn FnMut(&Stats) -> bool,
+) -> Result<SolverOk, SolverErr> {
+    let stages = Stage::escalating(method, &options);
+    let portfolio_ok = level.solve_portfolio(&stages, progress)?;
+    if let Some(index) = portfolio_ok.stage {
+        println!(
+            "Finished by stage {} of {}: {}",
+            index + 1,
+            stages.len(),
+            stages[index]
+        );
+    }
+    Ok(portfolio_ok.solver_ok)
+}
+
+fn solve_with_events(
+    level: &Level,
+    method: Method,
+    options: SolverOptions,
+    progress: &mut dyn FnMut(&Stats) -> bool,
+    path: &Path,
+) -> Result<SolverOk, SolverErr> {
+    let exit = |err: io::Error| -> ! {
+        eprintln!("Can't write events to {}: {err}", path.display());
+        process::exit(1);
+    };
+    let file = fs::File::create(path).unwrap_or_else(|err| exit(err));
+    let mut writer = EventWriter::new(io::BufWriter::new(file)).unwrap_or_else(|err| exit(err));
+    let result = level.solve_with_events(method, &options, progress, &mut writer);
+    writer.finish().unwrap_or_else(|err| exit(err));
+    result
+}
+
+/// The format is picked by the extension, DOT is the default
+#[cfg(feature = "graph")]
+fn save_trace(path: &Path, trace: &SearchTrace) {
+    let result = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("graphml") => trace.write_graphml(path),
+        Some("json") => trace.write_json(path),
+        _ => trace.write_dot(path),
+    };
+    if let Err(err) = result {
+        eprintln!("Can't save graph to {}: {err}", path.display());
+        process::exit(1);
+    }
+}
+
+fn load_checkpoint(path: &Path) -> Checkpoint {
+    let checkpoint = fs::read(path)
+        .and_then(|compressed| zstd::decode_all(compressed.as_slice()))
+        .map_err(|err| err.to_string())
+        .and_then(|json| serde_json::from_slice(&json).map_err(|err| err.to_string()));
+    checkpoint.unwrap_or_else(|err| {
+        eprintln!("Can't load checkpoint {}: {err}", path.display());
+        process::exit(1);
+    })
+}
+
+/// Formats an optional count for the summary, `missing` when there is none
+fn summary_count(value: Option<usize>, missing: &str) -> String {
+    value.map_or_else(|| missing.to_owned(), |value| value.to_string())
+}
+
+fn print_summary(summaries: &[LevelSummary]) {
+    let name_width = summaries
+        .iter()
+        .map(|summary| summary.name.len())
+        .chain(std::iter::once("Level".len()))
+        .max()
+        .expect("Chain is never empty");
+
+    println!(
+        "{:name_width$}  {:11}  {:>8}  {:>8}  {:>12}  {:>12}  {:>10}  {:>12}",
+        "Level", "Result", "Moves", "Pushes", "Created", "Visited", "Time (s)", "Memory (B)"
+    );
+    for summary in summaries {
+        println!(
+            "{:name_width$}  {:11}  {:>8}  {:>8}  {:>12}  {:>12}  {:>10.3}  {:>12}",
+            summary.name,
+            if summary.solved {
+                "solved"
+            } else if summary.interrupted {
+                "interrupted"
+            } else {
+                "no solution"
+            },
+            summary_count(summary.moves, "-"),
+            summary_count(summary.pushes, "-"),
+            summary.created,
+            summary.visited,
+            summary.time,
+            summary.memory
+        );
+    }
+
+    let solved = summaries.iter().filter(|summary| summary.solved).count();
+    let time: f64 = summaries.iter().map(|summary| summary.time).sum();
+    println!(
+        "Solved {solved} of {} levels in {time:.3} s",
+        summaries.len()
+    );
+}
+
+fn summary_csv(summaries: &[LevelSummary]) -> String {
+    use std::fmt::Write;
+
+    let mut csv = String::from("level,solved,moves,pushes,created,visited,time,memory\n");
+    for summary in summaries {
+        writeln!(
+            csv,
+            "\"{}\",{},{},{},{},{},{:.3},{}",
+            summary.name.replace('"', "\"\""),
+            summary.solved,
+            summary_count(summary.moves, ""),
+            summary_count(summary.pushes, ""),
+            summary.created,
+            summary.visited,
+            summary.time,
+            summary.memory
+        )
+        .expect("Writing to a String can't fail");
+    }
+    csv
+}
+
+const STATS_CSV_HEADER: &str = "level,depth,created,unique,duplicates,unknown\n";
+
+/// One row per depth and a totals row with `total` instead of the depth
+fn stats_csv_rows(level: &str, stats: &Stats) -> String {
+    use std::fmt::Write;
+
+    let level = level.replace('"', "\"\"");
+    let mut csv = String::new();
+    for depth in stats.by_depth() {
+        writeln!(
+            csv,
+            "\"{level}\",{},{},{},{},{}",
+            depth.depth,
+            depth.created,
+            depth.unique_visited,
+            depth.reached_duplicates,
+            depth.not_reached()
+        )
+        .expect("Writing to a String can't fail");
+    }
+    let created = stats.total_created();
+    let visited = stats.total_unique_visited();
+    let duplicates = stats.total_reached_duplicates();
+    writeln!(
+        csv,
+        "\"{level}\",total,{created},{visited},{duplicates},{}",
+        created - visited - duplicates
+    )
+    .expect("Writing to a String can't fail");
+    csv
+}
+
+fn write_file(path: &Path, contents: &str) {
+    fs::write(path, contents).unwrap_or_else(|err| {
+        eprintln!("Can't write {}: {err}", path.display());
+        process::exit(1);
+    });
+}
+
+/// For subcommands which work with exactly one level
+fn load_single_level(matches: &ArgMatches) -> LoadedLevel {
+    let level_file = matches
+        .get_one::<OsString>(LEVEL_FILE)
+        .expect("Level path is required");
+    let level_num = matches.get_one::<usize>(LEVEL).copied();
+    let mut levels = load_levels([level_file], level_num, false, get_parse_options(matches));
+    if levels.len() != 1 {
+        eprintln!(
+            "Can't load level: {} contains multiple levels, use --level",
+            level_file.to_string_lossy()
+        );
+        process::exit(1);
+    }
+    levels.remove(0)
+}
+
+fn load_solution(matches: &ArgMatches) -> Moves {
+    let solution_file = matches
+        .get_one::<PathBuf>(SOLUTION_FILE)
+        .expect("Solution path is required");
+    let lurd = fs::read_to_string(solution_file).unwrap_or_else(|err| {
+        eprintln!("Can't load solution: {err}");
+        process::exit(1);
+    });
+    Moves::from_lurd(&lurd).unwrap_or_else(|err| {
+        eprintln!("Can't load solution: {err}");
+        process::exit(1);
+    })
+}
+
+fn verify(matches: &ArgMatches) {
+    let loaded = load_single_level(matches);
+    let moves = load_solution(matches);
+
+    match loaded.level.verify_solution(&moves) {
+        Ok(info) => {
+            println!("Valid solution");
+            println!("Moves: {}", info.move_cnt);
+            println!("Pushes: {}", info.push_cnt);
+            println!("Box lines: {}", info.box_lines);
+            println!("Box changes: {}", info.box_changes);
+        }
+        Err(err) => {
+            println!("Invalid solution: {err}");
+            process::exit(1);
+        }
+    }
+}
+
+fn optimize(matches: &ArgMatches) {
+    let loaded = load_single_level(matches);
+    let moves = load_solution(matches);
+    let metric = match matches.get_one::<String>(METRIC).map(String::as_str) {
+        Some(PUSHES) => Metric::Pushes,
+        _ => Metric::Moves,
+    };
+    let window = *matches
+        .get_one::<u32>(WINDOW)
+        .expect("Window has a default") as usize;
+
+    println!("Optimizing {}...", loaded.name);
+    match loaded.level.optimize(&moves, metric, window) {
+        Ok(optimized) => {
+            println!("{optimized}");
+            println!("Moves: {} (was {})", optimized.move_cnt(), moves.move_cnt());
+            println!(
+                "Pushes: {} (was {})",
+                optimized.push_cnt(),
+                moves.push_cnt()
+            );
+        }
+        Err(err) => {
+            println!("Invalid solution: {err}");
+            process::exit(1);
+        }
+    }
+}
+
+fn analyze(matches: &ArgMatches) {
+    let lower_bound = matches.get_flag(LOWER_BOUND);
+    let rooms = matches.get_flag(ROOMS);
+    let difficulty = matches.get_flag(DIFFICULTY);
+    let count_solutions = matches.get_one::<usize>(COUNT_SOLUTIONS).copied();
+    let report = matches.get_flag(REPORT);
+    let json = matches.get_flag(JSON);
+    for LoadedLevel { name, level, .. } in load_levels_from_args(matches) {
+        if json {
+            #[derive(Serialize)]
+            struct NamedReport {
+                name: String,
+                #[serde(flatten)]
+                report: LevelReport,
+            }
+
+            match level.report() {
+                Ok(report) => {
+                    let named = NamedReport { name, report };
+                    let line = serde_json::to_string(&named).expect("Reports are valid JSON");
+                    println!("{line}");
+                }
+                Err(err) => eprintln!("{name}: invalid level: {err}"),
+            }
+            continue;
+        }
+
+        println!("Analyzing {name}...");
+        match level.analyze() {
+            Ok(analysis) => {
+                // analysis succeeded so the level is valid and the unwraps below can't fail
+                if report {
+                    print!("{}", level.report().unwrap());
+                } else {
+                    print!("{analysis}");
+                }
+                // the report already includes them
+                if lower_bound && !report {
+                    let pushes = level.lower_bound(Metric::Pushes).unwrap();
+                    let moves = level.lower_bound(Metric::Moves).unwrap();
+                    println!("Lower bound: {pushes} pushes, {moves} moves");
+                }
+                if rooms {
+                    print!("{}", level.map_analysis().unwrap());
+                }
+                if difficulty {
+                    print!("{}", level.estimate_difficulty().unwrap());
+                }
+                if let Some(limit) = count_solutions {
+                    let all = level.optimal_solutions(Method::Pushes, limit).unwrap();
+                    println!("Push-optimal solutions: {}", all.count);
+                    for moves in &all.solutions {
+                        println!("{moves}");
+                    }
+                }
+                println!();
+            }
+            Err(err) => println!("Invalid level: {err}\n"),
+        }
+    }
+}
+
+fn fmt(matches: &ArgMatches) {
+    let format = get_format(matches);
+    let levels = load_levels_from_args(matches);
+    let quarter_turns = match matches.get_one::<String>(ROTATE).map(String::as_str) {
+        Some("90") => 1,
+        Some("180") => 2,
+        Some("270") => 3,
+        _ => 0,
+    };
+    let axis = match matches.get_one::<String>(MIRROR).map(String::as_str) {
+        Some("horizontal") => Some(Axis::Horizontal),
+        Some("vertical") => Some(Axis::Vertical),
+        _ => None,
+    };
+    for (i, loaded) in levels.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        let mut level = loaded.level.clone();
+        for _ in 0..quarter_turns {
+            level = level.rotated(true);
+        }
+        if let Some(axis) = axis {
+            level = level.mirrored(axis);
+        }
+        print!("{}", level.format(format));
+    }
+}
+
+fn compare(matches: &ArgMatches) {
+    let load = |path: &PathBuf| {
+        let json = fs::read_to_string(path).unwrap_or_else(|err| {
+            eprintln!("Can't read {}: {err}", path.display());
+            process::exit(1);
+        });
+        Results::from_json(&json).unwrap_or_else(|err| {
+            eprintln!("{}: {err}", path.display());
+            process::exit(1);
+        })
+    };
+    let old = load(matches.get_one::<PathBuf>(OLD).expect("Required"));
+    let new = load(matches.get_one::<PathBuf>(NEW).expect("Required"));
+
+    let changes = old.compare(&new);
+    for change in &changes {
+        println!("{change}");
+    }
+    if changes.is_empty() {
+        println!("No changes in {} levels", old.levels.len());
+    } else {
+        process::exit(1);
+    }
+}
+
+fn verify_solutions(matches: &ArgMatches) {
+    // solution databases are collections so use all levels by default
+    let level_num = matches.get_one::<usize>(LEVEL).copied();
+    let levels = load_levels(
+        matches
+            .get_many::<OsString>(LEVEL_FILE)
+            .expect("Level path is required"),
+        level_num,
+        level_num.is_none(),
+        get_parse_options(matches),
+    );
+    let names: Vec<_> = levels.iter().map(|loaded| loaded.name.clone()).collect();
+    let collection = Collection::new(
+        levels
+            .into_iter()
+            .map(|loaded| (loaded.metadata, loaded.level))
+            .collect(),
+    );
+
+    let checks = collection.verify_solutions();
+    for check in &checks {
+        println!("{} {}: {check}", names[check.index], check.key);
+    }
+    let problems = checks.iter().filter(|check| check.is_discrepancy()).count();
+    println!(
+        "Checked {} solutions of {} levels, {problems} problems",
+        checks.len(),
+        names.len()
+    );
+    if problems > 0 {
+        process::exit(1);
+    }
+}
+
+fn dedupe(matches: &ArgMatches) {
+    let levels = load_levels(
+        matches
+            .get_many::<OsString>(LEVEL_FILE)
+            .expect("Level path is required"),
+        None,
+        true,
+        ParseOptions::default(),
+    );
+    let groups = collection::find_duplicates(levels.iter().map(|loaded| &loaded.level));
+    for group in &groups {
+        let first = &levels[group[0]];
+        println!("{}", first.name);
+        for &i in &group[1..] {
+            let kind = if levels[i].level.xsb().to_string() == first.level.xsb().to_string() {
+                "identical"
+            } else {
+                "equivalent"
+            };
+            println!("    {} ({kind})", levels[i].name);
+        }
+    }
+    let duplicate_cnt: usize = groups.iter().map(|group| group.len() - 1).sum();
+    println!(
+        "Found {duplicate_cnt} duplicates of {} levels among {} levels",
+        groups.len(),
+        levels.len()
+    );
+}
+
+fn generate(matches: &ArgMatches) {
+    let mode = if matches.get_flag(REVERSE) {
+        Mode::Reverse {
+            pulls: generator::DEFAULT_PULLS,
+        }
+    } else {
+        Mode::Random
+    };
+    let options = GeneratorOptions {
+        mode,
+        rows: *matches.get_one(ROWS).expect("Rows has a default"),
+        cols: *matches.get_one(COLS).expect("Cols has a default"),
+        boxes: *matches.get_one(BOXES).expect("Boxes has a default"),
+        min_pushes: *matches
+            .get_one(MIN_PUSHES)
+            .expect("Min pushes has a default"),
+        ..GeneratorOptions::default()
+    };
+    let seed = matches
+        .get_one(SEED)
+        .copied()
+        .unwrap_or_else(|| fastrand::u64(..));
+    match generator::generate(&options, seed) {
+        Ok(generated) => {
+            print!("{}", generated.level.xsb());
+            // stderr so the level alone can be redirected to a file
+            eprintln!(
+                "Seed: {seed}, optimal solution: {} pushes, {} moves",
+                generated.solution.push_cnt(),
+                generated.solution.move_cnt()
+            );
+        }
+        Err(err) => {
+            eprintln!("Can't generate level (seed {seed}): {err}");
+            process::exit(1);
+        }
+    }
+}
+
+fn play(matches: &ArgMatches) {
+    let LoadedLevel { name, level, .. } = load_single_level(matches);
+    let game = play_level(&name, level).unwrap_or_else(|err| {
+        eprintln!("Can't play: {err}");
+        process::exit(1);
+    });
+    if game.moves().move_cnt() > 0 {
+        println!("{}", game.moves());
+        println!("Moves: {}", game.moves().move_cnt());
+        println!("Pushes: {}", game.moves().push_cnt());
+    }
+}
+
+/// Puts the terminal into raw mode and restores it when dropped,
+/// even when returning early because of an error
+struct RawTerminal;
+
+impl RawTerminal {
+    fn enter() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), EnterAlternateScreen, cursor::Hide)?;
+        Ok(RawTerminal)
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        // nothing better to do with errors here
+        let _ = execute!(io::stdout(), cursor::Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+fn play_level(name: &str, level: Level) -> io::Result<Board> {
+    let _terminal = RawTerminal::enter()?;
+    let mut game = Board::new(level);
+    let mut message = String::new();
+    loop {
+        draw_game(name, &game, &message)?;
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        // windows also reports releasing keys
+        if key.kind == KeyEventKind::Release {
+            continue;
+        }
+        message.clear();
+
+        let dir = match key.code {
+            KeyCode::Up => Dir::Up,
+            KeyCode::Right => Dir::Right,
+            KeyCode::Down => Dir::Down,
+            KeyCode::Left => Dir::Left,
+            KeyCode::Tab if game.player_cnt() > 1 => {
+                game.next_player();
+                continue;
+            }
+            KeyCode::Char('u') => {
+                if !game.undo() {
+                    message.push_str("Nothing to undo");
+                }
+                continue;
+            }
+            KeyCode::Char(ch @ ('s' | 'S')) => {
+                message = ask_solver(name, &mut game, ch == 'S')?;
+                continue;
+            }
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break,
+            KeyCode::Char('q') | KeyCode::Esc => break,
+            _ => continue,
+        };
+        // pulling is optional so the player can still walk away from a box
+        let pull =
+            game.level().variant() == Variant::Pull && key.modifiers.contains(KeyModifiers::SHIFT);
+        let outcome = if pull {
+            game.try_pull(dir)
+        } else {
+            game.try_move(dir)
+        };
+        match outcome {
+            MoveOutcome::Blocked(err) => message = err.to_string(),
+            _ if game.is_solved() => {
+                message = format!(
+                    "Solved in {} moves and {} pushes",
+                    game.moves().move_cnt(),
+                    game.moves().push_cnt()
+                );
+            }
+            _ => {}
+        }
+    }
+    Ok(game)
+}
+
+/// Solves the level from the current position and makes either
+/// the moves up to the first push (a hint) or the whole solution.
+/// Returns the message to show.
+fn ask_solver(name: &str, game: &mut Board, full: bool) -> io::Result<String> {
+    draw_game(name, game, "Solving... (Esc to stop)")?;
+
+    // the search runs on this thread so check for Esc in the progress callback
+    let mut progress = |_: &Stats| {
+        while let Ok(true) = event::poll(Duration::ZERO) {
+            if let Ok(Event::Key(key)) = event::read() {
+                if key.code == KeyCode::Esc {
+                    return false;
+                }
+            }
+        }
+        true
+    };
+    let solver_ok = match game.level().solve_with_progress(
+        Method::PushesMoves,
+        &SolverOptions::default(),
+        &mut progress,
+    ) {
+        Ok(solver_ok) => solver_ok,
+        Err(err) => return Ok(format!("Can't solve: {err}")),
+    };
+    let moves = match solver_ok.moves {
+        Some(moves) => moves,
+        None if solver_ok.outcome() == SearchOutcome::LimitReached => {
+            return Ok("Search stopped".to_owned())
+        }
+        None => match solver_ok.unsolvable {
+            Some(reason) => return Ok(format!("No solution from this position: {reason}")),
+            None => return Ok("No solution from this position".to_owned()),
+        },
+    };
+
+    let played = if full {
+        game.play(&moves)
+    } else {
+        game.play_pushes(&moves, 1)
+    };
+    played.expect("Solver returned an invalid solution");
+    if game.is_solved() {
+        Ok(format!(
+            "Solved in {} moves and {} pushes",
+            game.moves().move_cnt(),
+            game.moves().push_cnt()
+        ))
+    } else {
+        Ok(format!("Pushes left: {}", moves.push_cnt() - 1))
+    }
+}
+
+fn draw_game(name: &str, game: &Board, message: &str) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    queue!(stdout, Clear(ClearType::All), MoveTo(0, 0))?;
+    let map = game.level().xsb().to_string();
+    let player = if game.player_cnt() > 1 {
+        let (r, c) = game.level().player_positions()[game.player()];
+        format!("  Player: {} at [{r}, {c}]", game.player() + 1)
+    } else {
+        String::new()
+    };
+    let status = format!(
+        "Moves: {}  Pushes: {}{player}",
+        game.moves().move_cnt(),
+        game.moves().push_cnt()
+    );
+    let help = match game.level().variant() {
+        Variant::Push => "Arrows: move  u: undo  s: hint  S: solve  q: quit",
+        Variant::Multiban => "Arrows: move  Tab: next player  u: undo  s: hint  S: solve  q: quit",
+        Variant::Pull => "Arrows: move  Shift+arrows: pull  u: undo  s: hint  S: solve  q: quit",
+    };
+    // raw mode doesn't return to the start of the line on \n
+    for line in [name, ""]
+        .iter()
+        .copied()
+        .chain(map.lines())
+        .chain(["", &status, message, "", help].iter().copied())
+    {
+        queue!(stdout, Print(line), MoveToNextLine(1))?;
+    }
+    stdout.flush()
+}
+
+#[derive(Debug, Deserialize)]
+struct ServeRequest {
+    #[serde(default)]
+    id: serde_json::Value,
+    level: String,
+    method: Option<String>,
+    /// Seconds
+    timeout: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+enum ServeResponse {
+    Progress {
+        id: serde_json::Value,
+        created: i32,
+        visited: i32,
+    },
+    Result {
+        id: serde_json::Value,
+        solved: bool,
+        /// LURD
+        solution: Option<String>,
+        moves: Option<usize>,
+        pushes: Option<usize>,
+        created: i32,
+        visited: i32,
+        timed_out: bool,
+    },
+    Error {
+        id: serde_json::Value,
+        message: String,
+    },
+}
+
+fn serve(matches: &ArgMatches) {
+    let result = match matches.get_one::<String>(LISTEN) {
+        None => serve_connection(io::stdin().lock(), io::stdout().lock()),
+        Some(address) => TcpListener::bind(address).map(|listener| {
+            for stream in listener.incoming() {
+                // a failed connection shouldn't stop serving the other clients
+                let connection = stream.and_then(|stream| Ok((stream.try_clone()?, stream)));
+                let (reader, stream) = match connection {
+                    Ok(connection) => connection,
+                    Err(err) => {
+                        eprintln!("Can't accept connection: {err}");
+                        continue;
+                    }
+                };
+                // each connection gets its own thread so slow levels don't block other clients
+                thread::spawn(move || serve_connection(BufReader::new(reader), stream));
             }
+        }),
+    };
+    result.unwrap_or_else(|err| {
+        eprintln!("Can't serve: {err}");
+        process::exit(1);
+    });
+}
+
+fn serve_connection<R: BufRead, W: Write>(input: R, mut output: W) -> io::Result<()> {
+    fn send<W: Write>(output: &mut W, response: &ServeResponse) -> io::Result<()> {
+        serde_json::to_writer(&mut *output, response)?;
+        writeln!(output)?;
+        output.flush()
+    }
+
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
         }
+
+        let request: ServeRequest = match serde_json::from_str(&line) {
+            Ok(request) => request,
+            Err(err) => {
+                let id = serde_json::Value::Null;
+                send(
+                    &mut output,
+                    &ServeResponse::Error {
+                        id,
+                        message: err.to_string(),
+                    },
+                )?;
+                continue;
+            }
+        };
+        let ServeRequest {
+            id,
+            level,
+            method,
+            timeout,
+        } = request;
+        let parsed = level
+            .parse::<Level>()
+            .map_err(|err| err.to_string())
+            .and_then(|level| {
+                let method = method.as_deref().unwrap_or("any");
+                let method = method.parse::<Method>().map_err(|err| err.to_string())?;
+                // negative, infinite or NaN timeouts would panic in `Duration::from_secs_f64`
+                let timeout = timeout
+                    .map(Duration::try_from_secs_f64)
+                    .transpose()
+                    .map_err(|err| format!("Invalid timeout: {err}"))?;
+                Ok((level, method, timeout))
+            });
+        let (level, method, timeout) = match parsed {
+            Ok(parsed) => parsed,
+            Err(message) => {
+                send(&mut output, &ServeResponse::Error { id, message })?;
+                continue;
+            }
+        };
+
+        let started = Instant::now();
+        let mut timed_out = false;
+        // errors from inside the callback are returned after the search stops
+        let mut send_result = Ok(());
+        let mut progress = |stats: &Stats| {
+            let response = ServeResponse::Progress {
+                id: id.clone(),
+                created: stats.total_created(),
+                visited: stats.total_unique_visited(),
+            };
+            send_result = send(&mut output, &response);
+            timed_out = timeout.is_some_and(|timeout| started.elapsed() > timeout);
+            send_result.is_ok() && !timed_out
+        };
+        let solved = level.solve_with_progress(method, &SolverOptions::default(), &mut progress);
+        send_result?;
+
+        let response = match solved {
+            Ok(solver_ok) => ServeResponse::Result {
+                id,
+                solved: solver_ok.moves.is_some(),
+                solution: solver_ok.moves.as_ref().map(ToString::to_string),
+                moves: solver_ok.moves.as_ref().map(Moves::move_cnt),
+                pushes: solver_ok.moves.as_ref().map(Moves::push_cnt),
+                created: solver_ok.stats.total_created(),
+                visited: solver_ok.stats.total_unique_visited(),
+                timed_out,
+            },
+            Err(err) => ServeResponse::Error {
+                id,
+                message: err.to_string(),
+            },
+        };
+        send(&mut output, &response)?;
     }
+    Ok(())
 }
@@ -11,10 +11,13 @@ use crate::vec2d::Vec2d;
 pub(crate) trait Map {
     fn grid(&self) -> &Vec2d<MapCell>;
 
-    // this is a hack for things that are not performance critical
-    // like backtracking and replaying moves during formatting to share some code
-    // still would be nice to get rid of it someday
-    fn remover(&self) -> Option<Pos>;
+    fn goal_cnt(&self) -> usize;
+
+    /// Every goal has a box and there are no other boxes
+    /// (with removers, boxes not needed for goals have to be removed)
+    fn is_solved(&self, boxes: &[Pos]) -> bool {
+        boxes.len() == self.goal_cnt() && boxes.iter().all(|&b| self.grid()[b] == MapCell::Goal)
+    }
 
     fn xsb(&self) -> MapFormatter<'_> {
         self.format(Format::Xsb)
@@ -58,6 +61,7 @@ impl Debug for &dyn Map {
 pub(crate) enum MapType {
     Goals(GoalMap),
     Remover(RemoverMap),
+    Hybrid(HybridMap),
 }
 
 impl MapType {
@@ -65,6 +69,7 @@ impl MapType {
         match self {
             MapType::Goals(ref goals_map) => goals_map,
             MapType::Remover(ref remover_map) => remover_map,
+            MapType::Hybrid(ref hybrid_map) => hybrid_map,
         }
     }
 }
@@ -74,11 +79,12 @@ impl Map for MapType {
         self.map().grid()
     }
 
-    fn remover(&self) -> Option<Pos> {
-        match self {
-            MapType::Goals(gm) => gm.remover(),
-            MapType::Remover(rm) => rm.remover(),
-        }
+    fn goal_cnt(&self) -> usize {
+        self.map().goal_cnt()
+    }
+
+    fn is_solved(&self, boxes: &[Pos]) -> bool {
+        self.map().is_solved(boxes)
     }
 }
 
@@ -86,11 +92,17 @@ impl Map for MapType {
 pub(crate) struct GoalMap {
     pub(crate) grid: Vec2d<MapCell>,
     pub(crate) goals: Vec<Pos>,
+    /// There can be more goals than boxes, any of them can stay empty
+    pub(crate) extra_goals: bool,
 }
 
 impl GoalMap {
     pub(crate) fn new(grid: Vec2d<MapCell>, goals: Vec<Pos>) -> Self {
-        GoalMap { grid, goals }
+        GoalMap {
+            grid,
+            goals,
+            extra_goals: false,
+        }
     }
 }
 
@@ -99,8 +111,13 @@ impl Map for GoalMap {
         &self.grid
     }
 
-    fn remover(&self) -> Option<Pos> {
-        None
+    fn goal_cnt(&self) -> usize {
+        self.goals.len()
+    }
+
+    fn is_solved(&self, boxes: &[Pos]) -> bool {
+        (self.extra_goals || boxes.len() == self.goals.len())
+            && boxes.iter().all(|&b| self.grid[b] == MapCell::Goal)
     }
 }
 
@@ -122,12 +139,13 @@ impl Debug for GoalMap {
 #[derive(Clone)]
 pub(crate) struct RemoverMap {
     pub(crate) grid: Vec2d<MapCell>,
-    pub(crate) remover: Pos,
+    /// Any number of boxes can be pushed into any of them
+    pub(crate) removers: Vec<Pos>,
 }
 
 impl RemoverMap {
-    pub(crate) fn new(grid: Vec2d<MapCell>, remover: Pos) -> Self {
-        Self { grid, remover }
+    pub(crate) fn new(grid: Vec2d<MapCell>, removers: Vec<Pos>) -> Self {
+        Self { grid, removers }
     }
 }
 
@@ -136,8 +154,8 @@ impl Map for RemoverMap {
         &self.grid
     }
 
-    fn remover(&self) -> Option<Pos> {
-        Some(self.remover)
+    fn goal_cnt(&self) -> usize {
+        0
     }
 }
 
@@ -154,6 +172,47 @@ impl Debug for RemoverMap {
     }
 }
 
+/// Goals which all need a box and removers for the boxes which don't fit on goals
+#[derive(Clone)]
+pub(crate) struct HybridMap {
+    pub(crate) grid: Vec2d<MapCell>,
+    pub(crate) goals: Vec<Pos>,
+    pub(crate) removers: Vec<Pos>,
+}
+
+impl HybridMap {
+    pub(crate) fn new(grid: Vec2d<MapCell>, goals: Vec<Pos>, removers: Vec<Pos>) -> Self {
+        Self {
+            grid,
+            goals,
+            removers,
+        }
+    }
+}
+
+impl Map for HybridMap {
+    fn grid(&self) -> &Vec2d<MapCell> {
+        &self.grid
+    }
+
+    fn goal_cnt(&self) -> usize {
+        self.goals.len()
+    }
+}
+
+impl Display for HybridMap {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mf = MapFormatter::new(self.grid(), None, Format::Xsb);
+        write!(f, "{mf}")
+    }
+}
+
+impl Debug for HybridMap {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{self}")
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
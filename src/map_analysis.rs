@@ -0,0 +1,247 @@
+//! Decomposition of the area reachable by the player (ignoring boxes)
+//! into rooms, corridors and articulation squares.
+
+use std::fmt::{self, Display, Formatter};
+
+use crate::data::{MapCell, Pos, DIRECTIONS};
+use crate::level::Level;
+use crate::solver::preprocessing;
+use crate::solver::SolverErr;
+use crate::vec2d::Vec2d;
+
+/// The structure of the playable area, all positions are `(row, column)` in row-major order
+#[derive(Debug, Clone)]
+pub struct MapAnalysis {
+    /// Connected groups of squares which are not corridors
+    pub rooms: Vec<Vec<(usize, usize)>>,
+    /// Squares with walls on both sides, either horizontally or vertically
+    pub corridors: Vec<(usize, usize)>,
+    /// Squares which split the playable area into disconnected parts when blocked (e.g. by a box)
+    pub articulation_squares: Vec<(usize, usize)>,
+    /// The map with rooms labeled by letters, corridors by `=` and articulation squares by `+`
+    map: String,
+}
+
+impl Level {
+    pub fn map_analysis(&self) -> Result<MapAnalysis, SolverErr> {
+        let grid = preprocessing::check_reachability(self.map(), &self.state)?;
+        let mut playable = grid.scratchpad();
+        for pos in grid.positions() {
+            playable[pos] = grid[pos] != MapCell::Wall;
+        }
+
+        let corridors = corridors(&playable);
+        let rooms = rooms(&playable, &corridors);
+        let articulation_squares = articulation_squares(&playable, self.state.player_pos);
+
+        let mut room_ids = grid.scratchpad();
+        for (i, room) in rooms.iter().enumerate() {
+            for &pos in room {
+                room_ids[pos] = Some(i);
+            }
+        }
+
+        let mut map = String::new();
+        for r in 0..grid.rows() {
+            for c in 0..grid.cols() {
+                let pos = Pos::new(r, c);
+                let ch = if grid[pos] == MapCell::Wall && self.map().grid()[pos] == MapCell::Wall {
+                    '#'
+                } else if !playable[pos] {
+                    ' '
+                } else if articulation_squares[pos] {
+                    '+'
+                } else if let Some(room) = room_ids[pos] {
+                    (b'a' + (room % 26) as u8) as char
+                } else {
+                    // squares not in any room are corridors
+                    '='
+                };
+                map.push(ch);
+            }
+            // same as XSB output
+            let trimmed = map.trim_end_matches(' ').len();
+            map.truncate(trimmed);
+            map.push('\n');
+        }
+
+        Ok(MapAnalysis {
+            rooms: rooms
+                .into_iter()
+                .map(|room| room.into_iter().map(Into::into).collect())
+                .collect(),
+            corridors: positions(&corridors),
+            articulation_squares: positions(&articulation_squares),
+            map,
+        })
+    }
+}
+
+fn positions(squares: &Vec2d<bool>) -> Vec<(usize, usize)> {
+    squares
+        .positions()
+        .filter(|&pos| squares[pos])
+        .map(Into::into)
+        .collect()
+}
+
+/// Playable squares whose left and right or top and bottom neighbors are not playable
+pub(crate) fn corridors(playable: &Vec2d<bool>) -> Vec2d<bool> {
+    let mut corridors = playable.scratchpad();
+    for pos in playable.positions() {
+        if !playable[pos] {
+            continue;
+        }
+        let [up, right, down, left] = pos.neighbors();
+        corridors[pos] =
+            (!playable[up] && !playable[down]) || (!playable[left] && !playable[right]);
+    }
+    corridors
+}
+
+/// Connected components of playable squares which are not corridors
+pub(crate) fn rooms(playable: &Vec2d<bool>, corridors: &Vec2d<bool>) -> Vec<Vec<Pos>> {
+    let mut rooms = Vec::new();
+    let mut visited = playable.scratchpad();
+    for start in playable.positions() {
+        if !playable[start] || corridors[start] || visited[start] {
+            continue;
+        }
+        visited[start] = true;
+        let mut room = Vec::new();
+        let mut to_visit = vec![start];
+        while let Some(pos) = to_visit.pop() {
+            room.push(pos);
+            for &dir in &DIRECTIONS {
+                let new_pos = pos + dir;
+                if playable[new_pos] && !corridors[new_pos] && !visited[new_pos] {
+                    visited[new_pos] = true;
+                    to_visit.push(new_pos);
+                }
+            }
+        }
+        room.sort();
+        rooms.push(room);
+    }
+    rooms
+}
+
+/// Cut vertices of the graph of playable squares connected to `start`
+/// found by an iterative version of Tarjan's algorithm (large levels would overflow the stack)
+pub(crate) fn articulation_squares(playable: &Vec2d<bool>, start: Pos) -> Vec2d<bool> {
+    let mut articulation = playable.scratchpad();
+    // discovery times starting at 1, 0 means not visited yet
+    let mut discovered: Vec2d<u32> = playable.scratchpad();
+    // the earliest discovered square reachable from the subtree using one back edge
+    let mut low: Vec2d<u32> = playable.scratchpad();
+    let mut time = 1;
+    discovered[start] = time;
+    low[start] = time;
+    let mut start_children = 0;
+
+    // square, its parent in the DFS tree and the index of the next direction to explore
+    let mut stack = vec![(start, start, 0)];
+    while let Some(&(pos, parent, next_dir)) = stack.last() {
+        if next_dir < DIRECTIONS.len() {
+            stack.last_mut().expect("Stack is not empty").2 += 1;
+            let new_pos = pos + DIRECTIONS[next_dir];
+            if !playable[new_pos] || new_pos == parent {
+                continue;
+            }
+            if discovered[new_pos] == 0 {
+                time += 1;
+                discovered[new_pos] = time;
+                low[new_pos] = time;
+                if pos == start {
+                    start_children += 1;
+                }
+                stack.push((new_pos, pos, 0));
+            } else {
+                low[pos] = low[pos].min(discovered[new_pos]);
+            }
+        } else {
+            stack.pop();
+            if pos != start {
+                low[parent] = low[parent].min(low[pos]);
+                if parent != start && low[pos] >= discovered[parent] {
+                    articulation[parent] = true;
+                }
+            }
+        }
+    }
+    // the root of the DFS tree is special
+    articulation[start] = start_children > 1;
+
+    articulation
+}
+
+impl Display for MapAnalysis {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Rooms: {}", self.rooms.len())?;
+        writeln!(f, "Corridor squares: {}", self.corridors.len())?;
+        writeln!(
+            f,
+            "Articulation squares: {}",
+            self.articulation_squares.len()
+        )?;
+        write!(f, "{}", self.map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rooms_and_corridors() {
+        let level: Level = r"
+#########
+#   #   #
+# @   $ #
+#   # . #
+#########"
+            .parse()
+            .unwrap();
+        let analysis = level.map_analysis().unwrap();
+        assert_eq!(analysis.rooms.len(), 2);
+        assert_eq!(analysis.rooms[0].len(), 9);
+        assert_eq!(analysis.rooms[1][0], (1, 5));
+        assert_eq!(analysis.corridors, vec![(2, 4)]);
+        assert_eq!(analysis.articulation_squares, vec![(2, 3), (2, 4), (2, 5)]);
+        assert_eq!(
+            analysis.to_string(),
+            r"Rooms: 2
+Corridor squares: 1
+Articulation squares: 3
+#########
+#aaa#bbb#
+#aa+++bb#
+#aaa#bbb#
+#########
+"
+        );
+    }
+
+    #[test]
+    fn articulation() {
+        // a cycle has no articulation squares, a dead end does
+        let level: Level = r"
+######
+#    #
+# ## ###
+#@ $  .#
+########"
+            .parse()
+            .unwrap();
+        let analysis = level.map_analysis().unwrap();
+        assert_eq!(analysis.articulation_squares, vec![(3, 4), (3, 5)]);
+        assert_eq!(analysis.corridors.len(), 8);
+        assert_eq!(analysis.rooms.len(), 4);
+    }
+
+    #[test]
+    fn incomplete_border() {
+        let level: Level = "#####\n#@$.\n#####".parse().unwrap();
+        assert!(level.map_analysis().is_err());
+    }
+}
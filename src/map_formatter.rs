@@ -26,7 +26,9 @@ impl<'a> MapFormatter<'a> {
             for &b in &state.boxes {
                 state_grid[b] = Contents::Box;
             }
-            state_grid[state.player_pos] = Contents::Player;
+            for player_pos in state.players() {
+                state_grid[player_pos] = Contents::Player;
+            }
         }
 
         for r in 0..self.grid.rows() {
@@ -39,20 +41,58 @@ impl<'a> MapFormatter<'a> {
                 }
             }
 
-            for c in 0..=last_non_empty {
+            let row = (0..=last_non_empty).map(|c| {
                 let pos = Pos::new(r, c);
-                let cell = self.grid[pos];
-
-                match self.format {
-                    Format::Custom => Self::write_cell_custom(cell, state_grid[pos], f)?,
-                    Format::Xsb => Self::write_cell_xsb(cell, state_grid[pos], f)?,
+                (self.grid[pos], state_grid[pos])
+            });
+            match self.format {
+                Format::Custom => {
+                    for (cell, contents) in row {
+                        Self::write_cell_custom(cell, contents, f)?;
+                    }
+                    writeln!(f)?;
+                }
+                Format::Xsb => {
+                    for (cell, contents) in row {
+                        write!(f, "{}", Self::xsb_char(cell, contents))?;
+                    }
+                    writeln!(f)?;
+                }
+                Format::XsbRle => {
+                    // spaces can't be used because they'd get trimmed at the start and end of lines
+                    let chars: Vec<_> = row
+                        .map(|(cell, contents)| match Self::xsb_char(cell, contents) {
+                            ' ' => '-',
+                            c => c,
+                        })
+                        .collect();
+                    Self::write_row_rle(&chars, f)?;
+                    if r + 1 < self.grid.rows() {
+                        write!(f, "|")?;
+                    }
                 }
             }
+        }
+        if self.format == Format::XsbRle {
             writeln!(f)?;
         }
         Ok(())
     }
 
+    fn write_row_rle(chars: &[char], f: &mut Formatter<'_>) -> fmt::Result {
+        let mut i = 0;
+        while i < chars.len() {
+            let run = chars[i..].iter().take_while(|&&c| c == chars[i]).count();
+            if run > 1 {
+                write!(f, "{}{}", run, chars[i])?;
+            } else {
+                write!(f, "{}", chars[i])?;
+            }
+            i += run;
+        }
+        Ok(())
+    }
+
     fn write_cell_custom(cell: MapCell, contents: Contents, f: &mut Formatter<'_>) -> fmt::Result {
         if cell == MapCell::Wall {
             write!(f, "<>")?;
@@ -61,41 +101,41 @@ impl<'a> MapFormatter<'a> {
                 Contents::Empty => write!(f, " ")?,
                 Contents::Box => write!(f, "B")?,
                 Contents::Player => write!(f, "P")?,
-            };
+            }
             match cell {
                 MapCell::Empty => write!(f, " ")?,
                 MapCell::Goal => write!(f, "_")?,
                 MapCell::Remover => write!(f, "R")?,
                 MapCell::Wall => unreachable!("Wall again"),
-            };
+            }
         }
         Ok(())
     }
 
-    fn write_cell_xsb(cell: MapCell, contents: Contents, f: &mut Formatter<'_>) -> fmt::Result {
+    fn xsb_char(cell: MapCell, contents: Contents) -> char {
         match (cell, contents) {
-            (MapCell::Empty, Contents::Empty) => write!(f, " "),
-            (MapCell::Empty, Contents::Box) => write!(f, "$"),
-            (MapCell::Empty, Contents::Player) => write!(f, "@"),
-            (MapCell::Wall, Contents::Empty) => write!(f, "#"),
+            (MapCell::Empty, Contents::Empty) => ' ',
+            (MapCell::Empty, Contents::Box) => '$',
+            (MapCell::Empty, Contents::Player) => '@',
+            (MapCell::Wall, Contents::Empty) => '#',
             (MapCell::Wall, _) => unreachable!("Wall with non-empty contents"),
-            (MapCell::Goal, Contents::Empty) => write!(f, "."),
-            (MapCell::Goal, Contents::Box) => write!(f, "*"),
-            (MapCell::Goal, Contents::Player) => write!(f, "+"),
-            (MapCell::Remover, Contents::Empty) => write!(f, "r"),
+            (MapCell::Goal, Contents::Empty) => '.',
+            (MapCell::Goal, Contents::Box) => '*',
+            (MapCell::Goal, Contents::Player) => '+',
+            (MapCell::Remover, Contents::Empty) => 'r',
             (MapCell::Remover, Contents::Box) => unreachable!("Remover with box"),
-            (MapCell::Remover, Contents::Player) => write!(f, "R"),
+            (MapCell::Remover, Contents::Player) => 'R',
         }
     }
 }
 
-impl<'a> Display for MapFormatter<'a> {
+impl Display for MapFormatter<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         self.write_to_formatter(f)
     }
 }
 
-impl<'a> Debug for MapFormatter<'a> {
+impl Debug for MapFormatter<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         write!(f, "{self}")
     }
@@ -1,16 +1,56 @@
-use std::fmt::{self, Debug, Display, Formatter};
+use std::error::Error;
+use std::fmt::{self, Debug, Display, Formatter, Write as _};
+use std::iter::{self, FromIterator};
 
 use crate::data::Dir;
+use crate::level::Level;
+use crate::verify::LevelStates;
+
+/// Invalid character `ch` at `index` (counted in chars) in a LURD string
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LurdErr {
+    pub index: usize,
+    pub ch: char,
+}
+
+impl Display for LurdErr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Invalid character '{}' at index {} in LURD string",
+            self.ch, self.index
+        )
+    }
+}
+
+impl Error for LurdErr {}
+
+/// The highest count of a repeated move in run-length LURD (see `Moves::from_lurd`)
+pub const MAX_REPEAT: usize = u16::MAX as usize;
+
+/// The most moves `Moves::from_lurd` expands its input to, far more than any real solution
+pub const MAX_MOVES: usize = 10_000_000;
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Move {
     pub(crate) dir: Dir,
     pub(crate) is_push: bool,
+    /// Index of the player making the move, always 0 except in Multiban levels
+    pub(crate) player: u8,
 }
 
 impl Move {
     pub(crate) fn new(dir: Dir, is_push: bool) -> Self {
-        Move { dir, is_push }
+        Move {
+            dir,
+            is_push,
+            player: 0,
+        }
+    }
+
+    #[must_use]
+    pub(crate) fn with_player(self, player: u8) -> Self {
+        Move { player, ..self }
     }
 }
 
@@ -31,6 +71,15 @@ impl Debug for Move {
     }
 }
 
+/// One push of a solution returned without the player's moves (`SolveOutput::Pushes`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Push {
+    /// `(row, column)` of the box before the push
+    pub box_pos: (usize, usize),
+    /// Where the box moves (also when pulling)
+    pub dir: Dir,
+}
+
 #[derive(Clone, Default, PartialEq, Eq, Hash)]
 pub struct Moves(Vec<Move>);
 
@@ -40,6 +89,78 @@ impl Moves {
         Moves(moves)
     }
 
+    /// Parses moves in the LURD format - lowercase letters are moves, uppercase are pushes.
+    ///
+    /// Whitespace is ignored since long solutions are often split into multiple lines.
+    /// In Multiban levels, `[n]` makes the `n`th player (starting at 1) do the following moves.
+    /// A number before a letter repeats it (run-length LURD from `to_compressed_lurd`),
+    /// it has to be followed by the letter directly. Counts above `MAX_REPEAT`
+    /// are rejected at the digit which exceeds it and moves past the first `MAX_MOVES`
+    /// at their letter so short inputs can't take up all memory.
+    pub fn from_lurd(lurd: &str) -> Result<Self, LurdErr> {
+        // each letter with how many times it's repeated
+        let mut runs = Vec::new();
+        let mut total = 0;
+        let mut player = 0;
+        // where the count started and its value so far
+        let mut count: Option<(usize, char, usize)> = None;
+        let mut chars = lurd.chars().enumerate();
+        while let Some((index, ch)) = chars.next() {
+            if let Some(digit) = ch.to_digit(10) {
+                let (start, first, value) = count.unwrap_or((index, ch, 0));
+                let value = value * 10 + digit as usize;
+                if value > MAX_REPEAT {
+                    return Err(LurdErr { index, ch });
+                }
+                count = Some((start, first, value));
+                continue;
+            }
+            let dir = match ch.to_ascii_lowercase() {
+                'u' => Dir::Up,
+                'r' => Dir::Right,
+                'd' => Dir::Down,
+                'l' => Dir::Left,
+                '[' if count.is_none() => {
+                    player = parse_player(index, &mut chars)?;
+                    continue;
+                }
+                _ if ch.is_whitespace() && count.is_none() => continue,
+                _ => return Err(LurdErr { index, ch }),
+            };
+            let repeat = match count.take() {
+                Some((start, first, 0)) => {
+                    return Err(LurdErr {
+                        index: start,
+                        ch: first,
+                    })
+                }
+                Some((_, _, value)) => value,
+                None => 1,
+            };
+            total += repeat;
+            if total > MAX_MOVES {
+                return Err(LurdErr { index, ch });
+            }
+            runs.push((
+                Move::new(dir, ch.is_ascii_uppercase()).with_player(player),
+                repeat,
+            ));
+        }
+        if let Some((start, first, _)) = count {
+            // a count without a move
+            return Err(LurdErr {
+                index: start,
+                ch: first,
+            });
+        }
+
+        let mut moves = Vec::with_capacity(total);
+        for (mov, repeat) in runs {
+            moves.extend(iter::repeat_n(mov, repeat));
+        }
+        Ok(Moves(moves))
+    }
+
     pub fn move_cnt(&self) -> usize {
         self.0.len()
     }
@@ -48,14 +169,105 @@ impl Moves {
         self.0.iter().filter(|m| m.is_push).count()
     }
 
+    /// The same as `Display`, lowercase letters are moves, uppercase are pushes
+    pub fn to_lurd(&self) -> String {
+        self.to_string()
+    }
+
+    /// Run-length LURD - repeated moves are written once with their count before them,
+    /// e.g. `rrrUU` becomes `3r2U`. Much shorter for long solutions, `from_lurd` parses it back.
+    pub fn to_compressed_lurd(&self) -> String {
+        let mut lurd = String::new();
+        let mut player = 0;
+        for run in self.0.chunk_by(|a, b| a == b) {
+            let mov = run[0];
+            // writing to a String can't fail
+            if mov.player != player {
+                player = mov.player;
+                let _ = write!(lurd, "[{}]", u16::from(player) + 1);
+            }
+            if run.len() > 1 {
+                let _ = write!(lurd, "{}", run.len());
+            }
+            let _ = write!(lurd, "{mov}");
+        }
+        lurd
+    }
+
+    /// The moves in reverse order and opposite directions - walks the player back along the same path.
+    ///
+    /// Pushes become pulls so this solves the level played backwards
+    /// (from the solved position to the initial one, pulling boxes instead of pushing).
+    #[must_use]
+    pub fn reverse(&self) -> Moves {
+        self.0
+            .iter()
+            .rev()
+            .map(|mov| Move::new(mov.dir.inverse(), mov.is_push).with_player(mov.player))
+            .collect()
+    }
+
+    /// Keeps the moves up to and including the `push_cnt`th push, steps after it are removed.
+    /// Does nothing if there are fewer pushes.
+    pub fn truncate_to_push(&mut self, push_cnt: usize) {
+        let mut pushes = 0;
+        let end = self.0.iter().position(|mov| {
+            if pushes == push_cnt {
+                return true;
+            }
+            if mov.is_push {
+                pushes += 1;
+            }
+            false
+        });
+        if let Some(end) = end {
+            self.0.truncate(end);
+        }
+    }
+
+    /// Splits the moves after each push so every part is the steps to a box followed by a push.
+    /// Steps after the last push are the last part.
+    pub fn split_by_pushes(&self) -> Vec<Moves> {
+        let mut parts = Vec::new();
+        let mut part = Moves::default();
+        for &mov in &self.0 {
+            part.add(mov);
+            if mov.is_push {
+                parts.push(std::mem::take(&mut part));
+            }
+        }
+        if part.move_cnt() > 0 {
+            parts.push(part);
+        }
+        parts
+    }
+
+    /// Replays the moves on `level` and yields the level after each move.
+    ///
+    /// The first illegal move is returned as `VerifyErr::IllegalMove` and ends the iteration.
+    pub fn iter_states<'a>(&'a self, level: &'a Level) -> LevelStates<'a> {
+        LevelStates::new(level, self)
+    }
+
     pub(crate) fn add(&mut self, mov: Move) {
         self.0.push(mov);
     }
 
+    pub(crate) fn pop(&mut self) -> Option<Move> {
+        self.0.pop()
+    }
+
     pub(crate) fn extend(&mut self, moves: &Moves) {
         self.0.extend_from_slice(&moves.0);
     }
 
+    /// Makes the `player`th player do all the moves
+    pub(crate) fn set_player(&mut self, player: u8) {
+        for mov in &mut self.0 {
+            mov.player = player;
+        }
+    }
+
     #[allow(unused)]
     pub(crate) fn into_iter(self) -> ::std::vec::IntoIter<Move> {
         self.0.into_iter()
@@ -67,6 +279,47 @@ impl Moves {
     }
 }
 
+/// The rest of `[n]` after the `[` at `start`, returns the player index (`n - 1`)
+fn parse_player<I>(start: usize, chars: &mut I) -> Result<u8, LurdErr>
+where
+    I: Iterator<Item = (usize, char)>,
+{
+    let mut number: Option<u8> = None;
+    for (index, ch) in chars {
+        match ch {
+            ']' => {
+                return match number {
+                    Some(n) if n > 0 => Ok(n - 1),
+                    _ => Err(LurdErr { index, ch }),
+                };
+            }
+            _ => {
+                number = ch
+                    .to_digit(10)
+                    .and_then(|digit| {
+                        number
+                            .unwrap_or(0)
+                            .checked_mul(10)?
+                            .checked_add(digit as u8)
+                    })
+                    .map(Some)
+                    .ok_or(LurdErr { index, ch })?;
+            }
+        }
+    }
+    // unterminated
+    Err(LurdErr {
+        index: start,
+        ch: '[',
+    })
+}
+
+impl FromIterator<Move> for Moves {
+    fn from_iter<I: IntoIterator<Item = Move>>(iter: I) -> Self {
+        Moves(iter.into_iter().collect())
+    }
+}
+
 impl IntoIterator for Moves {
     type Item = Move;
     type IntoIter = ::std::vec::IntoIter<Move>;
@@ -87,7 +340,12 @@ impl<'a> IntoIterator for &'a Moves {
 
 impl Display for Moves {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut player = 0;
         for mov in self {
+            if mov.player != player {
+                player = mov.player;
+                write!(f, "[{}]", u16::from(player) + 1)?;
+            }
             write!(f, "{mov}")?;
         }
         Ok(())
@@ -104,6 +362,8 @@ impl Debug for Moves {
 mod tests {
     use super::*;
 
+    use crate::verify::{MoveErr, VerifyErr};
+
     #[test]
     fn formatting_moves() {
         let moves = Moves::new(vec![
@@ -119,6 +379,72 @@ mod tests {
         assert_eq!(moves.to_string(), "urdlURDL");
     }
 
+    #[test]
+    fn parsing_lurd() {
+        let moves = Moves::from_lurd("urdl\nURDL ").unwrap();
+        assert_eq!(moves.to_string(), "urdlURDL");
+        assert_eq!(moves.push_cnt(), 4);
+
+        assert_eq!(Moves::from_lurd("").unwrap(), Moves::default());
+        assert_eq!(Moves::from_lurd("ur x"), Err(LurdErr { index: 3, ch: 'x' }));
+
+        let moves = Moves::from_lurd("[2]ld[1]R[2]u").unwrap();
+        assert_eq!(moves.to_string(), "[2]ld[1]R[2]u");
+        assert_eq!(moves.move_cnt(), 4);
+        assert_eq!(Moves::from_lurd("[1]rR").unwrap().to_string(), "rR");
+        assert_eq!(Moves::from_lurd("r[0]"), Err(LurdErr { index: 3, ch: ']' }));
+        assert_eq!(Moves::from_lurd("r[2"), Err(LurdErr { index: 1, ch: '[' }));
+    }
+
+    #[test]
+    fn compressed_lurd() {
+        let moves = Moves::from_lurd("rrrUUlDDDDDDDDDDDDr").unwrap();
+        assert_eq!(moves.to_compressed_lurd(), "3r2Ul12Dr");
+        assert_eq!(Moves::from_lurd("3r2Ul12Dr").unwrap(), moves);
+        assert_eq!(Moves::from_lurd("3r 2U\nl12D1r").unwrap(), moves);
+        assert_eq!(moves.to_lurd(), "rrrUUlDDDDDDDDDDDDr");
+
+        let moves = Moves::from_lurd("[2]ll[1]RR[2]u").unwrap();
+        assert_eq!(moves.to_compressed_lurd(), "[2]2l[1]2R[2]u");
+        assert_eq!(
+            Moves::from_lurd(&moves.to_compressed_lurd()).unwrap(),
+            moves
+        );
+        assert_eq!(Moves::default().to_compressed_lurd(), "");
+
+        assert_eq!(Moves::from_lurd("3"), Err(LurdErr { index: 0, ch: '3' }));
+        assert_eq!(Moves::from_lurd("r0u"), Err(LurdErr { index: 1, ch: '0' }));
+        assert_eq!(Moves::from_lurd("2 r"), Err(LurdErr { index: 1, ch: ' ' }));
+        assert_eq!(
+            Moves::from_lurd("2[2]r"),
+            Err(LurdErr { index: 1, ch: '[' })
+        );
+        // counts are capped so a few characters can't allocate billions of moves
+        assert_eq!(Moves::from_lurd("65535r").unwrap().move_cnt(), MAX_REPEAT);
+        assert_eq!(
+            Moves::from_lurd("65536r"),
+            Err(LurdErr { index: 4, ch: '6' })
+        );
+        assert_eq!(
+            Moves::from_lurd("99999999999r"),
+            Err(LurdErr { index: 4, ch: '9' })
+        );
+        // and so is the total
+        let runs = MAX_MOVES / MAX_REPEAT;
+        let lurd = "65535r".repeat(runs + 1);
+        assert_eq!(
+            Moves::from_lurd(&lurd[..runs * 6]).unwrap().move_cnt(),
+            runs * MAX_REPEAT
+        );
+        assert_eq!(
+            Moves::from_lurd(&lurd),
+            Err(LurdErr {
+                index: lurd.len() - 1,
+                ch: 'r'
+            })
+        );
+    }
+
     #[test]
     fn extending_and_counting() {
         let mut moves1 = Moves::new(vec![
@@ -147,6 +473,67 @@ mod tests {
     }
 
     #[test]
+    fn manipulating() {
+        let moves = Moves::from_lurd("rRdLLuU").unwrap();
+        assert_eq!(moves.to_lurd(), "rRdLLuU");
+        assert_eq!(moves.reverse().to_lurd(), "DdRRuLl");
+        assert_eq!(moves.reverse().reverse(), moves);
+
+        let mut truncated = moves.clone();
+        truncated.truncate_to_push(2);
+        assert_eq!(truncated.to_lurd(), "rRdL");
+        truncated.truncate_to_push(5);
+        assert_eq!(truncated.to_lurd(), "rRdL");
+        truncated.truncate_to_push(0);
+        assert_eq!(truncated, Moves::default());
+
+        let parts: Vec<_> = moves.split_by_pushes().iter().map(Moves::to_lurd).collect();
+        assert_eq!(parts, ["rR", "dL", "L", "uU"]);
+        let parts: Vec<_> = Moves::from_lurd("Rlu")
+            .unwrap()
+            .split_by_pushes()
+            .iter()
+            .map(Moves::to_lurd)
+            .collect();
+        assert_eq!(parts, ["R", "lu"]);
+    }
+
+    #[test]
+    fn iterating_states() {
+        let level: Level = r"
+######
+#@$ .#
+######"
+            .parse()
+            .unwrap();
+        let moves = Moves::from_lurd("RRl").unwrap();
+        let states: Vec<_> = moves
+            .iter_states(&level)
+            .map(|level| level.unwrap().to_string())
+            .collect();
+        assert_eq!(
+            states,
+            [
+                "######\n# @$.#\n######\n",
+                "######\n#  @*#\n######\n",
+                "######\n# @ *#\n######\n",
+            ]
+        );
+
+        let moves = Moves::from_lurd("RRRl").unwrap();
+        let results: Vec<_> = moves.iter_states(&level).collect();
+        assert_eq!(results.len(), 3);
+        assert_eq!(
+            *results[2].as_ref().unwrap_err(),
+            VerifyErr::IllegalMove {
+                index: 2,
+                err: MoveErr::Blocked
+            }
+        );
+    }
+
+    #[test]
+    #[allow(clippy::explicit_iter_loop)] // testing all the ways to iterate
     fn iterating() {
         let v = vec![
             Move::new(Dir::Up, false),
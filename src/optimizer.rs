@@ -0,0 +1,451 @@
+//! Improving existing solutions by re-solving short parts of them optimally (vicinity search).
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+
+use crate::config::{Method, Metric, SolverOptions, Variant};
+use crate::data::{Dir, MapCell, Pos, DIRECTIONS};
+use crate::level::Level;
+use crate::map::{GoalMap, Map, MapType};
+use crate::moves::{Move, Moves};
+use crate::solver::backtracking;
+use crate::state::State;
+use crate::vec2d::Vec2d;
+use crate::verify::{self, VerifyErr};
+use crate::Solve;
+
+impl Level {
+    /// Tries to improve a solution by re-solving every `window` consecutive pushes optimally
+    /// and splicing in the result when it's better according to `metric`
+    /// (the other metric breaks ties). Repeats until no window can be improved.
+    ///
+    /// Steps after the last push are always removed.
+    /// Larger windows find more improvements but take longer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `window` is 0.
+    pub fn optimize(
+        &self,
+        moves: &Moves,
+        metric: Metric,
+        window: usize,
+    ) -> Result<Moves, VerifyErr> {
+        assert!(window > 0, "Window must contain at least one push");
+        self.verify_solution(moves)?;
+
+        let (mut cuts, mut states) = checkpoints(self, moves);
+        let last_cut = *cuts.last().expect("There's always the initial state");
+        let mut moves: Moves = moves.iter().copied().take(last_cut).collect();
+
+        let mut improved = true;
+        while improved {
+            improved = false;
+            let mut start = 0;
+            while start + 1 < cuts.len() {
+                let mut end = (start + window).min(cuts.len() - 1);
+                // windows can't express removing boxes using goals so stop before the remover
+                while states[end].boxes.len() != states[start].boxes.len() {
+                    end -= 1;
+                }
+                if end == start {
+                    start += 1;
+                    continue;
+                }
+                if let Some(better) =
+                    improve_window(self, &moves, &cuts, &states, start, end, metric)
+                {
+                    moves = better;
+                    let checkpoints = checkpoints(self, &moves);
+                    cuts = checkpoints.0;
+                    states = checkpoints.1;
+                    improved = true;
+                }
+                start += 1;
+            }
+        }
+        Ok(moves)
+    }
+
+    /// Reduces moves of a solution without changing its pushes
+    /// by finding the best order of the same pushes (each box is still pushed
+    /// along the same path but pushes of different boxes can be interleaved differently).
+    ///
+    /// Much cheaper than `Method::PushesMoves` but the result is not guaranteed to be move-optimal.
+    /// If there are too many orders to try or the level is not `Variant::Push`,
+    /// steps after the last push are only removed.
+    pub fn reorder_pushes(&self, moves: &Moves) -> Result<Moves, VerifyErr> {
+        self.verify_solution(moves)?;
+        let reordered = match self.variant() {
+            Variant::Push => PushChains::new(self, moves).best_order(self),
+            Variant::Pull | Variant::Multiban => None,
+        };
+        Ok(reordered.unwrap_or_else(|| {
+            let last_cut = moves
+                .iter()
+                .rposition(|mov| mov.is_push)
+                .map_or(0, |i| i + 1);
+            moves.iter().copied().take(last_cut).collect()
+        }))
+    }
+}
+
+/// Stop trying to reorder pushes after expanding this many nodes
+const MAX_REORDER_NODES: usize = 100_000;
+
+/// How many pushes of each box were made and the player position
+type Node = (Vec<usize>, Pos);
+
+/// Pushes of each box in the order they're made
+struct PushChains {
+    /// Box position before each push and the push direction
+    chains: Vec<Vec<(Pos, Dir)>>,
+    /// Box positions after each number of its pushes (`None` if removed by the remover)
+    positions: Vec<Vec<Option<Pos>>>,
+}
+
+impl PushChains {
+    /// The moves must be valid
+    fn new(level: &Level, moves: &Moves) -> Self {
+        let mut boxes: Vec<Option<Pos>> = level.state.boxes.iter().copied().map(Some).collect();
+        let mut chains = vec![Vec::new(); boxes.len()];
+        let mut positions: Vec<Vec<_>> = boxes.iter().map(|&b| vec![b]).collect();
+
+        let mut player_pos = level.state.player_pos;
+        for &mov in moves {
+            player_pos = player_pos + mov.dir;
+            if !mov.is_push {
+                continue;
+            }
+            let index = boxes
+                .iter()
+                .position(|&b| b == Some(player_pos))
+                .expect("Moves were verified");
+            let new_pos = player_pos + mov.dir;
+            let new_pos = if level.map().grid()[new_pos] == MapCell::Remover {
+                None
+            } else {
+                Some(new_pos)
+            };
+            boxes[index] = new_pos;
+            chains[index].push((player_pos, mov.dir));
+            positions[index].push(new_pos);
+        }
+        PushChains { chains, positions }
+    }
+
+    fn is_done(&self, progress: &[usize]) -> bool {
+        progress
+            .iter()
+            .zip(&self.chains)
+            .all(|(&done, chain)| done == chain.len())
+    }
+
+    fn boxes<'a>(&'a self, progress: &'a [usize]) -> impl Iterator<Item = Pos> + 'a {
+        progress
+            .iter()
+            .zip(&self.positions)
+            .filter_map(|(&done, positions)| positions[done])
+    }
+
+    /// Dijkstra over orders of pushes with moves as cost,
+    /// `None` if it had to give up
+    fn best_order(&self, level: &Level) -> Option<Moves> {
+        let map = level.map();
+        let start = (vec![0; self.chains.len()], level.state.player_pos);
+
+        // node -> (cost, previous node)
+        let mut nodes: HashMap<Node, (usize, Option<Node>)> = HashMap::new();
+        nodes.insert(start.clone(), (0, None));
+        let mut to_visit = BinaryHeap::new();
+        to_visit.push(Reverse((0, start)));
+
+        let mut expanded = 0;
+        while let Some(Reverse((cost, node))) = to_visit.pop() {
+            if nodes[&node].0 < cost {
+                continue;
+            }
+            let (ref progress, player_pos) = node;
+            if self.is_done(progress) {
+                return Some(self.reconstruct(level, &nodes, node));
+            }
+            expanded += 1;
+            if expanded > MAX_REORDER_NODES {
+                return None;
+            }
+
+            let mut box_grid = map.grid().scratchpad();
+            for b in self.boxes(progress) {
+                box_grid[b] = true;
+            }
+            let dists = player_dists(map, &box_grid, player_pos);
+
+            for (index, &done) in progress.iter().enumerate() {
+                let Some(&(box_pos, dir)) = self.chains[index].get(done) else {
+                    continue;
+                };
+                let Some(dist) = dists[box_pos - dir] else {
+                    continue;
+                };
+                if box_grid[box_pos + dir] {
+                    continue;
+                }
+                let mut new_progress = progress.clone();
+                new_progress[index] += 1;
+                let new_node = (new_progress, box_pos);
+                let new_cost = cost + dist + 1;
+                if nodes
+                    .get(&new_node)
+                    .is_some_and(|&(old_cost, _)| old_cost <= new_cost)
+                {
+                    continue;
+                }
+                nodes.insert(new_node.clone(), (new_cost, Some(node.clone())));
+                to_visit.push(Reverse((new_cost, new_node)));
+            }
+        }
+        None
+    }
+
+    fn reconstruct(
+        &self,
+        level: &Level,
+        nodes: &HashMap<Node, (usize, Option<Node>)>,
+        last: Node,
+    ) -> Moves {
+        let mut path = vec![last];
+        while let Some(prev) = nodes[path.last().expect("Path is never empty")].1.clone() {
+            path.push(prev);
+        }
+        path.reverse();
+
+        let mut moves = Moves::default();
+        let mut state = level.state.clone();
+        for pair in path.windows(2) {
+            let index = (0..pair[0].0.len())
+                .find(|&i| pair[0].0[i] != pair[1].0[i])
+                .expect("Exactly one box is pushed between nodes");
+            let (box_pos, dir) = self.chains[index][pair[0].0[index]];
+            let steps =
+                backtracking::player_steps(level.map(), &state, state.player_pos, box_pos - dir)
+                    .expect("The search only pushes reachable boxes");
+            for mov in steps.iter().copied().chain(Some(Move::new(dir, true))) {
+                state = verify::apply_move(level.map(), &state, Variant::Push, mov)
+                    .expect("The search only makes valid pushes");
+                moves.add(mov);
+            }
+        }
+        moves
+    }
+}
+
+/// Number of steps to each reachable position
+fn player_dists(map: &dyn Map, box_grid: &Vec2d<bool>, player_pos: Pos) -> Vec2d<Option<usize>> {
+    let mut dists = map.grid().scratchpad();
+    dists[player_pos] = Some(0);
+    let mut to_visit = VecDeque::new();
+    to_visit.push_back((player_pos, 0));
+    while let Some((pos, dist)) = to_visit.pop_front() {
+        for &dir in &DIRECTIONS {
+            let new_pos = pos + dir;
+            if map.grid()[new_pos] != MapCell::Wall
+                && !box_grid[new_pos]
+                && dists[new_pos].is_none()
+            {
+                dists[new_pos] = Some(dist + 1);
+                to_visit.push_back((new_pos, dist + 1));
+            }
+        }
+    }
+    dists
+}
+
+/// Indices of moves right after each push (starting with 0)
+/// and the states at those indices.
+/// The moves must be valid.
+fn checkpoints(level: &Level, moves: &Moves) -> (Vec<usize>, Vec<State>) {
+    let mut cuts = vec![0];
+    let mut states = vec![level.state.clone()];
+    let mut state = level.state.clone();
+    for (i, &mov) in moves.iter().enumerate() {
+        state = verify::apply_move(level.map(), &state, level.variant(), mov)
+            .expect("Moves were verified");
+        if mov.is_push {
+            cuts.push(i + 1);
+            states.push(state.clone());
+        }
+    }
+    (cuts, states)
+}
+
+/// Returns the whole solution with the part between checkpoints `start` and `end` replaced
+/// if a better way to get from one to the other is found
+fn improve_window(
+    level: &Level,
+    moves: &Moves,
+    cuts: &[usize],
+    states: &[State],
+    start: usize,
+    end: usize,
+    metric: Metric,
+) -> Option<Moves> {
+    let (from, to) = (&states[start], &states[end]);
+    let old: Moves = moves
+        .iter()
+        .copied()
+        .skip(cuts[start])
+        .take(cuts[end] - cuts[start])
+        .collect();
+
+    let method = match metric {
+        Metric::Moves => Method::MovesPushes,
+        Metric::Pushes => Method::PushesMoves,
+    };
+    let window_level = window_level(level, from, to);
+    // the window might not be a valid level on its own (e.g. unreachable boxes),
+    // then there's nothing to improve
+    let mut new = window_level
+        .solve_with_options(method, &SolverOptions::default())
+        .ok()?
+        .moves?;
+
+    // check against the real map - a box could be pushed over the remover
+    let reached = verify::apply_moves(level.map(), from, level.variant(), &new).ok()?;
+    if reached.boxes != to.boxes {
+        return None;
+    }
+    // the following moves expect the players where the original moves left them
+    let mut reached = reached;
+    for player in 0..reached.player_cnt() {
+        let (src, dest) = (reached.player(player), to.player(player));
+        let mut steps = backtracking::player_steps(level.map(), &reached, src, dest)?;
+        steps.set_player(player as u8);
+        new.extend(&steps);
+        reached = reached.moved(player, dest, reached.boxes.clone());
+    }
+
+    if cost(&new, metric) >= cost(&old, metric) {
+        return None;
+    }
+    Some(
+        moves
+            .iter()
+            .take(cuts[start])
+            .chain(&new)
+            .chain(moves.iter().skip(cuts[end]))
+            .copied()
+            .collect(),
+    )
+}
+
+/// The level starting at `from` with goals where the boxes are in `to`
+fn window_level(level: &Level, from: &State, to: &State) -> Level {
+    let mut grid = level.map().grid().clone();
+    for pos in grid.positions() {
+        if grid[pos] == MapCell::Goal || grid[pos] == MapCell::Remover {
+            grid[pos] = MapCell::Empty;
+        }
+    }
+    for &b in &to.boxes {
+        grid[b] = MapCell::Goal;
+    }
+    let map = GoalMap::new(grid, to.boxes.to_vec());
+    let mut window = Level::new(MapType::Goals(map), from.clone());
+    window.set_variant(level.variant());
+    window
+}
+
+/// Solution length in `metric`, the other one breaks ties
+pub(crate) fn cost(moves: &Moves, metric: Metric) -> (usize, usize) {
+    match metric {
+        Metric::Moves => (moves.move_cnt(), moves.push_cnt()),
+        Metric::Pushes => (moves.push_cnt(), moves.move_cnt()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn optimizing() {
+        let level: Level = r"
+#######
+#     #
+#@$  .#
+#######"
+            .parse()
+            .unwrap();
+
+        // pushing the box right, walking around it, pushing it back and then all the way
+        let moves = Moves::from_lurd("RurrdLulldRRRul").unwrap();
+        assert!(level.verify_solution(&moves).is_ok());
+
+        let pushes = level.optimize(&moves, Metric::Pushes, 10).unwrap();
+        assert_eq!(pushes.to_string(), "RRR");
+
+        // small windows can only make local improvements
+        let local = level.optimize(&moves, Metric::Moves, 1).unwrap();
+        assert_eq!(local.to_string(), "RurrdLulldRRR");
+    }
+
+    #[test]
+    fn optimizing_multiban() {
+        let level =
+            Level::parse_variant("#######\n#@$ @.#\n### ###\n  ###", Variant::Multiban).unwrap();
+        let moves = Moves::from_lurd("[2]lrld[1]RRR").unwrap();
+        let optimized = level.optimize(&moves, Metric::Moves, 10).unwrap();
+        assert_eq!(optimized.to_string(), "[2]ld[1]RRR");
+    }
+
+    #[test]
+    fn optimizing_remover() {
+        let level: Level = r"
+#######
+#     #
+#@$  r#
+#######"
+            .parse()
+            .unwrap();
+        let moves = Moves::from_lurd("RurrdLulldRRR").unwrap();
+        let optimized = level.optimize(&moves, Metric::Moves, 10).unwrap();
+        assert_eq!(optimized.to_string(), "RRR");
+        assert!(level.verify_solution(&optimized).is_ok());
+    }
+
+    #[test]
+    fn reordering_pushes() {
+        let level: Level = "###########\n#. $ @ $ .#\n###########".parse().unwrap();
+        // alternating between the boxes
+        let moves = Moves::from_lurd("lLrrrRllllLrrrrrR").unwrap();
+        assert!(level.verify_solution(&moves).is_ok());
+
+        let reordered = level.reorder_pushes(&moves).unwrap();
+        // either box can go first
+        assert_eq!(reordered.move_cnt(), 9);
+        assert_eq!(reordered.push_cnt(), 4);
+        assert!(level.verify_solution(&reordered).is_ok());
+
+        // the second box can only be pushed after the first one is removed
+        let level: Level = r"
+######
+#r$@ #
+# $  #
+#    #
+######"
+            .parse()
+            .unwrap();
+        let moves = Moves::from_lurd("LrddlUruLr").unwrap();
+        let reordered = level.reorder_pushes(&moves).unwrap();
+        assert_eq!(reordered.move_cnt(), 9);
+        assert_eq!(reordered.push_cnt(), 3);
+        assert!(level.verify_solution(&reordered).is_ok());
+    }
+
+    #[test]
+    fn invalid_solution() {
+        let level: Level = "#####\n#@$.#\n#####".parse().unwrap();
+        let moves = Moves::from_lurd("r").unwrap();
+        assert!(level.optimize(&moves, Metric::Moves, 5).is_err());
+    }
+}
@@ -1,44 +1,127 @@
+use std::borrow::Cow;
 use std::error::Error;
+use std::ffi::{OsStr, OsString};
 use std::fmt;
 use std::fmt::{Display, Formatter};
-use std::fs;
-use std::path::Path;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
-use crate::config::Format;
-use crate::data::{MapCell, Pos, MAX_SIZE};
-use crate::level::Level;
-use crate::map::{GoalMap, MapType, RemoverMap};
+use crate::collection;
+use crate::config::{Format, ParseOptions, Variant};
+use crate::data::{Coord, MapCell, Pos, MAX_PLAYERS, MAX_SIZE};
+use crate::level::{Level, LevelMetadata};
+use crate::map::{GoalMap, HybridMap, MapType, RemoverMap};
 use crate::state::State;
 use crate::vec2d::Vec2d;
-use crate::LoadLevel;
+use crate::{LoadLevel, SokobanError};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ParserErr {
-    Pos(usize, usize),
+    Syntax(SyntaxErr),
     TooLarge,
     MultiplePlayers,
-    MultipleRemovers,
-    BoxOnRemover,
     NoPlayer,
-    RemoverAndGoals,
+    TooManyPlayers,
 }
 
 impl Display for ParserErr {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        match *self {
-            ParserErr::Pos(r, c) => write!(f, "Invalid cell at pos: [{r}, {c}]"),
-            ParserErr::TooLarge => write!(f, "Map is larger than 255 rows/columns"),
+        match self {
+            ParserErr::Syntax(err) => write!(f, "{err}"),
+            ParserErr::TooLarge => write!(f, "Map is larger than {MAX_SIZE} rows/columns"),
             ParserErr::MultiplePlayers => write!(f, "More than one player"),
-            ParserErr::MultipleRemovers => write!(f, "Multiple removers - only one allowed"),
-            ParserErr::BoxOnRemover => write!(f, "Box on remover"),
             ParserErr::NoPlayer => write!(f, "No player"),
-            ParserErr::RemoverAndGoals => write!(f, "Map contains both remover and goals"),
+            ParserErr::TooManyPlayers => write!(f, "More than {MAX_PLAYERS} players"),
         }
     }
 }
 
-impl Error for ParserErr {}
+impl ParserErr {
+    /// `column` starts at 0 here
+    fn syntax(text: &str, line: usize, column: usize, kind: SyntaxErrKind) -> Self {
+        ParserErr::Syntax(SyntaxErr {
+            line,
+            column: column + 1,
+            text: text.to_owned(),
+            kind,
+        })
+    }
+}
+
+impl Error for ParserErr {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ParserErr::Syntax(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// What's wrong at the position of a `SyntaxErr`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SyntaxErrKind {
+    /// A character which isn't valid in the format or at this place in a custom format cell
+    InvalidChar(char),
+    /// A run-length count with nothing after it to repeat
+    DanglingCount,
+    /// A row of the custom format has an odd number of characters
+    OddRow,
+}
+
+impl Display for SyntaxErrKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match *self {
+            SyntaxErrKind::InvalidChar(ch) => write!(f, "Invalid character {ch:?}"),
+            SyntaxErrKind::DanglingCount => write!(f, "Count with nothing to repeat"),
+            SyntaxErrKind::OddRow => write!(
+                f,
+                "Odd number of characters in a row, cells are 2 characters wide"
+            ),
+        }
+    }
+}
+
+impl Error for SyntaxErrKind {}
+
+/// An error at a specific place in the level text, displayed with the line and a caret under the position
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SyntaxErr {
+    /// Starting at 1
+    pub line: usize,
+    /// Starting at 1, counted in characters after expanding tabs
+    pub column: usize,
+    /// The whole line after expanding tabs
+    pub text: String,
+    pub kind: SyntaxErrKind,
+}
+
+impl SyntaxErr {
+    /// The line and a `^` under the position
+    pub fn snippet(&self) -> String {
+        format!("{}\n{}^", self.text, " ".repeat(self.column - 1))
+    }
+}
+
+impl Display for SyntaxErr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at line {}, column {}:\n{}",
+            self.kind,
+            self.line,
+            self.column,
+            self.snippet()
+        )
+    }
+}
+
+impl Error for SyntaxErr {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.kind)
+    }
+}
 
 impl FromStr for Level {
     type Err = ParserErr;
@@ -48,105 +131,291 @@ impl FromStr for Level {
     }
 }
 
-impl<P> LoadLevel for P
-where
-    P: AsRef<Path>,
-{
-    fn load_level(&self) -> Result<Level, Box<dyn Error>> {
-        Ok(fs::read_to_string(self)?.parse()?)
+macro_rules! impl_load_level_for_paths {
+    ( $( $t:ty ),* ) => {
+        $(
+            impl LoadLevel for $t {
+                fn level_text(&self) -> Result<String, SokobanError> {
+                    Ok(fs::read_to_string(self)?)
+                }
+            }
+        )*
+    };
+}
+
+impl_load_level_for_paths!(str, String, Path, PathBuf, OsStr, OsString);
+
+impl LoadLevel for File {
+    fn level_text(&self) -> Result<String, SokobanError> {
+        let mut text = String::new();
+        (&*self).read_to_string(&mut text)?;
+        Ok(text)
     }
 }
 
-type ParseResult = Result<
-    (
-        Vec<Vec<MapCell>>,
-        Vec<Pos>,
-        Option<Pos>,
-        Vec<Pos>,
-        Option<Pos>,
-    ),
-    ParserErr,
->;
+impl<T: LoadLevel + ?Sized> LoadLevel for &T {
+    fn level_text(&self) -> Result<String, SokobanError> {
+        (**self).level_text()
+    }
+}
 
-fn parse(level: &str) -> Result<Level, ParserErr> {
-    if level.trim_start().contains('<') {
-        parse_format(level, Format::Custom)
-    } else {
-        parse_format(level, Format::Xsb)
+type ParseResult = Result<(Vec<Vec<MapCell>>, Vec<Pos>, Vec<Pos>, Vec<Pos>, Vec<Pos>), ParserErr>;
+
+impl Level {
+    /// Parses a level like `FromStr` and sets its variant.
+    ///
+    /// Multiple players are only allowed in `Variant::Multiban`.
+    pub fn parse_variant(level: &str, variant: Variant) -> Result<Level, ParserErr> {
+        Level::parse_with_options(level, variant.into())
+    }
+
+    /// Parses a level in the given format instead of detecting it.
+    ///
+    /// Detection only looks for `<` so text in other formats which contains it
+    /// (e.g. in a comment) needs this. `Format::Xsb` and `Format::XsbRle` are parsed the same way.
+    pub fn parse_with_format(level: &str, format: Format) -> Result<Level, ParserErr> {
+        parse_format(level, format, ParseOptions::default())
+    }
+
+    /// Parses a level in the XSB format (including run-length encoded rows)
+    /// without detecting the format
+    pub fn from_xsb_str(level: &str) -> Result<Level, ParserErr> {
+        Level::parse_with_format(level, Format::Xsb)
+    }
+
+    /// Parses a level in the custom format without detecting the format
+    pub fn from_custom_str(level: &str) -> Result<Level, ParserErr> {
+        Level::parse_with_format(level, Format::Custom)
+    }
+
+    /// Parses a level like `FromStr` with settings the level formats can't express
+    pub fn parse_with_options(level: &str, options: ParseOptions) -> Result<Level, ParserErr> {
+        if let Some(format) = options.format {
+            parse_format(level, format, options)
+        } else if level.trim_start().contains('<') {
+            parse_format(level, Format::Custom, options)
+        } else {
+            parse_format(level, Format::Xsb, options)
+        }
     }
 }
 
-fn parse_format(level: &str, format: Format) -> Result<Level, ParserErr> {
+fn parse(level: &str) -> Result<Level, ParserErr> {
+    Level::parse_variant(level, Variant::Push)
+}
+
+pub(crate) fn parse_format(
+    level: &str,
+    format: Format,
+    options: ParseOptions,
+) -> Result<Level, ParserErr> {
+    let level = normalize(level);
+    let (level, metadata) = match format {
+        Format::Custom => (Cow::Borrowed(&*level), LevelMetadata::default()),
+        Format::Xsb | Format::XsbRle => split_xsb_metadata(&level),
+    };
     // trim so we can specify levels using raw strings more easily
-    let level = level.trim_matches('\n').trim_end();
+    let trimmed = level.trim_start_matches('\n');
+    let first_line = level.len() - trimmed.len();
+    let level = trimmed.trim_end_matches('\n');
+
+    let (grid, goals, removers, boxes, players) = match format {
+        Format::Custom => parse_custom(trim_blank_lines_end(level), first_line)?,
+        // digits and `|` are not valid in normal XSB so we can always try to expand RLE
+        Format::Xsb | Format::XsbRle => parse_xsb(&expand_rle(level.trim_end(), first_line)?)?,
+    };
+    let mut level = level_from_parts(&grid, goals, &removers, boxes, &players, options)?;
+    level.metadata = metadata;
+    Ok(level)
+}
+
+/// Separates the board from `;` comments and `Key: value` lines before and after it.
+///
+/// Lines before the board are replaced by empty lines so line numbers in errors stay the same.
+/// Everything after the first comment or metadata line following the board is metadata
+/// (multi-line comments can contain any text). A comment right before the board is its title
+/// unless there's a `Title:` line, like in collections.
+fn split_xsb_metadata(level: &str) -> (Cow<'_, str>, LevelMetadata) {
+    let is_text =
+        |line: &str| line.trim_start().starts_with(';') || collection::key_value(line).is_some();
+    let lines: Vec<&str> = level.lines().collect();
+    let board_start = lines
+        .iter()
+        .position(|line| !line.trim().is_empty() && !is_text(line))
+        .unwrap_or(lines.len());
+    let board_end = board_start
+        + lines[board_start..]
+            .iter()
+            .position(|line| is_text(line))
+            .unwrap_or(lines.len() - board_start);
+    if board_start == 0 && board_end == lines.len() {
+        return (Cow::Borrowed(level), LevelMetadata::default());
+    }
+
+    let before = &lines[..board_start];
+    let mut text = before.to_vec();
+    text.extend_from_slice(&lines[board_end..]);
+    let mut metadata = collection::parse_metadata(&text);
+    if metadata.title.is_none() {
+        metadata.title = collection::title_line(before).map(str::to_owned);
+    }
+
+    let board = "\n".repeat(board_start) + &lines[board_start..board_end].join("\n");
+    (Cow::Owned(board), metadata)
+}
+
+/// Validation shared by all ways of creating a level.
+///
+/// Boxes which start on a remover are removed right away like when they're pushed there.
+pub(crate) fn level_from_parts(
+    grid: &[Vec<MapCell>],
+    goals: Vec<Pos>,
+    removers: &[Pos],
+    mut boxes: Vec<Pos>,
+    players: &[Pos],
+    options: ParseOptions,
+) -> Result<Level, ParserErr> {
+    if players.is_empty() {
+        return Err(ParserErr::NoPlayer);
+    }
+    if players.len() > 1 && options.variant != Variant::Multiban {
+        return Err(ParserErr::MultiplePlayers);
+    }
+    if players.len() > MAX_PLAYERS {
+        return Err(ParserErr::TooManyPlayers);
+    }
+    let grid = Vec2d::new(grid);
+    boxes.retain(|pos| !removers.contains(pos));
+    let state = State::with_players(players, boxes);
 
-    let (grid, goals, remover, boxes, player_pos) = match format {
-        Format::Custom => parse_custom(level)?,
-        Format::Xsb => parse_xsb(level)?,
+    let map = if removers.is_empty() {
+        // goals can be empty - it's handled as already solved later
+        let mut goal_map = GoalMap::new(grid, goals);
+        goal_map.extra_goals = options.extra_goals;
+        MapType::Goals(goal_map)
+    } else if goals.is_empty() {
+        MapType::Remover(RemoverMap::new(grid, removers.to_vec()))
+    } else {
+        MapType::Hybrid(HybridMap::new(grid, goals, removers.to_vec()))
     };
-    let player_pos = player_pos.ok_or(ParserErr::NoPlayer)?;
-    let grid = Vec2d::new(&grid);
-
-    if let Some(remover) = remover {
-        if goals.is_empty() {
-            Ok(Level::new(
-                MapType::Remover(RemoverMap::new(grid, remover)),
-                State::new(player_pos, boxes),
-            ))
-        } else {
-            Err(ParserErr::RemoverAndGoals)
+    let mut level = Level::new(map, state);
+    level.variant = options.variant;
+    Ok(level)
+}
+
+/// Columns between tab stops, the usual convention for XSB files
+const TAB_WIDTH: usize = 8;
+
+/// Makes text from any editor look like clean Unix text:
+/// removes a UTF-8 BOM, converts CRLF and CR line endings to LF and expands tabs to spaces.
+pub(crate) fn normalize(text: &str) -> Cow<'_, str> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    if !text.contains(['\r', '\t']) {
+        return Cow::Borrowed(text);
+    }
+
+    let mut normalized = String::with_capacity(text.len());
+    let mut column = 0;
+    let mut chars = text.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\r' | '\n' => {
+                if ch == '\r' {
+                    chars.next_if_eq(&'\n');
+                }
+                normalized.push('\n');
+                column = 0;
+            }
+            '\t' => {
+                let spaces = TAB_WIDTH - column % TAB_WIDTH;
+                normalized.extend(std::iter::repeat_n(' ', spaces));
+                column += spaces;
+            }
+            _ => {
+                normalized.push(ch);
+                column += 1;
+            }
         }
-    } else {
-        // goals can be empty - it's handled as already solved later
-        Ok(Level::new(
-            MapType::Goals(GoalMap::new(grid, goals)),
-            State::new(player_pos, boxes),
-        ))
     }
+    Cow::Owned(normalized)
+}
+
+/// Removes trailing lines with only whitespace.
+///
+/// Unlike `trim_end`, spaces at the end of the last row are kept because they're part of a cell.
+fn trim_blank_lines_end(level: &str) -> &str {
+    let content_end = level.trim_end().len();
+    let line_end = level[content_end..]
+        .find('\n')
+        .map_or(level.len(), |i| content_end + i);
+    &level[..line_end]
 }
 
-/// Parses my custom format
-fn parse_custom(level: &str) -> ParseResult {
+/// Parses my custom format.
+///
+/// Every cell is 2 characters wide: walls are `<>`, otherwise the first character
+/// is the contents (`B` box, `P` player or a space) and the second the square
+/// (`_` goal, `R` remover or a space). Lines starting with `;` are comments.
+/// Rows with an odd number of characters are an error unless the last one is a space.
+///
+/// Formatting a parsed level gives back the same text as long as it has no comments
+/// or trailing empty cells and no box starts on a remover.
+fn parse_custom(level: &str, first_line: usize) -> ParseResult {
     let mut grid = Vec::new();
     let mut goals = Vec::new();
-    let mut remover = None;
+    let mut removers = Vec::new();
     let mut boxes = Vec::new();
-    let mut player_pos = None;
-
-    for (r, line) in level.lines().enumerate() {
-        if r > MAX_SIZE {
+    let mut players = Vec::new();
+
+    // cells never start with `;` so comments can't be confused with them
+    for (r, (line_index, line)) in level
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.starts_with(';'))
+        .enumerate()
+    {
+        if r >= MAX_SIZE {
             return Err(ParserErr::TooLarge);
         }
+        let mut line = line;
+        if line.chars().count() % 2 != 0 {
+            // a trailing space is editor junk, not half of a cell
+            line = line.strip_suffix(' ').ok_or_else(|| {
+                let column = line.chars().count() - 1;
+                ParserErr::syntax(
+                    line,
+                    first_line + line_index + 1,
+                    column,
+                    SyntaxErrKind::OddRow,
+                )
+            })?;
+        }
+        let invalid = |c: usize, second: bool, ch| {
+            let column = 2 * c + usize::from(second);
+            let kind = SyntaxErrKind::InvalidChar(ch);
+            ParserErr::syntax(line, first_line + line_index + 1, column, kind)
+        };
         grid.push(Vec::new());
         let mut chars = line.chars();
         while let (Some(c1), Some(c2)) = (chars.next(), chars.next()) {
             let c = grid[r].len();
-            if c > MAX_SIZE {
+            if c >= MAX_SIZE {
                 return Err(ParserErr::TooLarge);
             }
-            let pos = Pos::new(r as u8, c as u8);
+            let pos = Pos::new(r as Coord, c as Coord);
 
-            let mut has_box = false;
             match c1 {
                 '<' => {
                     if c2 != '>' {
-                        return Err(ParserErr::Pos(r, c));
+                        return Err(invalid(c, true, c2));
                     }
                     grid[r].push(MapCell::Wall);
                     continue; // skip parsing c2
                 }
                 ' ' => {}
-                'B' => {
-                    boxes.push(pos);
-                    has_box = true;
-                }
-                'P' => {
-                    if player_pos.is_some() {
-                        return Err(ParserErr::MultiplePlayers);
-                    }
-                    player_pos = Some(pos);
-                }
-                _ => return Err(ParserErr::Pos(r, c)),
+                'B' => boxes.push(pos),
+                'P' => players.push(pos),
+                _ => return Err(invalid(c, false, c1)),
             }
             match c2 {
                 ' ' => grid[r].push(MapCell::Empty),
@@ -155,56 +424,101 @@ fn parse_custom(level: &str) -> ParseResult {
                     grid[r].push(MapCell::Goal);
                 }
                 'R' => {
-                    if remover.is_some() {
-                        return Err(ParserErr::MultipleRemovers);
-                    }
-                    if has_box {
-                        return Err(ParserErr::BoxOnRemover);
-                    }
-                    remover = Some(pos);
+                    removers.push(pos);
                     grid[r].push(MapCell::Remover);
                 }
-                _ => return Err(ParserErr::Pos(r, c)),
+                _ => return Err(invalid(c, true, c2)),
             }
         }
     }
 
-    Ok((grid, goals, remover, boxes, player_pos))
+    Ok((grid, goals, removers, boxes, players))
+}
+
+/// Characters of normal XSB, RLE adds digits and `|`
+const XSB_CHARS: &str = "#pP@+bB$*rR.-_ ";
+
+/// Expands run-length encoding (e.g. `3#` into `###`) and replaces `|` with newlines.
+///
+/// Also checks that all characters are valid so errors point into the original text.
+fn expand_rle(level: &str, first_line: usize) -> Result<String, ParserErr> {
+    let mut expanded = String::with_capacity(level.len());
+
+    for (line_index, line) in level.lines().enumerate() {
+        if line_index > 0 {
+            expanded.push('\n');
+        }
+        let err = |column, kind| ParserErr::syntax(line, first_line + line_index + 1, column, kind);
+        let mut c = 0;
+        let mut count: Option<usize> = None;
+        let mut count_start = 0;
+
+        for (column, cur_char) in line.chars().enumerate() {
+            if let Some(digit) = cur_char.to_digit(10) {
+                if count.is_none() {
+                    count_start = column;
+                }
+                let new_count = count.unwrap_or(0) * 10 + digit as usize;
+                if c + new_count > MAX_SIZE {
+                    return Err(ParserErr::TooLarge);
+                }
+                count = Some(new_count);
+                continue;
+            }
+
+            if cur_char == '|' {
+                if count.is_some() {
+                    return Err(err(count_start, SyntaxErrKind::DanglingCount));
+                }
+                expanded.push('\n');
+                c = 0;
+                continue;
+            }
+
+            if !XSB_CHARS.contains(cur_char) {
+                return Err(err(column, SyntaxErrKind::InvalidChar(cur_char)));
+            }
+            let repeat = count.take().unwrap_or(1);
+            for _ in 0..repeat {
+                expanded.push(cur_char);
+            }
+            c += repeat;
+        }
+        if count.is_some() {
+            return Err(err(count_start, SyntaxErrKind::DanglingCount));
+        }
+    }
+
+    Ok(expanded)
 }
 
 /// Parses (a subset of) the format described [here](http://www.sokobano.de/wiki/index.php?title=Level_format)
 fn parse_xsb(level: &str) -> ParseResult {
     let mut grid = Vec::new();
     let mut goals = Vec::new();
-    let mut remover = None;
+    let mut removers = Vec::new();
     let mut boxes = Vec::new();
-    let mut player_pos = None;
+    let mut players = Vec::new();
 
     for (r, line) in level.lines().enumerate() {
-        if r > MAX_SIZE {
+        if r >= MAX_SIZE {
             return Err(ParserErr::TooLarge);
         }
         let mut line_tiles = Vec::new();
         for (c, cur_char) in line.chars().enumerate() {
-            if c > MAX_SIZE {
+            if c >= MAX_SIZE {
                 return Err(ParserErr::TooLarge);
             }
-            let pos = Pos::new(r as u8, c as u8);
+            let pos = Pos::new(r as Coord, c as Coord);
 
             let tile = match cur_char {
                 '#' => MapCell::Wall,
                 'p' | '@' => {
-                    if player_pos.is_some() {
-                        return Err(ParserErr::MultiplePlayers);
-                    }
-                    player_pos = Some(pos);
+                    players.push(pos);
                     MapCell::Empty
                 }
                 'P' | '+' => {
-                    if player_pos.is_some() {
-                        return Err(ParserErr::MultiplePlayers);
-                    }
-                    player_pos = Some(pos);
+                    players.push(pos);
                     goals.push(pos);
                     MapCell::Goal
                 }
@@ -219,22 +533,13 @@ fn parse_xsb(level: &str) -> ParseResult {
                     MapCell::Goal
                 }
                 'r' => {
-                    if remover.is_some() {
-                        return Err(ParserErr::MultipleRemovers);
-                    }
-                    remover = Some(pos);
+                    removers.push(pos);
                     MapCell::Remover
                 }
                 'R' => {
-                    // this is player on remover, box on remover makes no sense
-                    if player_pos.is_some() {
-                        return Err(ParserErr::MultiplePlayers);
-                    }
-                    player_pos = Some(pos);
-                    if remover.is_some() {
-                        return Err(ParserErr::MultipleRemovers);
-                    }
-                    remover = Some(pos);
+                    // this is player on remover, XSB has no box on remover
+                    players.push(pos);
+                    removers.push(pos);
                     MapCell::Remover
                 }
                 '.' => {
@@ -242,14 +547,14 @@ fn parse_xsb(level: &str) -> ParseResult {
                     MapCell::Goal
                 }
                 ' ' | '-' | '_' => MapCell::Empty,
-                _ => return Err(ParserErr::Pos(r, c)),
+                _ => unreachable!("Characters are checked when expanding RLE"),
             };
             line_tiles.push(tile);
         }
         grid.push(line_tiles);
     }
 
-    Ok((grid, goals, remover, boxes, player_pos))
+    Ok((grid, goals, removers, boxes, players))
 }
 
 #[cfg(test)]
@@ -259,7 +564,7 @@ mod tests {
     #[test]
     fn custom_fail_empty() {
         let level = "";
-        assert_failure(level, ParserErr::NoPlayer);
+        assert_failure(level, &ParserErr::NoPlayer);
     }
 
     #[test]
@@ -269,28 +574,33 @@ mod tests {
 <>  <>
 <><><>
 ";
-        assert_failure(level, ParserErr::NoPlayer);
+        assert_failure(level, &ParserErr::NoPlayer);
     }
 
     #[test]
-    fn custom_fail_remover_and_goals() {
+    fn custom_remover_and_goals() {
         let level = r"
-<><><><>
-<>P  R<>
-<> _  <>
-<><><><>
+<><><><><><>
+<>P B    R<>
+<>   _B   <>
+<><><><><><>
 ";
-        assert_failure(level, ParserErr::RemoverAndGoals);
+        let level: Level = level.parse().unwrap();
+        assert_eq!(level.goal_positions(), vec![(2, 2)]);
+        assert_eq!(level.remover_positions(), vec![(1, 4)]);
+        assert_eq!(level.box_positions(), vec![(1, 2), (2, 3)]);
     }
 
     #[test]
-    fn custom_fail_box_on_remover() {
+    fn custom_box_on_remover() {
         let level = r"
-<><><><>
-<>P BR<>
-<><><><>
+<><><><><><>
+<>P BR   R<>
+<><><><><><>
 ";
-        assert_failure(level, ParserErr::BoxOnRemover);
+        let level: Level = level.parse().unwrap();
+        assert_eq!(level.box_positions(), vec![]);
+        assert_eq!(level.remover_positions(), vec![(1, 2), (1, 4)]);
     }
 
     #[test]
@@ -327,6 +637,138 @@ mod tests {
         assert_success_custom(level);
     }
 
+    #[test]
+    fn custom_odd_row() {
+        let level = "<><><>\n<>P <>\n<><><\n";
+        assert_syntax_err(level, 3, 5, SyntaxErrKind::OddRow);
+        // trailing spaces are part of cells
+        let level = "<><><>\n<>P <>\n<>   ";
+        assert!(level.parse::<Level>().is_ok());
+        let level = "<><><>\n<>P <>\n<>  B";
+        assert_syntax_err(level, 3, 5, SyntaxErrKind::OddRow);
+        let level = "<><><>\n<>P <>\n<>X <>";
+        assert_syntax_err(level, 3, 3, SyntaxErrKind::InvalidChar('X'));
+        // trailing blank lines are not
+        let level = "<><><>\n<>P <>\n<><><>\n  \n";
+        assert!(level.parse::<Level>().is_ok());
+    }
+
+    #[test]
+    fn custom_comments() {
+        let level = r"
+; a comment
+<><><><>
+;<> B <>
+<>P B_<>
+<><><><>
+";
+        let level: Level = level.parse().unwrap();
+        assert_eq!(level.box_positions(), vec![(1, 2)]);
+        assert_eq!(level.custom().to_string(), "<><><><>\n<>P B_<>\n<><><><>\n");
+    }
+
+    #[test]
+    fn messy_text() {
+        let clean = "#####\n#@$.#\n#####\n";
+        for messy in [
+            "#####\r\n#@$.#\r\n#####\r\n",
+            "\u{feff}#####\n#@$.#\n#####",
+            "#####\r#@$.#\r#####\r",
+            "#####   \n#@$.#\t\n#####\n\n",
+        ] {
+            let level: Level = messy.parse().unwrap();
+            assert_eq!(level.to_string(), clean);
+        }
+
+        // tabs are expanded to the next multiple of 8 columns
+        let level: Level = "##########\n#@\t$.#\n##########".parse().unwrap();
+        assert_eq!(level.box_positions(), vec![(1, 8)]);
+
+        let level: Level = "\u{feff}<><><><>\r\n<>P B_<> \r\n<><><><>\r\n"
+            .parse()
+            .unwrap();
+        assert_eq!(level.custom().to_string(), "<><><><>\n<>P B_<>\n<><><><>\n");
+
+        assert_syntax_err(
+            "\r\n#####\r\n#@$.#\r\n#\tX",
+            4,
+            9,
+            SyntaxErrKind::InvalidChar('X'),
+        );
+    }
+
+    /// A random level surrounded by walls in the custom format, formatted the same way as `MapFormatter`
+    fn random_custom_level(rng: &mut fastrand::Rng) -> String {
+        let rows = rng.usize(3..10);
+        let cols = rng.usize(3..10);
+        let player = (rng.usize(1..rows - 1), rng.usize(1..cols - 1));
+        let mut level = String::new();
+        for r in 0..rows {
+            for c in 0..cols {
+                if r == 0 || r == rows - 1 || c == 0 || c == cols - 1 {
+                    level.push_str("<>");
+                    continue;
+                }
+                let square = match rng.u8(0..10) {
+                    0..=1 => '_',
+                    2 => 'R',
+                    3..=4 => {
+                        if (r, c) != player {
+                            level.push_str("<>");
+                            continue;
+                        }
+                        ' '
+                    }
+                    _ => ' ',
+                };
+                let contents = if (r, c) == player {
+                    'P'
+                } else if square != 'R' && rng.u8(0..4) == 0 {
+                    'B'
+                } else {
+                    ' '
+                };
+                level.push(contents);
+                level.push(square);
+            }
+            level.push('\n');
+        }
+        level
+    }
+
+    #[test]
+    fn round_trips() {
+        let mut rng = fastrand::Rng::with_seed(42);
+        for _ in 0..500 {
+            let custom = random_custom_level(&mut rng);
+            let level = parse_format(&custom, Format::Custom, Variant::Push.into())
+                .unwrap_or_else(|err| panic!("{}:\n{}", err, custom));
+            assert_eq!(level.custom().to_string(), custom);
+            assert_eq!(Level::from_custom_str(&custom).unwrap(), level);
+
+            let xsb = level.xsb().to_string();
+            let parsed = parse_format(&xsb, Format::Xsb, Variant::Push.into()).unwrap();
+            assert_eq!(parsed.xsb().to_string(), xsb);
+            assert_eq!(parsed.custom().to_string(), custom);
+            assert_eq!(parsed, level);
+            assert_eq!(level.to_string().parse::<Level>().unwrap(), level);
+
+            let rle = level.format(Format::XsbRle).to_string();
+            let parsed = parse_format(&rle, Format::XsbRle, Variant::Push.into()).unwrap();
+            assert_eq!(parsed.custom().to_string(), custom);
+            assert_eq!(parsed, level);
+        }
+    }
+
+    #[test]
+    fn multiple_players() {
+        let level = "#####\n#@$@#\n# . #\n#####";
+        assert_failure(level, &ParserErr::MultiplePlayers);
+        let level = Level::parse_variant(level, Variant::Multiban).unwrap();
+        assert_eq!(level.player_positions(), vec![(1, 1), (1, 3)]);
+        assert_eq!(level.to_string(), "#####\n#@$@#\n# . #\n#####\n");
+    }
+
     #[test]
     fn xsb_fail_pos() {
         let level = r"
@@ -334,7 +776,18 @@ mod tests {
 #@X.#
 #####
 ";
-        assert_failure(level, ParserErr::Pos(1, 2));
+        assert_syntax_err(level, 3, 3, SyntaxErrKind::InvalidChar('X'));
+
+        let err = level.parse::<Level>().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid character 'X' at line 3, column 3:\n#@X.#\n  ^"
+        );
+        let source = err.source().unwrap();
+        assert_eq!(
+            source.source().unwrap().to_string(),
+            "Invalid character 'X'"
+        );
     }
 
     #[test]
@@ -375,21 +828,193 @@ mod tests {
         assert_success_xsb(level);
     }
 
-    fn assert_failure(input_level: &str, expected_err: ParserErr) {
+    #[test]
+    fn xsb_rle() {
+        let rle = "4#|#@$.#|4#";
+        let plain = r"
+####
+#@$.#
+####
+";
+        let rle_level: Level = rle.parse().unwrap();
+        let plain_level: Level = plain.parse().unwrap();
+        assert_eq!(rle_level.to_string(), plain_level.to_string());
+
+        let rle = "
+2-5#
+2-#2-.#
+3#-$-#
+#-2@#-#
+#2-$-2#
+7#
+";
+        assert_failure(rle, &ParserErr::MultiplePlayers);
+        let rle = rle.replace("2@", "@-");
+        assert_success_xsb_rle(&rle);
+
+        let expected = r"
+  #####
+  #  .#
+### $ #
+# @ # #
+#  $ ##
+#######
+";
+        let level: Level = rle.parse().unwrap();
+        assert_eq!(level.to_string(), expected.trim_start_matches('\n'));
+    }
+
+    #[test]
+    fn xsb_rle_fail() {
+        assert_syntax_err("4#|#@$.3|4#", 1, 8, SyntaxErrKind::DanglingCount);
+        assert_syntax_err("4#|#@$.#|4#3", 1, 12, SyntaxErrKind::DanglingCount);
+        let too_wide = format!("{}#|#@$.#|4#", MAX_SIZE + 1);
+        assert_failure(&too_wide, &ParserErr::TooLarge);
+    }
+
+    #[test]
+    fn load_level_sources() {
+        let path = "levels/custom/02-one-way-xsb.txt";
+        let level = path.load_level().unwrap().to_string();
+        let load = |source: &dyn LoadLevel| source.load_level().unwrap().to_string();
+        assert_eq!(load(&path.to_owned()), level);
+        assert_eq!(load(&Path::new(path)), level);
+        assert_eq!(load(&PathBuf::from(path)), level);
+        assert_eq!(load(&OsString::from(path)), level);
+        assert_eq!(load(&File::open(path).unwrap()), level);
+
+        let xsb = fs::read_to_string(path).unwrap();
+        assert_eq!(Level::from_xsb_str(&xsb).unwrap().to_string(), level);
+        assert!(Level::from_custom_str(&xsb).is_err());
+
+        let custom = fs::read_to_string("levels/custom/02-one-way.txt").unwrap();
+        let level = Level::from_custom_str(&custom).unwrap();
+        assert_eq!(level.custom().to_string(), custom.trim_start_matches('\n'));
+        assert!(Level::from_xsb_str(&custom).is_err());
+    }
+
+    #[test]
+    fn xsb_metadata() {
+        let level: Level = r"
+; Simple
+;
+; made for testing
+
+#####
+#@$.#
+#####
+
+Author: me
+Comment:
+a longer
+comment
+Comment-End:
+"
+        .parse()
+        .unwrap();
+        assert_eq!(level.to_string(), "#####\n#@$.#\n#####\n");
+        let metadata = level.metadata();
+        assert_eq!(metadata.title, None);
+        assert_eq!(metadata.author.as_deref(), Some("me"));
+        assert_eq!(
+            metadata.properties,
+            vec![("Comment".to_owned(), "a longer\ncomment".to_owned())]
+        );
+
+        let level: Level = "; Simple\nTitle: Not so simple\n#####\n#@$.#\n#####"
+            .parse()
+            .unwrap();
+        assert_eq!(level.metadata().title.as_deref(), Some("Not so simple"));
+        let level: Level = "; Simple\n4#|#@$.#|4#".parse().unwrap();
+        assert_eq!(level.metadata().title.as_deref(), Some("Simple"));
+        assert_eq!(level.to_string(), "####\n#@$.#\n####\n");
+        let level: Level = "#####\n#@$.#\n#####".parse().unwrap();
+        assert_eq!(level.metadata(), &LevelMetadata::default());
+
+        // line numbers still count the comments
+        assert_syntax_err(
+            "; Title\n\n#####\n#@X.#\n#####\nAuthor: me",
+            4,
+            3,
+            SyntaxErrKind::InvalidChar('X'),
+        );
+    }
+
+    #[test]
+    fn explicit_format() {
+        let custom = "<><><><><>\n<>P B  _<>\n<><><><><>";
+        assert!(Level::parse_with_format(custom, Format::Custom).is_ok());
+        assert!(matches!(
+            Level::parse_with_format(custom, Format::Xsb),
+            Err(ParserErr::Syntax(SyntaxErr {
+                kind: SyntaxErrKind::InvalidChar('<'),
+                ..
+            }))
+        ));
+
+        let xsb = "#####\n#@$.#\n#####";
+        let options = ParseOptions {
+            format: Some(Format::Custom),
+            ..ParseOptions::default()
+        };
+        assert!(Level::parse_with_options(xsb, options).is_err());
+        let options = ParseOptions {
+            format: Some(Format::XsbRle),
+            ..ParseOptions::default()
+        };
+        let level = Level::parse_with_options(xsb, options).unwrap();
+        assert_eq!(
+            level.to_string(),
+            Level::parse_with_format(xsb, Format::Xsb)
+                .unwrap()
+                .to_string()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "large-levels")]
+    fn large_level() {
+        use crate::config::Method;
+        use crate::Solve;
+
+        let level: Level = "300#|#@$.296#|300#".parse().unwrap();
+        assert_eq!(level.cols(), 300);
+        let moves = level.solve(Method::Pushes, false).unwrap().moves;
+        assert_eq!(moves.unwrap().to_string(), "R");
+    }
+
+    fn assert_failure(input_level: &str, expected_err: &ParserErr) {
         // shared for XSB and custom because no need to print here
-        assert_eq!(input_level.parse::<Level>().unwrap_err(), expected_err);
+        assert_eq!(&input_level.parse::<Level>().unwrap_err(), expected_err);
+    }
+
+    fn assert_syntax_err(input_level: &str, line: usize, column: usize, kind: SyntaxErrKind) {
+        match input_level.parse::<Level>().unwrap_err() {
+            ParserErr::Syntax(err) => {
+                assert_eq!((err.line, err.column, err.kind), (line, column, kind));
+            }
+            err => panic!("Expected a syntax error, got: {}", err),
+        }
     }
 
     fn assert_success_custom(input_level: &str) {
-        let level = parse_format(input_level, Format::Custom).unwrap();
+        let level = parse_format(input_level, Format::Custom, Variant::Push.into()).unwrap();
         assert_eq!(
             level.custom().to_string(),
             input_level.trim_start_matches('\n')
         );
     }
 
+    fn assert_success_xsb_rle(input_level: &str) {
+        let level = parse_format(input_level, Format::XsbRle, Variant::Push.into()).unwrap();
+        assert_eq!(
+            level.format(Format::XsbRle).to_string(),
+            input_level.trim_matches('\n').replace('\n', "|") + "\n"
+        );
+    }
+
     fn assert_success_xsb(input_level: &str) {
-        let level = parse_format(input_level, Format::Xsb).unwrap();
+        let level = parse_format(input_level, Format::Xsb, Variant::Push.into()).unwrap();
         assert_eq!(level.to_string(), input_level.trim_start_matches('\n'));
     }
 }
@@ -0,0 +1,235 @@
+//! The solver plugin interface used by Sokoban YASC, Sokoban++ and other players.
+//!
+//! Build a DLL with `cargo rustc --lib --crate-type cdylib --release --features plugin`
+//! and put it into the player's plugin directory.
+//!
+//! The board is passed as `width * height` XSB characters without any line separators,
+//! the solution is returned as a nul terminated LURD string.
+
+// the names of the exported functions are given by the interface
+#![allow(non_snake_case)]
+
+use std::convert::TryFrom;
+use std::ffi::{c_char, c_int, c_uint, c_void};
+use std::{ptr, slice};
+
+use crate::config::{Format, Method, SolverOptions, Variant};
+use crate::data::{MAX_BOXES, MAX_SIZE};
+use crate::parser;
+use crate::solver::{SearchOutcome, Stats};
+use crate::Solve;
+
+/// The return codes of `Solve` and `SolveEx` as defined by the plugin interface
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PluginResult {
+    Ok = 0,
+    Unsolved = 1,
+    TimeOut = 2,
+    Failed = 3,
+    InvalidLevel = 4,
+    GameTooLarge = 5,
+    SolutionTooLong = 6,
+    TerminatedByUser = 7,
+}
+
+/// Called periodically by the plugin, a non-zero return value means the user wants to stop the search
+pub type PluginCallback = unsafe extern "system" fn() -> c_int;
+
+const PLUGIN_NAME: &str = "sokoban-solver (push optimal)";
+
+// Push optimal solutions with minimal moves are what players usually expect from a solver.
+const METHOD: Method = Method::PushesMoves;
+
+/// Writes the plugin's name as a nul terminated string, truncated to fit the buffer.
+///
+/// # Safety
+///
+/// `name` must be valid for writes of `buffer_size` bytes.
+#[no_mangle]
+pub unsafe extern "system" fn GetPluginName(name: *mut c_char, buffer_size: c_uint) {
+    if name.is_null() || buffer_size == 0 {
+        return;
+    }
+    let len = PLUGIN_NAME.len().min(buffer_size as usize - 1);
+    ptr::copy_nonoverlapping(PLUGIN_NAME.as_ptr().cast(), name, len);
+    *name.add(len) = 0;
+}
+
+/// The largest levels the plugin accepts.
+///
+/// # Safety
+///
+/// All non-null pointers must be valid for writes, null ones are skipped.
+#[no_mangle]
+pub unsafe extern "system" fn GetConstraints(
+    max_width: *mut c_uint,
+    max_height: *mut c_uint,
+    max_boxes: *mut c_uint,
+) {
+    if !max_width.is_null() {
+        *max_width = MAX_SIZE as c_uint;
+    }
+    if !max_height.is_null() {
+        *max_height = MAX_SIZE as c_uint;
+    }
+    if !max_boxes.is_null() {
+        *max_boxes = MAX_BOXES as c_uint;
+    }
+}
+
+// `Configure` is optional in the interface and there are no settings so it's not exported.
+
+/// # Safety
+///
+/// See `SolveEx`.
+#[no_mangle]
+pub unsafe extern "system" fn Solve(
+    width: c_uint,
+    height: c_uint,
+    board: *const c_char,
+    solution: *mut c_char,
+    solution_buffer_size: c_uint,
+) -> c_int {
+    SolveEx(
+        width,
+        height,
+        board,
+        solution,
+        solution_buffer_size,
+        None,
+        ptr::null_mut(),
+    )
+}
+
+/// Solves the level and writes the solution as a nul terminated LURD string.
+///
+/// `callback` is polled periodically and can be used to stop the search.
+/// `plugin_status` is part of the interface but the solver doesn't report any status through it
+/// so it's never dereferenced and can be null.
+///
+/// # Safety
+///
+/// `board` must be valid for reads of `width * height` bytes
+/// and `solution` must be valid for writes of `solution_buffer_size` bytes.
+#[no_mangle]
+pub unsafe extern "system" fn SolveEx(
+    width: c_uint,
+    height: c_uint,
+    board: *const c_char,
+    solution: *mut c_char,
+    solution_buffer_size: c_uint,
+    callback: Option<PluginCallback>,
+    _plugin_status: *mut c_void,
+) -> c_int {
+    if board.is_null() || solution.is_null() {
+        return PluginResult::Failed as c_int;
+    }
+    let (width, height) = match (usize::try_from(width), usize::try_from(height)) {
+        (Ok(width), Ok(height)) if width <= MAX_SIZE && height <= MAX_SIZE => (width, height),
+        _ => return PluginResult::GameTooLarge as c_int,
+    };
+    let board = slice::from_raw_parts(board.cast::<u8>(), width * height);
+    let solution = slice::from_raw_parts_mut(solution.cast::<u8>(), solution_buffer_size as usize);
+
+    let mut progress = |_: &_| match callback {
+        Some(callback) => callback() == 0,
+        None => true,
+    };
+    solve(width, board, solution, &mut progress) as c_int
+}
+
+fn solve(
+    width: usize,
+    board: &[u8],
+    solution: &mut [u8],
+    progress: &mut dyn FnMut(&Stats) -> bool,
+) -> PluginResult {
+    let rows: Vec<_> = board
+        .chunks(width.max(1))
+        .map(String::from_utf8_lossy)
+        .collect();
+    let Ok(level) = parser::parse_format(&rows.join("\n"), Format::Xsb, Variant::Push.into())
+    else {
+        return PluginResult::InvalidLevel;
+    };
+
+    let options = SolverOptions::default();
+    let Ok(solver_ok) = level.solve_with_progress(METHOD, &options, progress) else {
+        return PluginResult::InvalidLevel;
+    };
+    let moves = match solver_ok.moves {
+        Some(moves) => moves.to_string(),
+        None if solver_ok.outcome() == SearchOutcome::ProvenUnsolvable => {
+            return PluginResult::Unsolved
+        }
+        None => return PluginResult::TerminatedByUser,
+    };
+
+    // leave space for the nul terminator
+    if moves.len() >= solution.len() {
+        return PluginResult::SolutionTooLong;
+    }
+    solution[..moves.len()].copy_from_slice(moves.as_bytes());
+    solution[moves.len()] = 0;
+    PluginResult::Ok
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CStr;
+
+    use super::*;
+
+    #[test]
+    fn solving() {
+        let board = "######@$.######";
+        let mut solution: [c_char; 8] = [1; 8];
+        let result = unsafe {
+            Solve(
+                5,
+                3,
+                board.as_ptr().cast(),
+                solution.as_mut_ptr(),
+                solution.len() as c_uint,
+            )
+        };
+        assert_eq!(result, PluginResult::Ok as c_int);
+        let solution = unsafe { CStr::from_ptr(solution.as_ptr()) };
+        assert_eq!(solution.to_str().unwrap(), "R");
+
+        let mut solution: [c_char; 1] = [1; 1];
+        let result = unsafe { Solve(5, 3, board.as_ptr().cast(), solution.as_mut_ptr(), 1) };
+        assert_eq!(result, PluginResult::SolutionTooLong as c_int);
+
+        let board = "######@$ ######";
+        let result = unsafe { Solve(5, 3, board.as_ptr().cast(), solution.as_mut_ptr(), 1) };
+        assert_eq!(result, PluginResult::InvalidLevel as c_int);
+
+        unsafe extern "system" fn stop() -> c_int {
+            1
+        }
+        let board = "#######@$ .#######";
+        let mut solution: [c_char; 8] = [1; 8];
+        let result = unsafe {
+            SolveEx(
+                6,
+                3,
+                board.as_ptr().cast(),
+                solution.as_mut_ptr(),
+                solution.len() as c_uint,
+                Some(stop),
+                ptr::null_mut(),
+            )
+        };
+        assert_eq!(result, PluginResult::TerminatedByUser as c_int);
+    }
+
+    #[test]
+    fn constraints() {
+        let mut max_width = 0;
+        let mut max_boxes = 0;
+        unsafe { GetConstraints(&raw mut max_width, ptr::null_mut(), &raw mut max_boxes) };
+        assert_eq!(max_width, MAX_SIZE as c_uint);
+        assert_eq!(max_boxes, MAX_BOXES as c_uint);
+    }
+}
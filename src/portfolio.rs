@@ -0,0 +1,167 @@
+//! Portfolio solving - cheap configurations first, stronger ones only for the levels they fail on.
+
+use std::fmt::{self, Display, Formatter};
+
+use crate::config::{AnyProfile, Method, SolverOptions};
+use crate::level::Level;
+use crate::solver::{IncrementalSolver, SearchOutcome, SolverErr, SolverOk, Stats};
+
+/// One configuration tried by `Level::solve_portfolio`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stage {
+    pub method: Method,
+    pub options: SolverOptions,
+}
+
+impl Stage {
+    /// A nearly greedy search with few states, weighted A* with more
+    /// and finally `method` with `options` as they are.
+    ///
+    /// Most levels of a typical pack are solved by the first stage in a fraction of a second,
+    /// the last one is only needed for the hard ones.
+    pub fn escalating(method: Method, options: &SolverOptions) -> Vec<Stage> {
+        let any = |any_profile, max_nodes| Stage {
+            method: Method::Any,
+            options: SolverOptions {
+                any_profile,
+                max_nodes: Some(max_nodes),
+                ..*options
+            },
+        };
+        vec![
+            any(AnyProfile::Fast, 10_000),
+            any(AnyProfile::Balanced, 1_000_000),
+            Stage {
+                method,
+                options: *options,
+            },
+        ]
+    }
+}
+
+impl Display for Stage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.method)?;
+        if self.method == Method::Any {
+            write!(f, " ({})", self.options.any_profile)?;
+        }
+        if let Some(max_depth) = self.options.max_depth {
+            write!(f, ", depth at most {max_depth}")?;
+        }
+        if let Some(max_nodes) = self.options.max_nodes {
+            write!(f, ", at most {max_nodes} states")?;
+        }
+        Ok(())
+    }
+}
+
+/// The result of `Level::solve_portfolio`
+#[derive(Debug)]
+pub struct PortfolioOk {
+    /// Index of the stage which solved the level or proved it unsolvable,
+    /// `None` if all stages hit their limits or the search was interrupted
+    pub stage: Option<usize>,
+    /// The result of the last stage which ran
+    pub solver_ok: SolverOk,
+}
+
+impl Level {
+    /// Tries the stages in order until one of them solves the level or proves it unsolvable.
+    ///
+    /// Preprocessing is shared by the stages. `progress` is shared too,
+    /// returning `false` from it stops the current stage and skips the rest.
+    ///
+    /// # Panics
+    ///
+    /// If `stages` is empty.
+    pub fn solve_portfolio(
+        &self,
+        stages: &[Stage],
+        progress: &mut dyn FnMut(&Stats) -> bool,
+    ) -> Result<PortfolioOk, SolverErr> {
+        solve_stages(&mut IncrementalSolver::new(), self, stages, progress)
+    }
+}
+
+pub(crate) fn solve_stages(
+    solver: &mut IncrementalSolver,
+    level: &Level,
+    stages: &[Stage],
+    progress: &mut dyn FnMut(&Stats) -> bool,
+) -> Result<PortfolioOk, SolverErr> {
+    assert!(!stages.is_empty(), "Portfolio needs at least one stage");
+
+    let mut last = None;
+    for (index, stage) in stages.iter().enumerate() {
+        let solver_ok =
+            solver.solve_with_progress(level, stage.method, &stage.options, progress)?;
+        if solver_ok.outcome() != SearchOutcome::LimitReached {
+            return Ok(PortfolioOk {
+                stage: Some(index),
+                solver_ok,
+            });
+        }
+        let interrupted = solver_ok.interrupted;
+        last = Some(solver_ok);
+        if interrupted {
+            break;
+        }
+    }
+    Ok(PortfolioOk {
+        stage: None,
+        solver_ok: last.expect("At least one stage ran"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn portfolio() {
+        let level: Level = r"
+########
+#@ $  .#
+#  ##  #
+# $  $.#
+#.     #
+########
+"
+        .parse()
+        .unwrap();
+        let limited = |max_nodes| Stage {
+            method: Method::Pushes,
+            options: SolverOptions {
+                max_nodes: Some(max_nodes),
+                ..SolverOptions::default()
+            },
+        };
+
+        let stages = [limited(1), limited(2), limited(1_000_000)];
+        let result = level.solve_portfolio(&stages, &mut |_| true).unwrap();
+        assert_eq!(result.stage, Some(2));
+        assert_eq!(result.solver_ok.metadata.method, Some(Method::Pushes));
+        assert!(result.solver_ok.moves.is_some());
+
+        let result = level.solve_portfolio(&stages[..2], &mut |_| true).unwrap();
+        assert_eq!(result.stage, None);
+        assert!(result.solver_ok.limit_reached.is_some());
+
+        let mut calls = 0;
+        let result = level
+            .solve_portfolio(&stages, &mut |_| {
+                calls += 1;
+                false
+            })
+            .unwrap();
+        assert_eq!(result.stage, None);
+        assert!(result.solver_ok.interrupted);
+        assert_eq!(calls, 1);
+
+        let stages = Stage::escalating(Method::Moves, &SolverOptions::default());
+        assert_eq!(stages[0].to_string(), "any (fast), at most 10000 states");
+        assert_eq!(stages[2].to_string(), "moves");
+        let result = level.solve_portfolio(&stages, &mut |_| true).unwrap();
+        assert_eq!(result.stage, Some(0));
+    }
+}
@@ -0,0 +1,232 @@
+//! Push distances of a single box ignoring all other boxes -
+//! what the solver's heuristic and dead square detection are based on.
+
+use std::borrow::Cow;
+use std::fmt::{self, Debug, Formatter};
+
+use crate::config::Variant;
+use crate::data::{Coord, Dir, MapCell, Pos, DIRECTIONS};
+use crate::level::Level;
+use crate::solver::preprocessing;
+use crate::solver::SolverErr;
+use crate::vec2d::Vec2d;
+
+/// How much of the push distances `Level::push_distances` computes up front
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PushDistancesMode {
+    /// Distances between all pairs of squares, the same table the solver uses.
+    /// Memory grows with the fourth power of the map's size (16 MiB for a 32x32 map).
+    #[default]
+    Full,
+    /// Only the distances to the closest goal or remover are kept,
+    /// the rest are recomputed on each query. Memory grows with the number of squares.
+    Lazy,
+}
+
+/// Numbers of pushes (pulls in `Variant::Pull`) needed to move a box from one square to another
+/// if it was the only box in the level.
+///
+/// Positions are `(row, column)`. A box can only reach some squares depending on which side
+/// the player starts on so most distances depend on it. `None` means the square can't be reached
+/// (or it's a wall or outside the area the player can reach).
+#[derive(Clone)]
+pub struct PushDistances {
+    mode: PushDistancesMode,
+    variant: Variant,
+    /// The map with unreachable squares turned into walls
+    grid: Vec2d<MapCell>,
+    /// Indexed by the start position and the direction from the player to the box,
+    /// only present in `PushDistancesMode::Full`
+    dists: Option<Vec2d<[Vec2d<Option<u16>>; 4]>>,
+    closest: Vec2d<Option<u16>>,
+}
+
+impl Level {
+    /// Computes how far boxes are from each other square and the closest goals or removers.
+    ///
+    /// Squares with no distance to a goal or remover are dead - a box pushed there
+    /// can never be solved.
+    pub fn push_distances(&self, mode: PushDistancesMode) -> Result<PushDistances, SolverErr> {
+        let grid = preprocessing::check_reachability(self.map(), &self.state)?;
+
+        let (dists, closest) = match mode {
+            PushDistancesMode::Full => {
+                let dists = preprocessing::push_dists(&grid, self.variant);
+                let mut closest = grid.scratchpad();
+                for pos in grid.positions() {
+                    if grid[pos] != MapCell::Wall {
+                        closest[pos] = dists[pos]
+                            .iter()
+                            .filter_map(|dests| preprocessing::closest_dest_dist(&grid, dests))
+                            .min();
+                    }
+                }
+                (Some(dists), closest)
+            }
+            PushDistancesMode::Lazy => {
+                let mut closest = grid.scratchpad();
+                for pos in grid.positions() {
+                    closest[pos] = DIRECTIONS
+                        .iter()
+                        .filter_map(|&dir| {
+                            let dests = lazy_dists(&grid, pos, dir, self.variant)?;
+                            preprocessing::closest_dest_dist(&grid, &dests)
+                        })
+                        .min();
+                }
+                (None, closest)
+            }
+        };
+
+        Ok(PushDistances {
+            mode,
+            variant: self.variant,
+            grid,
+            dists,
+            closest,
+        })
+    }
+}
+
+impl PushDistances {
+    pub fn mode(&self) -> PushDistancesMode {
+        self.mode
+    }
+
+    /// Fewest pushes from each square to any goal or remover, `None` for dead squares
+    pub fn closest(&self) -> &Vec2d<Option<u16>> {
+        &self.closest
+    }
+
+    /// Fewest pushes from the square to any goal or remover, `None` for dead squares
+    pub fn closest_from(&self, box_pos: (usize, usize)) -> Option<u16> {
+        self.closest.get(box_pos.0, box_pos.1).copied().flatten()
+    }
+
+    /// Push distances from the square to all squares with the player starting next to the box,
+    /// `player_to_box` is the direction from the player to the box.
+    ///
+    /// Borrowed in `PushDistancesMode::Full`, computed on each call in `PushDistancesMode::Lazy`.
+    pub fn from_side(
+        &self,
+        box_pos: (usize, usize),
+        player_to_box: Dir,
+    ) -> Cow<'_, Vec2d<Option<u16>>> {
+        let Some(pos) = self.pos(box_pos) else {
+            return Cow::Owned(self.grid.scratchpad());
+        };
+        match self.dists {
+            Some(ref dists) => Cow::Borrowed(&dists[pos][player_to_box as usize]),
+            None => Cow::Owned(
+                lazy_dists(&self.grid, pos, player_to_box, self.variant)
+                    .unwrap_or_else(|| self.grid.scratchpad()),
+            ),
+        }
+    }
+
+    /// Fewest pushes between two squares with the player starting on whichever side is best
+    pub fn between(&self, from: (usize, usize), to: (usize, usize)) -> Option<u16> {
+        DIRECTIONS
+            .iter()
+            .filter_map(|&dir| self.from_side(from, dir).get(to.0, to.1).copied().flatten())
+            .min()
+    }
+
+    /// The position if it's inside the map and not a wall
+    fn pos(&self, (r, c): (usize, usize)) -> Option<Pos> {
+        match self.grid.get(r, c) {
+            Some(&cell) if cell != MapCell::Wall => Some(Pos::new(r as Coord, c as Coord)),
+            _ => None,
+        }
+    }
+}
+
+impl Debug for PushDistances {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        // the full table is too big to be useful
+        f.debug_struct("PushDistances")
+            .field("mode", &self.mode)
+            .field("variant", &self.variant)
+            .field("closest", &self.closest)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Push distances from one position and side without the precomputed push directions,
+/// `None` if the box or the player would be in a wall
+fn lazy_dists(
+    grid: &Vec2d<MapCell>,
+    box_pos: Pos,
+    player_to_box: Dir,
+    variant: Variant,
+) -> Option<Vec2d<Option<u16>>> {
+    if grid[box_pos] == MapCell::Wall || grid[box_pos - player_to_box] == MapCell::Wall {
+        return None;
+    }
+    Some(preprocessing::push_dists_from(
+        grid,
+        box_pos,
+        player_to_box,
+        variant,
+        |pos, dir| preprocessing::box_dirs(grid, pos, dir, variant),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_and_lazy_agree() {
+        let level: Level = r"
+#######
+#@    #
+# $ # #
+#  #  #
+#    .#
+#######
+"
+        .parse()
+        .unwrap();
+
+        let full = level.push_distances(PushDistancesMode::Full).unwrap();
+        let lazy = level.push_distances(PushDistancesMode::Lazy).unwrap();
+        assert_eq!(full.mode(), PushDistancesMode::Full);
+        assert_eq!(lazy.mode(), PushDistancesMode::Lazy);
+        assert_eq!(full.closest(), lazy.closest());
+        for r in 0..6 {
+            for c in 0..7 {
+                for &dir in &DIRECTIONS {
+                    assert_eq!(full.from_side((r, c), dir), lazy.from_side((r, c), dir));
+                }
+            }
+        }
+
+        // 2 pushes down and 3 right, the player can get to both sides
+        assert_eq!(full.closest_from((2, 2)), Some(5));
+        assert_eq!(full.between((2, 2), (4, 5)), Some(5));
+        // corners are dead
+        assert_eq!(full.closest_from((1, 1)), None);
+        assert_eq!(full.between((1, 1), (4, 5)), None);
+        // walls and squares outside the map have no distances
+        assert_eq!(full.closest_from((0, 0)), None);
+        assert_eq!(full.closest_from((10, 10)), None);
+        assert_eq!(lazy.between((10, 10), (4, 5)), None);
+    }
+
+    #[test]
+    fn depends_on_side() {
+        let level: Level = "#######\n#@$  .#\n#######".parse().unwrap();
+        let dists = level.push_distances(PushDistancesMode::Lazy).unwrap();
+        assert_eq!(
+            dists.from_side((1, 2), Dir::Right).get(1, 5),
+            Some(&Some(3))
+        );
+        assert_eq!(dists.from_side((1, 2), Dir::Left).get(1, 5), Some(&None));
+
+        let mut level: Level = "########\n# .@$  #\n########".parse().unwrap();
+        level.set_variant(Variant::Pull);
+        let dists = level.push_distances(PushDistancesMode::Full).unwrap();
+        assert_eq!(dists.closest_from((1, 4)), Some(2));
+    }
+}
@@ -0,0 +1,348 @@
+//! A file format for the results of solving many levels so they can be compared between versions
+//! of the solver or with other tools.
+//!
+//! The file is JSON:
+//!
+//! ```json
+//! {
+//!   "version": 2,
+//!   "method": "pushes",
+//!   "levels": [
+//!     {
+//!       "name": "levels/custom/01-simplest-custom.txt", "solution": "R", "moves": 1, "pushes": 1, "created": 2, "visited": 2,
+//!       "depths": [
+//!         { "depth": 0, "created": 1, "unique_visited": 1, "reached_duplicates": 0 },
+//!         { "depth": 1, "created": 1, "unique_visited": 1, "reached_duplicates": 0 }
+//!       ],
+//!       "steps": "###\n#@#\n..."
+//!     },
+//!     { "name": "levels/custom/unsolvable.txt", "solution": null, "moves": null, "pushes": null, "created": 10, "visited": 10, "depths": [] }
+//!   ]
+//! }
+//! ```
+//!
+//! `method` is one of the `solve` flags (`moves-pushes`, `moves`, `pushes-moves`,
+//! `pushes-box-lines`, `pushes` or `any`), `solution` is in the LURD format
+//! and `created` and `visited` are the numbers of states. `depths` are the states
+//! by the cost of reaching them (empty when the result came from the cache)
+//! and the optional `steps` are the level drawn after each push (or move), see `LevelRecord::steps`.
+//! Levels whose search was interrupted are not recorded.
+//!
+//! Version 2 added `depths` and `steps`, version 1 files are still read (without them).
+//! Files with a newer `version` are rejected.
+
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::Method;
+use crate::level::Level;
+use crate::moves::Moves;
+use crate::solver::{DepthStats, SolverOk};
+
+/// The version written by this version of the solver
+pub const RESULTS_VERSION: u32 = 2;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Results {
+    pub version: u32,
+    pub method: Method,
+    pub levels: Vec<LevelRecord>,
+}
+
+/// The result of solving one level
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LevelRecord {
+    /// Usually the path of the level file (and the level number in a collection)
+    pub name: String,
+    /// LURD, `None` if the level has no solution
+    pub solution: Option<String>,
+    pub moves: Option<usize>,
+    pub pushes: Option<usize>,
+    pub created: i32,
+    pub visited: i32,
+    /// Empty if the stats by depth are not known
+    #[serde(default)]
+    pub depths: Vec<DepthStats>,
+    /// The level after each push of the solution (after each move with `include_steps`)
+    /// as printed by `Level::format_solution`, only recorded by `with_steps` because it's large
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub steps: Option<String>,
+}
+
+impl LevelRecord {
+    pub fn new(name: String, solver_ok: &SolverOk) -> Self {
+        let mut record = Self::from_parts(
+            name,
+            solver_ok.moves.as_ref(),
+            solver_ok.stats.total_created(),
+            solver_ok.stats.total_unique_visited(),
+        );
+        record.depths = solver_ok.stats.by_depth().collect();
+        record
+    }
+
+    /// Also records `steps`, the solution must be from `level`
+    #[must_use]
+    pub fn with_steps(mut self, level: &Level, moves: Option<&Moves>, include_steps: bool) -> Self {
+        self.steps = moves.map(|moves| level.xsb_solution(moves, include_steps).to_string());
+        self
+    }
+
+    pub fn from_parts(name: String, moves: Option<&Moves>, created: i32, visited: i32) -> Self {
+        LevelRecord {
+            name,
+            solution: moves.map(ToString::to_string),
+            moves: moves.map(Moves::move_cnt),
+            pushes: moves.map(Moves::push_cnt),
+            created,
+            visited,
+            depths: Vec::new(),
+            steps: None,
+        }
+    }
+}
+
+impl Results {
+    pub fn new(method: Method) -> Self {
+        Results {
+            version: RESULTS_VERSION,
+            method,
+            levels: Vec::new(),
+        }
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, ResultsErr> {
+        let results: Results = serde_json::from_str(json).map_err(ResultsErr::Json)?;
+        if results.version > RESULTS_VERSION {
+            return Err(ResultsErr::UnsupportedVersion(results.version));
+        }
+        Ok(results)
+    }
+
+    /// Pretty-printed with a trailing newline
+    ///
+    /// # Panics
+    ///
+    /// Never, all the fields serialize to JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Results are always valid JSON") + "\n"
+    }
+
+    /// Differences of `new` from `self` (the older results) in the order of `self`'s levels,
+    /// levels only in `new` are at the end
+    pub fn compare(&self, new: &Results) -> Vec<Change> {
+        let mut changes = Vec::new();
+        if self.method != new.method {
+            changes.push(Change::Method {
+                old: self.method,
+                new: new.method,
+            });
+        }
+
+        for old in &self.levels {
+            let Some(new) = new.levels.iter().find(|level| level.name == old.name) else {
+                changes.push(Change::Missing(old.name.clone()));
+                continue;
+            };
+            if old.solution.is_some() != new.solution.is_some() {
+                changes.push(Change::Solvability {
+                    name: old.name.clone(),
+                    solved: new.solution.is_some(),
+                });
+            } else if (old.moves, old.pushes) != (new.moves, new.pushes) {
+                changes.push(Change::Length {
+                    name: old.name.clone(),
+                    old: (old.moves, old.pushes),
+                    new: (new.moves, new.pushes),
+                });
+            } else if old.solution != new.solution {
+                changes.push(Change::Solution(old.name.clone()));
+            }
+            if (old.created, old.visited) != (new.created, new.visited) {
+                changes.push(Change::States {
+                    name: old.name.clone(),
+                    old: (old.created, old.visited),
+                    new: (new.created, new.visited),
+                });
+            }
+        }
+
+        for new in &new.levels {
+            if !self.levels.iter().any(|level| level.name == new.name) {
+                changes.push(Change::Added(new.name.clone()));
+            }
+        }
+        changes
+    }
+}
+
+/// A difference between two `Results`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    Method {
+        old: Method,
+        new: Method,
+    },
+    /// The level is only in the old results
+    Missing(String),
+    /// The level is only in the new results
+    Added(String),
+    /// The level became solvable or unsolvable
+    Solvability {
+        name: String,
+        solved: bool,
+    },
+    /// Moves and pushes
+    Length {
+        name: String,
+        old: (Option<usize>, Option<usize>),
+        new: (Option<usize>, Option<usize>),
+    },
+    /// A different solution of the same length
+    Solution(String),
+    /// Created and visited states
+    States {
+        name: String,
+        old: (i32, i32),
+        new: (i32, i32),
+    },
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        fn len(moves: Option<usize>) -> String {
+            moves.map_or_else(|| "-".to_owned(), |moves| moves.to_string())
+        }
+
+        match self {
+            Change::Method { old, new } => write!(f, "Method changed from {old} to {new}"),
+            Change::Missing(name) => write!(f, "{name}: missing"),
+            Change::Added(name) => write!(f, "{name}: added"),
+            Change::Solvability { name, solved: true } => write!(f, "{name}: now solved"),
+            Change::Solvability {
+                name,
+                solved: false,
+            } => write!(f, "{name}: no longer solved"),
+            Change::Length { name, old, new } => write!(
+                f,
+                "{name}: moves {} -> {}, pushes {} -> {}",
+                len(old.0),
+                len(new.0),
+                len(old.1),
+                len(new.1)
+            ),
+            Change::Solution(name) => write!(f, "{name}: different solution of the same length"),
+            Change::States { name, old, new } => write!(
+                f,
+                "{name}: created {} -> {}, visited {} -> {}",
+                old.0, new.0, old.1, new.1
+            ),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ResultsErr {
+    Json(serde_json::Error),
+    /// Written by a newer version of the solver
+    UnsupportedVersion(u32),
+}
+
+impl Display for ResultsErr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ResultsErr::Json(err) => write!(f, "Invalid results file: {err}"),
+            ResultsErr::UnsupportedVersion(version) => write!(
+                f,
+                "Results file version {version} is newer than the supported version {RESULTS_VERSION}"
+            ),
+        }
+    }
+}
+
+impl Error for ResultsErr {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ResultsErr::Json(err) => Some(err),
+            ResultsErr::UnsupportedVersion(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::level::Level;
+    use crate::Solve;
+
+    #[test]
+    fn record_and_compare() {
+        let level: Level = "######\n#@$ .#\n######".parse().unwrap();
+        let solver_ok = level.solve(Method::Pushes, false).unwrap();
+
+        let mut old = Results::new(Method::Pushes);
+        old.levels
+            .push(LevelRecord::new("a".to_owned(), &solver_ok));
+        old.levels
+            .push(LevelRecord::from_parts("b".to_owned(), None, 5, 5));
+        assert_eq!(old.levels[0].solution.as_deref(), Some("RR"));
+        assert_eq!(old.levels[0].pushes, Some(2));
+
+        let json = old.to_json();
+        assert_eq!(Results::from_json(&json).unwrap(), old);
+        assert!(old.compare(&old).is_empty());
+
+        let mut new = old.clone();
+        new.levels.remove(1);
+        new.levels[0].created += 1;
+        new.levels
+            .push(LevelRecord::from_parts("c".to_owned(), None, 1, 1));
+        assert_eq!(
+            old.compare(&new),
+            [
+                Change::States {
+                    name: "a".to_owned(),
+                    old: (3, 3),
+                    new: (4, 3)
+                },
+                Change::Missing("b".to_owned()),
+                Change::Added("c".to_owned()),
+            ]
+        );
+
+        let newer = json.replacen("\"version\": 2", "\"version\": 3", 1);
+        assert!(matches!(
+            Results::from_json(&newer),
+            Err(ResultsErr::UnsupportedVersion(3))
+        ));
+
+        // version 1 had no depths and steps
+        let v1 = r#"{"version": 1, "method": "pushes", "levels": [
+            {"name": "b", "solution": null, "moves": null, "pushes": null, "created": 5, "visited": 5}
+        ]}"#;
+        let v1 = Results::from_json(v1).unwrap();
+        assert_eq!(v1.levels[0], old.levels[1]);
+    }
+
+    #[test]
+    fn steps() {
+        let level: Level = "######\n#@$ .#\n######".parse().unwrap();
+        let solver_ok = level.solve(Method::Pushes, false).unwrap();
+        let record = LevelRecord::new("a".to_owned(), &solver_ok).with_steps(
+            &level,
+            solver_ok.moves.as_ref(),
+            false,
+        );
+        assert_eq!(record.depths.len(), 3);
+        assert_eq!(
+            record.steps.as_deref(),
+            Some("######\n#@$ .#\n######\n\n######\n# @$.#\n######\n\n######\n#  @*#\n######\n\n")
+        );
+        let mut results = Results::new(Method::Pushes);
+        results.levels.push(record);
+        assert_eq!(Results::from_json(&results.to_json()).unwrap(), results);
+    }
+}
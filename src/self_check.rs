@@ -0,0 +1,284 @@
+//! Independent checks of the solver's results - useful when experimenting with new prunings
+//! or heuristics which might find suboptimal solutions or miss existing ones.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+use crate::config::{Heuristic, Method, SolverOptions};
+use crate::game::Board;
+use crate::level::Level;
+use crate::moves::Moves;
+use crate::solver::{SearchOutcome, SolverErr, SolverOk};
+use crate::verify::MoveErr;
+use crate::Solve;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelfCheckErr {
+    /// The solution contains an illegal move
+    Replay(MoveErr),
+    /// All moves are legal but the level is not solved at the end
+    NotSolved,
+    /// The search with `Heuristic::MisplacedBoxes` found a solution of a different length
+    /// (or found one when there should be none or the other way around)
+    HeuristicMismatch {
+        method: Method,
+        /// Moves and pushes of the checked solution
+        found: Option<(usize, usize)>,
+        /// Moves and pushes of the solution found with the other heuristic
+        check: Option<(usize, usize)>,
+    },
+    /// Solutions of two methods contradict the optimality of the first one
+    MethodMismatch {
+        first: Method,
+        first_found: Option<(usize, usize)>,
+        second: Method,
+        second_found: Option<(usize, usize)>,
+    },
+    /// Searching with the other heuristic failed
+    Solver(SolverErr),
+}
+
+impl Display for SelfCheckErr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match *self {
+            SelfCheckErr::Replay(err) => write!(f, "Solution can't be replayed: {err}"),
+            SelfCheckErr::NotSolved => write!(f, "Solution doesn't solve the level"),
+            SelfCheckErr::HeuristicMismatch {
+                method,
+                found,
+                check,
+            } => write!(
+                f,
+                "{method} found {} but the search with a different heuristic found {}",
+                Lengths(found),
+                Lengths(check)
+            ),
+            SelfCheckErr::MethodMismatch {
+                first,
+                first_found,
+                second,
+                second_found,
+            } => write!(
+                f,
+                "{first} found {} which can't be optimal because {second} found {}",
+                Lengths(first_found),
+                Lengths(second_found)
+            ),
+            SelfCheckErr::Solver(ref err) => {
+                write!(f, "Search with a different heuristic failed: {err}")
+            }
+        }
+    }
+}
+
+impl Error for SelfCheckErr {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            SelfCheckErr::Replay(ref err) => Some(err),
+            SelfCheckErr::Solver(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+struct Lengths(Option<(usize, usize)>);
+
+impl Display for Lengths {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some((moves, pushes)) => write!(f, "{moves} moves, {pushes} pushes"),
+            None => write!(f, "no solution"),
+        }
+    }
+}
+
+impl Level {
+    /// Checks the result of a search independently of the search itself.
+    ///
+    /// The solution is replayed with `Board`. If `method` is optimal and
+    /// `options.suboptimality_percent` is 0, the level is also solved again
+    /// with `Heuristic::MisplacedBoxes` which must find a solution of the same length.
+    /// `None` means the search proved there is no solution (searches stopped by a limit
+    /// or interrupted prove nothing so they shouldn't be checked).
+    ///
+    /// The second search can take much longer than the original one.
+    pub fn self_check(
+        &self,
+        method: Method,
+        options: &SolverOptions,
+        solution: Option<&Moves>,
+    ) -> Result<(), SelfCheckErr> {
+        if let Some(moves) = solution {
+            let mut board = Board::new(self.clone());
+            board.play(moves).map_err(SelfCheckErr::Replay)?;
+            if !board.is_solved() {
+                return Err(SelfCheckErr::NotSolved);
+            }
+        }
+
+        if method == Method::Any || options.suboptimality_percent > 0 {
+            return Ok(());
+        }
+        let check_options = SolverOptions {
+            print_status: false,
+            checkpoint: false,
+            max_depth: None,
+            max_nodes: None,
+            status_interval: None,
+            heuristic: Heuristic::MisplacedBoxes,
+            ..*options
+        };
+        let check = self
+            .solve_with_options(method, &check_options)
+            .map_err(SelfCheckErr::Solver)?
+            .moves;
+        let found = solution.map(lengths);
+        let check = check.as_ref().map(lengths);
+        if found.map(|found| key(method, found)) != check.map(|check| key(method, check)) {
+            return Err(SelfCheckErr::HeuristicMismatch {
+                method,
+                found,
+                check,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Checks that the results of different methods (e.g. from `Level::solve_all_methods`)
+/// don't contradict each other - a solution found by any method can't be better
+/// than an optimal one in its metric and either all of them find a solution or none.
+///
+/// Results of searches which didn't finish and suboptimal solutions are skipped.
+pub fn check_methods(results: &BTreeMap<Method, SolverOk>) -> Result<(), SelfCheckErr> {
+    let finished: Vec<_> = results
+        .iter()
+        .filter(|(_, solver_ok)| solver_ok.outcome() != SearchOutcome::LimitReached)
+        .map(|(&method, solver_ok)| {
+            let found = solver_ok.moves.as_ref().map(lengths);
+            (method, solver_ok.suboptimality_percent == 0, found)
+        })
+        .collect();
+
+    for &(first, optimal, first_found) in &finished {
+        if first == Method::Any || !optimal {
+            continue;
+        }
+        for &(second, _, second_found) in &finished {
+            let contradicts = match (first_found, second_found) {
+                (Some(first_found), Some(second_found)) => {
+                    key(first, first_found) > key(first, second_found)
+                }
+                (None, None) => false,
+                _ => true,
+            };
+            if contradicts {
+                return Err(SelfCheckErr::MethodMismatch {
+                    first,
+                    first_found,
+                    second,
+                    second_found,
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+fn lengths(moves: &Moves) -> (usize, usize) {
+    (moves.move_cnt(), moves.push_cnt())
+}
+
+/// What the method minimizes, in order of importance
+fn key(method: Method, (moves, pushes): (usize, usize)) -> (usize, usize) {
+    match method {
+        Method::MovesPushes => (moves, pushes),
+        Method::Moves => (moves, 0),
+        Method::PushesMoves => (pushes, moves),
+        Method::PushesBoxLines | Method::Pushes | Method::Any => (pushes, 0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::solver::UnsolvableReason;
+
+    #[test]
+    fn checking_results() {
+        let level: Level = r"
+#######
+#     #
+#@$  .#
+#     #
+#######
+"
+        .parse()
+        .unwrap();
+        let options = SolverOptions::default();
+
+        let mut results = level.solve_all_methods(&options).unwrap();
+        check_methods(&results).unwrap();
+        for (&method, solver_ok) in &results {
+            level
+                .self_check(method, &options, solver_ok.moves.as_ref())
+                .unwrap();
+        }
+
+        // pushes the box back and forth
+        let detour = Moves::from_lurd("RurrdLulldRRR").unwrap();
+        assert_eq!(
+            level.self_check(Method::Any, &options, Some(&detour)),
+            Ok(())
+        );
+        assert_eq!(
+            level.self_check(Method::PushesMoves, &options, Some(&detour)),
+            Err(SelfCheckErr::HeuristicMismatch {
+                method: Method::PushesMoves,
+                found: Some((13, 5)),
+                check: Some((3, 3)),
+            })
+        );
+        results.get_mut(&Method::Pushes).unwrap().moves = Some(detour);
+        assert_eq!(
+            check_methods(&results),
+            Err(SelfCheckErr::MethodMismatch {
+                first: Method::Pushes,
+                first_found: Some((13, 5)),
+                second: Method::MovesPushes,
+                second_found: Some((3, 3)),
+            })
+        );
+        let pushes = results.get_mut(&Method::Pushes).unwrap();
+        pushes.moves = None;
+        pushes.unsolvable = Some(UnsolvableReason::Exhausted);
+        assert!(matches!(
+            check_methods(&results),
+            Err(SelfCheckErr::MethodMismatch {
+                second_found: None,
+                ..
+            })
+        ));
+
+        let unsolved = Moves::from_lurd("R").unwrap();
+        assert_eq!(
+            level.self_check(Method::Any, &options, Some(&unsolved)),
+            Err(SelfCheckErr::NotSolved)
+        );
+        let illegal = Moves::from_lurd("l").unwrap();
+        assert_eq!(
+            level.self_check(Method::Any, &options, Some(&illegal)),
+            Err(SelfCheckErr::Replay(MoveErr::Wall))
+        );
+        assert_eq!(
+            level.self_check(Method::Moves, &options, None),
+            Err(SelfCheckErr::HeuristicMismatch {
+                method: Method::Moves,
+                found: None,
+                check: Some((3, 3)),
+            })
+        );
+    }
+}
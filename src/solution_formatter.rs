@@ -1,23 +1,29 @@
+use std::convert::TryFrom;
 use std::fmt::{self, Debug, Display, Formatter};
 
-use crate::config::Format;
-use crate::data::MapCell;
+use crate::config::{Format, Method, Variant};
+use crate::data::{MapCell, Pos};
+use crate::level::LevelMetadata;
 use crate::map::Map;
-use crate::moves::Moves;
+use crate::moves::{Move, Moves};
 use crate::state::State;
+use crate::verify::{self, BoxAssignment, BoxFate, BoxTracker};
 
 pub struct SolutionFormatter<'a> {
     map: &'a dyn Map,
     initial_state: &'a State,
+    variant: Variant,
     moves: &'a Moves,
     include_steps: bool,
     format: Format,
+    annotated: bool,
 }
 
 impl<'a> SolutionFormatter<'a> {
     pub(crate) fn new(
         map: &'a dyn Map,
         initial_state: &'a State,
+        variant: Variant,
         moves: &'a Moves,
         include_steps: bool,
         format: Format,
@@ -25,59 +31,90 @@ impl<'a> SolutionFormatter<'a> {
         Self {
             map,
             initial_state,
+            variant,
             moves,
             include_steps,
             format,
+            annotated: false,
         }
     }
+
+    /// Label each frame with the move number, the pushed box and the push number
+    /// and end with the pushes summarized like `Level::push_summary`.
+    ///
+    /// Boxes are numbered from 1 in the order of `Level::box_positions`.
+    #[must_use]
+    pub fn annotated(mut self, annotated: bool) -> Self {
+        self.annotated = annotated;
+        self
+    }
 }
 
 impl Display for SolutionFormatter<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let (move_total, push_total) = (self.moves.move_cnt(), self.moves.push_cnt());
+        if self.annotated {
+            writeln!(f, "Start")?;
+        }
         writeln!(
             f,
             "{}",
             self.map.format_with_state(self.format, self.initial_state)
         )?;
         let mut last_state = self.initial_state.clone();
-        for &mov in self.moves {
+        let mut tracker = BoxTracker::new(self.initial_state);
+        let mut pushes = Vec::new();
+        for (index, &mov) in self.moves.iter().enumerate() {
             // instead of verifying moves, they could have a reference to the map
             // to prevent the user from passing moves from a different level but this is a nice sanity check
+            let new_state = verify::apply_move(self.map, &last_state, self.variant, mov)
+                .unwrap_or_else(|err| panic!("Invalid move {}: {}", mov, err));
 
-            let new_player_pos = last_state.player_pos + mov.dir;
-            assert_ne!(
-                self.map.grid()[new_player_pos],
-                MapCell::Wall,
-                "new_player_pos: {new_player_pos:?}"
-            );
-
-            let mut new_boxes = last_state.boxes.clone();
-            if mov.is_push {
-                let new_box_pos = new_player_pos + mov.dir;
-                assert_ne!(self.map.grid()[new_box_pos], MapCell::Wall);
-                assert!(!new_boxes.as_slice().contains(&new_box_pos));
-                let box_index = new_boxes
-                    .iter()
-                    .position(|&b| b == new_player_pos)
-                    .expect("Move is a push but there is no box");
-                new_boxes[box_index] = new_box_pos;
-                if let Some(rem_pos) = self.map.remover() {
-                    if new_box_pos == rem_pos {
-                        new_boxes.remove(box_index);
-                    }
+            if self.annotated {
+                if mov.is_push {
+                    let (id, _) = tracker.push(&last_state, &new_state);
+                    pushes.push((id, mov));
+                    writeln!(
+                        f,
+                        "Move {}/{move_total}, push {}/{push_total}: b{} {mov}",
+                        index + 1,
+                        pushes.len(),
+                        id + 1
+                    )?;
+                } else if self.include_steps {
+                    writeln!(f, "Move {}/{move_total}: {mov}", index + 1)?;
                 }
-            } else {
-                assert!(!new_boxes.as_slice().contains(&new_player_pos));
             }
-
-            let new_state = State::new(new_player_pos, new_boxes);
-
             if mov.is_push || self.include_steps {
                 writeln!(f, "{}", self.map.format_with_state(self.format, &new_state))?;
             }
 
             last_state = new_state;
         }
+        if self.annotated {
+            writeln!(f, "Pushes: {}", PushSummary(&pushes))?;
+        }
+        Ok(())
+    }
+}
+
+/// Consecutive pushes of the same box grouped like `b2:R,R,U b1:L`
+pub(crate) struct PushSummary<'a>(pub(crate) &'a [(usize, Move)]);
+
+impl Display for PushSummary<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut last_id = None;
+        for &(id, mov) in self.0 {
+            if last_id == Some(id) {
+                write!(f, ",{mov}")?;
+            } else {
+                if last_id.is_some() {
+                    write!(f, " ")?;
+                }
+                write!(f, "b{}:{mov}", id + 1)?;
+            }
+            last_id = Some(id);
+        }
         Ok(())
     }
 }
@@ -87,3 +124,163 @@ impl Debug for SolutionFormatter<'_> {
         write!(f, "{self}")
     }
 }
+
+/// A level with its solution in the .sok format used by YASC and other players
+pub struct SokFormatter<'a> {
+    map: &'a dyn Map,
+    initial_state: &'a State,
+    metadata: &'a LevelMetadata,
+    method: Method,
+    moves: &'a Moves,
+}
+
+impl<'a> SokFormatter<'a> {
+    pub(crate) fn new(
+        map: &'a dyn Map,
+        initial_state: &'a State,
+        metadata: &'a LevelMetadata,
+        method: Method,
+        moves: &'a Moves,
+    ) -> Self {
+        Self {
+            map,
+            initial_state,
+            metadata,
+            method,
+            moves,
+        }
+    }
+}
+
+impl Display for SokFormatter<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            self.map.format_with_state(Format::Xsb, self.initial_state)
+        )?;
+        if let Some(ref title) = self.metadata.title {
+            writeln!(f, "Title: {title}")?;
+        }
+        if let Some(ref author) = self.metadata.author {
+            writeln!(f, "Author: {author}")?;
+        }
+        for (key, value) in &self.metadata.properties {
+            // replaced by the new solution
+            if key.starts_with("Solution") {
+                continue;
+            }
+            if value.contains('\n') {
+                writeln!(f, "{key}:\n{value}\n{key}-End:")?;
+            } else {
+                writeln!(f, "{key}: {value}")?;
+            }
+        }
+        // the labels YASC uses for move and push optimized solutions
+        let label = match self.method {
+            Method::MovesPushes | Method::Moves => "Solution/Moves",
+            Method::PushesMoves | Method::PushesBoxLines | Method::Pushes => "Solution/Pushes",
+            Method::Any => "Solution",
+        };
+        writeln!(
+            f,
+            "{label} ({}/{}): {}",
+            self.moves.move_cnt(),
+            self.moves.push_cnt(),
+            self.moves
+        )
+    }
+}
+
+impl Debug for SokFormatter<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{self}")
+    }
+}
+
+/// The initial level with each box and the goal it ends up on labeled with the same letter.
+///
+/// Every square is 2 characters wide - the box or player (`@`) on it
+/// and then the square itself (walls are `##`, unused goals `.` and removers `r`).
+/// Removed boxes are listed below the map.
+pub struct AssignmentFormatter<'a> {
+    map: &'a dyn Map,
+    initial_state: &'a State,
+    assignment: &'a [BoxAssignment],
+}
+
+impl<'a> AssignmentFormatter<'a> {
+    pub(crate) fn new(
+        map: &'a dyn Map,
+        initial_state: &'a State,
+        assignment: &'a [BoxAssignment],
+    ) -> Self {
+        Self {
+            map,
+            initial_state,
+            assignment,
+        }
+    }
+
+    /// `a` to `z`, then `A` to `Z`, `?` when the letters run out
+    fn label(index: usize) -> char {
+        match u8::try_from(index) {
+            Ok(i @ 0..=25) => char::from(b'a' + i),
+            Ok(i @ 26..=51) => char::from(b'A' + i - 26),
+            _ => '?',
+        }
+    }
+}
+
+impl Display for AssignmentFormatter<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let grid = self.map.grid();
+        for r in 0..grid.rows() {
+            let mut line = String::new();
+            for c in 0..grid.cols() {
+                let pos = Pos::new(r, c);
+                let square = pos.into();
+                if grid[pos] == MapCell::Wall {
+                    line.push_str("##");
+                    continue;
+                }
+                let contents = if let Some(index) = self
+                    .assignment
+                    .iter()
+                    .position(|assigned| assigned.start == square)
+                {
+                    Self::label(index)
+                } else if self.initial_state.players().any(|player| player == pos) {
+                    '@'
+                } else {
+                    ' '
+                };
+                let cell = match grid[pos] {
+                    MapCell::Empty => ' ',
+                    MapCell::Wall => unreachable!("Walls are handled above"),
+                    MapCell::Goal => self
+                        .assignment
+                        .iter()
+                        .position(|assigned| assigned.fate == BoxFate::Goal(square))
+                        .map_or('.', Self::label),
+                    MapCell::Remover => 'r',
+                };
+                line.push(contents);
+                line.push(cell);
+            }
+            writeln!(f, "{}", line.trim_end())?;
+        }
+        for (index, assigned) in self.assignment.iter().enumerate() {
+            if let BoxFate::Removed { push } = assigned.fate {
+                writeln!(f, "{}: removed by push {}", Self::label(index), push + 1)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Debug for AssignmentFormatter<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{self}")
+    }
+}
@@ -1,17 +1,50 @@
 use std::cmp::Ordering;
+use std::convert::TryFrom;
 use std::fmt::{self, Debug, Display, Formatter, Result};
 use std::hash::Hash;
 use std::ops::{Add, Sub};
+use std::slice;
+use std::time::Duration;
 
 use separator::Separatable;
+use serde::{Deserialize, Serialize};
 
+use crate::config::TieBreak;
 use crate::state::State;
 
-#[derive(PartialEq, Eq)]
+#[cfg(feature = "open-list-stats")]
+use super::OpenListSnapshot;
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Stats {
     created_states: Vec<i32>,
     visited_states: Vec<i32>,
     duplicate_states: Vec<i32>,
+    /// Only valid for the search which produced it
+    #[serde(skip)]
+    memory: MemoryStats,
+    /// Total unique visited states when each depth was first visited
+    #[serde(default)]
+    depths_reached: Vec<i32>,
+    /// Includes the time before the search was saved into a checkpoint
+    #[serde(default)]
+    elapsed: Duration,
+    #[serde(default)]
+    pub(super) pruned: PruningStats,
+    /// Created states by their (weighted) heuristic value
+    #[serde(default)]
+    created_by_h: Vec<i32>,
+    /// Unique visited states by their (weighted) heuristic value
+    #[serde(default)]
+    visited_by_h: Vec<i32>,
+    /// The cost of the cheapest state on the open list whenever it changed
+    /// with the number of unique visited states at that point
+    #[serde(default)]
+    min_costs: Vec<(i32, u16)>,
+    /// Only valid for the search which produced it
+    #[cfg(feature = "open-list-stats")]
+    #[serde(skip)]
+    open_list: Option<OpenListSnapshot>,
 }
 
 impl Stats {
@@ -20,27 +53,141 @@ impl Stats {
             created_states: vec![],
             duplicate_states: vec![],
             visited_states: vec![],
+            memory: MemoryStats::default(),
+            depths_reached: vec![],
+            elapsed: Duration::ZERO,
+            pruned: PruningStats::default(),
+            created_by_h: vec![],
+            visited_by_h: vec![],
+            min_costs: vec![],
+            #[cfg(feature = "open-list-stats")]
+            open_list: None,
+        }
+    }
+
+    pub fn memory(&self) -> MemoryStats {
+        self.memory
+    }
+
+    /// How many pushes each pruning rejected
+    pub fn pruned(&self) -> PruningStats {
+        self.pruned
+    }
+
+    /// The distribution of heuristic values of created and visited states
+    /// and how the lower bound evolved
+    pub fn heuristic(&self) -> HeuristicStats<'_> {
+        HeuristicStats {
+            created_by_h: &self.created_by_h,
+            visited_by_h: &self.visited_by_h,
+            min_costs: &self.min_costs,
+        }
+    }
+
+    /// The composition of the open list when the search last reached a new depth
+    #[cfg(feature = "open-list-stats")]
+    pub fn open_list(&self) -> Option<&OpenListSnapshot> {
+        self.open_list.as_ref()
+    }
+
+    /// Returns the previous snapshot
+    #[cfg(feature = "open-list-stats")]
+    pub(super) fn set_open_list(&mut self, snapshot: OpenListSnapshot) -> Option<OpenListSnapshot> {
+        self.open_list.replace(snapshot)
+    }
+
+    pub(super) fn set_memory(&mut self, memory: MemoryStats) {
+        self.memory = memory;
+    }
+
+    /// Time spent searching, updated whenever the progress callback is called
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    pub(super) fn set_elapsed(&mut self, elapsed: Duration) {
+        self.elapsed = elapsed;
+    }
+
+    /// Unique visited states per second over the whole search
+    pub fn states_per_sec(&self) -> Option<f64> {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 {
+            Some(f64::from(self.total_unique_visited()) / secs)
+        } else {
+            None
+        }
+    }
+
+    /// Crude estimate of the time left until the search reaches `lower_bound` depth.
+    ///
+    /// Assumes the number of states needed to reach each new depth keeps growing
+    /// at the same average rate as so far and that the speed stays the same.
+    /// Since the solution can be longer than `lower_bound`, the search can take much longer.
+    pub fn eta(&self, lower_bound: u16) -> Option<Duration> {
+        let speed = self.states_per_sec()?;
+        let deepest = u16::try_from(self.depths_reached.len().checked_sub(1)?).ok()?;
+        let remaining_depths = lower_bound.checked_sub(deepest)?;
+        if remaining_depths == 0 {
+            return Some(Duration::ZERO);
         }
+        if deepest == 0 {
+            return None;
+        }
+        // individual depths are too noisy because A* doesn't visit them in order
+        let now = f64::from(self.depths_reached[usize::from(deepest)]);
+        let growth = now.powf(1.0 / f64::from(deepest));
+        let total = now * growth.powi(i32::from(remaining_depths));
+        Duration::try_from_secs_f64((total - now) / speed).ok()
+    }
+
+    /// States created, visited and reached again at each depth (the cost of reaching them)
+    pub fn by_depth(&self) -> impl Iterator<Item = DepthStats> + '_ {
+        // created_states should be the longest vec
+        let count = |counts: &[i32], depth: usize| counts.get(depth).copied().unwrap_or(0);
+        (0..self.created_states.len()).map(move |depth| DepthStats {
+            depth,
+            created: self.created_states[depth],
+            unique_visited: count(&self.visited_states, depth),
+            reached_duplicates: count(&self.duplicate_states, depth),
+        })
     }
 
-    pub(crate) fn total_created(&self) -> i32 {
+    pub fn total_created(&self) -> i32 {
         self.created_states.iter().sum::<i32>()
     }
 
-    pub(crate) fn total_unique_visited(&self) -> i32 {
+    pub fn total_unique_visited(&self) -> i32 {
         self.visited_states.iter().sum::<i32>()
     }
 
-    pub(crate) fn total_reached_duplicates(&self) -> i32 {
+    pub fn total_reached_duplicates(&self) -> i32 {
         self.duplicate_states.iter().sum::<i32>()
     }
 
-    pub(super) fn add_created(&mut self, depth: u16) -> bool {
+    pub(super) fn add_created(&mut self, depth: u16, h: u16) -> bool {
+        Self::add(&mut self.created_by_h, h);
         Self::add(&mut self.created_states, depth)
     }
 
-    pub(super) fn add_unique_visited(&mut self, depth: u16) -> bool {
-        Self::add(&mut self.visited_states, depth)
+    /// `cost` is the cost of the state which is always the cheapest on the open list
+    pub(super) fn add_unique_visited(&mut self, depth: u16, h: u16, cost: u16) -> bool {
+        Self::add(&mut self.visited_by_h, h);
+        if self.min_costs.last().is_none_or(|&(_, last)| last != cost) {
+            self.min_costs.push((self.total_unique_visited(), cost));
+        }
+        let new_depth = Self::add(&mut self.visited_states, depth);
+        if new_depth {
+            let total = self.total_unique_visited();
+            self.depths_reached.resize(usize::from(depth) + 1, total);
+        }
+        new_depth
+    }
+
+    /// For states which were counted as visited but need to be visited again
+    pub(super) fn remove_unique_visited(&mut self, depth: u16, h: u16) {
+        self.visited_states[usize::from(depth)] -= 1;
+        self.visited_by_h[usize::from(h)] -= 1;
     }
 
     pub(super) fn add_reached_duplicate(&mut self, depth: u16) -> bool {
@@ -61,9 +208,27 @@ impl Stats {
     }
 }
 
+// RSS is for the whole process so it's not deterministic
+impl PartialEq for Stats {
+    fn eq(&self, other: &Self) -> bool {
+        self.created_states == other.created_states
+            && self.visited_states == other.visited_states
+            && self.duplicate_states == other.duplicate_states
+            && self.memory.arena == other.memory.arena
+            && self.memory.open_list == other.memory.open_list
+            && self.memory.prevs == other.memory.prevs
+            && self.pruned == other.pruned
+            && self.created_by_h == other.created_by_h
+            && self.visited_by_h == other.visited_by_h
+            && self.min_costs == other.min_costs
+    }
+}
+
+impl Eq for Stats {}
+
 impl Debug for Stats {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
-        writeln!(f, "total created / unique visited / reached duplicates:",)?;
+        writeln!(f, "total created / unique visited / reached duplicates:")?;
         writeln!(
             f,
             "{:16}{:17}{}", // or "      {:12}{:19}{}" and 2 spaces around slashes
@@ -80,51 +245,187 @@ impl Debug for Stats {
 }
 
 impl Display for Stats {
+    // skipping just the block of writelns would need unstable attributes on expressions
+    #[rustfmt::skip]
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         let created = self.total_created();
         let visited = self.total_unique_visited();
         let duplicates = self.total_reached_duplicates();
         let left = created - visited - duplicates;
 
-        #[rustfmt::skip]
-        {
-            writeln!(f, "States created total: {}", created.separated_string())?;
-            writeln!(f, "Unique visited total: {}", visited.separated_string())?;
-            writeln!(f, "Reached duplicates total: {}", duplicates.separated_string())?;
-            writeln!(f, "Created but not reached total: {}",left.separated_string())?;
-            writeln!(f)?;
-            writeln!(f, "Depth          Created        Unique         Duplicates     Unknown (not reached)")?;
-        }
+        writeln!(f, "States created total: {}", created.separated_string())?;
+        writeln!(f, "Unique visited total: {}", visited.separated_string())?;
+        writeln!(f, "Reached duplicates total: {}", duplicates.separated_string())?;
+        writeln!(f, "Created but not reached total: {}",left.separated_string())?;
+        writeln!(f)?;
+        writeln!(f, "Depth          Created        Unique         Duplicates     Unknown (not reached)")?;
 
-        for i in 0..self.created_states.len() {
-            // created_states should be the longest vec
-            let depth = format!("{i}: ");
-            let created = self.created_states[i];
-            let visited = if i < self.visited_states.len() {
-                self.visited_states[i]
-            } else {
-                0
-            };
-            let duplicates = if i < self.duplicate_states.len() {
-                self.duplicate_states[i]
-            } else {
-                0
-            };
-            let left = created - visited - duplicates;
+        for depth in self.by_depth() {
             writeln!(
                 f,
                 "{:<15}{:<15}{:<15}{:<15}{}",
-                depth,
+                format!("{}: ", depth.depth),
+                depth.created.separated_string(),
+                depth.unique_visited.separated_string(),
+                depth.reached_duplicates.separated_string(),
+                depth.not_reached().separated_string()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// One row of the table in `Stats`' `Display`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DepthStats {
+    pub depth: usize,
+    pub created: i32,
+    pub unique_visited: i32,
+    pub reached_duplicates: i32,
+}
+
+impl DepthStats {
+    /// Created but still on the open list when the search ended
+    pub fn not_reached(&self) -> i32 {
+        self.created - self.unique_visited - self.reached_duplicates
+    }
+}
+
+/// Pushes (or pulls) which were never turned into states because they can't lead to a solution
+/// or a limit was reached. Not part of `Stats`' `Display` to keep it comparable
+/// between versions which prune differently.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PruningStats {
+    /// Boxes pushed onto squares from which they can't reach any goal or remover
+    pub dead_squares: u64,
+    /// Boxes pushed into removers in levels with goals while every goal still needed a box
+    pub removers: u64,
+    /// States deeper than `SolverOptions::max_depth`
+    pub max_depth: u64,
+    /// States of move-optimal searches whose boxes were already visited with the player
+    /// somewhere it can walk from without being further from the start
+    #[serde(default)]
+    pub near_duplicates: u64,
+}
+
+impl PruningStats {
+    pub fn total(&self) -> u64 {
+        self.dead_squares + self.removers + self.max_depth + self.near_duplicates
+    }
+}
+
+impl Display for PruningStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        writeln!(
+            f,
+            "Pruned: {} (dead squares: {}, removers: {}, max depth: {}, near duplicates: {})",
+            self.total().separated_string(),
+            self.dead_squares.separated_string(),
+            self.removers.separated_string(),
+            self.max_depth.separated_string(),
+            self.near_duplicates.separated_string()
+        )
+    }
+}
+
+/// How the heuristic guided the search. Not part of `Stats`' `Display`
+/// because it only matters when working on heuristics.
+///
+/// A heuristic which tightens the bound shifts states towards higher values
+/// and needs fewer visited states for each increase of the minimum cost.
+/// Only the most important part of the cost is used for searches optimizing several metrics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeuristicStats<'a> {
+    /// Number of created states for each heuristic value
+    pub created_by_h: &'a [i32],
+    /// Number of unique visited states for each heuristic value
+    pub visited_by_h: &'a [i32],
+    /// Each new minimum cost (f-value) of the open list with the number of unique visited states
+    /// before reaching it. Only increases unless the heuristic is inconsistent.
+    pub min_costs: &'a [(i32, u16)],
+}
+
+impl Display for HeuristicStats<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        writeln!(f, "Heuristic      Created        Unique")?;
+        for (h, &created) in self.created_by_h.iter().enumerate() {
+            let visited = self.visited_by_h.get(h).copied().unwrap_or(0);
+            writeln!(
+                f,
+                "{:<15}{:<15}{}",
+                format!("{h}: "),
                 created.separated_string(),
-                visited.separated_string(),
-                duplicates.separated_string(),
-                left.separated_string()
+                visited.separated_string()
+            )?;
+        }
+        writeln!(f)?;
+        writeln!(f, "Min cost       After visiting")?;
+        for &(visited, cost) in self.min_costs {
+            writeln!(
+                f,
+                "{:<15}{}",
+                format!("{cost}: "),
+                visited.separated_string()
             )?;
         }
         Ok(())
     }
 }
 
+/// Approximate peak memory used by the search in bytes.
+///
+/// The containers only grow during the search so these are their sizes at the end.
+/// Not part of `Stats`' `Display` because it depends on the platform.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryStats {
+    /// States (including their boxes)
+    pub arena: usize,
+    /// Allocated capacity of the open list
+    pub open_list: usize,
+    /// Allocated capacity of the map of previous states
+    pub prevs: usize,
+    /// Peak resident set size of the whole process (only available on Linux)
+    pub peak_rss: Option<usize>,
+}
+
+impl MemoryStats {
+    pub fn total(&self) -> usize {
+        self.arena + self.open_list + self.prevs
+    }
+}
+
+impl Display for MemoryStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        writeln!(
+            f,
+            "Approximate memory used: {} B (arena: {} B, open list: {} B, prevs: {} B)",
+            self.total().separated_string(),
+            self.arena.separated_string(),
+            self.open_list.separated_string(),
+            self.prevs.separated_string()
+        )?;
+        if let Some(peak_rss) = self.peak_rss {
+            writeln!(f, "Peak RSS: {} kB", (peak_rss / 1024).separated_string())?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads `VmHWM` from `/proc/self/status`
+pub(crate) fn peak_rss() -> Option<usize> {
+    #[cfg(target_os = "linux")]
+    {
+        let status = std::fs::read_to_string("/proc/self/status").ok()?;
+        let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+        let kb: usize = line.split_whitespace().nth(1)?.parse().ok()?;
+        Some(kb * 1024)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        None
+    }
+}
+
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 pub(crate) struct SearchNode<'a, C: Cost + Add<Output = C>> {
     pub(crate) state: &'a State,
@@ -142,6 +443,11 @@ impl<'a, C: Cost + Add<Output = C>> SearchNode<'a, C> {
             cost: dist + heuristic,
         }
     }
+
+    /// The most important part of the (weighted) heuristic
+    pub(crate) fn h(&self) -> u16 {
+        (self.cost - self.dist).depth()
+    }
 }
 
 pub(crate) trait Cost:
@@ -150,6 +456,23 @@ pub(crate) trait Cost:
     fn zero() -> Self;
     fn one() -> Self;
     fn depth(&self) -> u16;
+
+    /// Inflates a heuristic value by the given percentage (rounding down).
+    fn weighted(self, percent: u16) -> Self;
+
+    /// The values of the metrics it consists of, most important first
+    fn parts(&self) -> &[u16];
+
+    /// The inverse of `parts`, missing parts are 0 and extra ones are ignored
+    fn from_parts(parts: &[u16]) -> Self;
+}
+
+fn weight(value: u16, percent: u16) -> u16 {
+    if percent == 0 {
+        return value;
+    }
+    let weighted = u32::from(value) * (100 + u32::from(percent)) / 100;
+    u16::try_from(weighted).unwrap_or(u16::MAX)
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -195,75 +518,178 @@ impl Cost for SimpleCost {
     fn depth(&self) -> u16 {
         self.0
     }
+
+    fn weighted(self, percent: u16) -> Self {
+        SimpleCost(weight(self.0, percent))
+    }
+
+    fn parts(&self) -> &[u16] {
+        slice::from_ref(&self.0)
+    }
+
+    fn from_parts(parts: &[u16]) -> Self {
+        SimpleCost(parts.first().copied().unwrap_or(0))
+    }
 }
 
+/// Several metrics compared lexicographically - the first one is minimized,
+/// the rest only break ties
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub(crate) struct ComplexCost(pub(crate) u16, pub(crate) u16);
+pub(crate) struct ComplexCost<const N: usize = 2>(pub(crate) [u16; N]);
 
-impl Display for ComplexCost {
+impl<const N: usize> Display for ComplexCost<N> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "{}/{}", self.0, self.1)
+        for (i, part) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, "/")?;
+            }
+            write!(f, "{part}")?;
+        }
+        Ok(())
     }
 }
 
-impl Debug for ComplexCost {
+impl<const N: usize> Debug for ComplexCost<N> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         write!(f, "{self}")
     }
 }
 
-impl Add for ComplexCost {
+impl<const N: usize> Add for ComplexCost<N> {
     type Output = Self;
 
-    fn add(self, other: Self) -> Self {
-        ComplexCost(self.0 + other.0, self.1 + other.1)
+    fn add(mut self, other: Self) -> Self {
+        for (part, other) in self.0.iter_mut().zip(other.0) {
+            *part += other;
+        }
+        self
     }
 }
 
-impl Sub for ComplexCost {
+impl<const N: usize> Sub for ComplexCost<N> {
     type Output = Self;
 
-    fn sub(self, other: Self) -> Self {
-        ComplexCost(self.0 - other.0, self.1 - other.1)
+    fn sub(mut self, other: Self) -> Self {
+        for (part, other) in self.0.iter_mut().zip(other.0) {
+            *part -= other;
+        }
+        self
     }
 }
 
-impl Cost for ComplexCost {
+impl<const N: usize> Cost for ComplexCost<N> {
     fn zero() -> Self {
-        ComplexCost(0, 0)
+        ComplexCost([0; N])
     }
 
     fn one() -> Self {
-        ComplexCost(1, 0)
+        let mut one = Self::zero();
+        one.0[0] = 1;
+        one
     }
 
     fn depth(&self) -> u16 {
-        self.0
+        self.0[0]
+    }
+
+    fn weighted(self, percent: u16) -> Self {
+        ComplexCost(self.0.map(|part| weight(part, percent)))
+    }
+
+    fn parts(&self) -> &[u16] {
+        &self.0
+    }
+
+    fn from_parts(parts: &[u16]) -> Self {
+        let mut cost = Self::zero();
+        for (part, &saved) in cost.0.iter_mut().zip(parts) {
+            *part = saved;
+        }
+        cost
     }
 }
 
-pub(crate) struct CostComparator<'a, C: Cost + Add<Output = C>>(pub(crate) SearchNode<'a, C>);
+/// A node in the open list with its position among nodes of the same cost and distance
+/// and which of the nodes with the same cost go first
+pub(crate) struct CostComparator<'a, C: Cost + Add<Output = C>>(
+    pub(crate) SearchNode<'a, C>,
+    pub(crate) u64,
+    pub(crate) TieBreak,
+);
 
-impl<'a, C: Cost + Add<Output = C>> PartialOrd for CostComparator<'a, C> {
+impl<C: Cost + Add<Output = C>> PartialOrd for CostComparator<'_, C> {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
     }
 }
 
-impl<'a, C: Cost + Add<Output = C>> Ord for CostComparator<'a, C> {
+impl<C: Cost + Add<Output = C>> Ord for CostComparator<'_, C> {
     fn cmp(&self, other: &Self) -> Ordering {
         // orders according to cost lowest to highest
         // needs std::cmp::Reverse when using BinaryHeap (it's a max heap)
         // according to Criterion, the difference between Reversed and actually reversing the order
         // (if any) is usually within noise threshold
-        (self.0.cost).cmp(&(other.0.cost))
+        //
+        // Ties are broken explicitly so the search doesn't depend on BinaryHeap's internals:
+        // the deeper node (with the lower heuristic) first since it's closer to a solution
+        // (or the shallower one with `TieBreak::Shallower`), then in the order given by `TieBreaker`.
+        (self.0.cost)
+            .cmp(&(other.0.cost))
+            .then_with(|| {
+                // all nodes of one search use the same tie-breaking
+                let deeper_first = other.0.dist.cmp(&self.0.dist);
+                match self.2 {
+                    TieBreak::Deeper => deeper_first,
+                    TieBreak::Shallower => deeper_first.reverse(),
+                }
+            })
+            .then_with(|| self.1.cmp(&other.1))
     }
 }
 
-impl<'a, C: Cost + Add<Output = C>> PartialEq for CostComparator<'a, C> {
+impl<C: Cost + Add<Output = C>> PartialEq for CostComparator<'_, C> {
     fn eq(&self, other: &Self) -> bool {
-        self.0.cost == other.0.cost
+        self.cmp(other) == Ordering::Equal
     }
 }
 
-impl<'a, C: Cost + Add<Output = C>> Eq for CostComparator<'a, C> {}
+impl<C: Cost + Add<Output = C>> Eq for CostComparator<'_, C> {}
+
+/// Decides the order of nodes with the same cost - by distance according to `SolverOptions::tie_break`,
+/// then first created first or random with `SolverOptions::seed`
+#[derive(Debug)]
+pub(crate) struct TieBreaker {
+    /// How many nodes were created
+    created: u64,
+    rng: Option<fastrand::Rng>,
+    tie_break: TieBreak,
+}
+
+impl TieBreaker {
+    pub(crate) fn new(tie_break: TieBreak, seed: Option<u64>) -> Self {
+        Self {
+            created: 0,
+            rng: seed.map(fastrand::Rng::with_seed),
+            tie_break,
+        }
+    }
+
+    pub(crate) fn wrap<'a, C: Cost>(&mut self, node: SearchNode<'a, C>) -> CostComparator<'a, C> {
+        let order = match self.rng {
+            Some(ref mut rng) => rng.u64(..),
+            None => self.created,
+        };
+        self.created += 1;
+        CostComparator(node, order, self.tie_break)
+    }
+
+    /// Keeps the order of a node saved in a checkpoint, new nodes are created after it
+    pub(crate) fn wrap_restored<'a, C: Cost>(
+        &mut self,
+        node: SearchNode<'a, C>,
+        order: u64,
+    ) -> CostComparator<'a, C> {
+        self.created = self.created.max(order.saturating_add(1));
+        CostComparator(node, order, self.tie_break)
+    }
+}
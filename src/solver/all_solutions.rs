@@ -0,0 +1,321 @@
+//! Finding every optimal solution instead of stopping at the first one.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::time::Instant;
+
+use fnv::FnvHashMap;
+#[cfg(not(feature = "tracing"))]
+use log::debug;
+#[cfg(feature = "tracing")]
+use tracing::debug;
+use typed_arena::Arena;
+
+use crate::config::{Method, TieBreak};
+use crate::level::Level;
+use crate::map::{Map, MapType};
+use crate::moves::Moves;
+use crate::state::State;
+
+use super::a_star::{Cost, CostComparator, SearchNode, Stats, TieBreaker};
+use super::{
+    backtracking::MoveReconstructor, GameLogic, MoveLogic, MovePushLogic, PushBoxLineLogic,
+    PushLogic, PushMoveLogic, Scratch, Solver, SolverErr, SolverTrait,
+};
+
+/// The solutions found by `Level::optimal_solutions`
+#[derive(Debug)]
+pub struct OptimalSolutions {
+    /// How many different sequences of pushes have the optimal length (saturating at `u64::MAX`),
+    /// 0 if the level has no solution. Solutions which only differ in how the player walks
+    /// between pushes are counted once.
+    pub count: u64,
+    /// Up to `limit` of the counted solutions, in no particular order
+    pub solutions: Vec<Moves>,
+    pub stats: Stats,
+}
+
+impl Level {
+    /// Finds all solutions which are optimal for the method instead of only the first one.
+    ///
+    /// All of them are counted but only up to `limit` are returned.
+    /// The number of solutions is sometimes used as a measure of a level's quality
+    /// (a unique solution usually means no shortcuts). This has to visit every state
+    /// as cheap as the solution so it's slower than `solve`.
+    /// `Method::Any` is treated as `Method::Pushes`.
+    pub fn optimal_solutions(
+        &self,
+        method: Method,
+        limit: usize,
+    ) -> Result<OptimalSolutions, SolverErr> {
+        span!("optimal_solutions", %method, variant = %self.variant);
+        debug!("Processing level...");
+
+        Ok(match self.map {
+            MapType::Goals(ref goals_map) => {
+                let solver = Solver::new_with_goals(goals_map, &self.state, self.variant)?;
+                with_method(&solver, method, limit)
+            }
+            MapType::Remover(ref remover_map) => {
+                let solver = Solver::new_with_remover(remover_map, &self.state, self.variant)?;
+                with_method(&solver, method, limit)
+            }
+            MapType::Hybrid(ref hybrid_map) => {
+                let solver = Solver::new_with_hybrid(hybrid_map, &self.state, self.variant)?;
+                with_method(&solver, method, limit)
+            }
+        })
+    }
+}
+
+fn with_method<M>(solver: &Solver<M>, method: Method, limit: usize) -> OptimalSolutions
+where
+    M: Map,
+    Solver<M>: SolverTrait<M = M>,
+{
+    match method {
+        Method::MovesPushes => optimal_solutions(solver, MovePushLogic, limit),
+        Method::Moves => optimal_solutions(solver, MoveLogic, limit),
+        Method::PushesMoves => optimal_solutions(solver, PushMoveLogic, limit),
+        Method::PushesBoxLines => optimal_solutions(solver, PushBoxLineLogic, limit),
+        Method::Pushes | Method::Any => optimal_solutions(solver, PushLogic, limit),
+    }
+}
+
+fn optimal_solutions<M, GL>(solver: &Solver<M>, _: GL, limit: usize) -> OptimalSolutions
+where
+    M: Map,
+    Solver<M>: SolverTrait<M = M>,
+    GL: GameLogic<M>,
+{
+    let sd = solver.sd();
+    let mut stats = Stats::new();
+    let no_solutions = |stats| OptimalSolutions {
+        count: 0,
+        solutions: Vec::new(),
+        stats,
+    };
+
+    if sd.unsolvable.is_some()
+        || sd
+            .initial_state
+            .boxes
+            .iter()
+            .any(|&box_pos| sd.closest_push_dists[box_pos].is_none())
+    {
+        return no_solutions(stats);
+    }
+    if sd.solved_at_start() {
+        return OptimalSolutions {
+            count: 1,
+            solutions: vec![Moves::default()],
+            stats,
+        };
+    }
+
+    let started = Instant::now();
+    let arena = Arena::new();
+    let mut to_visit = BinaryHeap::new();
+    let mut tie_breaker = TieBreaker::new(TieBreak::default(), None);
+    let mut scratch = Scratch::new(&sd.map);
+    let mut new_states = Vec::new();
+    // the distance of each visited state and all states before it on the shortest paths to it
+    let mut prevs: FnvHashMap<&State, (GL::C, Vec<&State>)> = FnvHashMap::default();
+
+    let norm_initial_state = GL::preprocess_state(sd, &sd.initial_state);
+    let start = SearchNode::new(
+        &norm_initial_state,
+        None,
+        GL::C::zero(),
+        GL::initial_heuristic(sd, &norm_initial_state),
+    );
+    stats.add_created(start.dist.depth(), start.h());
+    to_visit.push(Reverse(tie_breaker.wrap(start)));
+
+    // Unlike the normal search, this doesn't stop at the first solution but visits
+    // every state as cheap as it to find all the shortest ways of reaching them.
+    // The heuristic is consistent so a state is first visited with its shortest distance
+    // and reaching it again with the same distance is another shortest path.
+    let mut best = None;
+    let mut solved_states = Vec::new();
+    while let Some(Reverse(CostComparator(cur_node, ..))) = to_visit.pop() {
+        if best.is_some_and(|best| cur_node.cost > best) {
+            break;
+        }
+
+        if let Some(&mut (dist, ref mut state_prevs)) = prevs.get_mut(cur_node.state) {
+            stats.add_reached_duplicate(cur_node.dist.depth());
+            let prev = cur_node.prev.expect("Only the initial state has no prev");
+            if cur_node.dist == dist && !state_prevs.contains(&prev) {
+                state_prevs.push(prev);
+            }
+            continue;
+        }
+        stats.add_unique_visited(cur_node.dist.depth(), cur_node.h(), cur_node.cost.depth());
+        prevs.insert(
+            cur_node.state,
+            (cur_node.dist, cur_node.prev.into_iter().collect()),
+        );
+
+        if cur_node.cost == cur_node.dist {
+            // heuristic is 0 so level is solved
+            best = Some(cur_node.cost);
+            solved_states.push(cur_node.state);
+            continue;
+        }
+
+        GL::expand(
+            sd,
+            cur_node.state,
+            &arena,
+            &mut stats.pruned,
+            &mut scratch,
+            &mut new_states,
+        );
+        for (neighbor_state, cost, h) in new_states.drain(..) {
+            let next_node = SearchNode::new(
+                neighbor_state,
+                Some(cur_node.state),
+                cur_node.dist + cost,
+                h,
+            );
+            stats.add_created(next_node.dist.depth(), next_node.h());
+            to_visit.push(Reverse(tie_breaker.wrap(next_node)));
+        }
+    }
+    stats.set_elapsed(started.elapsed());
+    if solved_states.is_empty() {
+        return no_solutions(stats);
+    }
+
+    // the number of shortest paths to each state, the prevs of a state are always closer to the start
+    let mut by_dist: Vec<_> = prevs.iter().collect();
+    by_dist.sort_by_key(|&(_, &(dist, _))| dist);
+    let mut path_cnts = FnvHashMap::default();
+    for (&state, (_, state_prevs)) in by_dist {
+        let path_cnt = if state_prevs.is_empty() {
+            1
+        } else {
+            state_prevs
+                .iter()
+                .map(|prev| path_cnts[prev])
+                .fold(0, u64::saturating_add)
+        };
+        path_cnts.insert(state, path_cnt);
+    }
+    let count = solved_states
+        .iter()
+        .map(|state| path_cnts[state])
+        .fold(0, u64::saturating_add);
+
+    let mut solutions = Vec::new();
+    let mut reconstructor = MoveReconstructor::new(&sd.map, &sd.initial_state, sd.variant);
+    for &solved_state in &solved_states {
+        if solutions.len() >= limit {
+            break;
+        }
+        collect_paths(&prevs, &mut vec![solved_state], &mut |path| {
+            solutions.push(reconstructor.reconstruct(path));
+            solutions.len() < limit
+        });
+    }
+
+    OptimalSolutions {
+        count,
+        solutions,
+        stats,
+    }
+}
+
+/// Follows `prevs` from the last state of `path` back to the initial state in all possible ways
+/// and calls `found` with each path (starting at the initial state) until it returns `false`.
+/// Returns `false` if it was stopped this way.
+fn collect_paths<'a, C>(
+    prevs: &FnvHashMap<&'a State, (C, Vec<&'a State>)>,
+    path: &mut Vec<&'a State>,
+    found: &mut dyn FnMut(&[&'a State]) -> bool,
+) -> bool {
+    let last = path.last().expect("Paths are never empty");
+    let state_prevs = &prevs[last].1;
+    if state_prevs.is_empty() {
+        let mut forward = path.clone();
+        forward.reverse();
+        return found(&forward);
+    }
+
+    for &prev in state_prevs {
+        path.push(prev);
+        let more = collect_paths(prevs, path, found);
+        path.pop();
+        if !more {
+            return false;
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashSet;
+
+    #[test]
+    fn counting_solutions() {
+        // the box can go right first or down first
+        let level: Level = r"
+#####
+#@  #
+# $ #
+#  .#
+#####
+"
+        .parse()
+        .unwrap();
+
+        for &method in &Method::OPTIMAL {
+            let all = level.optimal_solutions(method, 10).unwrap();
+            assert_eq!(all.count, 2, "{method}");
+            assert_eq!(all.solutions.len(), 2, "{method}");
+            let solutions: HashSet<_> = all.solutions.iter().map(Moves::to_lurd).collect();
+            assert_eq!(solutions.len(), 2, "{method}");
+            for moves in &all.solutions {
+                assert!(level.verify_solution(moves).is_ok());
+                assert_eq!(moves.push_cnt(), 2);
+                assert_eq!(moves.move_cnt(), 5);
+            }
+
+            let limited = level.optimal_solutions(method, 1).unwrap();
+            assert_eq!(limited.count, 2);
+            assert_eq!(limited.solutions.len(), 1);
+        }
+
+        // only one push-optimal solution even though the player can walk around the box both ways
+        let level: Level = r"
+######
+#    #
+# #$ #
+#@  .#
+######
+"
+        .parse()
+        .unwrap();
+        let all = level.optimal_solutions(Method::Pushes, 10).unwrap();
+        assert_eq!(all.count, 1);
+        assert_eq!(all.solutions[0].push_cnt(), 2);
+    }
+
+    #[test]
+    fn no_solutions() {
+        let level: Level = "#####\n#@$.#\n#####".parse().unwrap();
+        let solved = level.apply(&Moves::from_lurd("R").unwrap()).unwrap();
+        let all = solved.optimal_solutions(Method::Moves, 10).unwrap();
+        assert_eq!(all.count, 1);
+        assert_eq!(all.solutions, vec![Moves::default()]);
+
+        let level: Level = "######\n#@ $ #\n#.   #\n######".parse().unwrap();
+        let all = level.optimal_solutions(Method::Pushes, 10).unwrap();
+        assert_eq!(all.count, 0);
+        assert!(all.solutions.is_empty());
+    }
+}
@@ -1,26 +1,30 @@
-use std::borrow::Borrow;
-use std::collections::{HashMap, HashSet, VecDeque};
-use std::hash::{BuildHasher, Hash};
+use std::collections::{HashSet, VecDeque};
 
-use crate::data::{MapCell, Pos};
+use fnv::FnvHashMap;
+
+use crate::config::Variant;
+use crate::data::{Dir, MapCell, Pos, DIRECTIONS};
 use crate::map::Map;
-use crate::moves::{Move, Moves};
+use crate::moves::{Move, Moves, Push};
 use crate::state::State;
+use crate::vec2d::Vec2d;
 
 // Terminology:
 // move = changing player position by one cell
 // push = a move that changes a box position
 // step = a move that doesn't change a box position
 
-pub(crate) fn backtrack_prevs<T: Clone + Eq + Hash + Borrow<T>, H: BuildHasher>(
-    prevs: &HashMap<T, T, H>,
+/// The states leading to `final_state`, `prev` returns the state before the given one
+/// or the same state for the initial state
+pub(crate) fn backtrack_prevs<T: Copy + Eq>(
     final_state: T,
+    mut prev: impl FnMut(T) -> T,
 ) -> Vec<T> {
     let mut states = Vec::new();
-    let mut cur = &final_state;
+    let mut cur = final_state;
     loop {
-        states.push(cur.clone());
-        let prev = &prevs[cur];
+        states.push(cur);
+        let prev = prev(cur);
         if prev == cur {
             states.reverse();
             return states;
@@ -29,125 +33,297 @@ pub(crate) fn backtrack_prevs<T: Clone + Eq + Hash + Borrow<T>, H: BuildHasher>(
     }
 }
 
+/// Turns paths of states found by a search into moves.
+///
+/// Solutions of the same level can share the player's paths (e.g. when several of them
+/// start the same way) so reconstructing them with one reconstructor avoids repeating the searches.
 // dynamic dispatch has no perf impact here
-pub(crate) fn reconstruct_moves(
-    map: &dyn Map,
-    real_initial_player_pos: Pos,
-    states: &[&State],
-) -> Moves {
-    let mut moves = Moves::default();
-    let mut iter = states.iter();
-    let mut cur_state = iter.next().expect("There must be at least one state");
-
-    // the states we're getting here might have been normalized (depending on solving method)
-    // so we need to track the actual player positions (determined by how boxes are pushed)
-    let mut real_player_pos = real_initial_player_pos;
-
-    for next_state in iter {
-        let (new_moves, new_player_pos) =
-            moves_between_states(map, real_player_pos, cur_state, next_state);
-        moves.extend(&new_moves);
-        real_player_pos = new_player_pos;
-        cur_state = next_state;
+pub(crate) struct MoveReconstructor<'a> {
+    map: &'a dyn Map,
+    real_initial_state: &'a State,
+    variant: Variant,
+    paths: PlayerPaths,
+}
+
+/// What happened between two consecutive states of a solution
+struct Transition {
+    /// Empty when only the pushes are needed
+    moves: Moves,
+    player_pos: Pos,
+    /// The box position before the push and its direction
+    push: Option<(Pos, Dir)>,
+}
+
+impl<'a> MoveReconstructor<'a> {
+    pub(crate) fn new(map: &'a dyn Map, real_initial_state: &'a State, variant: Variant) -> Self {
+        MoveReconstructor {
+            map,
+            real_initial_state,
+            variant,
+            paths: PlayerPaths::new(),
+        }
+    }
+
+    pub(crate) fn reconstruct(&mut self, states: &[&State]) -> Moves {
+        let mut moves = Moves::default();
+        self.walk(states, true, |player, mut transition| {
+            transition.moves.set_player(player);
+            moves.extend(&transition.moves);
+        });
+        moves
+    }
+
+    /// Only the pushes, the player's walking between them is not searched for
+    /// except to choose between several removers.
+    pub(crate) fn pushes(&mut self, states: &[&State]) -> Vec<Push> {
+        let mut pushes = Vec::new();
+        self.walk(states, false, |_, transition| {
+            if let Some((box_pos, dir)) = transition.push {
+                pushes.push(Push {
+                    box_pos: box_pos.into(),
+                    dir,
+                });
+            }
+        });
+        pushes
+    }
+
+    fn walk(&mut self, states: &[&State], steps: bool, mut f: impl FnMut(u8, Transition)) {
+        let mut iter = states.iter();
+        let mut cur_state = iter.next().expect("There must be at least one state");
+
+        // the states we're getting here might have been normalized (depending on solving method)
+        // so we need to track the actual player positions (determined by how boxes are pushed)
+        // and with multiple players also their order (the states have them sorted)
+        let mut real_players: Vec<_> = self.real_initial_state.players().collect();
+
+        for next_state in iter {
+            let player = moved_player(&real_players, next_state);
+            let transition =
+                self.between_states(real_players[player], cur_state, next_state, steps);
+            real_players[player] = transition.player_pos;
+            f(player as u8, transition);
+            cur_state = next_state;
+        }
     }
 
-    moves
+    /// The difference between them must be any number of steps and one push (or pull),
+    /// with multiple players it can also be only steps
+    fn between_states(
+        &mut self,
+        old_player_pos: Pos,
+        old: &State,
+        new: &State,
+        steps: bool,
+    ) -> Transition {
+        let map = self.map;
+        if old.boxes == new.boxes {
+            let new_player_pos = new
+                .players()
+                .find(|&pos| old.players().all(|old_pos| old_pos != pos))
+                .expect("A player must move between states");
+            let moves = if steps {
+                self.paths
+                    .steps(map, old, old_player_pos, new_player_pos)
+                    .expect("Couldn't find a path for the player")
+            } else {
+                Moves::default()
+            };
+            return Transition {
+                moves,
+                player_pos: new_player_pos,
+                push: None,
+            };
+        }
+
+        let old_boxes: HashSet<_> = old.boxes.iter().collect();
+        let new_boxes: HashSet<_> = new.boxes.iter().collect();
+
+        let mut old_iter = old_boxes.difference(&new_boxes);
+        let mut new_iter = new_boxes.difference(&old_boxes);
+
+        let old_box_pos = **old_iter
+            .next()
+            .expect("There must be exactly one push between states");
+        assert!(
+            old_iter.next().is_none(),
+            "Only one box can change its position at a time"
+        );
+
+        // a removed box could have gone into any remover next to it,
+        // the resulting states are the same so use the one with the shortest path
+        let new_box_candidates: Vec<Pos> = match new_iter.next() {
+            None => old_box_pos
+                .neighbors()
+                .iter()
+                .copied()
+                .filter(|&pos| map.grid()[pos] == MapCell::Remover)
+                .collect(),
+            Some(&&pos) => vec![pos],
+        };
+        assert!(
+            new_iter.next().is_none(),
+            "Only one box can change its position at a time"
+        );
+        // without steps, the path is only needed to choose between removers
+        let find_paths = steps || new_box_candidates.len() > 1;
+
+        let variant = self.variant;
+        let paths = &mut self.paths;
+        new_box_candidates
+            .into_iter()
+            .filter_map(|new_box_pos| {
+                let push_dir = old_box_pos.dir_to(new_box_pos);
+                // when pulling, the player stands where the box ends up and steps away from it
+                let (player_pos_before_push, player_pos_after_push) = match variant {
+                    Variant::Push | Variant::Multiban => {
+                        (old_box_pos + push_dir.inverse(), old_box_pos)
+                    }
+                    Variant::Pull => (new_box_pos, new_box_pos + push_dir),
+                };
+                if map.grid()[player_pos_after_push] == MapCell::Wall
+                    || new.boxes.contains(&player_pos_after_push)
+                {
+                    return None;
+                }
+                let mut moves = if find_paths {
+                    paths.steps(map, old, old_player_pos, player_pos_before_push)?
+                } else {
+                    Moves::default()
+                };
+                moves.add(Move::new(push_dir, true));
+                Some(Transition {
+                    moves,
+                    player_pos: player_pos_after_push,
+                    push: Some((old_box_pos, push_dir)),
+                })
+            })
+            .min_by_key(|transition| transition.moves.move_cnt())
+            .expect("Couldn't find a path to the pushed box")
+    }
 }
 
-/// The difference between them must be any number of steps and one push
-fn moves_between_states(
+/// With multiple players, the states are not normalized
+/// so the one which moved is the one no longer at its position
+fn moved_player(real_players: &[Pos], new: &State) -> usize {
+    if real_players.len() == 1 {
+        return 0;
+    }
+    real_players
+        .iter()
+        .position(|&pos| new.players().all(|new_pos| new_pos != pos))
+        .expect("One player must move between states")
+}
+
+/// The shortest walk between the positions without pushing any boxes, `None` if there's none.
+/// With multiple players, the others (all except the one at `src_pos`) block it.
+pub(crate) fn player_steps(
     map: &dyn Map,
-    old_player_pos: Pos,
-    old: &State,
-    new: &State,
-) -> (Moves, Pos) {
-    let old_boxes: HashSet<_> = old.boxes.iter().collect();
-    let new_boxes: HashSet<_> = new.boxes.iter().collect();
-
-    let mut old_iter = old_boxes.difference(&new_boxes);
-    let mut new_iter = new_boxes.difference(&old_boxes);
-
-    let old_box_pos = **old_iter
-        .next()
-        .expect("There must be exactly one push between states");
-    assert!(
-        old_iter.next().is_none(),
-        "Only one box can change its position at a time"
-    );
-
-    let new_box_pos = match new_iter.next() {
-        None => map
-            .remover()
-            .expect("A box disappeared so there must be a remover"),
-        Some(&&pos) => pos,
-    };
-    assert!(
-        new_iter.next().is_none(),
-        "Only one box can change its position at a time"
-    );
-
-    let push_dir = old_box_pos.dir_to(new_box_pos);
-    let player_pos_before_push = old_box_pos + push_dir.inverse();
-    let mut moves = player_steps(map, old, old_player_pos, player_pos_before_push);
-    moves.add(Move::new(push_dir, true));
-
-    (moves, old_box_pos)
+    state: &State,
+    src_pos: Pos,
+    dest_pos: Pos,
+) -> Option<Moves> {
+    PlayerPaths::new().steps(map, state, src_pos, dest_pos)
+}
+
+/// Flood fills of the area the player can walk in, cached by the squares blocking it
+/// (boxes and other players) and where the player starts.
+///
+/// Several paths are often needed for the same boxes - reaching different removers,
+/// walking between pushes in Multiban levels or multiple solutions going through the same states.
+pub(crate) struct PlayerPaths {
+    /// The direction in which each square was entered on the shortest path to it
+    fills: FnvHashMap<(Vec<Pos>, Pos), Vec2d<Option<Dir>>>,
 }
 
-fn player_steps(map: &dyn Map, state: &State, src_pos: Pos, dest_pos: Pos) -> Moves {
-    if src_pos == dest_pos {
-        // because it's not a proper BFS with an open set
-        return Moves::default();
+impl PlayerPaths {
+    /// Each fill is as large as the map so the cache is cleared when it gets this big
+    const MAX_CACHED: usize = 1024;
+
+    pub(crate) fn new() -> Self {
+        PlayerPaths {
+            fills: FnvHashMap::default(),
+        }
+    }
+
+    /// Same as `player_steps`
+    pub(crate) fn steps(
+        &mut self,
+        map: &dyn Map,
+        state: &State,
+        src_pos: Pos,
+        dest_pos: Pos,
+    ) -> Option<Moves> {
+        if src_pos == dest_pos {
+            return Some(Moves::default());
+        }
+
+        let mut blocked = state.boxes.to_vec();
+        if state.player_cnt() > 1 {
+            blocked.extend(state.players().filter(|&pos| pos != src_pos));
+        }
+        let key = (blocked, src_pos);
+        if !self.fills.contains_key(&key) {
+            if self.fills.len() >= Self::MAX_CACHED {
+                self.fills.clear();
+            }
+            let fill = flood_fill(map, &key.0, src_pos);
+            self.fills.insert(key.clone(), fill);
+        }
+        let entered = &self.fills[&key];
+
+        let mut dirs = Vec::new();
+        let mut pos = dest_pos;
+        while pos != src_pos {
+            let dir = entered[pos]?;
+            dirs.push(dir);
+            pos = pos - dir;
+        }
+
+        let mut moves = Moves::default();
+        for &dir in dirs.iter().rev() {
+            moves.add(Move::new(dir, false));
+        }
+        Some(moves)
     }
+}
 
+/// BFS from `src_pos` recording the direction each reached square was entered from
+fn flood_fill(map: &dyn Map, blocked: &[Pos], src_pos: Pos) -> Vec2d<Option<Dir>> {
     let mut box_grid = map.grid().scratchpad();
-    for &b in &state.boxes {
-        box_grid[b] = true;
+    for &pos in blocked {
+        box_grid[pos] = true;
     }
 
-    let mut prevs = HashMap::new();
-    prevs.insert(src_pos, src_pos);
+    let mut entered = map.grid().scratchpad_with_default(None);
+    let mut visited = map.grid().scratchpad();
+    visited[src_pos] = true;
 
     let mut to_visit = VecDeque::new();
     to_visit.push_back(src_pos);
-
-    'bfs: loop {
-        let player_pos = to_visit
-            .pop_front()
-            .expect("Couldn't find a path to dest_pos");
-
-        for &new_player_pos in &player_pos.neighbors() {
+    while let Some(player_pos) = to_visit.pop_front() {
+        for &dir in &DIRECTIONS {
+            let new_player_pos = player_pos + dir;
             if map.grid()[new_player_pos] == MapCell::Wall
                 || box_grid[new_player_pos]
-                || prevs.contains_key(&new_player_pos)
+                || visited[new_player_pos]
             {
                 continue;
             }
-
-            prevs.insert(new_player_pos, player_pos);
-            if new_player_pos == dest_pos {
-                break 'bfs;
-            }
+            visited[new_player_pos] = true;
+            entered[new_player_pos] = Some(dir);
             to_visit.push_back(new_player_pos);
         }
     }
-
-    let positions = backtrack_prevs(&prevs, dest_pos);
-
-    let mut moves = Moves::default();
-    let mut iter = positions.iter();
-    let mut cur = iter.next().expect("There must be at least one position");
-    for next in iter {
-        moves.add(Move::new(cur.dir_to(*next), false));
-        cur = next;
-    }
-
-    moves
+    entered
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+
+    use std::collections::HashMap;
+
     use crate::config::Format;
     use crate::level::Level;
 
@@ -304,8 +480,9 @@ mod tests {
         prevs.insert(&level_state3.state, &level_state2.state);
         prevs.insert(&level_state4.state, &level_state3.state);
 
-        let states = backtrack_prevs(&prevs, &level_state4.state);
-        let moves = reconstruct_moves(&level_state1.map, level_initial.state.player_pos, &states);
+        let states = backtrack_prevs(&level_state4.state, |state| prevs[state]);
+        let moves = MoveReconstructor::new(&level_state1.map, &level_initial.state, Variant::Push)
+            .reconstruct(&states);
         assert_eq!(moves.to_string(), "ddDrrrddrruuuuuuluuulllLrrrrrR");
 
         let solution_pushes = level_initial
@@ -313,4 +490,41 @@ mod tests {
             .to_string();
         assert_eq!(solution_pushes, expected_pushes);
     }
+
+    #[test]
+    fn cached_paths() {
+        let level: Level = r"
+#######
+#@    #
+# $$  #
+#   # #
+#.. # #
+#######
+"
+        .parse()
+        .unwrap();
+
+        let mut paths = PlayerPaths::new();
+        let src = level.state.player_pos;
+        for dest in level.map.grid().positions() {
+            let fresh = player_steps(&level.map, &level.state, src, dest);
+            let cached = paths.steps(&level.map, &level.state, src, dest);
+            assert_eq!(fresh, cached);
+            if let Some(moves) = cached {
+                let walked = level.apply(&moves).unwrap();
+                assert_eq!(walked.state.player_pos, dest);
+            }
+        }
+        assert_eq!(paths.fills.len(), 1);
+
+        assert_eq!(
+            paths
+                .steps(&level.map, &level.state, src, src)
+                .unwrap()
+                .move_cnt(),
+            0
+        );
+        let box_pos = level.state.boxes[0];
+        assert_eq!(paths.steps(&level.map, &level.state, src, box_pos), None);
+    }
 }
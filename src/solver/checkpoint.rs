@@ -0,0 +1,175 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::convert::TryFrom;
+
+use fnv::FnvHashMap;
+use serde::{Deserialize, Serialize};
+use typed_arena::Arena;
+
+use crate::config::{Method, Variant};
+use crate::data::{Coord, Pos};
+use crate::state::State;
+
+use super::a_star::{Cost, CostComparator, SearchNode, Stats, TieBreaker};
+use super::prevs::{self, Prevs};
+
+/// An interrupted search which can be continued later using `Level::resume_with_progress`.
+///
+/// Contains the whole open list and all visited states so it can be as large
+/// as the memory used by the search.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Set by the caller of the search - the search itself doesn't know them
+    pub(super) method: Method,
+    pub(super) level: String,
+    #[serde(default)]
+    pub(super) variant: Variant,
+    pub(super) suboptimality_percent: u16,
+    pub(super) stats: Stats,
+    /// Each state is only stored once, the rest refer to them by index
+    states: Vec<SavedState>,
+    open: Vec<SavedNode>,
+    /// Indices of a visited state and its predecessor, in the order they were visited
+    prevs: Vec<(usize, usize)>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SavedState {
+    player: (usize, usize),
+    /// Only in Multiban levels
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    other_players: Vec<(usize, usize)>,
+    boxes: Vec<(usize, usize)>,
+    /// Only with `Method::PushesBoxLines` - where the last pushed box was and where it ended up
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_push: Option<((usize, usize), (usize, usize))>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SavedNode {
+    state: usize,
+    prev: Option<usize>,
+    /// Older checkpoints always have 2 parts
+    dist: Vec<u16>,
+    cost: Vec<u16>,
+    /// Position among nodes with the same cost and distance
+    #[serde(default)]
+    order: u64,
+}
+
+impl Checkpoint {
+    pub(super) fn new<'a, C, I>(
+        suboptimality_percent: u16,
+        stats: Stats,
+        open: I,
+        prevs: &Prevs<'a>,
+    ) -> Self
+    where
+        C: Cost + 'a,
+        I: IntoIterator<Item = (SearchNode<'a, C>, u64)>,
+    {
+        let mut indices = FnvHashMap::default();
+        let mut saved = Vec::new();
+        let mut index = |state: &'a State| {
+            *indices.entry(state).or_insert_with(|| {
+                saved.push(SavedState {
+                    player: state.player_pos.into(),
+                    other_players: state.other_players.iter().map(|&p| p.into()).collect(),
+                    boxes: state.boxes.iter().map(|&b| b.into()).collect(),
+                    last_push: state
+                        .last_push
+                        .map(|(pos, dir)| ((pos - dir).into(), pos.into())),
+                });
+                saved.len() - 1
+            })
+        };
+
+        let open = open
+            .into_iter()
+            .map(|(node, order)| SavedNode {
+                state: index(node.state),
+                prev: node.prev.map(&mut index),
+                dist: node.dist.parts().to_vec(),
+                cost: node.cost.parts().to_vec(),
+                order,
+            })
+            .collect();
+        let prevs = prevs
+            .iter()
+            .map(|(state, prev)| (index(state), index(prev)))
+            .collect();
+
+        Self {
+            method: Method::Any,
+            level: String::new(),
+            variant: Variant::Push,
+            suboptimality_percent,
+            stats,
+            states: saved,
+            open,
+            prevs,
+        }
+    }
+
+    /// Puts the saved states back into the data structures of a new search.
+    ///
+    /// `canonical` gives the key of visited states in maps with symmetries.
+    pub(super) fn restore<'a, C: Cost>(
+        &self,
+        states: &'a Arena<State>,
+        to_visit: &mut BinaryHeap<Reverse<CostComparator<'a, C>>>,
+        prevs: &mut Prevs<'a>,
+        tie_breaker: &mut TieBreaker,
+        mut canonical: impl FnMut(&State) -> Option<State>,
+    ) {
+        let pos = |(r, c): (usize, usize)| Pos::new(r as Coord, c as Coord);
+        let restored: Vec<&State> = self
+            .states
+            .iter()
+            .map(|saved| {
+                let boxes: Vec<_> = saved.boxes.iter().copied().map(pos).collect();
+                let mut state = State::new(pos(saved.player), boxes);
+                state.other_players = saved.other_players.iter().copied().map(pos).collect();
+                state.last_push = saved
+                    .last_push
+                    .map(|(from, to)| (pos(to), pos(from).dir_to(pos(to))));
+                &*states.alloc(state)
+            })
+            .collect();
+
+        // prevs can come after the states reached from them in older checkpoints
+        let visited: FnvHashMap<usize, u32> = self
+            .prevs
+            .iter()
+            .enumerate()
+            .map(|(i, &(state, _))| (state, u32::try_from(i).expect("Too many visited states")))
+            .collect();
+        for &(state, prev) in &self.prevs {
+            let state = restored[state];
+            let key = match canonical(state) {
+                Some(key) => &*states.alloc(key),
+                None => state,
+            };
+            prevs.insert(prevs::hash(key), state, key, Some(visited[&prev]));
+        }
+        for node in &self.open {
+            let search_node = SearchNode {
+                state: restored[node.state],
+                prev: node.prev.map(|prev| restored[prev]),
+                dist: C::from_parts(&node.dist),
+                cost: C::from_parts(&node.cost),
+            };
+            to_visit.push(Reverse(tie_breaker.wrap_restored(search_node, node.order)));
+        }
+    }
+
+    /// The method of the interrupted search, resuming always continues with the same one
+    pub fn method(&self) -> Method {
+        self.method
+    }
+
+    /// Stats of the search up to the point it was interrupted
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+}
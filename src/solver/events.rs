@@ -0,0 +1,273 @@
+//! Streaming every decision of the search, e.g. for visualizers.
+//!
+//! The binary format is a header (`SKEV` and a version byte) followed by 25 byte records:
+//! state hash (u64), parent hash (u64), flags (u8 - the decision in the low 2 bits,
+//! bit 2 set if there is a parent), distance (2x u16) and heuristic (2x u16), all little endian.
+
+use std::hash::{Hash, Hasher};
+use std::io::{self, Read, Write};
+use std::sync::mpsc::Sender;
+
+use fnv::FnvHasher;
+
+use crate::solver::a_star::{Cost, SearchNode};
+use crate::state::State;
+
+const MAGIC: &[u8; 4] = b"SKEV";
+const VERSION: u8 = 1;
+const RECORD_LEN: usize = 25;
+const HAS_PARENT: u8 = 0b100;
+
+/// What the search did with a node
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Decision {
+    /// Created by expanding its parent and added to the open list
+    Queued,
+    /// Popped from the open list but its state was already expanded
+    Duplicate,
+    /// Popped from the open list and its successors were generated
+    Expanded,
+    /// Popped from the open list and it's solved, this is the last event of a successful search
+    Solution,
+}
+
+/// One step of the search.
+///
+/// Distances and heuristics have 2 parts for methods which minimize 2 metrics
+/// (e.g. moves and pushes for `Method::MovesPushes`), the second is 0 otherwise.
+/// Searches with more criteria (`Level::solve_lexicographic`) only record the first 2.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SearchEvent {
+    /// Hash of the node's state, the same states always have the same hash
+    pub state: u64,
+    /// Hash of the state the node was created from, `None` for the initial state
+    pub parent: Option<u64>,
+    pub dist: [u16; 2],
+    pub heuristic: [u16; 2],
+    pub decision: Decision,
+}
+
+impl SearchEvent {
+    pub(crate) fn new<C: Cost>(node: &SearchNode<'_, C>, decision: Decision) -> Self {
+        // the format has room for 2 parts, that's enough for all methods
+        let two_parts = |cost: C| {
+            let parts = cost.parts();
+            [parts[0], parts.get(1).copied().unwrap_or(0)]
+        };
+        SearchEvent {
+            state: state_hash(node.state),
+            parent: node.prev.map(state_hash),
+            dist: two_parts(node.dist),
+            heuristic: two_parts(node.cost - node.dist),
+            decision,
+        }
+    }
+
+    fn to_bytes(self) -> [u8; RECORD_LEN] {
+        let mut bytes = [0; RECORD_LEN];
+        bytes[0..8].copy_from_slice(&self.state.to_le_bytes());
+        bytes[8..16].copy_from_slice(&self.parent.unwrap_or(0).to_le_bytes());
+        let decision = match self.decision {
+            Decision::Queued => 0,
+            Decision::Duplicate => 1,
+            Decision::Expanded => 2,
+            Decision::Solution => 3,
+        };
+        bytes[16] = decision | if self.parent.is_some() { HAS_PARENT } else { 0 };
+        let parts = self.dist.iter().chain(&self.heuristic);
+        for (i, part) in parts.enumerate() {
+            bytes[17 + 2 * i..19 + 2 * i].copy_from_slice(&part.to_le_bytes());
+        }
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8; RECORD_LEN]) -> io::Result<Self> {
+        let u64_at = |i: usize| {
+            let mut b = [0; 8];
+            b.copy_from_slice(&bytes[i..i + 8]);
+            u64::from_le_bytes(b)
+        };
+        let u16_at = |i: usize| u16::from_le_bytes([bytes[i], bytes[i + 1]]);
+        let flags = bytes[16];
+        let decision = match flags & 0b11 {
+            0 => Decision::Queued,
+            1 => Decision::Duplicate,
+            2 => Decision::Expanded,
+            _ => Decision::Solution,
+        };
+        if flags & !(0b11 | HAS_PARENT) != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid event flags: {flags:#b}"),
+            ));
+        }
+        Ok(SearchEvent {
+            state: u64_at(0),
+            parent: (flags & HAS_PARENT != 0).then(|| u64_at(8)),
+            dist: [u16_at(17), u16_at(19)],
+            heuristic: [u16_at(21), u16_at(23)],
+            decision,
+        })
+    }
+}
+
+fn state_hash(state: &State) -> u64 {
+    let mut hasher = FnvHasher::default();
+    state.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Receives events during `Level::solve_with_events`.
+///
+/// Called for every created node so it should be fast, slow consumers should use a channel.
+pub trait EventSink {
+    fn event(&mut self, event: &SearchEvent);
+}
+
+/// Events for another thread, sending stops silently when the receiver is dropped
+impl EventSink for Sender<SearchEvent> {
+    fn event(&mut self, event: &SearchEvent) {
+        let _ = self.send(*event);
+    }
+}
+
+/// Writes events in the compact binary format, use a `BufWriter` for files.
+#[derive(Debug)]
+pub struct EventWriter<W: Write> {
+    writer: W,
+    /// The search can't be interrupted by a failed write so it's reported at the end
+    err: Option<io::Error>,
+}
+
+impl<W: Write> EventWriter<W> {
+    /// Writes the header right away
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION])?;
+        Ok(EventWriter { writer, err: None })
+    }
+
+    /// Flushes the writer and returns it or the first error which happened while writing
+    pub fn finish(mut self) -> io::Result<W> {
+        if let Some(err) = self.err {
+            return Err(err);
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+impl<W: Write> EventSink for EventWriter<W> {
+    fn event(&mut self, event: &SearchEvent) {
+        if self.err.is_none() {
+            if let Err(err) = self.writer.write_all(&event.to_bytes()) {
+                self.err = Some(err);
+            }
+        }
+    }
+}
+
+/// Reads events written by `EventWriter`, use a `BufReader` for files.
+#[derive(Debug)]
+pub struct EventReader<R: Read> {
+    reader: R,
+}
+
+impl<R: Read> EventReader<R> {
+    /// Checks the header right away
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut header = [0; 5];
+        reader.read_exact(&mut header)?;
+        if &header[..4] != MAGIC || header[4] != VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Not a search event stream or an unsupported version",
+            ));
+        }
+        Ok(EventReader { reader })
+    }
+}
+
+impl<R: Read> Iterator for EventReader<R> {
+    type Item = io::Result<SearchEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut bytes = [0; RECORD_LEN];
+        let mut read = 0;
+        while read < RECORD_LEN {
+            match self.reader.read(&mut bytes[read..]) {
+                // clean end between records
+                Ok(0) if read == 0 => return None,
+                Ok(0) => return Some(Err(io::ErrorKind::UnexpectedEof.into())),
+                Ok(n) => read += n,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Some(Err(err)),
+            }
+        }
+        Some(SearchEvent::from_bytes(&bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use super::*;
+
+    use crate::config::{Method, SolverOptions};
+    use crate::level::Level;
+
+    #[test]
+    fn streaming() {
+        let level: Level = "#####\n#@$.#\n#####".parse().unwrap();
+        let mut writer = EventWriter::new(Vec::new()).unwrap();
+        let solver_ok = level
+            .solve_with_events(
+                Method::MovesPushes,
+                &SolverOptions::default(),
+                &mut |_| true,
+                &mut writer,
+            )
+            .unwrap();
+        assert!(solver_ok.moves.is_some());
+        let bytes = writer.finish().unwrap();
+        assert_eq!(bytes.len(), 5 + 4 * RECORD_LEN);
+
+        let events: Vec<_> = EventReader::new(bytes.as_slice())
+            .unwrap()
+            .collect::<io::Result<_>>()
+            .unwrap();
+        let decisions: Vec<_> = events.iter().map(|event| event.decision).collect();
+        assert_eq!(
+            decisions,
+            [
+                Decision::Queued,
+                Decision::Expanded,
+                Decision::Queued,
+                Decision::Solution
+            ]
+        );
+        assert_eq!(events[0].parent, None);
+        assert_eq!(events[2].parent, Some(events[1].state));
+        assert_eq!(events[3].dist, [1, 1]);
+        assert_eq!(events[3].heuristic, [0, 0]);
+
+        // the same events through a channel
+        let (mut sender, receiver) = mpsc::channel();
+        level
+            .solve_with_events(
+                Method::MovesPushes,
+                &SolverOptions::default(),
+                &mut |_| true,
+                &mut sender,
+            )
+            .unwrap();
+        drop(sender);
+        assert_eq!(receiver.iter().collect::<Vec<_>>(), events);
+
+        assert!(EventReader::new(&b"SKEV\x02"[..]).is_err());
+        let truncated = &bytes[..bytes.len() - 1];
+        let mut reader = EventReader::new(truncated).unwrap();
+        assert!(reader.nth(3).unwrap().is_err());
+    }
+}
@@ -1,7 +1,12 @@
-use std::{borrow::Cow, fs, process::Command};
+//! Recording the searched state space for visualization.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
 
-use dot::{Edges, GraphWalk, Id, LabelText, Labeller, Nodes, Style};
 use fnv::{FnvHashMap, FnvHashSet};
+use serde::Serialize;
 
 use crate::{
     map::Map,
@@ -9,26 +14,207 @@ use crate::{
     state::State,
 };
 
-type Nd = usize;
-type Ed = (usize, usize);
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum Type {
+/// What happened to a node after it was created
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NodeKind {
+    /// Still in the open list when the search ended
     Queued,
+    /// Its state was already created with a lower or equal distance so it could have been skipped
     AvoidableDuplicate,
+    /// Its state was already visited when it was popped from the open list
     Duplicate,
+    /// Expanded
     Unique,
 }
 
+impl NodeKind {
+    /// The same name as in JSON
+    fn name(self) -> &'static str {
+        match self {
+            NodeKind::Queued => "queued",
+            NodeKind::AvoidableDuplicate => "avoidable-duplicate",
+            NodeKind::Duplicate => "duplicate",
+            NodeKind::Unique => "unique",
+        }
+    }
+}
+
+/// A node of the search graph - one state with the distance it was reached with
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TraceNode {
+    /// The level with the node's state in XSB
+    pub xsb: String,
+    /// Distance from the initial state in the metrics minimized by the method
+    /// (e.g. moves and pushes for `Method::MovesPushes`)
+    pub dist: Vec<u16>,
+    /// Heuristic estimate of the remaining distance, in the same metrics as `dist`
+    pub heuristic: Vec<u16>,
+    /// Order in which the node was popped from the open list, `None` if it never was
+    pub visited: Option<usize>,
+    pub kind: NodeKind,
+    /// The node's state is part of the found solution
+    pub on_solution: bool,
+}
+
+/// The part of the state space explored by a search, returned in `SolverOk::trace`.
+///
+/// Every created node is included so it gets large quickly - only use it for small levels.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SearchTrace {
+    /// Nodes in the order they were created, the initial state is first
+    pub nodes: Vec<TraceNode>,
+    /// Indices into `nodes` of a node and a node created by expanding it
+    pub edges: Vec<(usize, usize)>,
+}
+
+impl SearchTrace {
+    /// Graphviz format, render it with e.g. `dot -Tpng -O state-space.dot`
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph G {\n");
+        dot.push_str("    graph [fontname = \"hack\"];\n");
+        dot.push_str("    node [fontname = \"hack\"];\n");
+        dot.push_str("    edge [fontname = \"hack\"];\n");
+        for (index, node) in self.nodes.iter().enumerate() {
+            let label = dot_escape(&self.label(index));
+            let style = match node.kind {
+                NodeKind::Queued => "solid",
+                _ => "filled",
+            };
+            let color = match node.kind {
+                NodeKind::Unique if node.on_solution => Some("red"),
+                NodeKind::Unique => Some("gold"),
+                NodeKind::AvoidableDuplicate => Some("green"),
+                NodeKind::Duplicate => Some("gray"),
+                NodeKind::Queued => None,
+            };
+            let _ = write!(dot, "    N{index}[label=\"{label}\"][style=\"{style}\"]");
+            if let Some(color) = color {
+                let _ = write!(dot, "[color=\"{color}\"]");
+            }
+            dot.push_str(";\n");
+        }
+        for &(from, to) in &self.edges {
+            // TODO this also highlights edges to dupes
+            if self.nodes[from].on_solution && self.nodes[to].on_solution {
+                let _ = writeln!(dot, "    N{from} -> N{to}[style=\"bold\"][color=\"red\"];");
+            } else {
+                let _ = writeln!(dot, "    N{from} -> N{to};");
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// XML format supported by e.g. yEd, Gephi and `NetworkX`
+    pub fn to_graphml(&self) -> String {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str("<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n");
+        let keys = [
+            ("xsb", "string"),
+            ("dist", "string"),
+            ("heuristic", "string"),
+            ("visited", "int"),
+            ("kind", "string"),
+            ("on_solution", "boolean"),
+        ];
+        for (name, ty) in keys {
+            let _ = writeln!(
+                xml,
+                "  <key id=\"{name}\" for=\"node\" attr.name=\"{name}\" attr.type=\"{ty}\"/>"
+            );
+        }
+        xml.push_str("  <graph id=\"G\" edgedefault=\"directed\">\n");
+        for (index, node) in self.nodes.iter().enumerate() {
+            let _ = writeln!(xml, "    <node id=\"N{index}\">");
+            let mut data = vec![
+                ("xsb", xml_escape(&node.xsb)),
+                ("dist", join(&node.dist)),
+                ("heuristic", join(&node.heuristic)),
+                ("kind", node.kind.name().to_owned()),
+                ("on_solution", node.on_solution.to_string()),
+            ];
+            if let Some(visited) = node.visited {
+                data.push(("visited", visited.to_string()));
+            }
+            for (key, value) in data {
+                let _ = writeln!(xml, "      <data key=\"{key}\">{value}</data>");
+            }
+            xml.push_str("    </node>\n");
+        }
+        for &(from, to) in &self.edges {
+            let _ = writeln!(xml, "    <edge source=\"N{from}\" target=\"N{to}\"/>");
+        }
+        xml.push_str("  </graph>\n</graphml>\n");
+        xml
+    }
+
+    /// # Panics
+    ///
+    /// Never, all the fields can be serialized.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("SearchTrace contains only serializable types")
+    }
+
+    pub fn write_dot<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_dot())
+    }
+
+    pub fn write_graphml<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_graphml())
+    }
+
+    pub fn write_json<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_json())
+    }
+
+    fn label(&self, index: usize) -> String {
+        let node = &self.nodes[index];
+        let visited = node
+            .visited
+            .map_or_else(|| "-".to_owned(), |v| v.to_string());
+        let cost: Vec<u16> = node
+            .dist
+            .iter()
+            .zip(&node.heuristic)
+            .map(|(d, h)| d.saturating_add(*h))
+            .collect();
+        format!(
+            "c/v: {index}/{visited}\nd: {}, h: {}\ncost: {}\n{}",
+            join(&node.dist),
+            join(&node.heuristic),
+            join(&cost),
+            node.xsb
+        )
+    }
+}
+
+fn join(parts: &[u16]) -> String {
+    let parts: Vec<_> = parts.iter().map(ToString::to_string).collect();
+    parts.join("/")
+}
+
+fn dot_escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\l")
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
 // TODO merge nodes with the same state? (make sure visited stays correct)
+/// Collects the trace during the search while the states are still in the arena
 #[derive(Debug)]
 pub(crate) struct Graph<'a, C: Cost> {
     map: &'a dyn Map,
     node_to_index: FnvHashMap<SearchNode<'a, C>, usize>,
-    /// node, visited counter, visited type
-    nodes: Vec<(SearchNode<'a, C>, usize, Type)>,
-    edges: Vec<(usize, usize)>,
-    solution_states: FnvHashSet<&'a State>,
+    nodes: Vec<SearchNode<'a, C>>,
+    trace: SearchTrace,
     visited_counter: usize,
 }
 
@@ -38,8 +224,7 @@ impl<'a, C: Cost> Graph<'a, C> {
             map,
             node_to_index: FnvHashMap::default(),
             nodes: Vec::new(),
-            edges: Vec::new(),
-            solution_states: FnvHashSet::default(),
+            trace: SearchTrace::default(),
             visited_counter: 0,
         }
     }
@@ -49,145 +234,96 @@ impl<'a, C: Cost> Graph<'a, C> {
 
         let node_index = self.nodes.len();
 
-        let mut node_type = Type::Queued;
-        for &(search_node, _, _) in &self.nodes {
+        let mut kind = NodeKind::Queued;
+        for search_node in &self.nodes {
             if node.state == search_node.state && node.dist >= search_node.dist {
-                node_type = Type::AvoidableDuplicate;
+                kind = NodeKind::AvoidableDuplicate;
                 break;
             }
         }
 
         self.node_to_index.insert(node, node_index);
-        self.nodes.push((node, 0, node_type));
+        self.nodes.push(node);
+        let parts = |cost: C| cost.parts().to_vec();
+        self.trace.nodes.push(TraceNode {
+            xsb: self.map.xsb_with_state(node.state).to_string(),
+            dist: parts(node.dist),
+            heuristic: parts(node.cost - node.dist),
+            visited: None,
+            kind,
+            on_solution: false,
+        });
 
         if let Some(prev) = prev {
-            let prev_index = self.node_to_index[&prev];
-            self.edges.push((prev_index, node_index));
+            let prev_index = self.index(prev);
+            self.trace.edges.push((prev_index, node_index));
+        }
+    }
+
+    /// Nodes restored from a checkpoint are added when they're first seen, without edges
+    fn index(&mut self, node: SearchNode<'a, C>) -> usize {
+        if let Some(&index) = self.node_to_index.get(&node) {
+            index
+        } else {
+            self.add(node, None);
+            self.nodes.len() - 1
         }
     }
 
     pub(crate) fn mark_duplicate(&mut self, node: SearchNode<'a, C>) {
-        let index = self.node_to_index[&node];
-        if self.nodes[index].2 != Type::AvoidableDuplicate {
-            self.nodes[index].2 = Type::Duplicate;
+        let index = self.index(node);
+        let trace_node = &mut self.trace.nodes[index];
+        if trace_node.kind != NodeKind::AvoidableDuplicate {
+            trace_node.kind = NodeKind::Duplicate;
         }
-        self.nodes[index].1 = self.visited_counter;
+        trace_node.visited = Some(self.visited_counter);
         self.visited_counter += 1;
     }
 
     pub(crate) fn mark_unique(&mut self, node: SearchNode<'a, C>) {
-        let index = self.node_to_index[&node];
-        self.nodes[index].1 = self.visited_counter;
+        let index = self.index(node);
+        let trace_node = &mut self.trace.nodes[index];
+        trace_node.visited = Some(self.visited_counter);
+        trace_node.kind = NodeKind::Unique;
         self.visited_counter += 1;
-        self.nodes[index].2 = Type::Unique;
-    }
-
-    pub(crate) fn draw_states(&mut self, solution_states: &'a [&'a State]) {
-        self.solution_states = solution_states.iter().copied().collect();
-
-        let mut writer = Vec::new();
-        dot::render(self, &mut writer).unwrap();
-        let s = String::from_utf8(writer).unwrap();
-        let s = s.replace("digraph G {", "digraph G {\n    graph [fontname = \"hack\"];\n    node [fontname = \"hack\"];\n    edge [fontname = \"hack\"];");
-        fs::write("state-space.dot", s).unwrap();
-
-        let status = Command::new("dot")
-            // PNG is bigger on disk but takes less memory to load than SVG
-            .args(["-Tpng", "-O", "state-space.dot"])
-            .status()
-            .unwrap();
-        assert!(status.success());
-    }
-}
-
-impl<'a, C: Cost> GraphWalk<'a, Nd, Ed> for Graph<'a, C> {
-    fn nodes(&'a self) -> Nodes<'a, Nd> {
-        (0..self.nodes.len()).collect()
-    }
-
-    fn edges(&'a self) -> Edges<'a, Ed> {
-        Cow::from(&self.edges)
-    }
-
-    fn source(&'a self, e: &Ed) -> Nd {
-        e.0
-    }
-
-    fn target(&'a self, e: &Ed) -> Nd {
-        e.1
-    }
-}
-
-impl<'a, C: Cost> Labeller<'a, Nd, Ed> for Graph<'a, C> {
-    fn graph_id(&'a self) -> Id<'a> {
-        Id::new("G").unwrap()
-    }
-
-    fn node_id(&'a self, n: &Nd) -> Id<'a> {
-        Id::new(format!("N{n}")).unwrap()
     }
 
-    fn node_label(&'a self, n: &Nd) -> LabelText<'a> {
-        let node = self.nodes[*n].0;
-        LabelText::EscStr(
-            format!(
-                "c/v: {}/{}\nd: {}, h: {}\ncost: {}\n{}",
-                n,
-                self.nodes[*n].1,
-                node.dist,
-                node.cost - node.dist,
-                node.cost,
-                self.map.xsb_with_state(node.state)
-            )
-            .into(),
-        )
-    }
-
-    fn node_style(&'a self, n: &Nd) -> Style {
-        let node_type = self.nodes[*n].2;
-        if node_type == Type::Queued {
-            Style::Solid
-        } else {
-            Style::Filled
+    /// Pass an empty slice if no solution was found
+    pub(crate) fn into_trace(mut self, solution_states: &[&State]) -> SearchTrace {
+        let solution_states: FnvHashSet<_> = solution_states.iter().copied().collect();
+        for (node, trace_node) in self.nodes.iter().zip(&mut self.trace.nodes) {
+            trace_node.on_solution = solution_states.contains(node.state);
         }
+        self.trace
     }
+}
 
-    fn node_color(&'a self, n: &Nd) -> Option<LabelText<'a>> {
-        let state = self.nodes[*n].0.state;
-        let node_type = self.nodes[*n].2;
-        let color_name = match node_type {
-            Type::Unique => {
-                if self.solution_states.contains(state) {
-                    "red"
-                } else {
-                    "gold"
-                }
-            }
-            Type::AvoidableDuplicate => "green",
-            Type::Duplicate => "gray",
-            Type::Queued => return None,
-        };
-        Some(LabelText::LabelStr(color_name.into()))
-    }
+#[cfg(test)]
+mod tests {
+    use crate::config::{Method, SolverOptions};
+    use crate::level::Level;
+    use crate::Solve;
 
-    // TODO this also highlights edges to dupes
-    fn edge_style(&'a self, e: &Ed) -> Style {
-        let state0 = self.nodes[e.0].0.state;
-        let state1 = self.nodes[e.1].0.state;
-        if self.solution_states.contains(state0) && self.solution_states.contains(state1) {
-            Style::Bold
-        } else {
-            Style::Solid
-        }
-    }
+    #[test]
+    fn tracing() {
+        let level: Level = "#####\n#@$.#\n#####".parse().unwrap();
+        let solver_ok = level
+            .solve_with_options(Method::Pushes, &SolverOptions::default())
+            .unwrap();
+        let trace = solver_ok.trace.unwrap();
+        assert_eq!(trace.nodes.len(), 2);
+        assert_eq!(trace.edges, [(0, 1)]);
+        assert!(trace.nodes.iter().all(|node| node.on_solution));
+        // states are normalized - the player is moved to the top left reachable square
+        assert_eq!(trace.nodes[1].xsb, "#####\n#@ *#\n#####\n");
+        assert_eq!(trace.nodes[1].dist, [1]);
 
-    fn edge_color(&'a self, e: &Ed) -> Option<LabelText<'a>> {
-        let state0 = self.nodes[e.0].0.state;
-        let state1 = self.nodes[e.1].0.state;
-        if self.solution_states.contains(state0) && self.solution_states.contains(state1) {
-            Some(LabelText::LabelStr("red".into()))
-        } else {
-            None
-        }
+        let dot = trace.to_dot();
+        assert!(dot.starts_with("digraph G {\n"));
+        assert!(dot.contains("N0 -> N1[style=\"bold\"][color=\"red\"];"));
+        assert!(trace
+            .to_graphml()
+            .contains("<edge source=\"N0\" target=\"N1\"/>"));
+        assert!(trace.to_json().contains("\"kind\":\"unique\""));
     }
 }
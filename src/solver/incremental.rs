@@ -0,0 +1,148 @@
+//! Re-solving a level after small edits without redoing all of the preprocessing.
+
+use std::fmt::{self, Debug, Formatter};
+use std::io;
+
+use crate::config::{Method, SolverOptions};
+use crate::level::Level;
+
+use super::preprocessing::PushDistsMemo;
+use super::{SolverErr, SolverOk, Stats};
+
+/// Solves a level repeatedly while it's being edited, e.g. so an editor can show
+/// whether the level is still solvable after every change.
+///
+/// Push distances are the most expensive part of preprocessing so the last ones are memoized
+/// and reused when the walls boxes see haven't changed. That's the case when edits only move
+/// goals, removers or boxes and players within the area reachable before and outside dead-end
+/// corridors. Changing the walls, the reachable area or which corridors have a box
+/// or the player in them recomputes the distances in full.
+/// The rest of preprocessing (dead squares, closest push distances, symmetries, ...)
+/// is recomputed on every solve.
+/// The memoized distances take about as much memory as during a normal solve.
+#[derive(Default)]
+pub struct IncrementalSolver {
+    memo: PushDistsMemo,
+}
+
+impl IncrementalSolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Solves `level` which is usually the previously solved level with a small change
+    pub fn solve(
+        &mut self,
+        level: &Level,
+        method: Method,
+        options: &SolverOptions,
+    ) -> Result<SolverOk, SolverErr> {
+        self.solve_with_progress(level, method, options, &mut |_| true)
+    }
+
+    /// Like `solve` but the search can be stopped by returning `false` from `progress`,
+    /// e.g. when another edit makes the result useless
+    pub fn solve_with_progress(
+        &mut self,
+        level: &Level,
+        method: Method,
+        options: &SolverOptions,
+        progress: &mut dyn FnMut(&Stats) -> bool,
+    ) -> Result<SolverOk, SolverErr> {
+        level.search(
+            method,
+            *options,
+            None,
+            progress,
+            None,
+            &mut io::stdout(),
+            &mut self.memo,
+        )
+    }
+
+    /// The last solve reused the push distances from the one before it
+    pub fn reused_push_dists(&self) -> bool {
+        self.memo.reused
+    }
+}
+
+impl Debug for IncrementalSolver {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IncrementalSolver")
+            .field("reused_push_dists", &self.memo.reused)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::Solve;
+
+    #[test]
+    fn reuses_push_dists() {
+        let mut solver = IncrementalSolver::new();
+        let solve = |solver: &mut IncrementalSolver, level: &str| {
+            let level: Level = level.parse().unwrap();
+            let solver_ok = solver.solve(&level, Method::Pushes, &SolverOptions::default());
+            let expected = level.solve(Method::Pushes, false);
+            assert_eq!(
+                solver_ok.as_ref().map(|ok| ok.moves.clone()),
+                expected.as_ref().map(|ok| ok.moves.clone())
+            );
+            solver_ok
+        };
+
+        solve(
+            &mut solver,
+            r"
+#######
+#@ $ .#
+#  $ .#
+#######
+",
+        )
+        .unwrap();
+        assert!(!solver.reused_push_dists());
+
+        // moved a box and a goal
+        solve(
+            &mut solver,
+            r"
+#######
+#@$  .#
+#  $. #
+#######
+",
+        )
+        .unwrap();
+        assert!(solver.reused_push_dists());
+
+        // moved a wall
+        solve(
+            &mut solver,
+            r"
+#######
+#@$  .#
+## $. #
+#######
+",
+        )
+        .unwrap();
+        assert!(!solver.reused_push_dists());
+
+        // errors in preprocessing are the same as without the memo
+        let err = solve(
+            &mut solver,
+            r"
+#######
+#@$  .#
+## $  #
+#######
+",
+        )
+        .unwrap_err();
+        assert_eq!(err, SolverErr::DiffBoxesGoals);
+    }
+}
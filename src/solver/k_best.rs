@@ -0,0 +1,247 @@
+//! Finding several of the shortest solutions, not all of them optimal.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use fnv::FnvHashMap;
+#[cfg(not(feature = "tracing"))]
+use log::debug;
+#[cfg(feature = "tracing")]
+use tracing::debug;
+use typed_arena::Arena;
+
+use crate::config::Method;
+use crate::level::Level;
+use crate::map::{Map, MapType};
+use crate::moves::Moves;
+use crate::state::State;
+
+use super::a_star::{Cost, PruningStats};
+use super::{
+    backtracking::MoveReconstructor, GameLogic, MoveLogic, MovePushLogic, PushBoxLineLogic,
+    PushLogic, PushMoveLogic, Scratch, Solver, SolverErr, SolverTrait,
+};
+
+impl Level {
+    /// Finds up to `k` different solutions, the shortest ones (in the method's metrics) first.
+    ///
+    /// The first one is optimal like the one from `solve`, the rest can be longer.
+    /// Hint systems can use them to offer alternatives. Solutions never visit the same state twice
+    /// (walking in circles would give infinitely many).
+    ///
+    /// Each state is visited at most `k` times so this uses up to `k` times as much memory
+    /// as `solve` and unsolvable levels are searched `k` times. Because of the limit,
+    /// a solution is occasionally skipped when all the shorter paths to one of its states
+    /// can't continue without going in circles. `Method::Any` is treated as `Method::Pushes`.
+    pub fn solve_k_best(&self, method: Method, k: usize) -> Result<Vec<Moves>, SolverErr> {
+        span!("solve_k_best", %method, variant = %self.variant);
+        debug!("Processing level...");
+
+        Ok(match self.map {
+            MapType::Goals(ref goals_map) => {
+                let solver = Solver::new_with_goals(goals_map, &self.state, self.variant)?;
+                with_method(&solver, method, k)
+            }
+            MapType::Remover(ref remover_map) => {
+                let solver = Solver::new_with_remover(remover_map, &self.state, self.variant)?;
+                with_method(&solver, method, k)
+            }
+            MapType::Hybrid(ref hybrid_map) => {
+                let solver = Solver::new_with_hybrid(hybrid_map, &self.state, self.variant)?;
+                with_method(&solver, method, k)
+            }
+        })
+    }
+}
+
+fn with_method<M>(solver: &Solver<M>, method: Method, k: usize) -> Vec<Moves>
+where
+    M: Map,
+    Solver<M>: SolverTrait<M = M>,
+{
+    match method {
+        Method::MovesPushes => k_best(solver, MovePushLogic, k),
+        Method::Moves => k_best(solver, MoveLogic, k),
+        Method::PushesMoves => k_best(solver, PushMoveLogic, k),
+        Method::PushesBoxLines => k_best(solver, PushBoxLineLogic, k),
+        Method::Pushes | Method::Any => k_best(solver, PushLogic, k),
+    }
+}
+
+/// A path from the initial state, unlike the normal search one state can be on many of them
+struct PathNode<'a, C> {
+    state: &'a State,
+    prev: Option<usize>,
+    dist: C,
+}
+
+fn k_best<M, GL>(solver: &Solver<M>, _: GL, k: usize) -> Vec<Moves>
+where
+    M: Map,
+    Solver<M>: SolverTrait<M = M>,
+    GL: GameLogic<M>,
+{
+    let sd = solver.sd();
+    if k == 0
+        || sd.unsolvable.is_some()
+        || sd
+            .initial_state
+            .boxes
+            .iter()
+            .any(|&box_pos| sd.closest_push_dists[box_pos].is_none())
+    {
+        return Vec::new();
+    }
+    if sd.solved_at_start() {
+        return vec![Moves::default()];
+    }
+
+    let arena = Arena::new();
+    let norm_initial_state = GL::preprocess_state(sd, &sd.initial_state);
+    let mut nodes = vec![PathNode {
+        state: &norm_initial_state,
+        prev: None,
+        dist: GL::C::zero(),
+    }];
+    // ordered like the normal search - by cost, then the deeper node, then the order of creation
+    let mut to_visit = BinaryHeap::new();
+    to_visit.push(Reverse((
+        GL::initial_heuristic(sd, &norm_initial_state),
+        Reverse(GL::C::zero()),
+        0,
+    )));
+    let mut visits: FnvHashMap<&State, usize> = FnvHashMap::default();
+    // there are no stats to report them in
+    let mut pruned = PruningStats::default();
+    let mut scratch = Scratch::new(&sd.map);
+    let mut new_states = Vec::new();
+
+    // The k shortest paths can only go through each state at most k times
+    // so the n-th time a solved state is reached, it's by the n-th shortest path.
+    let mut solutions = Vec::new();
+    let mut reconstructor = MoveReconstructor::new(&sd.map, &sd.initial_state, sd.variant);
+    while let Some(Reverse((cost, _, index))) = to_visit.pop() {
+        let state = nodes[index].state;
+        let visit_cnt = visits.entry(state).or_insert(0);
+        if *visit_cnt >= k {
+            continue;
+        }
+        *visit_cnt += 1;
+
+        let dist = nodes[index].dist;
+        if cost == dist {
+            // heuristic is 0 so level is solved
+            let path = path_states(&nodes, index);
+            solutions.push(reconstructor.reconstruct(&path));
+            if solutions.len() == k {
+                break;
+            }
+            continue;
+        }
+
+        GL::expand(
+            sd,
+            state,
+            &arena,
+            &mut pruned,
+            &mut scratch,
+            &mut new_states,
+        );
+        for (neighbor_state, step_cost, h) in new_states.drain(..) {
+            if on_path(&nodes, index, neighbor_state) {
+                continue;
+            }
+            let next_dist = dist + step_cost;
+            to_visit.push(Reverse((next_dist + h, Reverse(next_dist), nodes.len())));
+            nodes.push(PathNode {
+                state: neighbor_state,
+                prev: Some(index),
+                dist: next_dist,
+            });
+        }
+    }
+
+    solutions
+}
+
+/// Whether the path ending with `nodes[index]` already contains the state
+fn on_path<C>(nodes: &[PathNode<'_, C>], index: usize, state: &State) -> bool {
+    let mut cur = Some(index);
+    while let Some(i) = cur {
+        if nodes[i].state == state {
+            return true;
+        }
+        cur = nodes[i].prev;
+    }
+    false
+}
+
+/// The states of the path ending with `nodes[index]`, starting at the initial state
+fn path_states<'a, C>(nodes: &[PathNode<'a, C>], index: usize) -> Vec<&'a State> {
+    let mut states = Vec::new();
+    let mut cur = Some(index);
+    while let Some(i) = cur {
+        states.push(nodes[i].state);
+        cur = nodes[i].prev;
+    }
+    states.reverse();
+    states
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashSet;
+
+    use crate::config::Metric;
+    use crate::Solve;
+
+    #[test]
+    fn k_best_solutions() {
+        let level: Level = r"
+#######
+#@    #
+# $   #
+#     #
+#    .#
+#######
+"
+        .parse()
+        .unwrap();
+
+        for &method in &Method::OPTIMAL {
+            let optimal = level.solve(method, false).unwrap().moves.unwrap();
+            let solutions = level.solve_k_best(method, 10).unwrap();
+            assert_eq!(solutions.len(), 10, "{method}");
+
+            let metric = |moves: &Moves| match method {
+                Method::MovesPushes => (moves.move_cnt(), moves.push_cnt()),
+                Method::Moves => (moves.move_cnt(), 0),
+                Method::PushesMoves => (moves.push_cnt(), moves.move_cnt()),
+                Method::PushesBoxLines | Method::Pushes | Method::Any => (moves.push_cnt(), 0),
+            };
+            assert_eq!(metric(&solutions[0]), metric(&optimal), "{method}");
+            for pair in solutions.windows(2) {
+                assert!(metric(&pair[0]) <= metric(&pair[1]), "{}", method);
+            }
+            let distinct: HashSet<_> = solutions.iter().map(Moves::to_lurd).collect();
+            assert_eq!(distinct.len(), solutions.len(), "{method}");
+            for moves in &solutions {
+                assert!(level.verify_solution(moves).is_ok());
+            }
+            // there are many push-optimal solutions but fewer move-optimal ones
+            if method.metric() == Metric::Moves {
+                assert!(metric(&solutions[9]) > metric(&optimal), "{}", method);
+            }
+        }
+    }
+
+    #[test]
+    fn fewer_solutions_than_k() {
+        let level: Level = "#####\n#@$.#\n#####".parse().unwrap();
+        let solutions = level.solve_k_best(Method::Moves, 5).unwrap();
+        assert_eq!(solutions, vec![Moves::from_lurd("R").unwrap()]);
+        assert!(level.solve_k_best(Method::Moves, 0).unwrap().is_empty());
+    }
+}
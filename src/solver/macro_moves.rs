@@ -0,0 +1,278 @@
+//! Macro moves of `AnyProfile::Macro` - several pushes of one box made as a single step.
+//!
+//! A box pushed into a tunnel keeps going until it leaves it and a box pushed into a room
+//! with empty goals goes on to the empty goal furthest from where it entered.
+//! Macro states are added next to the single pushes and cost all the pushes they make
+//! so no solution is lost and the solutions are as long as without them,
+//! the search just gets deep faster.
+
+use std::convert::TryFrom;
+
+use fnv::FnvHashSet;
+
+use crate::data::{Dir, MapCell, Pos, DIRECTIONS};
+use crate::map::Map;
+use crate::map_analysis;
+use crate::state::State;
+use crate::vec2d::Vec2d;
+
+use super::{BoxIndex, Scratch, Solver, SolverTrait, StaticData};
+
+/// The rooms of the map, computed once per level
+#[derive(Debug, Clone)]
+pub(super) struct Rooms {
+    /// Room of each square which is not a tunnel, see `map_analysis::rooms`
+    ids: Vec2d<Option<u32>>,
+}
+
+impl Rooms {
+    pub(super) fn new(grid: &Vec2d<MapCell>) -> Self {
+        let mut playable = grid.scratchpad();
+        for pos in grid.positions() {
+            playable[pos] = grid[pos] != MapCell::Wall;
+        }
+        let corridors = map_analysis::corridors(&playable);
+        let mut ids = grid.scratchpad();
+        for (i, room) in map_analysis::rooms(&playable, &corridors)
+            .iter()
+            .enumerate()
+        {
+            let id = u32::try_from(i).expect("More rooms than squares");
+            for &pos in room {
+                ids[pos] = Some(id);
+            }
+        }
+        Rooms { ids }
+    }
+}
+
+/// The box which moved between two states with the same number of boxes and the direction it moved
+pub(super) fn pushed_box(before: &State, after: &State) -> Option<(Pos, Dir)> {
+    if before.boxes.len() != after.boxes.len() {
+        return None;
+    }
+    let from = before.boxes.iter().find(|pos| !after.boxes.contains(pos))?;
+    let to = after.boxes.iter().find(|pos| !before.boxes.contains(pos))?;
+    Some((*to, from.dir_to(*to)))
+}
+
+/// The state after the box at `box_pos`, just pushed in `dir`, continues through a tunnel
+/// and then into a goal room, with the number of pushes made after the first one.
+/// `None` if neither applies.
+pub(super) fn macro_push<M>(
+    sd: &StaticData<M>,
+    state: &State,
+    box_pos: Pos,
+    dir: Dir,
+    scratch: &mut Scratch,
+) -> Option<(State, u16)>
+where
+    M: Map,
+    Solver<M>: SolverTrait<M = M>,
+{
+    let grid = sd.map.grid();
+    scratch.set_boxes(&state.boxes);
+    // the moved box is tracked separately, the grid only has the others
+    scratch.box_grid[box_pos] = BoxIndex::MAX;
+
+    let free = |scratch: &Scratch, pos: Pos| {
+        matches!(grid[pos], MapCell::Empty | MapCell::Goal)
+            && scratch.box_grid[pos] == BoxIndex::MAX
+            && sd.closest_push_dists[pos].is_some()
+    };
+
+    let mut box_to = box_pos;
+    let mut player_pos = box_pos - dir;
+    let mut pushes = 0;
+    let (side1, side2) = match dir {
+        Dir::Up | Dir::Down => (Dir::Left, Dir::Right),
+        Dir::Left | Dir::Right => (Dir::Up, Dir::Down),
+    };
+    while grid[box_to] == MapCell::Empty
+        && grid[box_to + side1] == MapCell::Wall
+        && grid[box_to + side2] == MapCell::Wall
+        && free(scratch, box_to + dir)
+    {
+        player_pos = box_to;
+        box_to = box_to + dir;
+        pushes += 1;
+    }
+
+    let entered_room = sd.rooms.ids[box_to].is_some()
+        && sd.rooms.ids[box_to] != sd.rooms.ids[box_to - dir]
+        && grid[box_to] != MapCell::Goal;
+    if entered_room {
+        if let Some((goal, goal_player_pos, goal_pushes)) =
+            furthest_goal(sd, scratch, box_to, player_pos, &free)
+        {
+            box_to = goal;
+            player_pos = goal_player_pos;
+            pushes += goal_pushes;
+        }
+    }
+
+    let result = if pushes > 0 {
+        let boxes: Vec<_> = state
+            .boxes
+            .iter()
+            .map(|&pos| if pos == box_pos { box_to } else { pos })
+            .collect();
+        scratch.box_grid[box_to] = 0;
+        let norm_player_pos = scratch.normalized_pos(&sd.map, player_pos);
+        scratch.box_grid[box_to] = BoxIndex::MAX;
+        Some((State::new(norm_player_pos, boxes), pushes))
+    } else {
+        None
+    };
+    scratch.clear_boxes(&state.boxes);
+    result
+}
+
+/// Pushes the box at `box_pos` around its room with the other boxes in place
+/// and returns the empty goal needing the most pushes, where the player ends up and the pushes
+fn furthest_goal<M: Map>(
+    sd: &StaticData<M>,
+    scratch: &mut Scratch,
+    box_pos: Pos,
+    player_pos: Pos,
+    free: &impl Fn(&Scratch, Pos) -> bool,
+) -> Option<(Pos, Pos, u16)> {
+    let room = sd.rooms.ids[box_pos];
+    let reached = push_search(sd, scratch, box_pos, player_pos, |scratch, pos| {
+        sd.rooms.ids[pos] == room && free(scratch, pos)
+    });
+    // breadth-first so later goals are never closer
+    reached
+        .iter()
+        .rev()
+        .find(|node| sd.map.grid()[node.box_pos] == MapCell::Goal && node.pushes > 0)
+        .map(|node| (node.box_pos, node.player_pos, node.pushes))
+}
+
+/// The states after each push of a solution step which moved one box by several pushes,
+/// `None` if the step was a single push.
+///
+/// The box path is searched for again instead of remembering the macro moves,
+/// it's only done for the solution so it's cheap.
+pub(super) fn single_pushes<M: Map>(
+    sd: &StaticData<M>,
+    scratch: &mut Scratch,
+    before: &State,
+    after: &State,
+) -> Option<Vec<State>> {
+    if before.boxes.len() != after.boxes.len() {
+        return None;
+    }
+    let from = *before.boxes.iter().find(|pos| !after.boxes.contains(pos))?;
+    let to = *after.boxes.iter().find(|pos| !before.boxes.contains(pos))?;
+    if from.neighbors().contains(&to) {
+        return None;
+    }
+
+    let grid = sd.map.grid();
+    scratch.set_boxes(&before.boxes);
+    scratch.box_grid[from] = BoxIndex::MAX;
+    let reached = push_search(sd, scratch, from, before.player_pos, |scratch, pos| {
+        matches!(grid[pos], MapCell::Empty | MapCell::Goal)
+            && scratch.box_grid[pos] == BoxIndex::MAX
+    });
+    scratch.clear_boxes(&before.boxes);
+
+    let mut index = reached
+        .iter()
+        .position(|node| node.box_pos == to)
+        .expect("Macro move can't be repeated");
+    let mut box_path = Vec::new();
+    while index != 0 {
+        box_path.push(reached[index].box_pos);
+        index = reached[index].parent;
+    }
+    let states = box_path
+        .iter()
+        .rev()
+        .map(|&box_pos| {
+            let boxes: Vec<_> = before
+                .boxes
+                .iter()
+                .map(|&pos| if pos == from { box_pos } else { pos })
+                .collect();
+            // the reconstructor follows the pushes, not the player positions
+            State::new(box_pos, boxes)
+        })
+        .collect();
+    Some(states)
+}
+
+/// One position of the box in `push_search`
+struct PushNode {
+    box_pos: Pos,
+    /// Where the player stands after the push
+    player_pos: Pos,
+    pushes: u16,
+    /// Index of the node it was pushed from
+    parent: usize,
+}
+
+/// Breadth-first search of the positions the box at `box_pos` can be pushed to
+/// through squares for which `allowed` holds, the other boxes are in `box_grid`
+fn push_search<M: Map>(
+    sd: &StaticData<M>,
+    scratch: &mut Scratch,
+    box_pos: Pos,
+    player_pos: Pos,
+    allowed: impl Fn(&Scratch, Pos) -> bool,
+) -> Vec<PushNode> {
+    let mut visited = FnvHashSet::default();
+    visited.insert((box_pos, player_pos));
+    let mut reached = vec![PushNode {
+        box_pos,
+        player_pos,
+        pushes: 0,
+        parent: 0,
+    }];
+    let mut next = 0;
+    while next < reached.len() {
+        let PushNode {
+            box_pos,
+            player_pos,
+            pushes,
+            ..
+        } = reached[next];
+        player_reach(sd, scratch, box_pos, player_pos);
+        for &dir in &DIRECTIONS {
+            let dest = box_pos + dir;
+            if scratch.reached.is_marked(box_pos - dir)
+                && allowed(scratch, dest)
+                && visited.insert((dest, box_pos))
+            {
+                reached.push(PushNode {
+                    box_pos: dest,
+                    player_pos: box_pos,
+                    pushes: pushes + 1,
+                    parent: next,
+                });
+            }
+        }
+        next += 1;
+    }
+    reached
+}
+
+/// Marks the squares the player can walk to with the box at `box_pos` and the others in `box_grid`
+fn player_reach<M: Map>(sd: &StaticData<M>, scratch: &mut Scratch, box_pos: Pos, player_pos: Pos) {
+    scratch.start_search();
+    scratch.reach(player_pos);
+    scratch.to_visit.clear();
+    scratch.to_visit.push(player_pos);
+    while let Some(pos) = scratch.to_visit.pop() {
+        for &new_pos in &pos.neighbors() {
+            if sd.map.grid()[new_pos] != MapCell::Wall
+                && scratch.box_grid[new_pos] == BoxIndex::MAX
+                && new_pos != box_pos
+                && scratch.reach(new_pos)
+            {
+                scratch.to_visit.push(new_pos);
+            }
+        }
+    }
+}
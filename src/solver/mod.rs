@@ -1,33 +1,72 @@
 pub(crate) mod a_star;
-mod backtracking;
-mod preprocessing;
+mod all_solutions;
+pub(crate) mod backtracking;
+mod checkpoint;
+mod events;
+mod incremental;
+mod k_best;
+mod macro_moves;
+mod near_duplicates;
+pub(crate) mod preprocessing;
+mod prevs;
+mod race;
+mod symmetry;
 
 #[cfg(feature = "graph")]
 mod graph;
+#[cfg(feature = "open-list-stats")]
+mod open_list;
 
 use std::cmp::Reverse;
-use std::collections::{BinaryHeap, VecDeque};
+use std::collections::{BTreeMap, BinaryHeap, VecDeque};
+use std::convert::TryFrom;
 use std::error::Error;
 use std::fmt;
 use std::fmt::{Display, Formatter};
+use std::io::{self, Write};
+use std::mem;
+use std::thread;
+use std::time::{Duration, Instant};
 
-use fnv::FnvHashMap; // using rustc-hash gives the same results, maybe bench again when able to solve levels with many boxes
+#[cfg(not(feature = "tracing"))]
 use log::debug;
+use separator::Separatable;
+#[cfg(feature = "tracing")]
+use tracing::debug;
 use typed_arena::Arena;
 
-use crate::config::Method;
-use crate::data::{MapCell, Pos, DIRECTIONS, MAX_BOXES};
+use crate::config::{
+    AnyProfile, Criterion, Heuristic, Method, Metric, SolveOutput, SolverOptions, Variant,
+};
+use crate::data::{BoxIndex, Dir, MapCell, Pos, DIRECTIONS, MAX_BOXES};
 use crate::level::Level;
-use crate::map::{GoalMap, Map, MapType, RemoverMap};
-use crate::moves::Moves;
-use crate::state::State;
+use crate::map::{GoalMap, HybridMap, Map, MapType, RemoverMap};
+use crate::moves::{Moves, Push};
+use crate::state::{Boxes, State, INLINE_BOXES};
 use crate::vec2d::Vec2d;
 use crate::Solve;
 
-use self::a_star::{ComplexCost, Cost, CostComparator, SearchNode, SimpleCost, Stats};
+use self::a_star::{ComplexCost, Cost, CostComparator, SearchNode, SimpleCost, TieBreaker};
+use self::backtracking::MoveReconstructor;
+use self::macro_moves::Rooms;
+use self::near_duplicates::NearDuplicates;
+use self::preprocessing::{PushDistsMemo, SideDists};
+use self::prevs::Prevs;
+use self::symmetry::Symmetry;
+
+pub use self::a_star::{DepthStats, HeuristicStats, MemoryStats, PruningStats, Stats};
+pub use self::all_solutions::OptimalSolutions;
+pub use self::checkpoint::Checkpoint;
+pub use self::events::{Decision, EventReader, EventSink, EventWriter, SearchEvent};
+pub use self::incremental::IncrementalSolver;
+pub use self::race::RaceWinner;
 
 #[cfg(feature = "graph")]
 use self::graph::Graph;
+#[cfg(feature = "graph")]
+pub use self::graph::{NodeKind, SearchTrace, TraceNode};
+#[cfg(feature = "open-list-stats")]
+pub use self::open_list::{OpenListDiff, OpenListSnapshot};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SolverErr {
@@ -37,6 +76,9 @@ pub enum SolverErr {
     UnreachableRemover,
     TooMany,
     DiffBoxesGoals,
+    CheckpointMismatch,
+    /// `Level::solve_lexicographic` needs at least one criterion and each at most once
+    InvalidCriteria,
 }
 
 impl Display for SolverErr {
@@ -51,9 +93,15 @@ impl Display for SolverErr {
                 f,
                 "Unreachable goals - some goals don't have a box but can't be reached"
             ),
-            SolverErr::UnreachableRemover => write!(f, "Remover is not reachable"),
+            SolverErr::UnreachableRemover => write!(f, "No remover is reachable"),
             SolverErr::TooMany => write!(f, "More than {MAX_BOXES} reachable boxes or goals"),
             SolverErr::DiffBoxesGoals => write!(f, "Different number of reachable boxes and goals"),
+            SolverErr::CheckpointMismatch => {
+                write!(f, "Checkpoint was saved for a different level or variant")
+            }
+            SolverErr::InvalidCriteria => {
+                write!(f, "Criteria must not be empty or contain duplicates")
+            }
         }
     }
 }
@@ -63,47 +111,648 @@ impl Error for SolverErr {}
 #[derive(Debug)]
 pub struct SolverOk {
     pub moves: Option<Moves>,
+    /// The solution's pushes with `SolveOutput::Pushes`, `moves` is then always `None`
+    pub pushes: Option<Vec<Push>>,
     pub stats: Stats,
+    /// The solution is guaranteed to be at most this many percent longer than an optimal one
+    /// (in the metric minimized by the method). 0 means optimal.
+    pub suboptimality_percent: u16,
+    /// The search was stopped by the progress callback before finding a solution
+    /// so `moves` being `None` doesn't mean the level is unsolvable.
+    pub interrupted: bool,
+    /// Why there's no solution, `None` if a solution was found or the search was interrupted
+    pub unsolvable: Option<UnsolvableReason>,
+    /// The state of an interrupted search if `SolverOptions::checkpoint` was set
+    pub checkpoint: Option<Checkpoint>,
+    /// The search stopped because of `SolverOptions::max_depth` or `SolverOptions::max_nodes`
+    /// without finding a solution
+    pub limit_reached: Option<LimitReached>,
+    /// The level was already solved (e.g. it has no boxes or all of them are on goals)
+    /// so the solution is empty and no search was done
+    pub solved_at_start: bool,
+    /// What the solver did and how long each part took
+    pub metadata: SolveMetadata,
+    /// All nodes created by the search, `None` if the level didn't need searching
+    #[cfg(feature = "graph")]
+    pub trace: Option<SearchTrace>,
+}
+
+/// How a `SolverOk` was found, e.g. to attribute the time spent to the parts of the solver
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SolveMetadata {
+    /// `None` for `Level::solve_lexicographic`
+    pub method: Option<Method>,
+    pub heuristic: Heuristic,
+    /// Which limit stopped the search, `None` if it found a solution or searched everything
+    pub limit_hit: Option<Limit>,
+    /// The whole solve call, with `Level::solve_all_methods` it includes all the searches
+    pub wall_time: Duration,
+    /// Validating the level and computing dead squares and distances
+    /// (shared by all searches of `Level::solve_all_methods`)
+    pub preprocessing_time: Duration,
+    /// Searching without backtracking, unlike `Stats::elapsed` it doesn't include
+    /// the time before a checkpoint was saved
+    pub search_time: Duration,
+    /// Turning the solution's states into moves or pushes
+    pub backtracking_time: Duration,
+}
+
+/// What stopped a search early
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Limit {
+    /// `SolverOptions::max_depth`
+    MaxDepth,
+    /// `SolverOptions::max_nodes`
+    MaxNodes,
+    /// The progress callback returned `false`
+    Interrupted,
+}
+
+/// How far a search stopped by a limit got
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LimitReached {
+    /// Any solution is at least this long (in the metric minimized first by the method)
+    pub lower_bound: u16,
+    /// The largest distance from the start of a visited state
+    pub deepest: u16,
+}
+
+impl Display for LimitReached {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "lower bound: {}, deepest visited: {}",
+            self.lower_bound, self.deepest
+        )
+    }
+}
+
+/// How the solver proved a level has no solution
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UnsolvableReason {
+    /// The box at `(row, column)` starts on a square from which it can't be pushed
+    /// to any goal (or the remover)
+    DeadSquare { box_pos: (usize, usize) },
+    /// Even ignoring each other, these boxes can only be pushed to fewer goals than there are boxes
+    Unmatchable {
+        boxes: Vec<(usize, usize)>,
+        goals: Vec<(usize, usize)>,
+    },
+    /// All states reachable without pushing boxes onto dead squares were searched
+    Exhausted,
+}
+
+impl Display for UnsolvableReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match *self {
+            UnsolvableReason::DeadSquare { box_pos: (r, c) } => {
+                write!(f, "Box at [{r}, {c}] is on a dead square")
+            }
+            UnsolvableReason::Unmatchable {
+                ref boxes,
+                ref goals,
+            } => {
+                fn positions(positions: &[(usize, usize)]) -> String {
+                    let positions: Vec<_> = positions
+                        .iter()
+                        .map(|(r, c)| format!("[{r}, {c}]"))
+                        .collect();
+                    positions.join(", ")
+                }
+                write!(
+                    f,
+                    "Boxes at {} can only reach goals at {}",
+                    positions(boxes),
+                    positions(goals)
+                )
+            }
+            UnsolvableReason::Exhausted => write!(f, "All reachable states were searched"),
+        }
+    }
 }
 
 impl SolverOk {
-    fn new(moves: Option<Moves>, stats: Stats) -> Self {
-        Self { moves, stats }
+    fn new(moves: Option<Moves>, stats: Stats, suboptimality_percent: u16) -> Self {
+        Self {
+            moves,
+            pushes: None,
+            stats,
+            suboptimality_percent,
+            interrupted: false,
+            unsolvable: None,
+            checkpoint: None,
+            limit_reached: None,
+            solved_at_start: false,
+            metadata: SolveMetadata::default(),
+            #[cfg(feature = "graph")]
+            trace: None,
+        }
+    }
+
+    fn solved_at_start(output: SolveOutput, stats: Stats) -> Self {
+        let mut solver_ok = match output {
+            SolveOutput::Moves => Self::new(Some(Moves::default()), stats, 0),
+            SolveOutput::Pushes => {
+                let mut solver_ok = Self::new(None, stats, 0);
+                solver_ok.pushes = Some(Vec::new());
+                solver_ok
+            }
+        };
+        solver_ok.solved_at_start = true;
+        solver_ok
+    }
+
+    fn unsolvable(stats: Stats, reason: UnsolvableReason) -> Self {
+        let mut solver_ok = Self::new(None, stats, 0);
+        solver_ok.unsolvable = Some(reason);
+        solver_ok
+    }
+
+    /// Whether `moves` being `None` can be trusted to mean there's no solution
+    pub fn outcome(&self) -> SearchOutcome {
+        if self.moves.is_some() || self.pushes.is_some() {
+            SearchOutcome::Solved
+        } else if self.unsolvable.is_some() {
+            SearchOutcome::ProvenUnsolvable
+        } else {
+            SearchOutcome::LimitReached
+        }
     }
 }
 
+/// How a search ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SearchOutcome {
+    /// `SolverOk::moves` (or `SolverOk::pushes`) contains a solution
+    Solved,
+    /// There is no solution, `SolverOk::unsolvable` says how it was proven
+    ProvenUnsolvable,
+    /// The search stopped early (`SolverOptions::max_depth`, `SolverOptions::max_nodes`
+    /// or the progress callback) so there might be a solution
+    LimitReached,
+}
+
+/// How often (in unique visited states) the progress callback is called
+/// in addition to every time a new depth is reached
+pub const PROGRESS_INTERVAL: i32 = 10_000;
+
+/// Searches running at least this long print a progress estimate at every new depth
+const ETA_AFTER: Duration = Duration::from_secs(1);
+
+/// How often (in unique visited states) the clock is checked for `SolverOptions::status_interval`
+const STATUS_CHECK_INTERVAL: i32 = 1024;
+
 impl Solve for Level {
-    fn solve(&self, method: Method, print_status: bool) -> Result<SolverOk, SolverErr> {
+    fn solve_with_progress(
+        &self,
+        method: Method,
+        options: &SolverOptions,
+        progress: &mut dyn FnMut(&Stats) -> bool,
+    ) -> Result<SolverOk, SolverErr> {
+        self.search(
+            method,
+            *options,
+            None,
+            progress,
+            None,
+            &mut io::stdout(),
+            &mut PushDistsMemo::default(),
+        )
+    }
+}
+
+impl Level {
+    /// Continues a search interrupted with `SolverOptions::checkpoint` set.
+    ///
+    /// The method and suboptimality are taken from the checkpoint, other options from `options`.
+    /// Returns `SolverErr::CheckpointMismatch` if the checkpoint is for a different level or variant.
+    pub fn resume_with_progress(
+        &self,
+        checkpoint: &Checkpoint,
+        options: &SolverOptions,
+        progress: &mut dyn FnMut(&Stats) -> bool,
+    ) -> Result<SolverOk, SolverErr> {
+        if checkpoint.level != self.xsb().to_string() || checkpoint.variant != self.variant {
+            return Err(SolverErr::CheckpointMismatch);
+        }
+        let options = SolverOptions {
+            suboptimality_percent: checkpoint.suboptimality_percent,
+            // already included in the saved suboptimality
+            any_profile: AnyProfile::Thorough,
+            ..*options
+        };
+        self.search(
+            checkpoint.method,
+            options,
+            Some(checkpoint),
+            progress,
+            None,
+            &mut io::stdout(),
+            &mut PushDistsMemo::default(),
+        )
+    }
+
+    /// Like `solve_with_progress` but reports every node the search creates or visits to `events`
+    pub fn solve_with_events(
+        &self,
+        method: Method,
+        options: &SolverOptions,
+        progress: &mut dyn FnMut(&Stats) -> bool,
+        events: &mut dyn EventSink,
+    ) -> Result<SolverOk, SolverErr> {
+        self.search(
+            method,
+            *options,
+            None,
+            progress,
+            Some(events),
+            &mut io::stdout(),
+            &mut PushDistsMemo::default(),
+        )
+    }
+
+    /// Like `solve_with_progress` but the status output enabled by `SolverOptions::print_status`
+    /// and `SolverOptions::status_interval` is written to `output` instead of stdout
+    pub fn solve_with_output(
+        &self,
+        method: Method,
+        options: &SolverOptions,
+        progress: &mut dyn FnMut(&Stats) -> bool,
+        output: &mut dyn Write,
+    ) -> Result<SolverOk, SolverErr> {
+        self.search(
+            method,
+            *options,
+            None,
+            progress,
+            None,
+            output,
+            &mut PushDistsMemo::default(),
+        )
+    }
+
+    /// Minimizes the criteria in order - the solution is optimal in the first one
+    /// and each of the others only breaks ties between solutions equal in all before it.
+    ///
+    /// This covers combinations without a `Method` (e.g. pushes, then moves, then box changes)
+    /// but the methods are faster for the combinations they have. `SolverOptions::checkpoint`
+    /// and `SolverOptions::reorder_pushes` are ignored.
+    /// Returns `SolverErr::InvalidCriteria` if `criteria` is empty or has duplicates.
+    pub fn solve_lexicographic(
+        &self,
+        criteria: &[Criterion],
+        options: &SolverOptions,
+        progress: &mut dyn FnMut(&Stats) -> bool,
+    ) -> Result<SolverOk, SolverErr> {
+        if criteria.is_empty() || (1..criteria.len()).any(|i| criteria[..i].contains(&criteria[i]))
+        {
+            return Err(SolverErr::InvalidCriteria);
+        }
+        span!("solve_lexicographic", ?criteria, variant = %self.variant);
         debug!("Processing level...");
 
+        let options = SolverOptions {
+            checkpoint: false,
+            ..*options
+        };
+        let output = &mut io::stdout();
+        let started = Instant::now();
+        let mut solver_ok = match self.map {
+            MapType::Goals(ref goals_map) => {
+                let solver = Solver::new_with_goals(goals_map, &self.state, self.variant)?
+                    .with_heuristic(options.heuristic)
+                    .with_criteria(criteria);
+                solver.search(options, LexicographicLogic, None, progress, None, output)
+            }
+            MapType::Remover(ref remover_map) => {
+                let solver = Solver::new_with_remover(remover_map, &self.state, self.variant)?
+                    .with_heuristic(options.heuristic)
+                    .with_criteria(criteria);
+                solver.search(options, LexicographicLogic, None, progress, None, output)
+            }
+            MapType::Hybrid(ref hybrid_map) => {
+                let solver = Solver::new_with_hybrid(hybrid_map, &self.state, self.variant)?
+                    .with_heuristic(options.heuristic)
+                    .with_criteria(criteria);
+                solver.search(options, LexicographicLogic, None, progress, None, output)
+            }
+        };
+        solver_ok.metadata.wall_time = started.elapsed();
+        Ok(solver_ok)
+    }
+
+    // private and most callers pass the defaults, a struct for the arguments wouldn't help much
+    #[allow(clippy::too_many_arguments)]
+    fn search(
+        &self,
+        method: Method,
+        options: SolverOptions,
+        resume: Option<&Checkpoint>,
+        progress: &mut dyn FnMut(&Stats) -> bool,
+        events: Option<&mut dyn EventSink>,
+        output: &mut dyn Write,
+        memo: &mut PushDistsMemo,
+    ) -> Result<SolverOk, SolverErr> {
+        span!("solve", %method, variant = %self.variant);
+        debug!("Processing level...");
+        let started = Instant::now();
+
         // I am not quite sure how to merge these branches.
         // It should be possible with trait objects but they have additional restrictions
         // (https://doc.rust-lang.org/error-index.html#E0038) plus even then I might run
         // into [this](https://github.com/rust-lang/rust/issues/23856) bug.
         // It might be easier to keep the 2 branches.
 
-        match self.map {
+        let mut solver_ok = match self.map {
             MapType::Goals(ref goals_map) => {
-                let solver = Solver::new_with_goals(goals_map, &self.state)?;
+                let solver =
+                    Solver::new_with_goals_memoized(goals_map, &self.state, self.variant, memo)?
+                        .with_heuristic(options.heuristic);
+                search_with_method(&solver, method, options, resume, progress, events, output)
+            }
+            MapType::Remover(ref remover_map) => {
+                let solver = Solver::new_with_remover_memoized(
+                    remover_map,
+                    &self.state,
+                    self.variant,
+                    memo,
+                )?
+                .with_heuristic(options.heuristic);
+                search_with_method(&solver, method, options, resume, progress, events, output)
+            }
+            MapType::Hybrid(ref hybrid_map) => {
+                let solver =
+                    Solver::new_with_hybrid_memoized(hybrid_map, &self.state, self.variant, memo)?
+                        .with_heuristic(options.heuristic);
+                search_with_method(&solver, method, options, resume, progress, events, output)
+            }
+        };
 
-                match method {
-                    Method::MovesPushes => Ok(solver.search(print_status, MovePushLogic)),
-                    Method::Moves => Ok(solver.search(print_status, MoveLogic)),
-                    Method::PushesMoves => Ok(solver.search(print_status, PushMoveLogic)),
-                    Method::Pushes | Method::Any => Ok(solver.search(print_status, PushLogic)),
-                }
+        if options.reorder_pushes && method == Method::Pushes {
+            solver_ok.moves = solver_ok.moves.map(|moves| {
+                self.reorder_pushes(&moves)
+                    .expect("Solver returned an invalid solution")
+            });
+        }
+        if let Some(ref mut checkpoint) = solver_ok.checkpoint {
+            checkpoint.method = method;
+            checkpoint.level = self.xsb().to_string();
+            checkpoint.variant = self.variant;
+        }
+        solver_ok.metadata.wall_time = started.elapsed();
+        Ok(solver_ok)
+    }
+}
+
+impl Level {
+    /// Solves the level using all of `Method::OPTIMAL`.
+    ///
+    /// Preprocessing is only done once and shared by all the searches
+    /// so this is faster than calling `solve` for each method.
+    pub fn solve_all_methods(
+        &self,
+        options: &SolverOptions,
+    ) -> Result<BTreeMap<Method, SolverOk>, SolverErr> {
+        span!("solve_all_methods", variant = %self.variant);
+        debug!("Processing level...");
+        let started = Instant::now();
+
+        let mut results = match self.map {
+            MapType::Goals(ref goals_map) => {
+                let solver = Solver::new_with_goals(goals_map, &self.state, self.variant)?
+                    .with_heuristic(options.heuristic);
+                search_all_methods(&solver, *options)
             }
             MapType::Remover(ref remover_map) => {
-                let solver = Solver::new_with_remover(remover_map, &self.state)?;
+                let solver = Solver::new_with_remover(remover_map, &self.state, self.variant)?
+                    .with_heuristic(options.heuristic);
+                search_all_methods(&solver, *options)
+            }
+            MapType::Hybrid(ref hybrid_map) => {
+                let solver = Solver::new_with_hybrid(hybrid_map, &self.state, self.variant)?
+                    .with_heuristic(options.heuristic);
+                search_all_methods(&solver, *options)
+            }
+        };
+        let wall_time = started.elapsed();
+        for solver_ok in results.values_mut() {
+            solver_ok.metadata.wall_time = wall_time;
+        }
+        Ok(results)
+    }
 
-                match method {
-                    Method::MovesPushes => Ok(solver.search(print_status, MovePushLogic)),
-                    Method::Moves => Ok(solver.search(print_status, MoveLogic)),
-                    Method::PushesMoves => Ok(solver.search(print_status, PushMoveLogic)),
-                    Method::Pushes | Method::Any => Ok(solver.search(print_status, PushLogic)),
-                }
+    /// Squares from which a box can never be pushed to a goal (or the remover).
+    ///
+    /// Corners and squares along walls between corners are always included
+    /// (except in `Variant::Pull` where boxes can be pulled out of them).
+    /// Squares found by computing push distances are only included
+    /// if the level is valid for the solver (e.g. surrounded by walls).
+    pub fn dead_squares(&self) -> Vec2d<bool> {
+        dead_squares(self).unwrap_or_else(|_| self.simple_dead_squares())
+    }
+
+    fn simple_dead_squares(&self) -> Vec2d<bool> {
+        match self.variant {
+            Variant::Push | Variant::Multiban => {
+                let players: Vec<_> = self.state.players().collect();
+                preprocessing::simple_dead_squares(self.map().grid(), &players)
+            }
+            Variant::Pull => self.map().grid().scratchpad(),
+        }
+    }
+}
+
+impl Level {
+    /// A lower bound on the length of any solution in the given metric.
+    ///
+    /// This is the value of the solver's heuristic for the initial state
+    /// (plus the steps needed to reach the first box when counting moves)
+    /// so it's cheap to compute and useful to estimate how hard a level is.
+    /// Returns `None` if the level is not valid for the solver.
+    pub fn lower_bound(&self, metric: Metric) -> Option<u32> {
+        match self.map {
+            MapType::Goals(ref goals_map) => {
+                let solver = Solver::new_with_goals(goals_map, &self.state, self.variant).ok()?;
+                Some(lower_bound(solver.sd(), metric))
+            }
+            MapType::Remover(ref remover_map) => {
+                let solver =
+                    Solver::new_with_remover(remover_map, &self.state, self.variant).ok()?;
+                Some(lower_bound(solver.sd(), metric))
+            }
+            MapType::Hybrid(ref hybrid_map) => {
+                let solver = Solver::new_with_hybrid(hybrid_map, &self.state, self.variant).ok()?;
+                Some(lower_bound(solver.sd(), metric))
+            }
+        }
+    }
+}
+
+fn lower_bound<M: Map>(sd: &StaticData<M>, metric: Metric) -> u32 {
+    let pushes = u32::from(heuristic(sd, &sd.initial_state));
+    match metric {
+        Metric::Pushes => pushes,
+        Metric::Moves if pushes == 0 => 0,
+        Metric::Moves => pushes + steps_to_closest_box(&sd.map, &sd.initial_state),
+    }
+}
+
+/// Before the first push, a player has to walk next to a box without going through any
+fn steps_to_closest_box<M: Map>(map: &M, state: &State) -> u32 {
+    let mut box_grid = map.grid().scratchpad();
+    for &b in &state.boxes {
+        box_grid[b] = true;
+    }
+
+    let mut visited = map.grid().scratchpad();
+    let mut to_visit = VecDeque::new();
+    for player_pos in state.players() {
+        visited[player_pos] = true;
+        to_visit.push_back((player_pos, 0));
+    }
+
+    while let Some((player_pos, steps)) = to_visit.pop_front() {
+        for &dir in &DIRECTIONS {
+            let new_pos = player_pos + dir;
+            if box_grid[new_pos] {
+                return steps;
+            }
+            if map.grid()[new_pos] != MapCell::Wall && !visited[new_pos] {
+                visited[new_pos] = true;
+                to_visit.push_back((new_pos, steps + 1));
+            }
+        }
+    }
+
+    // all boxes are unreachable without pushing - can't happen in a valid level
+    0
+}
+
+/// Squares from which a box can't reach any goal (or the remover)
+pub(crate) fn dead_squares(level: &Level) -> Result<Vec2d<bool>, SolverErr> {
+    fn dead_squares<M: Map>(sd: &StaticData<M>, simple: Vec2d<bool>) -> Vec2d<bool> {
+        let grid = sd.map.grid();
+        let mut dead = simple;
+        for pos in grid.positions() {
+            // push dists should find all simple dead squares but let's not rely on it
+            dead[pos] |= grid[pos] != MapCell::Wall && sd.closest_push_dists[pos].is_none();
+        }
+        dead
+    }
+
+    let simple = level.simple_dead_squares();
+    match level.map {
+        MapType::Goals(ref goals_map) => {
+            let solver = Solver::new_with_goals(goals_map, &level.state, level.variant)?;
+            Ok(dead_squares(solver.sd(), simple))
+        }
+        MapType::Remover(ref remover_map) => {
+            let solver = Solver::new_with_remover(remover_map, &level.state, level.variant)?;
+            Ok(dead_squares(solver.sd(), simple))
+        }
+        MapType::Hybrid(ref hybrid_map) => {
+            let solver = Solver::new_with_hybrid(hybrid_map, &level.state, level.variant)?;
+            Ok(dead_squares(solver.sd(), simple))
+        }
+    }
+}
+
+fn search_with_method<M>(
+    solver: &Solver<M>,
+    method: Method,
+    options: SolverOptions,
+    resume: Option<&Checkpoint>,
+    progress: &mut dyn FnMut(&Stats) -> bool,
+    events: Option<&mut dyn EventSink>,
+    output: &mut dyn Write,
+) -> SolverOk
+where
+    M: Map,
+    Solver<M>: SolverTrait<M = M>,
+{
+    span!("search", %method);
+    let mut solver_ok = match method {
+        Method::MovesPushes => {
+            solver.search(options, MovePushLogic, resume, progress, events, output)
+        }
+        Method::Moves => solver.search(options, MoveLogic, resume, progress, events, output),
+        Method::PushesMoves => {
+            solver.search(options, PushMoveLogic, resume, progress, events, output)
+        }
+        Method::PushesBoxLines => {
+            solver.search(options, PushBoxLineLogic, resume, progress, events, output)
+        }
+        Method::Pushes => solver.search(options, PushLogic, resume, progress, events, output),
+        Method::Any => {
+            let options = SolverOptions {
+                suboptimality_percent: options
+                    .suboptimality_percent
+                    .max(options.any_profile.suboptimality_percent()),
+                ..options
+            };
+            if options.any_profile == AnyProfile::Macro {
+                solver.search(options, MacroLogic, resume, progress, events, output)
+            } else {
+                solver.search(options, PushLogic, resume, progress, events, output)
             }
         }
+    };
+    solver_ok.metadata.method = Some(method);
+    #[cfg(feature = "tracing")]
+    tracing::debug!(
+        outcome = ?solver_ok.outcome(),
+        moves = solver_ok.moves.as_ref().map(Moves::move_cnt),
+        pushes = solver_ok.moves.as_ref().map(Moves::push_cnt),
+        created = solver_ok.stats.total_created(),
+        visited = solver_ok.stats.total_unique_visited(),
+        duplicates = solver_ok.stats.total_reached_duplicates(),
+        memory = solver_ok.stats.memory().total(),
+        elapsed_ms = solver_ok.stats.elapsed().as_millis() as u64,
+        "Search finished"
+    );
+    solver_ok
+}
+
+fn search_all_methods<M>(solver: &Solver<M>, options: SolverOptions) -> BTreeMap<Method, SolverOk>
+where
+    M: Map + Sync,
+    Solver<M>: SolverTrait<M = M>,
+{
+    if options.parallel {
+        thread::scope(|scope| {
+            let handles: Vec<_> = Method::OPTIMAL
+                .iter()
+                .map(|&method| {
+                    let handle = scope.spawn(move || {
+                        let output = &mut io::stdout();
+                        search_with_method(
+                            solver,
+                            method,
+                            options,
+                            None,
+                            &mut |_| true,
+                            None,
+                            output,
+                        )
+                    });
+                    (method, handle)
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|(method, handle)| (method, handle.join().expect("Search thread panicked")))
+                .collect()
+        })
+    } else {
+        Method::OPTIMAL
+            .iter()
+            .map(|&method| {
+                let output = &mut io::stdout();
+                let solver_ok =
+                    search_with_method(solver, method, options, None, &mut |_| true, None, output);
+                (method, solver_ok)
+            })
+            .collect()
     }
 }
 
@@ -118,14 +767,77 @@ struct Solver<M: Map> {
 struct StaticData<M: Map> {
     map: M,
     initial_state: State,
+    variant: Variant,
+    /// Distances in pulls for `Variant::Pull`
     closest_push_dists: Vec2d<Option<u16>>,
+    /// Distances which depend on the player's side of the box where `closest_push_dists`
+    /// would make the heuristic inconsistent
+    side_dists: Vec2d<Option<SideDists>>,
+    /// Preprocessing already found the initial state can't be solved
+    unsolvable: Option<UnsolvableReason>,
+    heuristic: Heuristic,
+    /// Distances to each goal or remover for `Heuristic::GoalPullDists`, empty with other heuristics
+    goal_pull_dists: Vec<Vec2d<Option<u16>>>,
+    /// What `LexicographicLogic` minimizes, empty with other logics
+    criteria: Vec<Criterion>,
+    /// How long creating the solver took, reported in `SolveMetadata`
+    preprocessing_time: Duration,
+    /// Rotations and reflections which keep the map the same, the search only visits
+    /// one state of those which are images of each other
+    symmetries: Vec<Symmetry>,
+    /// Where boxes are pushed by the macro moves of `AnyProfile::Macro`
+    rooms: Rooms,
+}
+
+impl<M: Map> StaticData<M> {
+    /// Levels without boxes or with all boxes on goals need no search,
+    /// all searches check this first so the heuristics and backtracking never see them
+    fn solved_at_start(&self) -> bool {
+        self.map.is_solved(&self.initial_state.boxes)
+    }
+}
+
+impl<M: Map> Solver<M> {
+    fn with_heuristic(mut self, heuristic: Heuristic) -> Self {
+        let started = Instant::now();
+        self.sd.heuristic = heuristic;
+        self.sd.goal_pull_dists = match heuristic {
+            Heuristic::GoalPullDists => {
+                preprocessing::goal_pull_dists(self.sd.map.grid(), self.sd.variant)
+            }
+            Heuristic::PushDists | Heuristic::MisplacedBoxes => Vec::new(),
+        };
+        self.sd.preprocessing_time += started.elapsed();
+        self
+    }
+
+    fn with_criteria(mut self, criteria: &[Criterion]) -> Self {
+        self.sd.criteria = criteria.to_vec();
+        self
+    }
 }
 
 impl Solver<GoalMap> {
-    fn new_with_goals(map: &GoalMap, state: &State) -> Result<Solver<GoalMap>, SolverErr> {
+    fn new_with_goals(
+        map: &GoalMap,
+        state: &State,
+        variant: Variant,
+    ) -> Result<Solver<GoalMap>, SolverErr> {
+        Self::new_with_goals_memoized(map, state, variant, &mut PushDistsMemo::default())
+    }
+
+    fn new_with_goals_memoized(
+        map: &GoalMap,
+        state: &State,
+        variant: Variant,
+        memo: &mut PushDistsMemo,
+    ) -> Result<Solver<GoalMap>, SolverErr> {
+        span!("preprocessing");
+        let started = Instant::now();
+
         // Guarantees we have here:
         // - the player exists and therefore map is at least 1x1.
-        // - rows and cols is <= 255
+        // - rows and cols is <= MAX_SIZE
         // Do some more low level checking so we can omit some checks later.
 
         let processed_grid = preprocessing::check_reachability(map, state)?;
@@ -144,44 +856,113 @@ impl Solver<GoalMap> {
         for &pos in &map.goals {
             if processed_grid[pos] != MapCell::Wall {
                 reachable_goals.push(pos);
-            } else if !state.boxes.contains(&pos) {
+            } else if !state.boxes.contains(&pos) && !map.extra_goals {
                 return Err(SolverErr::UnreachableGoals);
             }
         }
 
-        if reachable_boxes.len() != reachable_goals.len() {
+        if reachable_boxes.len() > reachable_goals.len()
+            || (reachable_boxes.len() < reachable_goals.len() && !map.extra_goals)
+        {
             return Err(SolverErr::DiffBoxesGoals);
         }
 
-        // only 255 boxes max because 255 (index of the 256th box) is used to represent empty in expand_{move,push}
         if reachable_boxes.len() > MAX_BOXES {
             return Err(SolverErr::TooMany);
         }
 
-        let processed_map = GoalMap::new(processed_grid, reachable_goals);
-        let clean_state = State::new(state.player_pos, reachable_boxes);
-        let push_dists = preprocessing::push_dists(&processed_map);
-        let closest_push_dists = preprocessing::closest_push_dists(&processed_map, &push_dists);
+        let mut processed_map = GoalMap::new(processed_grid, reachable_goals);
+        processed_map.extra_goals = map.extra_goals;
+        let mut clean_state = state.clone();
+        clean_state.boxes = reachable_boxes.into();
+        let box_grid = preprocessing::box_grid(processed_map.grid(), &clean_state, variant);
+        let push_dists = memo.push_dists(&box_grid, variant);
+        let closest_push_dists = preprocessing::closest_push_dists(&processed_map, push_dists);
+        let side_dists = preprocessing::side_dists(&processed_map, push_dists);
+        let symmetries = symmetry::symmetries(processed_map.grid(), state);
+        let rooms = Rooms::new(processed_map.grid());
+
+        // per-box checks only find boxes which can't reach any goal,
+        // this also finds boxes which can only reach goals needed by other boxes
+        let reachable: Vec<Vec<usize>> = clean_state
+            .boxes
+            .iter()
+            .map(|&box_pos| {
+                (0..processed_map.goals.len())
+                    .filter(|&goal_index| {
+                        let goal_pos = processed_map.goals[goal_index];
+                        box_pos == goal_pos
+                            || push_dists[box_pos]
+                                .iter()
+                                .any(|dists| dists[goal_pos].is_some())
+                    })
+                    .collect()
+            })
+            .collect();
+        let unsolvable = preprocessing::unmatchable_boxes(&reachable, processed_map.goals.len())
+            .map(|(boxes, goals)| UnsolvableReason::Unmatchable {
+                boxes: boxes
+                    .into_iter()
+                    .map(|i| clean_state.boxes[i].into())
+                    .collect(),
+                goals: goals
+                    .into_iter()
+                    .map(|i| processed_map.goals[i].into())
+                    .collect(),
+            });
+
         Ok(Solver {
             sd: StaticData {
                 map: processed_map,
                 initial_state: clean_state,
+                variant,
                 closest_push_dists,
+                side_dists,
+                unsolvable,
+                heuristic: Heuristic::default(),
+                goal_pull_dists: Vec::new(),
+                criteria: Vec::new(),
+                preprocessing_time: started.elapsed(),
+                symmetries,
+                rooms,
             },
         })
     }
 }
 
 impl Solver<RemoverMap> {
-    fn new_with_remover(map: &RemoverMap, state: &State) -> Result<Solver<RemoverMap>, SolverErr> {
+    fn new_with_remover(
+        map: &RemoverMap,
+        state: &State,
+        variant: Variant,
+    ) -> Result<Solver<RemoverMap>, SolverErr> {
+        Self::new_with_remover_memoized(map, state, variant, &mut PushDistsMemo::default())
+    }
+
+    fn new_with_remover_memoized(
+        map: &RemoverMap,
+        state: &State,
+        variant: Variant,
+        memo: &mut PushDistsMemo,
+    ) -> Result<Solver<RemoverMap>, SolverErr> {
+        span!("preprocessing");
+        let started = Instant::now();
+
         // Guarantees we have here:
         // - the player exists and therefore map is at least 1x1.
-        // - rows and cols is <= 255
+        // - rows and cols is <= MAX_SIZE
         // Do some more low level checking so we can omit some checks later.
 
         let processed_grid = preprocessing::check_reachability(map, state)?;
 
-        if processed_grid[map.remover] == MapCell::Wall {
+        // unreachable removers are harmless, they're just never used
+        let reachable_removers: Vec<_> = map
+            .removers
+            .iter()
+            .copied()
+            .filter(|&pos| processed_grid[pos] != MapCell::Wall)
+            .collect();
+        if reachable_removers.is_empty() {
             return Err(SolverErr::UnreachableRemover);
         }
 
@@ -195,19 +976,119 @@ impl Solver<RemoverMap> {
         // This should not upset the heuristics (since they already have to handle that case on remover maps)
         // or backtracking (since there are no moves).
 
-        // only 255 boxes max because 255 (index of the 256th box) is used to represent empty in expand_{move,push}
         if state.boxes.len() > MAX_BOXES {
             return Err(SolverErr::TooMany);
         }
 
-        let processed_map = RemoverMap::new(processed_grid, map.remover);
-        let push_dists = preprocessing::push_dists(&processed_map);
-        let closest_push_dists = preprocessing::closest_push_dists(&processed_map, &push_dists);
+        let processed_map = RemoverMap::new(processed_grid, reachable_removers);
+        let box_grid = preprocessing::box_grid(processed_map.grid(), state, variant);
+        let push_dists = memo.push_dists(&box_grid, variant);
+        let closest_push_dists = preprocessing::closest_push_dists(&processed_map, push_dists);
+        let side_dists = preprocessing::side_dists(&processed_map, push_dists);
+        let symmetries = symmetry::symmetries(processed_map.grid(), state);
+        let rooms = Rooms::new(processed_map.grid());
         Ok(Solver {
             sd: StaticData {
                 map: processed_map,
                 initial_state: state.clone(),
+                variant,
+                closest_push_dists,
+                side_dists,
+                // any box can go to any remover so reaching one is enough
+                unsolvable: None,
+                heuristic: Heuristic::default(),
+                goal_pull_dists: Vec::new(),
+                criteria: Vec::new(),
+                preprocessing_time: started.elapsed(),
+                symmetries,
+                rooms,
+            },
+        })
+    }
+}
+
+impl Solver<HybridMap> {
+    fn new_with_hybrid(
+        map: &HybridMap,
+        state: &State,
+        variant: Variant,
+    ) -> Result<Solver<HybridMap>, SolverErr> {
+        Self::new_with_hybrid_memoized(map, state, variant, &mut PushDistsMemo::default())
+    }
+
+    fn new_with_hybrid_memoized(
+        map: &HybridMap,
+        state: &State,
+        variant: Variant,
+        memo: &mut PushDistsMemo,
+    ) -> Result<Solver<HybridMap>, SolverErr> {
+        span!("preprocessing");
+        let started = Instant::now();
+
+        let processed_grid = preprocessing::check_reachability(map, state)?;
+
+        // boxes on unreachable goals are decoration like in goal levels,
+        // other unreachable boxes can't be removed
+        let mut reachable_boxes = Vec::new();
+        for &pos in &state.boxes {
+            if processed_grid[pos] != MapCell::Wall {
+                reachable_boxes.push(pos);
+            } else if !map.goals.contains(&pos) {
+                return Err(SolverErr::UnreachableBoxes);
+            }
+        }
+
+        let mut reachable_goals = Vec::new();
+        for &pos in &map.goals {
+            if processed_grid[pos] != MapCell::Wall {
+                reachable_goals.push(pos);
+            } else if !state.boxes.contains(&pos) {
+                return Err(SolverErr::UnreachableGoals);
+            }
+        }
+
+        let reachable_removers: Vec<_> = map
+            .removers
+            .iter()
+            .copied()
+            .filter(|&pos| processed_grid[pos] != MapCell::Wall)
+            .collect();
+
+        // extra boxes are fine as long as they can be removed
+        if reachable_boxes.len() < reachable_goals.len()
+            || (reachable_boxes.len() > reachable_goals.len() && reachable_removers.is_empty())
+        {
+            return Err(SolverErr::DiffBoxesGoals);
+        }
+
+        if reachable_boxes.len() > MAX_BOXES {
+            return Err(SolverErr::TooMany);
+        }
+
+        let processed_map = HybridMap::new(processed_grid, reachable_goals, reachable_removers);
+        let mut clean_state = state.clone();
+        clean_state.boxes = reachable_boxes.into();
+        let box_grid = preprocessing::box_grid(processed_map.grid(), &clean_state, variant);
+        let push_dists = memo.push_dists(&box_grid, variant);
+        let closest_push_dists = preprocessing::closest_push_dists(&processed_map, push_dists);
+        let side_dists = preprocessing::side_dists(&processed_map, push_dists);
+        let symmetries = symmetry::symmetries(processed_map.grid(), state);
+        let rooms = Rooms::new(processed_map.grid());
+        Ok(Solver {
+            sd: StaticData {
+                map: processed_map,
+                initial_state: clean_state,
+                variant,
                 closest_push_dists,
+                side_dists,
+                // matching boxes to goals doesn't account for boxes which get removed
+                unsolvable: None,
+                heuristic: Heuristic::default(),
+                goal_pull_dists: Vec::new(),
+                criteria: Vec::new(),
+                preprocessing_time: started.elapsed(),
+                symmetries,
+                rooms,
             },
         })
     }
@@ -218,10 +1099,46 @@ trait SolverTrait {
 
     fn sd(&self) -> &StaticData<Self::M>;
 
-    fn push_box(sd: &StaticData<Self::M>, state: &State, box_index: u8, push_dest: Pos)
-        -> Vec<Pos>;
+    /// The boxes after the push, `None` if it would make the level unsolvable
+    fn push_box(
+        sd: &StaticData<Self::M>,
+        state: &State,
+        box_index: BoxIndex,
+        push_dest: Pos,
+    ) -> Option<Boxes>;
+
+    fn search<GL: GameLogic<Self::M>>(
+        &self,
+        options: SolverOptions,
+        logic: GL,
+        resume: Option<&Checkpoint>,
+        progress: &mut dyn FnMut(&Stats) -> bool,
+        events: Option<&mut dyn EventSink>,
+        output: &mut dyn Write,
+    ) -> SolverOk
+    where
+        Solver<<Self as SolverTrait>::M>: SolverTrait,
+    {
+        let started = Instant::now();
+        let mut solver_ok = self.run_search(options, logic, resume, progress, events, output);
+        let metadata = &mut solver_ok.metadata;
+        metadata.heuristic = self.sd().heuristic;
+        metadata.preprocessing_time = self.sd().preprocessing_time;
+        metadata.search_time = started.elapsed().saturating_sub(metadata.backtracking_time);
+        solver_ok
+    }
 
-    fn search<GL: GameLogic<Self::M>>(&self, print_status: bool, _: GL) -> SolverOk
+    /// `search` without the times and settings in `SolveMetadata`
+    /// except `limit_hit` and `backtracking_time`
+    fn run_search<GL: GameLogic<Self::M>>(
+        &self,
+        options: SolverOptions,
+        _: GL,
+        resume: Option<&Checkpoint>,
+        progress: &mut dyn FnMut(&Stats) -> bool,
+        mut events: Option<&mut dyn EventSink>,
+        output: &mut dyn Write,
+    ) -> SolverOk
     where
         Solver<<Self as SolverTrait>::M>: SolverTrait,
     {
@@ -229,26 +1146,27 @@ trait SolverTrait {
 
         let mut stats = Stats::new();
 
+        if self.sd().solved_at_start() {
+            return SolverOk::solved_at_start(options.output, stats);
+        }
+
         // boxes that can't reach any goals
         // normally such states would not be generated at all but the first one is not generated so needs to be checked
         for &box_pos in &self.sd().initial_state.boxes {
             if self.sd().closest_push_dists[box_pos].is_none() {
-                return SolverOk::new(None, stats);
+                let reason = UnsolvableReason::DeadSquare {
+                    box_pos: box_pos.into(),
+                };
+                return SolverOk::unsolvable(stats, reason);
             }
         }
-
-        // already solved
-        if self
-            .sd()
-            .initial_state
-            .boxes
-            .iter()
-            .all(|&box_pos| self.sd().map.grid()[box_pos] == MapCell::Goal)
-        {
-            return SolverOk::new(Some(Moves::default()), stats);
+        if let Some(ref reason) = self.sd().unsolvable {
+            return SolverOk::unsolvable(stats, reason.clone());
         }
 
         let states = Arena::new();
+        // remover levels can have fewer boxes later, this is only an estimate anyway
+        let box_cnt = self.sd().initial_state.boxes.len();
 
         #[cfg(feature = "graph")]
         let mut graph = Graph::new(&self.sd().map);
@@ -261,26 +1179,62 @@ trait SolverTrait {
         //let mut biggest = 0;
 
         // note to future self: if experimenting with overcommit, a hashmap will use all the capacity it's given
-        let mut prevs = FnvHashMap::default();
+        let mut prevs = Prevs::new(!self.sd().symmetries.is_empty());
+        // visited states by their boxes, only filled for methods which keep the exact player position
+        let mut near_duplicates = NearDuplicates::new();
+        let mut tie_breaker = TieBreaker::new(options.tie_break, options.seed);
+        let mut scratch = Scratch::new(&self.sd().map);
+        let mut new_states = Vec::new();
 
         // this might be more trouble than it's worth, we avoid expanding a whole *one* extra state
         // but it looks cleaner when printing graphs of the state space
-        let norm_initial_state = GL::preprocess_state(&self.sd().map, &self.sd().initial_state);
+        let norm_initial_state = GL::preprocess_state(self.sd(), &self.sd().initial_state);
         let start = SearchNode::new(
             &norm_initial_state,
             None,
             GL::C::zero(),
-            GL::initial_heuristic(self.sd(), &norm_initial_state),
+            GL::initial_heuristic(self.sd(), &norm_initial_state)
+                .weighted(options.suboptimality_percent),
         );
-        stats.add_created(start.dist.depth());
-        to_visit.push(Reverse(CostComparator(start)));
+        if let Some(checkpoint) = resume {
+            checkpoint.restore(
+                &states,
+                &mut to_visit,
+                &mut prevs,
+                &mut tie_breaker,
+                |state| {
+                    symmetry::canonical(
+                        &self.sd().map,
+                        &self.sd().symmetries,
+                        state,
+                        GL::normalized_player(self.sd()),
+                        &mut scratch,
+                    )
+                },
+            );
+            stats = checkpoint.stats.clone();
+        } else {
+            stats.add_created(start.dist.depth(), start.h());
+            to_visit.push(Reverse(tie_breaker.wrap(start)));
+            emit(&mut events, &start, Decision::Queued);
+
+            #[cfg(feature = "graph")]
+            graph.add(start, None);
+        }
         //in_queue.insert(start.state, start.dist); // using dist or cost is the same because h is the same
 
-        #[cfg(feature = "graph")]
-        graph.add(start, None);
+        #[cfg(feature = "open-list-stats")]
+        let mut prev_snapshot: Option<OpenListSnapshot> = None;
 
+        let started = Instant::now();
+        let elapsed_before = stats.elapsed();
+        let mut visited_cnt = 0;
+        let mut last_status = (started, 0);
+        let mut deepest = 0;
+        // the cheapest node not created because of max_depth
+        let mut min_pruned: Option<GL::C> = None;
         //let mut counter = 0;
-        while let Some(Reverse(CostComparator(cur_node))) = to_visit.pop() {
+        while let Some(Reverse(CostComparator(cur_node, cur_order, _))) = to_visit.pop() {
             /*counter += 1;
             if counter % 100_000 == 0 {
                 use crate::map::Map;
@@ -288,51 +1242,251 @@ trait SolverTrait {
                 println!("{}", self.sd().map.xsb_with_state(&cur_node.state));
             }*/
 
-            if prevs.contains_key(cur_node.state) {
+            // states which are images of each other under a symmetry of the map share one entry
+            let canonical = symmetry::canonical(
+                &self.sd().map,
+                &self.sd().symmetries,
+                cur_node.state,
+                GL::normalized_player(self.sd()),
+                &mut scratch,
+            );
+            let hash = prevs::hash(canonical.as_ref().unwrap_or(cur_node.state));
+            if prevs
+                .find(hash, canonical.as_ref().unwrap_or(cur_node.state))
+                .is_some()
+            {
                 stats.add_reached_duplicate(cur_node.dist.depth());
+                emit(&mut events, &cur_node, Decision::Duplicate);
 
                 #[cfg(feature = "graph")]
                 graph.mark_duplicate(cur_node);
 
                 continue;
             }
-            if stats.add_unique_visited(cur_node.dist.depth()) && print_status {
-                println!("Visited new depth: {}", cur_node.dist.depth());
-                println!("{stats:?}");
-            }
+            let near_duplicate = GL::walk_cost(0).is_some()
+                && cur_node.state.player_cnt() == 1
+                && near_duplicates.is_dominated(
+                    &self.sd().map,
+                    cur_node.state,
+                    cur_node.dist,
+                    |moves| GL::walk_cost(moves).expect("Checked above"),
+                );
+            if near_duplicate {
+                stats.add_reached_duplicate(cur_node.dist.depth());
+                stats.pruned.near_duplicates += 1;
+                emit(&mut events, &cur_node, Decision::Duplicate);
 
-            #[cfg(feature = "graph")]
-            graph.mark_unique(cur_node);
+                #[cfg(feature = "graph")]
+                graph.mark_duplicate(cur_node);
 
-            // insert when expanding and not when generating
-            // otherwise we might overwrite the shortest path with longer ones
-            if let Some(p) = cur_node.prev {
-                prevs.insert(cur_node.state, p);
-            } else {
-                // initial state has no prev - hack to avoid Option
-                prevs.insert(cur_node.state, cur_node.state);
+                continue;
+            }
+            let new_depth = stats.add_unique_visited(
+                cur_node.dist.depth(),
+                cur_node.h(),
+                cur_node.cost.depth(),
+            );
+            visited_cnt += 1;
+            let report = new_depth || visited_cnt % PROGRESS_INTERVAL == 0;
+            if report {
+                stats.set_elapsed(elapsed_before + started.elapsed());
+            }
+            #[cfg(feature = "open-list-stats")]
+            if new_depth {
+                prev_snapshot = stats.set_open_list(OpenListSnapshot::new(
+                    to_visit
+                        .iter()
+                        .map(|Reverse(CostComparator(node, ..))| node),
+                ));
+            }
+            if report && !progress(&stats) {
+                debug!("Interrupted");
+                stats.set_memory(memory_stats(&states, box_cnt, &to_visit, &prevs));
+                stats.set_elapsed(elapsed_before + started.elapsed());
+                let checkpoint = if options.checkpoint {
+                    // the current state has to be visited again after resuming
+                    let mut saved_stats = stats.clone();
+                    saved_stats.remove_unique_visited(cur_node.dist.depth(), cur_node.h());
+                    let open = to_visit
+                        .iter()
+                        .map(|&Reverse(CostComparator(node, order, _))| (node, order))
+                        .chain(Some((cur_node, cur_order)));
+                    Some(Checkpoint::new(
+                        options.suboptimality_percent,
+                        saved_stats,
+                        open,
+                        &prevs,
+                    ))
+                } else {
+                    None
+                };
+                let mut solver_ok = SolverOk::new(None, stats, 0);
+                solver_ok.interrupted = true;
+                solver_ok.metadata.limit_hit = Some(Limit::Interrupted);
+                solver_ok.checkpoint = checkpoint;
+                #[cfg(feature = "graph")]
+                {
+                    solver_ok.trace = Some(graph.into_trace(&[]));
+                }
+                return solver_ok;
+            }
+            if let Some(interval) = options.status_interval {
+                let elapsed = last_status.0.elapsed();
+                if visited_cnt % STATUS_CHECK_INTERVAL == 0 && elapsed >= interval {
+                    let speed = f64::from(visited_cnt - last_status.1) / elapsed.as_secs_f64();
+                    // status output is best effort, failing to write it shouldn't stop the search
+                    let _ = writeln!(
+                        output,
+                        "Status: {} states/s, {} unique visited, {} in open list, f-value {}",
+                        (speed as i64).separated_string(),
+                        stats.total_unique_visited().separated_string(),
+                        to_visit.len().separated_string(),
+                        cur_node.cost
+                    );
+                    last_status = (Instant::now(), visited_cnt);
+                }
+            }
+            #[cfg(feature = "tracing")]
+            if new_depth {
+                tracing::debug!(
+                    depth = cur_node.dist.depth(),
+                    f = %cur_node.cost,
+                    created = stats.total_created(),
+                    visited = stats.total_unique_visited(),
+                    duplicates = stats.total_reached_duplicates(),
+                    open = to_visit.len(),
+                    elapsed_ms = stats.elapsed().as_millis() as u64,
+                    "Visited new depth"
+                );
+            }
+            if new_depth && options.print_status {
+                let _ = writeln!(output, "Visited new depth: {}", cur_node.dist.depth());
+                let _ = writeln!(output, "{stats:?}");
+                // short searches don't need it and it would make the output nondeterministic
+                if stats.elapsed() >= ETA_AFTER {
+                    print_eta(output, &stats, cur_node.dist.depth(), cur_node.cost.depth());
+                }
+
+                #[cfg(feature = "open-list-stats")]
+                if let Some(snapshot) = stats.open_list() {
+                    if let Some(ref prev) = prev_snapshot {
+                        let _ = writeln!(output, "{}", snapshot.diff(prev));
+                    }
+                    let _ = writeln!(output, "{snapshot}");
+                }
+            }
+
+            #[cfg(feature = "graph")]
+            graph.mark_unique(cur_node);
+
+            // insert when expanding and not when generating
+            // otherwise we might overwrite the shortest path with longer ones
+            // the prev was expanded as it is so its key leads to the same state
+            let prev = cur_node.prev.map(|prev| {
+                let canonical = symmetry::canonical(
+                    &self.sd().map,
+                    &self.sd().symmetries,
+                    prev,
+                    GL::normalized_player(self.sd()),
+                    &mut scratch,
+                );
+                let key = canonical.as_ref().unwrap_or(prev);
+                prevs
+                    .find(prevs::hash(key), key)
+                    .expect("Prev is always visited")
+            });
+            let key = match canonical {
+                Some(canonical) => &*states.alloc(canonical),
+                None => cur_node.state,
+            };
+            let index = prevs.insert(hash, cur_node.state, key, prev);
+            if GL::walk_cost(0).is_some() && cur_node.state.player_cnt() == 1 {
+                near_duplicates.insert(cur_node.state, cur_node.dist);
             }
 
             if cur_node.cost == cur_node.dist {
                 // heuristic is 0 so level is solved
                 debug!("Solved, backtracking path");
-
-                let solution_states = backtracking::backtrack_prevs(&prevs, cur_node.state);
-
-                #[cfg(feature = "graph")]
-                graph.draw_states(&solution_states);
+                emit(&mut events, &cur_node, Decision::Solution);
+
+                // the rest of the block is cheap so the span includes it
+                span!("backtracking");
+                let backtracking_started = Instant::now();
+                let solution_states: Vec<_> =
+                    backtracking::backtrack_prevs(index, |index| prevs.prev(index))
+                        .into_iter()
+                        .map(|index| prevs.state(index))
+                        .collect();
+                let solution_states =
+                    GL::single_pushes(self.sd(), solution_states, &states, &mut scratch);
 
                 //println!("biggest queue: {}", biggest);
 
-                let moves = backtracking::reconstruct_moves(
+                let mut reconstructor = MoveReconstructor::new(
                     &self.sd().map,
-                    self.sd().initial_state.player_pos,
-                    &solution_states,
+                    &self.sd().initial_state,
+                    self.sd().variant,
                 );
-                return SolverOk::new(Some(moves), stats);
+                let mut solver_ok = match options.output {
+                    SolveOutput::Moves => {
+                        let moves = reconstructor.reconstruct(&solution_states);
+                        SolverOk::new(Some(moves), stats, options.suboptimality_percent)
+                    }
+                    SolveOutput::Pushes => {
+                        let mut solver_ok =
+                            SolverOk::new(None, stats, options.suboptimality_percent);
+                        solver_ok.pushes = Some(reconstructor.pushes(&solution_states));
+                        solver_ok
+                    }
+                };
+                solver_ok.metadata.backtracking_time = backtracking_started.elapsed();
+                solver_ok
+                    .stats
+                    .set_memory(memory_stats(&states, box_cnt, &to_visit, &prevs));
+                solver_ok
+                    .stats
+                    .set_elapsed(elapsed_before + started.elapsed());
+                #[cfg(feature = "graph")]
+                let solver_ok = SolverOk {
+                    trace: Some(graph.into_trace(&solution_states)),
+                    ..solver_ok
+                };
+                return solver_ok;
+            }
+
+            deepest = deepest.max(cur_node.dist.depth());
+            if options
+                .max_nodes
+                .is_some_and(|max| i64::from(visited_cnt) >= i64::from(max))
+            {
+                debug!("Node limit reached");
+                // nothing in the open list is cheaper than the current node
+                let cost = min_pruned.map_or(cur_node.cost, |pruned| pruned.min(cur_node.cost));
+                stats.set_memory(memory_stats(&states, box_cnt, &to_visit, &prevs));
+                stats.set_elapsed(elapsed_before + started.elapsed());
+                let mut solver_ok = SolverOk::new(None, stats, 0);
+                solver_ok.limit_reached = Some(LimitReached {
+                    lower_bound: unweighted_lower_bound(cost, options.suboptimality_percent),
+                    deepest,
+                });
+                solver_ok.metadata.limit_hit = Some(Limit::MaxNodes);
+                #[cfg(feature = "graph")]
+                {
+                    solver_ok.trace = Some(graph.into_trace(&[]));
+                }
+                return solver_ok;
             }
 
-            for (neighbor_state, cost, h) in GL::expand(self.sd(), cur_node.state, &states) {
+            emit(&mut events, &cur_node, Decision::Expanded);
+            GL::expand(
+                self.sd(),
+                cur_node.state,
+                &states,
+                &mut stats.pruned,
+                &mut scratch,
+                &mut new_states,
+            );
+            for (neighbor_state, cost, h) in new_states.drain(..) {
                 // Insert everything and ignore duplicates when popping. This wastes memory
                 // but when I filter them out here using a HashMap, pushes/boxxle2/4 becomes 8x slower
                 // and generates much more states (although pushes/original/1 becomes about 2x faster).
@@ -345,15 +1499,26 @@ trait SolverTrait {
                 // If it's possible to insert states into prevs when expanding (might need updating when a better prev is found),
                 // we could reduce the size of SearchNode by removing prev.
 
+                // Weighted A* - inflating an admissible heuristic by a factor w
+                // guarantees the solution is at most w times longer than optimal.
                 let next_node = SearchNode::new(
                     neighbor_state,
                     Some(cur_node.state),
                     cur_node.dist + cost,
-                    h,
+                    h.weighted(options.suboptimality_percent),
                 );
-                stats.add_created(next_node.dist.depth());
+                if options
+                    .max_depth
+                    .is_some_and(|max| next_node.dist.depth() > max)
+                {
+                    min_pruned = Some(min_pruned.map_or(next_node.cost, |c| c.min(next_node.cost)));
+                    stats.pruned.max_depth += 1;
+                    continue;
+                }
+                stats.add_created(next_node.dist.depth(), next_node.h());
 
-                to_visit.push(Reverse(CostComparator(next_node)));
+                to_visit.push(Reverse(tie_breaker.wrap(next_node)));
+                emit(&mut events, &next_node, Decision::Queued);
 
                 #[cfg(feature = "graph")]
                 graph.add(next_node, Some(cur_node));
@@ -387,7 +1552,95 @@ trait SolverTrait {
             }
         }
 
-        SolverOk::new(None, stats)
+        stats.set_memory(memory_stats(&states, box_cnt, &to_visit, &prevs));
+        stats.set_elapsed(elapsed_before + started.elapsed());
+        let solver_ok = if let Some(cost) = min_pruned {
+            // solutions might exist deeper
+            let mut solver_ok = SolverOk::new(None, stats, 0);
+            solver_ok.limit_reached = Some(LimitReached {
+                lower_bound: unweighted_lower_bound(cost, options.suboptimality_percent),
+                deepest,
+            });
+            solver_ok.metadata.limit_hit = Some(Limit::MaxDepth);
+            solver_ok
+        } else {
+            SolverOk::unsolvable(stats, UnsolvableReason::Exhausted)
+        };
+        #[cfg(feature = "graph")]
+        let solver_ok = SolverOk {
+            trace: Some(graph.into_trace(&[])),
+            ..solver_ok
+        };
+        solver_ok
+    }
+}
+
+/// Crude progress estimate for searches running longer than `ETA_AFTER`
+fn print_eta(output: &mut dyn Write, stats: &Stats, depth: u16, lower_bound: u16) {
+    let speed = stats.states_per_sec().unwrap_or(0.0);
+    let eta = stats
+        .eta(lower_bound)
+        .map_or_else(|| "unknown".to_owned(), format_duration);
+    let _ = writeln!(
+        output,
+        "Depth {depth} of at least {lower_bound}, {} states/s, rough ETA {eta}",
+        (speed as i64).separated_string()
+    );
+}
+
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (days, hours, mins, secs) = (secs / 86400, secs / 3600 % 24, secs / 60 % 60, secs % 60);
+    if days > 365 {
+        "more than a year".to_owned()
+    } else if days > 0 {
+        format!("{days}d {hours}h")
+    } else if hours > 0 {
+        format!("{hours}h {mins}m")
+    } else if mins > 0 {
+        format!("{mins}m {secs}s")
+    } else {
+        format!("{secs}s")
+    }
+}
+
+/// Weighted A* can overestimate the remaining distance by up to `percent`
+/// so the real distance can be correspondingly lower than the cost
+fn unweighted_lower_bound<C: Cost>(cost: C, percent: u16) -> u16 {
+    let bound = (u32::from(cost.depth()) * 100).div_ceil(100 + u32::from(percent));
+    u16::try_from(bound).unwrap_or(u16::MAX)
+}
+
+fn emit<C: Cost>(
+    events: &mut Option<&mut dyn EventSink>,
+    node: &SearchNode<'_, C>,
+    decision: Decision,
+) {
+    if let Some(ref mut sink) = *events {
+        sink.event(&SearchEvent::new(node, decision));
+    }
+}
+
+/// Boxes are only allocated separately from the state when there are too many to store inline
+fn boxes_heap_size(box_cnt: usize) -> usize {
+    if box_cnt > INLINE_BOXES {
+        box_cnt * mem::size_of::<Pos>()
+    } else {
+        0
+    }
+}
+
+fn memory_stats<T>(
+    states: &Arena<State>,
+    box_cnt: usize,
+    to_visit: &BinaryHeap<T>,
+    prevs: &Prevs<'_>,
+) -> MemoryStats {
+    MemoryStats {
+        arena: states.len() * (mem::size_of::<State>() + boxes_heap_size(box_cnt)),
+        open_list: to_visit.capacity() * mem::size_of::<T>(),
+        prevs: prevs.memory(),
+        peak_rss: a_star::peak_rss(),
     }
 }
 
@@ -401,12 +1654,12 @@ impl SolverTrait for Solver<GoalMap> {
     fn push_box(
         _sd: &StaticData<Self::M>,
         state: &State,
-        box_index: u8,
+        box_index: BoxIndex,
         push_dest: Pos,
-    ) -> Vec<Pos> {
+    ) -> Option<Boxes> {
         let mut new_boxes = state.boxes.clone();
         new_boxes[box_index as usize] = push_dest;
-        new_boxes
+        Some(new_boxes)
     }
 }
 
@@ -420,16 +1673,43 @@ impl SolverTrait for Solver<RemoverMap> {
     fn push_box(
         sd: &StaticData<Self::M>,
         state: &State,
-        box_index: u8,
+        box_index: BoxIndex,
+        push_dest: Pos,
+    ) -> Option<Boxes> {
+        let mut new_boxes = state.boxes.clone();
+        if sd.map.grid()[push_dest] == MapCell::Remover {
+            new_boxes.remove(box_index as usize);
+        } else {
+            new_boxes[box_index as usize] = push_dest;
+        }
+        Some(new_boxes)
+    }
+}
+
+impl SolverTrait for Solver<HybridMap> {
+    type M = HybridMap;
+
+    fn sd(&self) -> &StaticData<Self::M> {
+        &self.sd
+    }
+
+    fn push_box(
+        sd: &StaticData<Self::M>,
+        state: &State,
+        box_index: BoxIndex,
         push_dest: Pos,
-    ) -> Vec<Pos> {
+    ) -> Option<Boxes> {
         let mut new_boxes = state.boxes.clone();
         if sd.map.grid()[push_dest] == MapCell::Remover {
+            // every goal still needs a box
+            if new_boxes.len() <= sd.map.goals.len() {
+                return None;
+            }
             new_boxes.remove(box_index as usize);
         } else {
             new_boxes[box_index as usize] = push_dest;
         }
-        new_boxes
+        Some(new_boxes)
     }
 }
 
@@ -440,8 +1720,16 @@ where
 {
     type C: Cost;
 
-    fn preprocess_state(_map: &M, state: &State) -> State {
-        state.clone()
+    fn preprocess_state(_sd: &StaticData<M>, state: &State) -> State {
+        let mut state = state.clone();
+        state.sort_players();
+        state
+    }
+
+    /// Whether states only keep the top-left square the player can reach (see `normalized_pos`)
+    /// instead of the exact player position
+    fn normalized_player(_sd: &StaticData<M>) -> bool {
+        false
     }
 
     fn initial_heuristic(sd: &StaticData<M>, state: &State) -> Self::C {
@@ -454,11 +1742,34 @@ where
         Self::C::zero()
     }
 
+    /// The cost of walking `moves` steps without pushing if states keep the exact player position,
+    /// used to prune states which a visited state with the same boxes can walk to
+    fn walk_cost(_moves: u16) -> Option<Self::C> {
+        None
+    }
+
+    /// Splits the steps of a solution which make several pushes into single pushes
+    /// because `MoveReconstructor` expects one push between states
+    fn single_pushes<'a>(
+        _sd: &StaticData<M>,
+        states: Vec<&'a State>,
+        _arena: &'a Arena<State>,
+        _scratch: &mut Scratch,
+    ) -> Vec<&'a State> {
+        states
+    }
+
+    /// Appends the states reachable with one push (or one walk in multiban) to `new_states`
+    /// with the cost of getting there and their heuristic.
+    /// Pushes rejected by prunings are counted in `pruned`.
     fn expand<'a>(
         sd: &StaticData<M>,
         state: &State,
         arena: &'a Arena<State>,
-    ) -> Vec<(&'a State, Self::C, Self::C)>;
+        pruned: &mut PruningStats,
+        scratch: &mut Scratch,
+        new_states: &mut Vec<(&'a State, Self::C, Self::C)>,
+    );
 }
 
 struct MovePushLogic;
@@ -470,15 +1781,29 @@ where
 {
     type C = ComplexCost;
 
+    fn walk_cost(moves: u16) -> Option<Self::C> {
+        Some(ComplexCost([moves, 0]))
+    }
+
     fn expand<'a>(
         sd: &StaticData<M>,
         cur_state: &State,
         arena: &'a Arena<State>,
-    ) -> Vec<(&'a State, Self::C, Self::C)> {
-        expand_bfs(sd, cur_state, arena)
-            .into_iter()
-            .map(|(state, moves, h)| (state, ComplexCost(moves, 1), ComplexCost(h, h)))
-            .collect()
+        pruned: &mut PruningStats,
+        scratch: &mut Scratch,
+        new_states: &mut Vec<(&'a State, Self::C, Self::C)>,
+    ) {
+        expand_bfs(
+            sd,
+            cur_state,
+            arena,
+            pruned,
+            false,
+            scratch,
+            |state, moves, pushes, h| {
+                new_states.push((state, ComplexCost([moves, pushes]), ComplexCost([h, h])));
+            },
+        );
     }
 }
 
@@ -491,11 +1816,18 @@ where
 {
     type C = SimpleCost;
 
+    fn walk_cost(moves: u16) -> Option<Self::C> {
+        Some(SimpleCost(moves))
+    }
+
     fn expand<'a>(
         sd: &StaticData<M>,
         cur_state: &State,
         arena: &'a Arena<State>,
-    ) -> Vec<(&'a State, Self::C, Self::C)> {
+        pruned: &mut PruningStats,
+        scratch: &mut Scratch,
+        new_states: &mut Vec<(&'a State, Self::C, Self::C)>,
+    ) {
         // I thought this would produce the same results as MovePushLogic because of the way the priority queue works
         // but boxxle1/9 begs to differ. Upon further consideration, it should be possible to craft a level
         // with more solutions that have the same number of moves but different number of pushes
@@ -505,10 +1837,17 @@ where
         // Oh well, I don't fully understand why my program works (the exact way it does).
         // Then again, the priority queue works correctly, just the implementation details are different than
         // what I'd expect.
-        expand_bfs(sd, cur_state, arena)
-            .into_iter()
-            .map(|(state, moves, h)| (state, SimpleCost(moves), SimpleCost(h)))
-            .collect()
+        expand_bfs(
+            sd,
+            cur_state,
+            arena,
+            pruned,
+            false,
+            scratch,
+            |state, moves, _, h| {
+                new_states.push((state, SimpleCost(moves), SimpleCost(h)));
+            },
+        );
     }
 }
 
@@ -521,15 +1860,29 @@ where
 {
     type C = ComplexCost;
 
+    fn walk_cost(moves: u16) -> Option<Self::C> {
+        Some(ComplexCost([0, moves]))
+    }
+
     fn expand<'a>(
         sd: &StaticData<M>,
         cur_state: &State,
         arena: &'a Arena<State>,
-    ) -> Vec<(&'a State, Self::C, Self::C)> {
-        expand_bfs(sd, cur_state, arena)
-            .into_iter()
-            .map(|(state, moves, h)| (state, ComplexCost(1, moves), ComplexCost(h, h)))
-            .collect()
+        pruned: &mut PruningStats,
+        scratch: &mut Scratch,
+        new_states: &mut Vec<(&'a State, Self::C, Self::C)>,
+    ) {
+        expand_bfs(
+            sd,
+            cur_state,
+            arena,
+            pruned,
+            false,
+            scratch,
+            |state, moves, pushes, h| {
+                new_states.push((state, ComplexCost([pushes, moves]), ComplexCost([h, h])));
+            },
+        );
     }
 }
 
@@ -542,204 +1895,860 @@ where
 {
     type C = SimpleCost;
 
-    fn preprocess_state(map: &M, state: &State) -> State {
+    fn preprocess_state(sd: &StaticData<M>, state: &State) -> State {
+        if state.player_cnt() > 1 {
+            // players block each other so their exact positions matter
+            let mut state = state.clone();
+            state.sort_players();
+            return state;
+        }
         State::new(
-            normalized_pos(map, state.player_pos, &state.boxes),
+            normalized_pos(&sd.map, state.player_pos, &state.boxes),
             state.boxes.clone(),
         )
     }
 
+    fn normalized_player(_sd: &StaticData<M>) -> bool {
+        true
+    }
+
     fn expand<'a>(
         sd: &StaticData<M>,
         cur_state: &State,
         arena: &'a Arena<State>,
-    ) -> Vec<(&'a State, Self::C, Self::C)> {
-        expand_dfs(sd, cur_state, arena)
-            .into_iter()
-            .map(|(state, h)| (state, SimpleCost(1), SimpleCost(h)))
-            .collect()
+        pruned: &mut PruningStats,
+        scratch: &mut Scratch,
+        new_states: &mut Vec<(&'a State, Self::C, Self::C)>,
+    ) {
+        expand_dfs(
+            sd,
+            cur_state,
+            arena,
+            pruned,
+            false,
+            scratch,
+            |state, pushes, h| {
+                new_states.push((state, SimpleCost(pushes), SimpleCost(h)));
+            },
+        );
     }
 }
 
-fn expand_bfs<'a, M>(
-    sd: &StaticData<M>,
-    cur_state: &State,
-    arena: &'a Arena<State>,
-) -> Vec<(&'a State, u16, u16)>
+/// `PushLogic` plus the macro moves of `AnyProfile::Macro` (see `macro_moves`)
+struct MacroLogic;
+
+impl<M> GameLogic<M> for MacroLogic
 where
     M: Map,
     Solver<M>: SolverTrait<M = M>,
 {
-    let mut new_states = Vec::new();
+    type C = SimpleCost;
 
-    let mut box_grid = sd.map.grid().scratchpad_with_default(255_u8);
-    for (i, b) in cur_state.boxes.iter().enumerate() {
-        box_grid[*b] = i as u8;
+    fn preprocess_state(sd: &StaticData<M>, state: &State) -> State {
+        <PushLogic as GameLogic<M>>::preprocess_state(sd, state)
     }
 
-    // find each box and each direction from which it can be pushed
-    let mut reachable = sd.map.grid().scratchpad();
-    reachable[cur_state.player_pos] = true;
-
-    // this needs to be a BFS because we need the actual move cost
-    let mut to_visit = VecDeque::new();
-    to_visit.push_back((cur_state.player_pos, 0));
+    fn normalized_player(_sd: &StaticData<M>) -> bool {
+        true
+    }
 
-    while let Some((player_pos, steps)) = to_visit.pop_front() {
-        for &dir in &DIRECTIONS {
-            let new_player_pos = player_pos + dir;
-            let box_index = box_grid[new_player_pos];
-            if box_index < 255 {
-                // new_pos has a box
-                let push_dest = new_player_pos + dir;
-                if box_grid[push_dest] == 255 && sd.closest_push_dists[push_dest].is_some() {
-                    // new state to explore
-                    let new_boxes = Solver::<M>::push_box(sd, cur_state, box_index, push_dest);
-                    let new_state = arena.alloc(State::new(new_player_pos, new_boxes));
-                    let h = push_dists_heuristic(sd, new_state);
-                    // cost is number of steps plus the push
-                    new_states.push((&*new_state, steps + 1, h));
-                }
-            } else if sd.map.grid()[new_player_pos] != MapCell::Wall && !reachable[new_player_pos] {
-                // new_pos is empty and not yet visited
-                reachable[new_player_pos] = true;
-                to_visit.push_back((new_player_pos, steps + 1));
+    fn single_pushes<'a>(
+        sd: &StaticData<M>,
+        states: Vec<&'a State>,
+        arena: &'a Arena<State>,
+        scratch: &mut Scratch,
+    ) -> Vec<&'a State> {
+        let mut split = vec![states[0]];
+        for pair in states.windows(2) {
+            if let Some(pushes) = macro_moves::single_pushes(sd, scratch, pair[0], pair[1]) {
+                // the last one is replaced by the real state to keep its player position
+                let intermediate = pushes.len() - 1;
+                split.extend(
+                    pushes
+                        .into_iter()
+                        .take(intermediate)
+                        .map(|state| &*arena.alloc(state)),
+                );
             }
+            split.push(pair[1]);
         }
+        split
     }
 
-    new_states
+    fn expand<'a>(
+        sd: &StaticData<M>,
+        cur_state: &State,
+        arena: &'a Arena<State>,
+        pruned: &mut PruningStats,
+        scratch: &mut Scratch,
+        new_states: &mut Vec<(&'a State, Self::C, Self::C)>,
+    ) {
+        let first_new = new_states.len();
+        <PushLogic as GameLogic<M>>::expand(sd, cur_state, arena, pruned, scratch, new_states);
+        if sd.variant != Variant::Push || cur_state.player_cnt() > 1 {
+            return;
+        }
+        for i in first_new..new_states.len() {
+            let state = new_states[i].0;
+            let Some((box_pos, dir)) = macro_moves::pushed_box(cur_state, state) else {
+                continue;
+            };
+            if let Some((macro_state, pushes)) =
+                macro_moves::macro_push(sd, state, box_pos, dir, scratch)
+            {
+                let macro_state = arena.alloc(macro_state);
+                let h = heuristic(sd, macro_state);
+                new_states.push((macro_state, SimpleCost(1 + pushes), SimpleCost(h)));
+            }
+        }
+    }
 }
 
-fn expand_dfs<'a, M>(
-    sd: &StaticData<M>,
-    cur_state: &State,
-    arena: &'a Arena<State>,
-) -> Vec<(&'a State, u16)>
+/// Pushes first, then box lines (see `SolutionInfo::box_lines`).
+///
+/// Walking doesn't break a line so the player position is normalized like with `PushLogic`,
+/// states only additionally remember the last push.
+struct PushBoxLineLogic;
+
+impl<M> GameLogic<M> for PushBoxLineLogic
 where
     M: Map,
     Solver<M>: SolverTrait<M = M>,
 {
-    let mut new_states = Vec::new();
+    type C = ComplexCost;
 
-    let mut box_grid = sd.map.grid().scratchpad_with_default(255_u8);
-    for (i, b) in cur_state.boxes.iter().enumerate() {
-        box_grid[*b] = i as u8;
+    fn preprocess_state(sd: &StaticData<M>, state: &State) -> State {
+        <PushLogic as GameLogic<M>>::preprocess_state(sd, state)
     }
 
-    // find each box and each direction from which it can be pushed
-    let mut reachable = sd.map.grid().scratchpad();
-    reachable[cur_state.player_pos] = true;
+    fn normalized_player(_sd: &StaticData<M>) -> bool {
+        true
+    }
 
-    // Vec is noticeably faster than VecDeque on some levels
-    let mut to_visit = vec![cur_state.player_pos];
+    fn expand<'a>(
+        sd: &StaticData<M>,
+        cur_state: &State,
+        arena: &'a Arena<State>,
+        pruned: &mut PruningStats,
+        scratch: &mut Scratch,
+        new_states: &mut Vec<(&'a State, Self::C, Self::C)>,
+    ) {
+        expand_dfs(
+            sd,
+            cur_state,
+            arena,
+            pruned,
+            true,
+            scratch,
+            |state, pushes, h| {
+                let lines = u16::from(pushes > 0 && !continues_line(cur_state, state));
+                // the number of lines has no useful lower bound which would keep the heuristic consistent
+                new_states.push((state, ComplexCost([pushes, lines]), ComplexCost([h, 0])));
+            },
+        );
+    }
+}
 
-    while let Some(player_pos) = to_visit.pop() {
-        for &dir in &DIRECTIONS {
-            let new_player_pos = player_pos + dir;
-            let box_index = box_grid[new_player_pos];
-            if box_index < 255 {
-                // new_pos has a box
-                let push_dest = new_player_pos + dir;
-                if box_grid[push_dest] == 255 && sd.closest_push_dists[push_dest].is_some() {
-                    // new state to explore
-                    let new_boxes = Solver::<M>::push_box(sd, cur_state, box_index, push_dest);
-                    let norm_player_pos = normalized_pos(&sd.map, new_player_pos, &new_boxes);
-                    let new_state = arena.alloc(State::new(norm_player_pos, new_boxes));
-                    let h = push_dists_heuristic(sd, new_state);
-                    new_states.push((&*new_state, h));
-                }
-            } else if sd.map.grid()[new_player_pos] != MapCell::Wall && !reachable[new_player_pos] {
-                // new_pos is empty and not yet visited
-                reachable[new_player_pos] = true;
-                to_visit.push(new_player_pos);
+/// Minimizes `StaticData::criteria` in order.
+///
+/// The player position is only kept exactly when moves are minimized
+/// and the last push is only tracked when box lines or changes are.
+struct LexicographicLogic;
+
+impl<M> GameLogic<M> for LexicographicLogic
+where
+    M: Map,
+    Solver<M>: SolverTrait<M = M>,
+{
+    type C = ComplexCost<{ Criterion::MAX }>;
+
+    fn preprocess_state(sd: &StaticData<M>, state: &State) -> State {
+        if sd.criteria.contains(&Criterion::Moves) {
+            let mut state = state.clone();
+            state.sort_players();
+            state
+        } else {
+            <PushLogic as GameLogic<M>>::preprocess_state(sd, state)
+        }
+    }
+
+    fn normalized_player(sd: &StaticData<M>) -> bool {
+        !sd.criteria.contains(&Criterion::Moves)
+    }
+
+    fn expand<'a>(
+        sd: &StaticData<M>,
+        cur_state: &State,
+        arena: &'a Arena<State>,
+        pruned: &mut PruningStats,
+        scratch: &mut Scratch,
+        new_states: &mut Vec<(&'a State, Self::C, Self::C)>,
+    ) {
+        let track_pushes = sd.criteria.iter().any(|&criterion| {
+            criterion == Criterion::BoxLines || criterion == Criterion::BoxChanges
+        });
+        let mut add = |state: &'a State, moves, pushes, h| {
+            let mut cost = Self::C::zero();
+            let mut heuristic = Self::C::zero();
+            for (i, criterion) in sd.criteria.iter().enumerate() {
+                // the push heuristic is also a lower bound on moves,
+                // box lines and changes have none which would keep it consistent
+                (cost.0[i], heuristic.0[i]) = match criterion {
+                    Criterion::Moves => (moves, h),
+                    Criterion::Pushes => (pushes, h),
+                    Criterion::BoxLines => (
+                        u16::from(pushes > 0 && !continues_line(cur_state, state)),
+                        0,
+                    ),
+                    Criterion::BoxChanges => {
+                        (u16::from(pushes > 0 && !same_box(cur_state, state)), 0)
+                    }
+                };
             }
+            new_states.push((state, cost, heuristic));
+        };
+        if sd.criteria.contains(&Criterion::Moves) {
+            expand_bfs(sd, cur_state, arena, pruned, track_pushes, scratch, add);
+        } else {
+            expand_dfs(
+                sd,
+                cur_state,
+                arena,
+                pruned,
+                track_pushes,
+                scratch,
+                |state, pushes, h| add(state, 0, pushes, h),
+            );
         }
     }
+}
 
-    new_states
+/// The push which created `new_state` moved the same box as the last one
+fn same_box(cur_state: &State, new_state: &State) -> bool {
+    match (cur_state.last_push, new_state.last_push) {
+        (Some((cur_pos, _)), Some((new_pos, new_dir))) => new_pos - new_dir == cur_pos,
+        _ => false,
+    }
 }
 
-fn push_dists_heuristic<M: Map>(sd: &StaticData<M>, state: &State) -> u16 {
-    // thanks to precomputed distances, this is the same for goals and remover
-    let mut goal_dist_sum = 0;
+/// The push which created `new_state` moved the same box the same way as the last one
+fn continues_line(cur_state: &State, new_state: &State) -> bool {
+    same_box(cur_state, new_state)
+        && cur_state.last_push.map(|(_, dir)| dir) == new_state.last_push.map(|(_, dir)| dir)
+}
 
-    for &box_pos in &state.boxes {
-        goal_dist_sum += sd.closest_push_dists[box_pos].expect("Box on unreachable cell");
+/// Buffers reused by all expansions of one search so expanding doesn't allocate
+/// anything except the new states
+struct Scratch {
+    /// Index of the box on each square, `BoxIndex::MAX` if there's none.
+    /// Only the boxes of the state being expanded are set.
+    box_grid: Vec2d<BoxIndex>,
+    reached: Marks,
+    to_visit: Vec<Pos>,
+    to_visit_steps: VecDeque<(Pos, u16)>,
+    /// Pushes (or pulls) found by the player search, as the square the player moves from and the direction
+    moves: Vec<(Pos, Dir)>,
+    /// Normalizing the player position of a new state happens in the middle of the expanding search
+    /// so it needs its own marks
+    norm_reached: Marks,
+    norm_to_visit: Vec<Pos>,
+}
+
+impl Scratch {
+    fn new<M: Map>(map: &M) -> Self {
+        Scratch {
+            box_grid: map.grid().scratchpad_with_default(BoxIndex::MAX),
+            reached: Marks::new(map),
+            to_visit: Vec::new(),
+            to_visit_steps: VecDeque::new(),
+            moves: Vec::new(),
+            norm_reached: Marks::new(map),
+            norm_to_visit: Vec::new(),
+        }
     }
 
-    goal_dist_sum
-}
+    fn set_boxes(&mut self, boxes: &[Pos]) {
+        for (i, &box_pos) in boxes.iter().enumerate() {
+            self.box_grid[box_pos] = i as BoxIndex;
+        }
+    }
 
-fn normalized_pos<M: Map>(map: &M, player_pos: Pos, boxes: &[Pos]) -> Pos {
-    // note that pushing a box can reveal or hide new areas on both goal and remover maps
-    // (and reusing is not worth it according to Brian Damgaard)
-    // http://www.sokobano.de/wiki/index.php?title=Sokoban_solver_%22scribbles%22_by_Brian_Damgaard_about_the_YASS_solver#Re-using_the_calculated_player.27s_reachable_squares
+    fn clear_boxes(&mut self, boxes: &[Pos]) {
+        for &box_pos in boxes {
+            self.box_grid[box_pos] = BoxIndex::MAX;
+        }
+    }
 
-    let mut top_left = player_pos;
+    /// Forgets all reached squares
+    fn start_search(&mut self) {
+        self.reached.clear();
+    }
 
-    // this could be reused from the expand fn, just modified, then restored
-    let mut box_grid = map.grid().scratchpad();
-    for &b in boxes {
-        box_grid[b] = true;
+    /// Marks the square as reached, returns whether it wasn't reached before
+    fn reach(&mut self, pos: Pos) -> bool {
+        self.reached.mark(pos)
     }
 
-    let mut to_visit = vec![player_pos];
+    /// The normalized player position after a box moved from `box_from` to `box_to`
+    /// (`None` if it was removed). `box_grid` is changed to match the new state
+    /// only for the duration of the call.
+    fn normalized_pos_after_move<M: Map>(
+        &mut self,
+        map: &M,
+        player_pos: Pos,
+        box_from: Pos,
+        box_to: Option<Pos>,
+    ) -> Pos {
+        let box_index = self.box_grid[box_from];
+        self.box_grid[box_from] = BoxIndex::MAX;
+        if let Some(box_to) = box_to {
+            self.box_grid[box_to] = box_index;
+        }
 
-    let mut visited = map.grid().scratchpad();
-    visited[player_pos] = true;
+        let norm_pos = self.normalized_pos(map, player_pos);
 
-    while let Some(cur_pos) = to_visit.pop() {
-        for &new_pos in &cur_pos.neighbors() {
-            if visited[new_pos] {
-                continue;
-            }
-            visited[new_pos] = true;
+        if let Some(box_to) = box_to {
+            self.box_grid[box_to] = BoxIndex::MAX;
+        }
+        self.box_grid[box_from] = box_index;
+        norm_pos
+    }
 
-            if map.grid()[new_pos] == MapCell::Wall || box_grid[new_pos] {
-                continue;
+    /// The top-left square reachable by the player with the boxes currently in `box_grid`
+    fn normalized_pos<M: Map>(&mut self, map: &M, player_pos: Pos) -> Pos {
+        // note that pushing a box can reveal or hide new areas on both goal and remover maps
+        // so `expand_dfs` only reuses its own search when the push provably does neither
+        // (Brian Damgaard found reusing in general not worth it)
+        // http://www.sokobano.de/wiki/index.php?title=Sokoban_solver_%22scribbles%22_by_Brian_Damgaard_about_the_YASS_solver#Re-using_the_calculated_player.27s_reachable_squares
+
+        let mut top_left = player_pos;
+
+        self.norm_reached.clear();
+        self.norm_reached.mark(player_pos);
+
+        self.norm_to_visit.clear();
+        self.norm_to_visit.push(player_pos);
+
+        while let Some(cur_pos) = self.norm_to_visit.pop() {
+            for &new_pos in &cur_pos.neighbors() {
+                if !self.norm_reached.mark(new_pos) {
+                    continue;
+                }
+
+                if map.grid()[new_pos] == MapCell::Wall || self.box_grid[new_pos] < BoxIndex::MAX {
+                    continue;
+                }
+
+                self.norm_to_visit.push(new_pos);
+                if new_pos < top_left {
+                    top_left = new_pos;
+                }
             }
+        }
+
+        top_left
+    }
+}
+
+/// A grid of marks which can be all cleared in constant time.
+///
+/// Marked squares hold the current `generation` so the grid doesn't have to be cleared between searches.
+struct Marks {
+    grid: Vec2d<u32>,
+    generation: u32,
+}
 
-            to_visit.push(new_pos);
-            if new_pos < top_left {
-                top_left = new_pos;
+impl Marks {
+    fn new<M: Map>(map: &M) -> Self {
+        Marks {
+            grid: map.grid().scratchpad(),
+            generation: 0,
+        }
+    }
+
+    fn clear(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+        if self.generation == 0 {
+            // squares marked ~4 billion searches ago would look marked again
+            for pos in self.grid.positions() {
+                self.grid[pos] = 0;
             }
+            self.generation = 1;
         }
     }
 
-    top_left
+    fn is_marked(&self, pos: Pos) -> bool {
+        self.grid[pos] == self.generation
+    }
+
+    /// Returns whether the square wasn't marked before
+    fn mark(&mut self, pos: Pos) -> bool {
+        let new = self.grid[pos] != self.generation;
+        self.grid[pos] = self.generation;
+        new
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Calls `new_state` with the new states, the number of moves, pushes and the heuristic.
+/// With `track_pushes`, the new states remember the push which created them (`State::last_push`).
+fn expand_bfs<'a, M>(
+    sd: &StaticData<M>,
+    cur_state: &State,
+    arena: &'a Arena<State>,
+    pruned: &mut PruningStats,
+    track_pushes: bool,
+    scratch: &mut Scratch,
+    mut new_state: impl FnMut(&'a State, u16, u16, u16),
+) where
+    M: Map,
+    Solver<M>: SolverTrait<M = M>,
+{
+    if cur_state.player_cnt() > 1 {
+        expand_players(
+            sd,
+            cur_state,
+            arena,
+            pruned,
+            track_pushes,
+            scratch,
+            new_state,
+        );
+        return;
+    }
 
-    #[test]
-    fn pos_normalization() {
-        let levels = [
-            r"
-#####
-#   #
-# @ #
-#   #
-#####
-",
-            r"
-#####
-##  #
-# @ #
-#   #
-#####
-",
-            r"
-#####
-#$  #
-# @ #
-#   #
-#####
-",
-            r"
-#####
+    scratch.set_boxes(&cur_state.boxes);
+
+    // find each box and each direction from which it can be pushed
+    scratch.start_search();
+    scratch.reach(cur_state.player_pos);
+
+    // this needs to be a BFS because we need the actual move cost
+    scratch.to_visit_steps.clear();
+    scratch.to_visit_steps.push_back((cur_state.player_pos, 0));
+
+    while let Some((player_pos, steps)) = scratch.to_visit_steps.pop_front() {
+        for &dir in &DIRECTIONS {
+            let new_player_pos = player_pos + dir;
+            let box_index = scratch.box_grid[new_player_pos];
+            if box_index < BoxIndex::MAX {
+                // new_pos has a box
+                let push_dest = new_player_pos + dir;
+                if sd.variant != Variant::Pull && scratch.box_grid[push_dest] == BoxIndex::MAX {
+                    if let Some(new_boxes) = try_push(sd, cur_state, box_index, push_dest, pruned) {
+                        // new state to explore
+                        let mut state = State::new(new_player_pos, new_boxes);
+                        if track_pushes {
+                            state.last_push = Some((push_dest, dir));
+                        }
+                        let state = arena.alloc(state);
+                        let h = heuristic(sd, state);
+                        // cost is number of steps plus the push
+                        new_state(state, steps + 1, 1, h);
+                    }
+                }
+            } else if sd.map.grid()[new_player_pos] != MapCell::Wall {
+                if let Some(new_boxes) =
+                    pull_box(sd, cur_state, &scratch.box_grid, player_pos, dir, pruned)
+                {
+                    let mut state = State::new(new_player_pos, new_boxes);
+                    if track_pushes {
+                        state.last_push = Some((player_pos, dir));
+                    }
+                    let state = arena.alloc(state);
+                    let h = heuristic(sd, state);
+                    new_state(state, steps + 1, 1, h);
+                }
+                if scratch.reach(new_player_pos) {
+                    // new_pos is empty and not yet visited
+                    scratch
+                        .to_visit_steps
+                        .push_back((new_player_pos, steps + 1));
+                }
+            }
+        }
+    }
+
+    scratch.clear_boxes(&cur_state.boxes);
+}
+
+/// Calls `new_state` with the new states, the number of pushes and the heuristic.
+/// With `track_pushes`, the new states remember the push which created them (`State::last_push`).
+fn expand_dfs<'a, M>(
+    sd: &StaticData<M>,
+    cur_state: &State,
+    arena: &'a Arena<State>,
+    pruned: &mut PruningStats,
+    track_pushes: bool,
+    scratch: &mut Scratch,
+    mut new_state: impl FnMut(&'a State, u16, u16),
+) where
+    M: Map,
+    Solver<M>: SolverTrait<M = M>,
+{
+    if cur_state.player_cnt() > 1 {
+        expand_players(
+            sd,
+            cur_state,
+            arena,
+            pruned,
+            track_pushes,
+            scratch,
+            |state, _, pushes, h| new_state(state, pushes, h),
+        );
+        return;
+    }
+
+    scratch.set_boxes(&cur_state.boxes);
+
+    // find each box and each direction from which it can be pushed
+    // and the whole reachable area which most new states share with this one
+    scratch.start_search();
+    scratch.reach(cur_state.player_pos);
+    let mut top_left = cur_state.player_pos;
+
+    // Vec is noticeably faster than VecDeque on some levels
+    scratch.to_visit.clear();
+    scratch.to_visit.push(cur_state.player_pos);
+    scratch.moves.clear();
+
+    while let Some(player_pos) = scratch.to_visit.pop() {
+        for &dir in &DIRECTIONS {
+            let new_player_pos = player_pos + dir;
+            if scratch.box_grid[new_player_pos] < BoxIndex::MAX {
+                // new_pos has a box
+                if sd.variant != Variant::Pull
+                    && scratch.box_grid[new_player_pos + dir] == BoxIndex::MAX
+                {
+                    scratch.moves.push((player_pos, dir));
+                }
+            } else if sd.map.grid()[new_player_pos] != MapCell::Wall {
+                if sd.variant == Variant::Pull {
+                    scratch.moves.push((player_pos, dir));
+                }
+                if scratch.reach(new_player_pos) {
+                    // new_pos is empty and not yet visited
+                    scratch.to_visit.push(new_player_pos);
+                    if new_player_pos < top_left {
+                        top_left = new_player_pos;
+                    }
+                }
+            }
+        }
+    }
+
+    for i in 0..scratch.moves.len() {
+        let (player_pos, dir) = scratch.moves[i];
+        let new_player_pos = player_pos + dir;
+        if sd.variant != Variant::Pull {
+            let push_dest = new_player_pos + dir;
+            let box_index = scratch.box_grid[new_player_pos];
+            if let Some(new_boxes) = try_push(sd, cur_state, box_index, push_dest, pruned) {
+                // new state to explore
+                let box_to = kept_box(cur_state, &new_boxes, push_dest);
+                let norm_player_pos =
+                    match pushed_area_top_left(sd, scratch, top_left, new_player_pos, box_to) {
+                        Some(pos) => pos,
+                        None => scratch.normalized_pos_after_move(
+                            &sd.map,
+                            new_player_pos,
+                            new_player_pos,
+                            box_to,
+                        ),
+                    };
+                let mut state = State::new(norm_player_pos, new_boxes);
+                if track_pushes {
+                    state.last_push = Some((push_dest, dir));
+                }
+                let state = arena.alloc(state);
+                let h = heuristic(sd, state);
+                new_state(state, 1, h);
+            }
+        } else if let Some(new_boxes) =
+            pull_box(sd, cur_state, &scratch.box_grid, player_pos, dir, pruned)
+        {
+            // the box moves into the reachable area and can split it so it has to be searched again
+            let box_to = kept_box(cur_state, &new_boxes, player_pos);
+            let norm_player_pos = scratch.normalized_pos_after_move(
+                &sd.map,
+                new_player_pos,
+                player_pos - dir,
+                box_to,
+            );
+            let mut state = State::new(norm_player_pos, new_boxes);
+            if track_pushes {
+                state.last_push = Some((player_pos, dir));
+            }
+            let state = arena.alloc(state);
+            let h = heuristic(sd, state);
+            new_state(state, 1, h);
+        }
+    }
+
+    scratch.clear_boxes(&cur_state.boxes);
+}
+
+/// In `Variant::Multiban` levels with more than one player, each player can push
+/// and the others block it like boxes. Calls `new_state` the same way as `expand_bfs`.
+///
+/// Walking without pushing also creates new states (with 0 pushes)
+/// because players can be in the way of each other's paths and pushes.
+fn expand_players<'a, M>(
+    sd: &StaticData<M>,
+    cur_state: &State,
+    arena: &'a Arena<State>,
+    pruned: &mut PruningStats,
+    track_pushes: bool,
+    scratch: &mut Scratch,
+    mut new_state: impl FnMut(&'a State, u16, u16, u16),
+) where
+    M: Map,
+    Solver<M>: SolverTrait<M = M>,
+{
+    scratch.set_boxes(&cur_state.boxes);
+    // multiban is rare enough that these aren't worth keeping in the scratch buffers
+    let mut player_grid = sd.map.grid().scratchpad();
+    for player_pos in cur_state.players() {
+        player_grid[player_pos] = true;
+    }
+    let cur_h = heuristic(sd, cur_state);
+
+    for (index, start_pos) in cur_state.players().enumerate() {
+        scratch.start_search();
+        scratch.reach(start_pos);
+        let mut walks = Vec::new();
+
+        scratch.to_visit_steps.clear();
+        scratch.to_visit_steps.push_back((start_pos, 0));
+
+        while let Some((player_pos, steps)) = scratch.to_visit_steps.pop_front() {
+            for &dir in &DIRECTIONS {
+                let new_player_pos = player_pos + dir;
+                let box_index = scratch.box_grid[new_player_pos];
+                if new_player_pos != start_pos && player_grid[new_player_pos] {
+                    continue;
+                }
+                if box_index < BoxIndex::MAX {
+                    let push_dest = new_player_pos + dir;
+                    let new_boxes = if scratch.box_grid[push_dest] == BoxIndex::MAX
+                        && !player_grid[push_dest]
+                    {
+                        try_push(sd, cur_state, box_index, push_dest, pruned)
+                    } else {
+                        None
+                    };
+                    if let Some(new_boxes) = new_boxes {
+                        let mut state = cur_state.moved(index, new_player_pos, new_boxes);
+                        state.sort_players();
+                        if track_pushes {
+                            state.last_push = Some((push_dest, dir));
+                        }
+                        let state = arena.alloc(state);
+                        let h = heuristic(sd, state);
+                        new_state(state, steps + 1, 1, h);
+                    }
+                } else if sd.map.grid()[new_player_pos] != MapCell::Wall
+                    && scratch.reach(new_player_pos)
+                {
+                    scratch
+                        .to_visit_steps
+                        .push_back((new_player_pos, steps + 1));
+                    walks.push((new_player_pos, steps + 1));
+                }
+            }
+        }
+
+        for (player_pos, steps) in walks {
+            let mut state = cur_state.moved(index, player_pos, cur_state.boxes.clone());
+            state.sort_players();
+            new_state(arena.alloc(state), steps, 0, cur_h);
+        }
+    }
+
+    scratch.clear_boxes(&cur_state.boxes);
+}
+
+/// The normalized player position after pushing the box from `box_from` to `box_to`
+/// if it follows from the area reached before the push (with top-left square `top_left`).
+///
+/// When the box lands outside of the area, the area stays connected and only gains `box_from`
+/// unless the box was blocking the way to more squares.
+/// In the remaining cases the caller has to search the new area.
+fn pushed_area_top_left<M: Map>(
+    sd: &StaticData<M>,
+    scratch: &Scratch,
+    top_left: Pos,
+    box_from: Pos,
+    box_to: Option<Pos>,
+) -> Option<Pos> {
+    // a removed box frees its destination which can lead anywhere
+    let box_to = box_to?;
+    if scratch.reached.is_marked(box_to) {
+        return None;
+    }
+    let opens_new_squares = box_from.neighbors().iter().any(|&pos| {
+        pos != box_to
+            && sd.map.grid()[pos] != MapCell::Wall
+            && scratch.box_grid[pos] == BoxIndex::MAX
+            && !scratch.reached.is_marked(pos)
+    });
+    if opens_new_squares {
+        None
+    } else {
+        Some(top_left.min(box_from))
+    }
+}
+
+/// `Some(box_to)` unless the moved box was removed by a remover
+fn kept_box(cur_state: &State, new_boxes: &[Pos], box_to: Pos) -> Option<Pos> {
+    if new_boxes.len() == cur_state.boxes.len() {
+        Some(box_to)
+    } else {
+        None
+    }
+}
+
+/// The boxes after pushing one to `push_dest` unless a pruning rejects it.
+/// The caller checks the destination is free.
+fn try_push<M>(
+    sd: &StaticData<M>,
+    state: &State,
+    box_index: BoxIndex,
+    push_dest: Pos,
+    pruned: &mut PruningStats,
+) -> Option<Boxes>
+where
+    M: Map,
+    Solver<M>: SolverTrait<M = M>,
+{
+    if sd.closest_push_dists[push_dest].is_none() {
+        pruned.dead_squares += 1;
+        return None;
+    }
+    let new_boxes = Solver::<M>::push_box(sd, state, box_index, push_dest);
+    if new_boxes.is_none() {
+        pruned.removers += 1;
+    }
+    new_boxes
+}
+
+/// In `Variant::Pull`, the boxes after the player steps from `player_pos` in `dir`
+/// dragging the box behind it, if there is one and it doesn't end up on a dead square.
+/// The caller checks the player's destination is free.
+fn pull_box<M>(
+    sd: &StaticData<M>,
+    state: &State,
+    box_grid: &Vec2d<BoxIndex>,
+    player_pos: Pos,
+    dir: Dir,
+    pruned: &mut PruningStats,
+) -> Option<Boxes>
+where
+    M: Map,
+    Solver<M>: SolverTrait<M = M>,
+{
+    let box_index = box_grid[player_pos - dir];
+    if sd.variant == Variant::Pull && box_index < BoxIndex::MAX {
+        try_push(sd, state, box_index, player_pos, pruned)
+    } else {
+        None
+    }
+}
+
+fn heuristic<M: Map>(sd: &StaticData<M>, state: &State) -> u16 {
+    if sd.heuristic == Heuristic::GoalPullDists {
+        return goal_pull_heuristic(sd, state);
+    }
+
+    // thanks to precomputed distances, this is the same for goals and remover
+    let mut goal_dist_sum = 0;
+
+    for &box_pos in &state.boxes {
+        // with more players, any of them could do the next push
+        let side_dist = match sd.side_dists[box_pos] {
+            Some(ref side_dists) if state.player_cnt() == 1 => side_dists.dist(state.player_pos),
+            _ => None,
+        };
+        // no part with a goal means the state is a dead end, any admissible value works
+        let dist = side_dist
+            .or(sd.closest_push_dists[box_pos])
+            .expect("Box on unreachable cell");
+        goal_dist_sum += match sd.heuristic {
+            Heuristic::PushDists => dist,
+            // every box not on a goal needs at least one more push
+            Heuristic::MisplacedBoxes => dist.min(1),
+            Heuristic::GoalPullDists => unreachable!(),
+        };
+    }
+
+    goal_dist_sum
+}
+
+fn goal_pull_heuristic<M: Map>(sd: &StaticData<M>, state: &State) -> u16 {
+    let dists = &sd.goal_pull_dists;
+    let closest_sum = || {
+        state
+            .boxes
+            .iter()
+            .map(|&box_pos| dists.iter().filter_map(|d| d[box_pos]).min().unwrap_or(0))
+            .sum()
+    };
+
+    // the distances are to goals and removers, a remover can take any number of boxes
+    if dists.len() != sd.map.goal_cnt() {
+        return closest_sum();
+    }
+    let costs: Vec<Vec<_>> = state
+        .boxes
+        .iter()
+        .map(|&box_pos| dists.iter().map(|d| d[box_pos]).collect())
+        .collect();
+    match preprocessing::min_assignment(&costs, dists.len()) {
+        Some(total) => u16::try_from(total).unwrap_or(u16::MAX),
+        // the state is a dead end, any admissible value works
+        None => closest_sum(),
+    }
+}
+
+fn normalized_pos<M: Map>(map: &M, player_pos: Pos, boxes: &[Pos]) -> Pos {
+    let mut scratch = Scratch::new(map);
+    scratch.set_boxes(boxes);
+    scratch.normalized_pos(map, player_pos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashSet;
+
+    use crate::config::{ParseOptions, TieBreak};
+
+    #[test]
+    fn pos_normalization() {
+        let levels = [
+            r"
+#####
+#   #
+# @ #
+#   #
+#####
+",
+            r"
+#####
+##  #
+# @ #
+#   #
+#####
+",
+            r"
+#####
+#$  #
+# @ #
+#   #
+#####
+",
+            r"
+#####
 #*  #
 # @ #
 #   #
@@ -784,6 +2793,39 @@ mod tests {
         }
     }
 
+    #[test]
+    fn expand_normalizes() {
+        // pushes which open new areas, split the area or keep it the same
+        let level = r"
+#########
+#   #   #
+# @ $   #
+#   #  $#
+## $#. .#
+#.   ####
+#########
+";
+        let level: Level = level.parse().unwrap();
+        let solver = Solver::new_with_goals(level.goal_map(), &level.state, Variant::Push).unwrap();
+        let states = Arena::new();
+        let mut new_states = Vec::new();
+        PushLogic::expand(
+            &solver.sd,
+            &solver.sd.initial_state,
+            &states,
+            &mut PruningStats::default(),
+            &mut Scratch::new(&solver.sd.map),
+            &mut new_states,
+        );
+        // pushing the bottom box down is pruned - the dead end right of it is filled
+        // so the box can't be pushed left towards the goal anymore
+        assert_eq!(new_states.len(), 2);
+        for (state, _, _) in new_states {
+            let np = normalized_pos(&level.map, state.player_pos, &state.boxes);
+            assert_eq!(state.player_pos, np, "State:\n{state:?}");
+        }
+    }
+
     #[test]
     fn incomplete_border() {
         let level0 = r"
@@ -814,12 +2856,43 @@ mod tests {
         for level in &[level0, level1, level2, level3, level4] {
             let level: Level = level.parse().unwrap();
             assert_eq!(
-                Solver::new_with_goals(level.goal_map(), &level.state).unwrap_err(),
+                Solver::new_with_goals(level.goal_map(), &level.state, Variant::Push).unwrap_err(),
                 SolverErr::IncompleteBorder
             );
         }
     }
 
+    #[test]
+    fn decoration() {
+        // frames around the parts the player can't reach are incomplete or missing
+        let level0 = r"
+  #####
+  #@$.#
+  #####
+
+ # * #
+#   #
+  -
+";
+        let level1 = r"
+#######    #
+#@$  .#   # #
+####### #
+   ###
+  #   #   *
+";
+        // squares missing at the end of short rows are outside
+        let level2 = "######\n#@$ .# # #\n######\n";
+        for level in &[level0, level1, level2] {
+            let level: Level = level.parse().unwrap();
+            let solver_ok = level.solve(Method::Pushes, false).unwrap();
+            assert!(solver_ok.moves.is_some());
+            assert!(level.map_analysis().is_ok());
+            assert!(level.normalize().is_ok());
+            assert!(level.estimate_difficulty().is_ok());
+        }
+    }
+
     #[test]
     fn unreachable_boxes_goals() {
         let level = r"
@@ -829,7 +2902,7 @@ mod tests {
 ";
         let level: Level = level.parse().unwrap();
         assert_eq!(
-            Solver::new_with_goals(level.goal_map(), &level.state).unwrap_err(),
+            Solver::new_with_goals(level.goal_map(), &level.state, Variant::Push).unwrap_err(),
             SolverErr::UnreachableBoxes
         );
     }
@@ -843,7 +2916,7 @@ mod tests {
 ";
         let level: Level = level.parse().unwrap();
         assert_eq!(
-            Solver::new_with_remover(level.remover_map(), &level.state).unwrap_err(),
+            Solver::new_with_remover(level.remover_map(), &level.state, Variant::Push).unwrap_err(),
             SolverErr::UnreachableBoxes
         );
     }
@@ -857,7 +2930,7 @@ mod tests {
 ";
         let level: Level = level.parse().unwrap();
         assert_eq!(
-            Solver::new_with_goals(level.goal_map(), &level.state).unwrap_err(),
+            Solver::new_with_goals(level.goal_map(), &level.state, Variant::Push).unwrap_err(),
             SolverErr::UnreachableGoals
         );
     }
@@ -871,39 +2944,278 @@ mod tests {
 ";
         let level: Level = level.parse().unwrap();
         assert_eq!(
-            Solver::new_with_remover(level.remover_map(), &level.state).unwrap_err(),
+            Solver::new_with_remover(level.remover_map(), &level.state, Variant::Push).unwrap_err(),
             SolverErr::UnreachableRemover
         );
     }
 
     #[test]
-    fn too_many() {
+    fn unreachable_remover_custom() {
         let level = r"
-##################
-#****************#
-#****************#
-#****************#
-#****************#
-#****************#
-#****************#
-#****************#
-#****************#
-#****************#
-#****************#
-#****************#
-#****************#
-#****************#
-#****************#
-#****************#
-#****************#
-#@################
-###
+<><><><><><><><>
+<>P B B <>   R<>
+<><><><><><><><>
 ";
         let level: Level = level.parse().unwrap();
+        assert_eq!(
+            Solver::new_with_remover(level.remover_map(), &level.state, Variant::Push).unwrap_err(),
+            SolverErr::UnreachableRemover
+        );
+    }
+
+    #[test]
+    fn multiple_removers() {
+        let level: Level = "#########\n#r  $@ r#\n#########".parse().unwrap();
+        let moves = level
+            .solve(Method::MovesPushes, false)
+            .unwrap()
+            .moves
+            .unwrap();
+        assert_eq!(moves.to_string(), "LLL");
+
+        // the box could go into either remover, the closer one is used
+        let level: Level = "#####\n# r #\n#r$ #\n# @ #\n#####".parse().unwrap();
+        let moves = level
+            .solve(Method::MovesPushes, false)
+            .unwrap()
+            .moves
+            .unwrap();
+        assert_eq!(moves.to_string(), "U");
+    }
+
+    #[test]
+    fn multiple_removers_custom() {
+        let level: Level = "<><><><><><><><><>\n<> R    B P    R<>\n<><><><><><><><><>"
+            .parse()
+            .unwrap();
+        let moves = level
+            .solve(Method::MovesPushes, false)
+            .unwrap()
+            .moves
+            .unwrap();
+        assert_eq!(moves.to_string(), "LLL");
+
+        let level: Level = "<><><><><>\n<>   R  <>\n<> RB   <>\n<>  P   <>\n<><><><><>"
+            .parse()
+            .unwrap();
+        let moves = level
+            .solve(Method::MovesPushes, false)
+            .unwrap()
+            .moves
+            .unwrap();
+        assert_eq!(moves.to_string(), "U");
+    }
+
+    #[test]
+    fn extra_goals() {
+        let xsb = "#######\n#. $@.#\n#######";
+        let level: Level = xsb.parse().unwrap();
+        assert_eq!(
+            level.solve(Method::Pushes, false).unwrap_err(),
+            SolverErr::DiffBoxesGoals
+        );
+
+        let options = ParseOptions {
+            extra_goals: true,
+            ..ParseOptions::default()
+        };
+        let level = Level::parse_with_options(xsb, options).unwrap();
+        assert!(level.extra_goals());
+        let moves = level
+            .solve(Method::MovesPushes, false)
+            .unwrap()
+            .moves
+            .unwrap();
+        assert_eq!(moves.to_string(), "LL");
+        assert!(level.verify_solution(&moves).is_ok());
+        assert!(level.verify_solution(&Moves::default()).is_err());
+
+        // boxes still can't outnumber goals
+        let level = Level::parse_with_options("######\n#.$$@#\n######", options).unwrap();
+        assert_eq!(
+            level.solve(Method::Pushes, false).unwrap_err(),
+            SolverErr::DiffBoxesGoals
+        );
+    }
+
+    #[test]
+    fn tie_breaking() {
+        let level: Level = r"
+#######
+#@    #
+#     #
+#  $  #
+#     #
+#    .#
+#######
+"
+        .parse()
+        .unwrap();
+        let solve = |seed| {
+            let options = SolverOptions {
+                seed,
+                ..SolverOptions::default()
+            };
+            level.solve_with_options(Method::Moves, &options).unwrap()
+        };
+
+        let default = solve(None).moves.unwrap();
+        assert_eq!(solve(None).moves.unwrap(), default);
+        let mut solutions = HashSet::new();
+        for seed in 0..10 {
+            let random = solve(Some(seed)).moves.unwrap();
+            assert_eq!(solve(Some(seed)).moves.unwrap(), random);
+            assert_eq!(random.move_cnt(), default.move_cnt());
+            assert_eq!(random.push_cnt(), default.push_cnt());
+            solutions.insert(random.to_string());
+        }
+        assert!(solutions.len() > 1);
+
+        // diving deeper first visits fewer states before the solution on an open level
+        let solve = |tie_break| {
+            let options = SolverOptions {
+                tie_break,
+                ..SolverOptions::default()
+            };
+            level.solve_with_options(Method::Pushes, &options).unwrap()
+        };
+        let deeper = solve(TieBreak::Deeper);
+        let shallower = solve(TieBreak::Shallower);
+        let (deeper_moves, shallower_moves) = (deeper.moves.unwrap(), shallower.moves.unwrap());
+        assert_eq!(deeper_moves.push_cnt(), shallower_moves.push_cnt());
+        assert!(
+            deeper.stats.total_unique_visited() < shallower.stats.total_unique_visited(),
+            "{} {}",
+            deeper.stats.total_unique_visited(),
+            shallower.stats.total_unique_visited()
+        );
+    }
+
+    #[test]
+    fn eta() {
+        let mut stats = Stats::new();
+        assert_eq!(stats.states_per_sec(), None);
+        assert_eq!(stats.eta(10), None);
 
-        let err = Solver::new_with_goals(level.goal_map(), &level.state).unwrap_err();
+        // the number of visited states doubles with every new depth
+        for depth in [0, 1, 1, 2, 2, 2, 2, 3] {
+            stats.add_unique_visited(depth, 0, depth);
+        }
+        stats.set_elapsed(Duration::from_secs(2));
+        assert_eq!(stats.states_per_sec(), Some(4.0));
+        // reaching depth 5 should take 32 states total
+        let eta = stats.eta(5).unwrap().as_secs_f64();
+        assert!((eta - 6.0).abs() < 0.001, "{}", eta);
+        assert_eq!(stats.eta(3), Some(Duration::ZERO));
+
+        assert_eq!(format_duration(Duration::from_secs(59)), "59s");
+        assert_eq!(format_duration(Duration::from_secs(3 * 60 + 5)), "3m 5s");
+        assert_eq!(
+            format_duration(Duration::from_secs(2 * 86400 + 3700)),
+            "2d 1h"
+        );
+        assert_eq!(
+            format_duration(Duration::from_hours(400 * 24)),
+            "more than a year"
+        );
+    }
+
+    #[test]
+    fn limits() {
+        let level: Level = "######\n#@$ .#\n######".parse().unwrap();
+        let solve = |max_depth, max_nodes| {
+            let options = SolverOptions {
+                max_depth,
+                max_nodes,
+                ..SolverOptions::default()
+            };
+            level.solve_with_options(Method::Pushes, &options).unwrap()
+        };
+
+        let solver_ok = solve(Some(1), None);
+        assert_eq!(solver_ok.outcome(), SearchOutcome::LimitReached);
+        assert!(solver_ok.moves.is_none());
+        assert!(solver_ok.unsolvable.is_none());
+        assert_eq!(
+            solver_ok.limit_reached,
+            Some(LimitReached {
+                lower_bound: 2,
+                deepest: 1
+            })
+        );
+        let solver_ok = solve(Some(2), None);
+        assert_eq!(solver_ok.outcome(), SearchOutcome::Solved);
+        assert_eq!(solver_ok.moves.unwrap().to_string(), "RR");
+        assert!(solver_ok.limit_reached.is_none());
+
+        let solver_ok = solve(None, Some(2));
+        assert_eq!(solver_ok.outcome(), SearchOutcome::LimitReached);
+        assert!(solver_ok.moves.is_none());
+        assert_eq!(solver_ok.stats.total_unique_visited(), 2);
+        assert_eq!(
+            solver_ok.limit_reached,
+            Some(LimitReached {
+                lower_bound: 2,
+                deepest: 1
+            })
+        );
+
+        // exhausting the search without pruning anything is still a proof
+        let level: Level = "#######\n#@$$..#\n#######".parse().unwrap();
+        let options = SolverOptions {
+            max_depth: Some(10),
+            ..SolverOptions::default()
+        };
+        let solver_ok = level.solve_with_options(Method::Pushes, &options).unwrap();
+        assert_eq!(solver_ok.outcome(), SearchOutcome::ProvenUnsolvable);
+        assert!(solver_ok.moves.is_none());
+        assert!(solver_ok.limit_reached.is_none());
+        assert!(solver_ok.unsolvable.is_some());
+    }
+
+    #[test]
+    fn hybrid() {
+        // the extra box goes into the remover
+        let level: Level = "########\n#r$@ $.#\n########".parse().unwrap();
+        let moves = level
+            .solve(Method::MovesPushes, false)
+            .unwrap()
+            .moves
+            .unwrap();
+        assert_eq!(moves.to_string(), "LrrR");
+        assert!(level.verify_solution(&moves).is_ok());
+
+        // the only box is needed for the goal
+        let level: Level = "#######\n#r $@.#\n#######".parse().unwrap();
+        assert!(level.solve(Method::Pushes, false).unwrap().moves.is_none());
+
+        let level: Level = "#######\n#r@$..#\n#######".parse().unwrap();
+        assert_eq!(
+            level.solve(Method::Pushes, false).unwrap_err(),
+            SolverErr::DiffBoxesGoals
+        );
+    }
+
+    #[test]
+    fn too_many() {
+        // a square of boxes on goals with more boxes than allowed
+        let side = (1..=MAX_BOXES)
+            .find(|side| side * side > MAX_BOXES)
+            .unwrap();
+        let mut rows = vec!["#".repeat(side + 2)];
+        rows.extend((0..side).map(|_| format!("#{}#", "*".repeat(side))));
+        rows.push(format!("#@{}", "#".repeat(side)));
+        rows.push("###".to_owned());
+        let level = rows.join("\n");
+        let level: Level = level.parse().unwrap();
+
+        let err =
+            Solver::new_with_goals(level.goal_map(), &level.state, Variant::Push).unwrap_err();
         assert_eq!(err, SolverErr::TooMany);
-        assert_eq!(err.to_string(), "More than 255 reachable boxes or goals");
+        assert_eq!(
+            err.to_string(),
+            format!("More than {MAX_BOXES} reachable boxes or goals")
+        );
     }
 
     #[test]
@@ -915,7 +3227,7 @@ mod tests {
 ";
         let level: Level = level.parse().unwrap();
         assert_eq!(
-            Solver::new_with_goals(level.goal_map(), &level.state).unwrap_err(),
+            Solver::new_with_goals(level.goal_map(), &level.state, Variant::Push).unwrap_err(),
             SolverErr::DiffBoxesGoals
         );
     }
@@ -930,7 +3242,7 @@ mod tests {
         .trim_start_matches('\n');
 
         let level: Level = level.parse().unwrap();
-        let solver = Solver::new_with_goals(level.goal_map(), &level.state).unwrap();
+        let solver = Solver::new_with_goals(level.goal_map(), &level.state, Variant::Push).unwrap();
 
         let processed_empty_level: &str = r"
 #######
@@ -942,8 +3254,8 @@ mod tests {
 
         assert_eq!(solver.sd.initial_state.player_pos, Pos { r: 1, c: 1 });
         assert_eq!(
-            solver.sd.initial_state.boxes,
-            vec![Pos { r: 1, c: 2 }, Pos { r: 1, c: 4 }]
+            solver.sd.initial_state.boxes[..],
+            [Pos { r: 1, c: 2 }, Pos { r: 1, c: 4 }]
         );
     }
 
@@ -962,12 +3274,53 @@ mod tests {
 <><><><><>
 ";
         let level: Level = level.parse().unwrap();
-        let solver = Solver::new_with_goals(level.goal_map(), &level.state).unwrap();
+        let solver = Solver::new_with_goals(level.goal_map(), &level.state, Variant::Push).unwrap();
         let states = Arena::new();
-        let neighbor_states = PushLogic::expand(&solver.sd, &solver.sd.initial_state, &states);
+        let mut neighbor_states = Vec::new();
+        PushLogic::expand(
+            &solver.sd,
+            &solver.sd.initial_state,
+            &states,
+            &mut PruningStats::default(),
+            &mut Scratch::new(&solver.sd.map),
+            &mut neighbor_states,
+        );
         assert_eq!(neighbor_states.len(), 2);
     }
 
+    #[test]
+    fn scratch_reuse() {
+        let level: Level = "#####\n#@$.#\n#####".parse().unwrap();
+        let mut scratch = Scratch::new(level.goal_map());
+        let pos = Pos::new(1, 1);
+
+        scratch.start_search();
+        assert!(scratch.reach(pos));
+        assert!(!scratch.reach(pos));
+        scratch.start_search();
+        assert!(scratch.reach(pos));
+
+        // squares reached before the generation wraps around are forgotten
+        scratch.reached.generation = u32::MAX;
+        scratch.reached.grid[pos] = 1;
+        scratch.start_search();
+        assert!(scratch.reach(pos));
+
+        scratch.set_boxes(&level.state.boxes);
+        assert_eq!(scratch.box_grid[Pos::new(1, 2)], 0);
+        let norm_pos = scratch.normalized_pos_after_move(
+            level.goal_map(),
+            Pos::new(1, 2),
+            Pos::new(1, 2),
+            Some(Pos::new(1, 3)),
+        );
+        assert_eq!(norm_pos, pos);
+        assert_eq!(scratch.box_grid[Pos::new(1, 2)], 0);
+        assert_eq!(scratch.box_grid[Pos::new(1, 3)], BoxIndex::MAX);
+        scratch.clear_boxes(&level.state.boxes);
+        assert_eq!(scratch.box_grid[Pos::new(1, 2)], BoxIndex::MAX);
+    }
+
     #[test]
     fn expand_move1() {
         let level = r"
@@ -979,9 +3332,17 @@ mod tests {
  ####
 ";
         let level: Level = level.parse().unwrap();
-        let solver = Solver::new_with_goals(level.goal_map(), &level.state).unwrap();
+        let solver = Solver::new_with_goals(level.goal_map(), &level.state, Variant::Push).unwrap();
         let states = Arena::new();
-        let neighbor_states = MoveLogic::expand(&solver.sd, &solver.sd.initial_state, &states);
+        let mut neighbor_states = Vec::new();
+        MoveLogic::expand(
+            &solver.sd,
+            &solver.sd.initial_state,
+            &states,
+            &mut PruningStats::default(),
+            &mut Scratch::new(&solver.sd.map),
+            &mut neighbor_states,
+        );
         assert_eq!(neighbor_states.len(), 7);
     }
 
@@ -996,9 +3357,957 @@ mod tests {
  ####
 ";
         let level: Level = level.parse().unwrap();
-        let solver = Solver::new_with_goals(level.goal_map(), &level.state).unwrap();
+        let solver = Solver::new_with_goals(level.goal_map(), &level.state, Variant::Push).unwrap();
         let states = Arena::new();
-        let neighbor_states = MoveLogic::expand(&solver.sd, &solver.sd.initial_state, &states);
+        let mut neighbor_states = Vec::new();
+        MoveLogic::expand(
+            &solver.sd,
+            &solver.sd.initial_state,
+            &states,
+            &mut PruningStats::default(),
+            &mut Scratch::new(&solver.sd.map),
+            &mut neighbor_states,
+        );
         assert_eq!(neighbor_states.len(), 4);
     }
+
+    #[test]
+    fn all_methods() {
+        let level = r"
+#######
+#@ $ .#
+# $  .#
+#######
+";
+        let level: Level = level.parse().unwrap();
+
+        for &parallel in &[false, true] {
+            let options = SolverOptions {
+                parallel,
+                ..SolverOptions::default()
+            };
+            let results = level.solve_all_methods(&options).unwrap();
+            assert_eq!(results.keys().copied().collect::<Vec<_>>(), Method::OPTIMAL);
+            for (&method, result) in &results {
+                let expected = level.solve(method, false).unwrap();
+                assert_eq!(result.moves, expected.moves);
+                assert_eq!(result.stats, expected.stats);
+            }
+        }
+    }
+
+    #[test]
+    fn dead_squares() {
+        // the top row is dead because of push dists in the first level
+        // and because of the corners in the second which has an incomplete border
+        let complete = r"
+######
+#    #
+#@$ .#
+######";
+        let incomplete = r"
+######
+#    #
+#@$ .#
+#  ###
+###";
+        let complete: Level = complete.parse().unwrap();
+        assert_eq!(
+            complete.dead_squares().to_string(),
+            "000000\n011110\n010000\n000000\n"
+        );
+        let incomplete: Level = incomplete.parse().unwrap();
+        assert_eq!(
+            incomplete.dead_squares().to_string(),
+            "000000\n011110\n010000\n011000\n000000\n"
+        );
+        assert_eq!(incomplete.dead_squares().get(3, 1), Some(&true));
+        assert_eq!(incomplete.dead_squares().get(5, 1), None);
+    }
+
+    #[test]
+    fn status_output() {
+        let level: Level = "#######\n#@ $ .#\n#######".parse().unwrap();
+        let options = SolverOptions {
+            print_status: true,
+            ..SolverOptions::default()
+        };
+        let mut output = Vec::new();
+        let solver_ok = level
+            .solve_with_output(Method::Pushes, &options, &mut |_| true, &mut output)
+            .unwrap();
+        assert_eq!(solver_ok.moves.unwrap().push_cnt(), 2);
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("Visited new depth: 0\n"));
+        assert!(output.contains("Visited new depth: 2\n"));
+
+        let mut output = Vec::new();
+        level
+            .solve_with_output(
+                Method::Pushes,
+                &SolverOptions::default(),
+                &mut |_| true,
+                &mut output,
+            )
+            .unwrap();
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn pruning_stats() {
+        // pushing the box left puts it in a corner
+        let level: Level = "######\n#    #\n#@$ .#\n######".parse().unwrap();
+        let pruned = level.solve(Method::Pushes, false).unwrap().stats.pruned();
+        assert!(pruned.dead_squares > 0);
+        assert_eq!(pruned.removers, 0);
+        assert_eq!(pruned.max_depth, 0);
+        assert_eq!(pruned.total(), pruned.dead_squares);
+
+        let options = SolverOptions {
+            max_depth: Some(1),
+            ..SolverOptions::default()
+        };
+        let solver_ok = level.solve_with_options(Method::Pushes, &options).unwrap();
+        assert_eq!(solver_ok.moves, None);
+        assert!(solver_ok.stats.pruned().max_depth > 0);
+    }
+
+    #[test]
+    fn near_duplicates() {
+        // pushing the left box right and back leaves the player on the other side of it
+        let level: Level = r"
+########
+#      #
+# $@$  #
+#    . #
+#   .  #
+########
+"
+        .parse()
+        .unwrap();
+        for &method in &[Method::MovesPushes, Method::Moves, Method::PushesMoves] {
+            let solver_ok = level.solve(method, false).unwrap();
+            assert!(solver_ok.stats.pruned().near_duplicates > 0, "{}", method);
+            // the searches of all solutions don't prune near duplicates
+            let optimal = level.optimal_solutions(method, 1).unwrap();
+            let moves = solver_ok.moves.unwrap();
+            let expected = &optimal.solutions[0];
+            assert_eq!(moves.move_cnt(), expected.move_cnt(), "{method}");
+            assert_eq!(moves.push_cnt(), expected.push_cnt(), "{method}");
+        }
+        let pruned = level.solve(Method::Pushes, false).unwrap().stats.pruned();
+        assert_eq!(pruned.near_duplicates, 0);
+    }
+
+    #[test]
+    fn lower_bound() {
+        let level: Level = "#######\n#@ $ .#\n#######".parse().unwrap();
+        assert_eq!(level.lower_bound(Metric::Pushes), Some(2));
+        assert_eq!(level.lower_bound(Metric::Moves), Some(3));
+
+        let solved: Level = "#####\n#@ *#\n#####".parse().unwrap();
+        assert_eq!(solved.lower_bound(Metric::Pushes), Some(0));
+        assert_eq!(solved.lower_bound(Metric::Moves), Some(0));
+
+        let invalid: Level = "#####\n#@$.\n#####".parse().unwrap();
+        assert_eq!(invalid.lower_bound(Metric::Pushes), None);
+    }
+
+    #[test]
+    fn interrupted() {
+        let level: Level = "#######\n#@ $ .#\n#######".parse().unwrap();
+        let mut calls = 0;
+        let solver_ok = level
+            .solve_with_progress(Method::Pushes, &SolverOptions::default(), &mut |_| {
+                calls += 1;
+                false
+            })
+            .unwrap();
+        assert_eq!(calls, 1);
+        assert!(solver_ok.interrupted);
+        assert_eq!(solver_ok.moves, None);
+        assert_eq!(solver_ok.unsolvable, None);
+
+        let solver_ok = level.solve(Method::Pushes, false).unwrap();
+        assert!(!solver_ok.interrupted);
+        assert!(solver_ok.moves.is_some());
+    }
+
+    #[cfg(feature = "open-list-stats")]
+    #[test]
+    fn open_list_in_progress() {
+        let level: Level = "########\n#@ $  .#\n#  $  .#\n########".parse().unwrap();
+        let mut snapshots = Vec::new();
+        level
+            .solve_with_progress(
+                Method::MovesPushes,
+                &SolverOptions::default(),
+                &mut |stats| {
+                    snapshots.push(stats.open_list().cloned());
+                    true
+                },
+            )
+            .unwrap();
+        // every report so far was at a new depth
+        assert!(snapshots.len() > 1);
+        assert!(snapshots.iter().all(Option::is_some));
+        let last = snapshots.last().unwrap().as_ref().unwrap();
+        assert_eq!(last.f().values().sum::<usize>(), last.len());
+        assert_eq!(last.f().keys().next().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn unsolvable_reasons() {
+        let level: Level = "#######\n#@ $ .#\n#######".parse().unwrap();
+        assert_eq!(level.solve(Method::Pushes, false).unwrap().unsolvable, None);
+
+        let level: Level = "#######\n#@  .$#\n#######".parse().unwrap();
+        let solver_ok = level.solve(Method::Pushes, false).unwrap();
+        assert_eq!(solver_ok.moves, None);
+        assert_eq!(
+            solver_ok.unsolvable,
+            Some(UnsolvableReason::DeadSquare { box_pos: (1, 5) })
+        );
+
+        // both boxes can only be pushed along the top wall to the same goal
+        let level: Level = r"
+#######
+#. $$ #
+#  @  #
+#.    #
+#######"
+            .parse()
+            .unwrap();
+        let solver_ok = level.solve(Method::Pushes, false).unwrap();
+        assert_eq!(solver_ok.stats.total_created(), 0);
+        let reason = solver_ok.unsolvable.unwrap();
+        assert_eq!(
+            reason,
+            UnsolvableReason::Unmatchable {
+                boxes: vec![(1, 3), (1, 4)],
+                goals: vec![(1, 1)],
+            }
+        );
+        assert_eq!(
+            reason.to_string(),
+            "Boxes at [1, 3], [1, 4] can only reach goals at [1, 1]"
+        );
+
+        // both boxes can reach the goals but not both at once
+        let level: Level = "########\n#@$$ ..#\n########".parse().unwrap();
+        for &method in &Method::OPTIMAL {
+            let solver_ok = level.solve(method, false).unwrap();
+            assert_eq!(solver_ok.moves, None);
+            assert_eq!(solver_ok.unsolvable, Some(UnsolvableReason::Exhausted));
+        }
+    }
+
+    #[test]
+    fn memory() {
+        let level: Level = "#######\n#@ $ .#\n#######".parse().unwrap();
+        let memory = level.solve(Method::Pushes, false).unwrap().stats.memory();
+        assert!(memory.arena > 0);
+        assert!(memory.open_list > 0);
+        assert!(memory.prevs > 0);
+        assert_eq!(
+            memory.total(),
+            memory.arena + memory.open_list + memory.prevs
+        );
+        #[cfg(target_os = "linux")]
+        assert!(memory.peak_rss.is_some());
+    }
+
+    #[test]
+    fn bounded_suboptimality() {
+        let level = r"
+########
+#@ $  .#
+#  ##  #
+# $  $.#
+#.     #
+########
+";
+        let level: Level = level.parse().unwrap();
+
+        for &method in &Method::OPTIMAL {
+            let optimal = level.solve(method, false).unwrap();
+            let optimal_moves = optimal.moves.unwrap();
+            assert_eq!(optimal.suboptimality_percent, 0);
+
+            for &percent in &[10, 50, 200] {
+                let options = SolverOptions {
+                    suboptimality_percent: percent,
+                    ..SolverOptions::default()
+                };
+                let result = level.solve_with_options(method, &options).unwrap();
+                assert_eq!(result.suboptimality_percent, percent);
+                let moves = result.moves.unwrap();
+
+                let (len, optimal_len) = match method {
+                    Method::MovesPushes | Method::Moves => {
+                        (moves.move_cnt(), optimal_moves.move_cnt())
+                    }
+                    Method::PushesMoves | Method::PushesBoxLines | Method::Pushes | Method::Any => {
+                        (moves.push_cnt(), optimal_moves.push_cnt())
+                    }
+                };
+                assert!(len * 100 <= optimal_len * (100 + usize::from(percent)));
+            }
+        }
+    }
+
+    #[test]
+    fn any_profiles() {
+        let level = r"
+########
+#@ $  .#
+#  ##  #
+# $  $.#
+#.     #
+########
+";
+        let level: Level = level.parse().unwrap();
+        let optimal = level.solve(Method::Pushes, false).unwrap().moves.unwrap();
+
+        for &(profile, percent) in &[
+            (AnyProfile::Fast, 400),
+            (AnyProfile::Balanced, 100),
+            (AnyProfile::Macro, 100),
+            (AnyProfile::Thorough, 0),
+        ] {
+            let options = SolverOptions {
+                any_profile: profile,
+                ..SolverOptions::default()
+            };
+            let result = level.solve_with_options(Method::Any, &options).unwrap();
+            assert_eq!(result.suboptimality_percent, percent);
+            let pushes = result.moves.unwrap().push_cnt();
+            assert!(pushes * 100 <= optimal.push_cnt() * (100 + usize::from(percent)));
+
+            // a higher explicit suboptimality wins
+            let options = SolverOptions {
+                suboptimality_percent: 500,
+                ..options
+            };
+            let result = level.solve_with_options(Method::Any, &options).unwrap();
+            assert_eq!(result.suboptimality_percent, 500);
+        }
+
+        let result = level.solve(Method::Any, false).unwrap();
+        assert_eq!(result.suboptimality_percent, 0);
+        assert_eq!(result.moves.unwrap().push_cnt(), optimal.push_cnt());
+    }
+
+    #[test]
+    fn macro_moves() {
+        // a long tunnel into a goal room
+        let level = r"
+#########
+#       #
+#@ $ $  #
+#### ####
+#### ####
+##      #
+##      #
+##  ..  #
+##      #
+#########
+";
+        let level: Level = level.parse().unwrap();
+        let options = |any_profile| SolverOptions {
+            any_profile,
+            ..SolverOptions::default()
+        };
+        let balanced = level
+            .solve_with_options(Method::Any, &options(AnyProfile::Balanced))
+            .unwrap();
+        let with_macros = level
+            .solve_with_options(Method::Any, &options(AnyProfile::Macro))
+            .unwrap();
+        let moves = with_macros.moves.unwrap();
+        assert!(level.verify_solution(&moves).is_ok());
+        // macros cost all their pushes so the bound still holds
+        assert!(moves.push_cnt() <= balanced.moves.unwrap().push_cnt() * 2);
+        assert!(with_macros.stats.total_unique_visited() < balanced.stats.total_unique_visited());
+    }
+
+    #[test]
+    fn heuristic_stats() {
+        let level = r"
+########
+#@ $  .#
+#  ##  #
+# $  $.#
+#.     #
+########
+";
+        let level: Level = level.parse().unwrap();
+        let solver_ok = level.solve(Method::Pushes, false).unwrap();
+        let stats = solver_ok.stats.heuristic();
+        assert_eq!(
+            stats.created_by_h.iter().sum::<i32>(),
+            solver_ok.stats.total_created()
+        );
+        assert_eq!(
+            stats.visited_by_h.iter().sum::<i32>(),
+            solver_ok.stats.total_unique_visited()
+        );
+        // the goal state is the only one with h = 0 which must be visited
+        assert_eq!(stats.visited_by_h[0], 1);
+
+        // the lower bound only grows until it reaches the solution length
+        assert_eq!(stats.min_costs[0].0, 0);
+        let &(_, last) = stats.min_costs.last().unwrap();
+        assert_eq!(usize::from(last), solver_ok.moves.unwrap().push_cnt());
+        assert!(stats.min_costs.windows(2).all(|w| w[0].1 < w[1].1));
+    }
+
+    #[test]
+    fn consistent_heuristic() {
+        // the box is closer to the left goal but from the left side it can only go right
+        let level: Level = "############\n#.  $@    *#\n############".parse().unwrap();
+        let solver = Solver::new_with_goals(level.goal_map(), &level.state, Variant::Push).unwrap();
+        assert_eq!(heuristic(&solver.sd, &solver.sd.initial_state), 3);
+        let state = State::new(Pos::new(1, 3), solver.sd.initial_state.boxes.clone());
+        assert_eq!(heuristic(&solver.sd, &state), 6);
+
+        // a push could drop the heuristic by 3 so the search expanded some states
+        // before finding the shortest path to them and the result depended on tie-breaking
+        let level: Level = r"
+############
+#    #     ###
+#    #       #
+#    #$####  #
+#    r @ ##  #
+#    # #  $ ##
+###### ##$ $ #
+  # $  $ $   #
+  #    #     #
+  ############
+"
+        .parse()
+        .unwrap();
+        let pushes = level.solve(Method::Pushes, false).unwrap().moves.unwrap();
+        let pushes_moves = level
+            .solve(Method::PushesMoves, false)
+            .unwrap()
+            .moves
+            .unwrap();
+        assert_eq!(pushes.push_cnt(), 60);
+        assert_eq!(pushes_moves.push_cnt(), 60);
+    }
+
+    #[test]
+    fn goal_pull_heuristic() {
+        // both boxes are closest to the right goal
+        let level: Level = r"
+##########
+#.  @$ $.#
+#        #
+##########
+"
+        .parse()
+        .unwrap();
+        let solver = Solver::new_with_goals(level.goal_map(), &level.state, Variant::Push).unwrap();
+        assert_eq!(heuristic(&solver.sd, &solver.sd.initial_state), 4);
+        let solver = solver.with_heuristic(Heuristic::GoalPullDists);
+        assert_eq!(heuristic(&solver.sd, &solver.sd.initial_state), 5);
+
+        let options = SolverOptions {
+            heuristic: Heuristic::GoalPullDists,
+            ..SolverOptions::default()
+        };
+        for &method in &Method::OPTIMAL {
+            let expected = level.solve(method, false).unwrap().moves.unwrap();
+            let moves = level
+                .solve_with_options(method, &options)
+                .unwrap()
+                .moves
+                .unwrap();
+            assert_eq!(moves.move_cnt(), expected.move_cnt(), "{method}");
+            assert_eq!(moves.push_cnt(), expected.push_cnt(), "{method}");
+        }
+
+        // removers can take any number of boxes
+        let level: Level = "#######\n#@$  r#\n#  $  #\n#     #\n#######"
+            .parse()
+            .unwrap();
+        let moves = level
+            .solve_with_options(Method::Pushes, &options)
+            .unwrap()
+            .moves
+            .unwrap();
+        assert_eq!(moves.push_cnt(), 6);
+    }
+
+    #[test]
+    fn pushes_box_lines() {
+        let levels = [
+            r"
+#######
+#@$ . #
+# $ . #
+#######
+",
+            r"
+#######
+#     #
+#@$   #
+#    .#
+#######
+",
+            r"
+#######
+#@$ r #
+# $ r #
+#######
+",
+        ];
+        for level in &levels {
+            let level: Level = level.parse().unwrap();
+            let box_lines = |moves: &Moves| level.verify_solution(moves).unwrap().box_lines;
+            let pushes = level.solve(Method::Pushes, false).unwrap().moves.unwrap();
+            let moves = level
+                .solve(Method::PushesBoxLines, false)
+                .unwrap()
+                .moves
+                .unwrap();
+            assert_eq!(moves.push_cnt(), pushes.push_cnt(), "{level}");
+
+            // walking doesn't matter so the push-optimal solutions are all the candidates
+            let all = level.optimal_solutions(Method::Pushes, 100).unwrap();
+            assert!(all.count > 1 && all.count <= 100, "{}", level);
+            let box_lines: Vec<_> = all.solutions.iter().map(box_lines).collect();
+            let fewest = *box_lines.iter().min().unwrap();
+            assert!(box_lines.iter().any(|&lines| lines > fewest), "{}", level);
+            assert_eq!(
+                level.verify_solution(&moves).unwrap().box_lines,
+                fewest,
+                "{level}"
+            );
+        }
+    }
+
+    #[test]
+    fn lexicographic() {
+        let level = r"
+########
+#@ $  .#
+#  ##  #
+# $  $.#
+#.     #
+########
+";
+        let level: Level = level.parse().unwrap();
+        let solve = |criteria: &[Criterion]| {
+            level
+                .solve_lexicographic(criteria, &SolverOptions::default(), &mut |_| true)
+                .unwrap()
+                .moves
+                .unwrap()
+        };
+        let info = |moves: &Moves| level.verify_solution(moves).unwrap();
+
+        for &(method, criteria) in &[
+            (
+                Method::MovesPushes,
+                &[Criterion::Moves, Criterion::Pushes][..],
+            ),
+            (Method::PushesMoves, &[Criterion::Pushes, Criterion::Moves]),
+            (
+                Method::PushesBoxLines,
+                &[Criterion::Pushes, Criterion::BoxLines],
+            ),
+        ] {
+            let expected = info(&level.solve(method, false).unwrap().moves.unwrap());
+            let moves = solve(criteria);
+            let found = info(&moves);
+            assert_eq!(found.move_cnt, expected.move_cnt, "{method}");
+            assert_eq!(found.push_cnt, expected.push_cnt, "{method}");
+            assert_eq!(found.box_lines, expected.box_lines, "{method}");
+        }
+
+        // checked against all push-optimal solutions
+        let all = level.optimal_solutions(Method::Pushes, 1000).unwrap();
+        assert!(all.count <= 1000);
+        let fewest_changes = all
+            .solutions
+            .iter()
+            .map(|moves| info(moves).box_changes)
+            .min()
+            .unwrap();
+        let found = info(&solve(&[
+            Criterion::Pushes,
+            Criterion::BoxChanges,
+            Criterion::Moves,
+        ]));
+        assert_eq!(found.box_changes, fewest_changes);
+        let found_moves = all
+            .solutions
+            .iter()
+            .filter(|&moves| info(moves).box_changes == fewest_changes)
+            .map(|moves| info(moves).move_cnt)
+            .min()
+            .unwrap();
+        assert!(found.move_cnt <= found_moves);
+
+        let invalid = |criteria: &[Criterion]| {
+            level
+                .solve_lexicographic(criteria, &SolverOptions::default(), &mut |_| true)
+                .unwrap_err()
+        };
+        assert_eq!(invalid(&[]), SolverErr::InvalidCriteria);
+        assert_eq!(
+            invalid(&[Criterion::Pushes, Criterion::Moves, Criterion::Pushes]),
+            SolverErr::InvalidCriteria
+        );
+    }
+
+    #[test]
+    fn reorder_pushes() {
+        let level = r"
+########
+#@ $  .#
+#  ##  #
+# $  $.#
+#.     #
+########
+";
+        let level: Level = level.parse().unwrap();
+
+        let pushes = level.solve(Method::Pushes, false).unwrap().moves.unwrap();
+        let pushes_moves = level
+            .solve(Method::PushesMoves, false)
+            .unwrap()
+            .moves
+            .unwrap();
+        let options = SolverOptions {
+            reorder_pushes: true,
+            ..SolverOptions::default()
+        };
+        let reordered = level
+            .solve_with_options(Method::Pushes, &options)
+            .unwrap()
+            .moves
+            .unwrap();
+
+        assert!(level.verify_solution(&reordered).is_ok());
+        assert_eq!(reordered.push_cnt(), pushes.push_cnt());
+        assert!(reordered.move_cnt() <= pushes.move_cnt());
+        assert!(reordered.move_cnt() >= pushes_moves.move_cnt());
+    }
+
+    #[test]
+    fn pushes_output() {
+        let level = r"
+########
+#@ $  .#
+#  ##  #
+# $  $.#
+#.     #
+########
+";
+        let level: Level = level.parse().unwrap();
+        let options = SolverOptions {
+            output: SolveOutput::Pushes,
+            reorder_pushes: true,
+            ..SolverOptions::default()
+        };
+
+        for &method in &Method::OPTIMAL {
+            let full = level.solve(method, false).unwrap().moves.unwrap();
+            let solver_ok = level.solve_with_options(method, &options).unwrap();
+            assert_eq!(solver_ok.outcome(), SearchOutcome::Solved, "{method}");
+            assert!(solver_ok.moves.is_none(), "{}", method);
+            let pushes = solver_ok.pushes.unwrap();
+
+            let mut player_pos = level.state.player_pos;
+            let mut expected = Vec::new();
+            for mov in &full {
+                player_pos = player_pos + mov.dir;
+                if mov.is_push {
+                    expected.push(Push {
+                        box_pos: player_pos.into(),
+                        dir: mov.dir,
+                    });
+                }
+            }
+            assert_eq!(pushes, expected, "{method}");
+        }
+
+        let solved: Level = "#####\n#@* #\n#####".parse().unwrap();
+        let solver_ok = solved.solve_with_options(Method::Pushes, &options).unwrap();
+        assert_eq!(solver_ok.outcome(), SearchOutcome::Solved);
+        assert_eq!(solver_ok.pushes, Some(Vec::new()));
+    }
+
+    #[test]
+    fn metadata() {
+        let level: Level = "######\n#    #\n#@$ .#\n######".parse().unwrap();
+        let options = SolverOptions {
+            heuristic: Heuristic::GoalPullDists,
+            ..SolverOptions::default()
+        };
+        let solver_ok = level.solve_with_options(Method::Moves, &options).unwrap();
+        let metadata = solver_ok.metadata;
+        assert_eq!(metadata.method, Some(Method::Moves));
+        assert_eq!(metadata.heuristic, Heuristic::GoalPullDists);
+        assert_eq!(metadata.limit_hit, None);
+        assert!(
+            metadata.preprocessing_time + metadata.search_time + metadata.backtracking_time
+                <= metadata.wall_time
+        );
+
+        let solver_ok = level
+            .solve_with_progress(Method::Pushes, &SolverOptions::default(), &mut |_| false)
+            .unwrap();
+        assert_eq!(solver_ok.metadata.limit_hit, Some(Limit::Interrupted));
+        assert_eq!(solver_ok.metadata.backtracking_time, Duration::ZERO);
+        let options = SolverOptions {
+            max_nodes: Some(1),
+            ..SolverOptions::default()
+        };
+        assert_eq!(
+            level
+                .solve_with_options(Method::Pushes, &options)
+                .unwrap()
+                .metadata
+                .limit_hit,
+            Some(Limit::MaxNodes)
+        );
+        let options = SolverOptions {
+            max_depth: Some(1),
+            ..SolverOptions::default()
+        };
+        assert_eq!(
+            level
+                .solve_with_options(Method::Pushes, &options)
+                .unwrap()
+                .metadata
+                .limit_hit,
+            Some(Limit::MaxDepth)
+        );
+
+        let criteria = [Criterion::Pushes, Criterion::Moves];
+        let solver_ok = level
+            .solve_lexicographic(&criteria, &SolverOptions::default(), &mut |_| true)
+            .unwrap();
+        assert_eq!(solver_ok.metadata.method, None);
+        for (method, solver_ok) in level.solve_all_methods(&SolverOptions::default()).unwrap() {
+            assert_eq!(solver_ok.metadata.method, Some(method));
+        }
+    }
+
+    #[test]
+    fn solved_at_start() {
+        for path in [
+            "levels/custom/00-empty.txt",
+            "levels/custom/00-solved.txt",
+            "levels/custom/remover-00-solved.txt",
+        ] {
+            let level: Level = std::fs::read_to_string(path).unwrap().parse().unwrap();
+            for method in [
+                Method::MovesPushes,
+                Method::Moves,
+                Method::PushesMoves,
+                Method::PushesBoxLines,
+                Method::Pushes,
+                Method::Any,
+            ] {
+                let solver_ok = level.solve(method, false).unwrap();
+                assert!(solver_ok.solved_at_start, "{} {}", path, method);
+                assert_eq!(solver_ok.outcome(), SearchOutcome::Solved);
+                assert_eq!(solver_ok.moves, Some(Moves::default()));
+                assert_eq!(solver_ok.stats.total_created(), 0);
+            }
+        }
+
+        let level: Level = "#####\n#@$.#\n#####".parse().unwrap();
+        let solver_ok = level.solve(Method::Pushes, false).unwrap();
+        assert!(!solver_ok.solved_at_start);
+    }
+
+    #[test]
+    fn checkpoint_resume() {
+        let level = r"
+########
+#@ $  .#
+#  ##  #
+# $  $.#
+#.     #
+########
+";
+        let level: Level = level.parse().unwrap();
+        let options = SolverOptions {
+            checkpoint: true,
+            ..SolverOptions::default()
+        };
+
+        for &method in &Method::OPTIMAL {
+            let expected = level.solve(method, false).unwrap().moves.unwrap();
+
+            let mut calls = 0;
+            let interrupted = level
+                .solve_with_progress(method, &options, &mut |_| {
+                    calls += 1;
+                    calls < 3
+                })
+                .unwrap();
+            assert!(interrupted.interrupted);
+            let checkpoint = interrupted.checkpoint.unwrap();
+            assert_eq!(checkpoint.method(), method);
+
+            // make sure nothing is lost when saving to disk
+            let json = serde_json::to_string(&checkpoint).unwrap();
+            let checkpoint: Checkpoint = serde_json::from_str(&json).unwrap();
+
+            let resumed = level
+                .resume_with_progress(&checkpoint, &options, &mut |_| true)
+                .unwrap();
+            assert!(!resumed.interrupted);
+            assert!(resumed.checkpoint.is_none());
+            let moves = resumed.moves.unwrap();
+            assert!(level.verify_solution(&moves).is_ok());
+            let (len, expected_len) = match method {
+                Method::MovesPushes | Method::Moves => (moves.move_cnt(), expected.move_cnt()),
+                Method::PushesMoves | Method::PushesBoxLines | Method::Pushes | Method::Any => {
+                    (moves.push_cnt(), expected.push_cnt())
+                }
+            };
+            assert_eq!(len, expected_len);
+            assert!(
+                resumed.stats.total_unique_visited() > checkpoint.stats().total_unique_visited()
+            );
+        }
+
+        let checkpoint = level
+            .solve_with_progress(Method::Pushes, &options, &mut |_| false)
+            .unwrap()
+            .checkpoint
+            .unwrap();
+        let other: Level = "#####\n#@$.#\n#####".parse().unwrap();
+        assert_eq!(
+            other
+                .resume_with_progress(&checkpoint, &options, &mut |_| true)
+                .unwrap_err(),
+            SolverErr::CheckpointMismatch
+        );
+    }
+
+    #[test]
+    fn pull_variant() {
+        let mut level: Level = "#######\n# $@. #\n#######".parse().unwrap();
+        assert!(level.solve(Method::Pushes, false).unwrap().moves.is_none());
+        level.set_variant(Variant::Pull);
+        let moves = level.solve(Method::Pushes, false).unwrap().moves.unwrap();
+        assert_eq!(moves.to_string(), "RR");
+
+        // corners are not dead when pulling
+        let level = r"
+#######
+#$    #
+#     #
+#  .  #
+#    @#
+#######
+";
+        let mut level: Level = level.parse().unwrap();
+        level.set_variant(Variant::Pull);
+        assert!(!level.dead_squares()[Pos::new(1, 1)]);
+        for (method, solver_ok) in level.solve_all_methods(&SolverOptions::default()).unwrap() {
+            let moves = solver_ok.moves.unwrap();
+            assert!(
+                level.verify_solution(&moves).is_ok(),
+                "{}: {}",
+                method,
+                moves
+            );
+        }
+        let moves = level
+            .solve(Method::MovesPushes, false)
+            .unwrap()
+            .moves
+            .unwrap();
+        assert_eq!((moves.move_cnt(), moves.push_cnt()), (12, 4));
+    }
+
+    #[test]
+    fn multiban() {
+        // the second player has to make room for the first one
+        let level = r"
+#######
+#@$ @.#
+### ###
+  ###
+";
+        let level = Level::parse_variant(level, Variant::Multiban).unwrap();
+        for (method, solver_ok) in level.solve_all_methods(&SolverOptions::default()).unwrap() {
+            let moves = solver_ok.moves.unwrap();
+            assert!(
+                level.verify_solution(&moves).is_ok(),
+                "{}: {}",
+                method,
+                moves
+            );
+            assert_eq!(moves.push_cnt(), 3, "{method}: {moves}");
+        }
+        let moves = level
+            .solve(Method::MovesPushes, false)
+            .unwrap()
+            .moves
+            .unwrap();
+        assert_eq!(moves.to_string(), "[2]ld[1]RRR");
+    }
+
+    #[test]
+    fn symmetries() {
+        // mirrored left to right
+        let level: Level = r"
+###########
+#    @    #
+# $$ # $$ #
+#  #   #  #
+#.. # # ..#
+###########
+"
+        .parse()
+        .unwrap();
+        let solver = Solver::new_with_goals(level.goal_map(), &level.state, Variant::Push).unwrap();
+        assert_eq!(solver.sd.symmetries.len(), 1);
+        let mut asymmetric =
+            Solver::new_with_goals(level.goal_map(), &level.state, Variant::Push).unwrap();
+        asymmetric.sd.symmetries.clear();
+
+        for method in [
+            Method::MovesPushes,
+            Method::Moves,
+            Method::PushesMoves,
+            Method::PushesBoxLines,
+            Method::Pushes,
+        ] {
+            let search = |solver| {
+                search_with_method(
+                    solver,
+                    method,
+                    SolverOptions::default(),
+                    None,
+                    &mut |_| true,
+                    None,
+                    &mut io::sink(),
+                )
+            };
+            let with = search(&solver);
+            let without = search(&asymmetric);
+            let with_moves = with.moves.unwrap();
+            let without_moves = without.moves.unwrap();
+            assert!(level.verify_solution(&with_moves).is_ok(), "{}", method);
+            assert_eq!(with_moves.move_cnt(), without_moves.move_cnt(), "{method}");
+            assert_eq!(with_moves.push_cnt(), without_moves.push_cnt(), "{method}");
+            assert!(
+                with.stats.total_unique_visited() < without.stats.total_unique_visited(),
+                "{}",
+                method
+            );
+        }
+    }
 }
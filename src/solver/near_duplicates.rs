@@ -0,0 +1,88 @@
+//! Pruning states which only differ from an already visited state in where the player is.
+//!
+//! Move-optimal searches keep the exact player position so the same boxes are visited
+//! with the player next to each box side it pushed from. If the player could walk
+//! from a visited state's position to the new one and still not be further from the start,
+//! everything reachable from the new state is reachable from the visited one at no higher cost
+//! (walking is reversible so the distance works both ways).
+
+use std::mem;
+
+use fnv::FnvHashMap;
+
+use crate::data::{MapCell, Pos};
+use crate::map::Map;
+use crate::state::State;
+
+use super::a_star::Cost;
+
+/// Visited states grouped by their boxes
+pub(super) struct NearDuplicates<'a, C> {
+    groups: FnvHashMap<&'a [Pos], Vec<(Pos, C)>>,
+}
+
+impl<'a, C: Cost> NearDuplicates<'a, C> {
+    pub(super) fn new() -> Self {
+        NearDuplicates {
+            groups: FnvHashMap::default(),
+        }
+    }
+
+    /// Whether a visited state with the same boxes is at most as far from the start
+    /// as `state` (at `dist`) after walking to its player position,
+    /// `walk_cost` converts the number of steps into the search's cost.
+    pub(super) fn is_dominated<M: Map>(
+        &self,
+        map: &M,
+        state: &State,
+        dist: C,
+        walk_cost: impl Fn(u16) -> C,
+    ) -> bool {
+        let Some(visited) = self.groups.get(&*state.boxes) else {
+            return false;
+        };
+
+        let mut seen = map.grid().scratchpad();
+        seen[state.player_pos] = true;
+        let mut box_grid = map.grid().scratchpad();
+        for &box_pos in &state.boxes {
+            box_grid[box_pos] = true;
+        }
+
+        // BFS from the new position finds the distances to all the visited ones
+        let mut remaining = visited.len();
+        let mut to_visit = vec![state.player_pos];
+        let mut next = Vec::new();
+        let mut depth = 0;
+        while !to_visit.is_empty() && remaining > 0 {
+            for &pos in &to_visit {
+                for &(visited_pos, visited_dist) in visited {
+                    if visited_pos == pos {
+                        if visited_dist + walk_cost(depth) <= dist {
+                            return true;
+                        }
+                        remaining -= 1;
+                    }
+                }
+                for &new_pos in &pos.neighbors() {
+                    if !seen[new_pos] && map.grid()[new_pos] != MapCell::Wall && !box_grid[new_pos]
+                    {
+                        seen[new_pos] = true;
+                        next.push(new_pos);
+                    }
+                }
+            }
+            to_visit.clear();
+            mem::swap(&mut to_visit, &mut next);
+            depth += 1;
+        }
+        false
+    }
+
+    pub(super) fn insert(&mut self, state: &'a State, dist: C) {
+        self.groups
+            .entry(&state.boxes)
+            .or_default()
+            .push((state.player_pos, dist));
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+use std::ops::Add;
+
+use crate::solver::a_star::{Cost, SearchNode};
+
+/// Composition of the open list at one point during the search.
+///
+/// Useful to see whether the search is plateauing (many nodes with the same f)
+/// and whether different tie-breaking could help.
+/// The histograms are keyed by the parts of the cost, most important first
+/// (e.g. moves and pushes for `Method::MovesPushes`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenListSnapshot {
+    len: usize,
+    f: BTreeMap<Vec<u16>, usize>,
+    g: BTreeMap<Vec<u16>, usize>,
+    h: BTreeMap<Vec<u16>, usize>,
+}
+
+impl OpenListSnapshot {
+    pub(crate) fn new<'a, C, I>(nodes: I) -> Self
+    where
+        C: Cost + Add<Output = C> + 'a,
+        I: IntoIterator<Item = &'a SearchNode<'a, C>>,
+    {
+        let mut snapshot = Self {
+            len: 0,
+            f: BTreeMap::new(),
+            g: BTreeMap::new(),
+            h: BTreeMap::new(),
+        };
+        for node in nodes {
+            snapshot.len += 1;
+            *snapshot.f.entry(node.cost.parts().to_vec()).or_insert(0) += 1;
+            *snapshot.g.entry(node.dist.parts().to_vec()).or_insert(0) += 1;
+            *snapshot
+                .h
+                .entry((node.cost - node.dist).parts().to_vec())
+                .or_insert(0) += 1;
+        }
+        snapshot
+    }
+
+    /// Number of nodes on the open list
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// How many nodes share the lowest f value - these are the candidates for expansion
+    pub fn plateau(&self) -> usize {
+        self.f.values().next().copied().unwrap_or(0)
+    }
+
+    /// Number of nodes by their cost (distance plus heuristic)
+    pub fn f(&self) -> &BTreeMap<Vec<u16>, usize> {
+        &self.f
+    }
+
+    /// Number of nodes by their distance from the initial state
+    pub fn g(&self) -> &BTreeMap<Vec<u16>, usize> {
+        &self.g
+    }
+
+    /// Number of nodes by their (weighted) heuristic value
+    pub fn h(&self) -> &BTreeMap<Vec<u16>, usize> {
+        &self.h
+    }
+
+    pub fn diff<'a>(&'a self, prev: &'a Self) -> OpenListDiff<'a> {
+        OpenListDiff { cur: self, prev }
+    }
+}
+
+impl Display for OpenListSnapshot {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Open list: {} nodes, {} with lowest f",
+            self.len,
+            self.plateau()
+        )?;
+        write_histogram(f, "f", &self.f)?;
+        write_histogram(f, "g", &self.g)?;
+        write_histogram(f, "h", &self.h)
+    }
+}
+
+/// Writes the parts of a cost the same way costs are displayed
+fn write_cost(f: &mut Formatter<'_>, parts: &[u16]) -> fmt::Result {
+    for (i, part) in parts.iter().enumerate() {
+        if i > 0 {
+            write!(f, "/")?;
+        }
+        write!(f, "{part}")?;
+    }
+    Ok(())
+}
+
+fn write_histogram(
+    f: &mut Formatter<'_>,
+    name: &str,
+    histogram: &BTreeMap<Vec<u16>, usize>,
+) -> fmt::Result {
+    write!(f, "{name}:")?;
+    for (value, count) in histogram {
+        write!(f, " ")?;
+        write_cost(f, value)?;
+        write!(f, ": {count}")?;
+    }
+    writeln!(f)
+}
+
+/// Change in the composition of the open list between two snapshots
+#[derive(Debug, Clone, Copy)]
+pub struct OpenListDiff<'a> {
+    cur: &'a OpenListSnapshot,
+    prev: &'a OpenListSnapshot,
+}
+
+impl<'a> OpenListDiff<'a> {
+    #[allow(clippy::cast_possible_wrap)]
+    fn histogram_diff(
+        cur: &'a BTreeMap<Vec<u16>, usize>,
+        prev: &'a BTreeMap<Vec<u16>, usize>,
+    ) -> BTreeMap<&'a [u16], i64> {
+        let mut diff = BTreeMap::new();
+        for (value, &count) in cur {
+            *diff.entry(value.as_slice()).or_insert(0) += count as i64;
+        }
+        for (value, &count) in prev {
+            *diff.entry(value.as_slice()).or_insert(0) -= count as i64;
+        }
+        diff.retain(|_, change| *change != 0);
+        diff
+    }
+}
+
+impl Display for OpenListDiff<'_> {
+    #[allow(clippy::cast_possible_wrap)]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Open list change: {:+} nodes",
+            self.cur.len as i64 - self.prev.len as i64
+        )?;
+        for (name, cur, prev) in [
+            ("f", &self.cur.f, &self.prev.f),
+            ("g", &self.cur.g, &self.prev.g),
+            ("h", &self.cur.h, &self.prev.h),
+        ] {
+            write!(f, "{name}:")?;
+            for (value, change) in Self::histogram_diff(cur, prev) {
+                write!(f, " ")?;
+                write_cost(f, value)?;
+                write!(f, ": {change:+}")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::data::Pos;
+    use crate::solver::a_star::SimpleCost;
+    use crate::state::State;
+
+    #[test]
+    fn snapshots() {
+        let state = State::new(Pos::new(1, 1), vec![]);
+        let node = |g, h| SearchNode::new(&state, None, SimpleCost(g), SimpleCost(h));
+
+        let prev_nodes = [node(1, 3), node(2, 2), node(2, 3)];
+        let cur_nodes = [node(2, 2), node(2, 3), node(3, 1), node(3, 2)];
+        let prev = OpenListSnapshot::new(&prev_nodes);
+        let cur = OpenListSnapshot::new(&cur_nodes);
+
+        assert_eq!(prev.plateau(), 2);
+        assert_eq!(cur.plateau(), 2);
+        assert_eq!(
+            cur.to_string(),
+            "Open list: 4 nodes, 2 with lowest f\n\
+             f: 4: 2 5: 2\n\
+             g: 2: 2 3: 2\n\
+             h: 1: 1 2: 2 3: 1\n"
+        );
+        assert_eq!(
+            cur.diff(&prev).to_string(),
+            "Open list change: +1 nodes\n\
+             f: 5: +1\n\
+             g: 1: -1 3: +2\n\
+             h: 1: +1 2: +1 3: -1\n"
+        );
+    }
+}
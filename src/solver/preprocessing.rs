@@ -1,41 +1,43 @@
 use std::collections::VecDeque;
 
 use crate::{
-    data::{Dir, MapCell, Pos, DIRECTIONS},
+    config::Variant,
+    data::{Coord, Dir, MapCell, Pos, DIRECTIONS},
     map::Map,
     solver::SolverErr,
     state::State,
     vec2d::Vec2d,
 };
 
-pub(crate) fn check_reachability<M: Map>(
+/// Finds the squares the players can reach and turns all the others into walls.
+///
+/// The only error is a player being able to walk off the map. The rest doesn't need
+/// to be surrounded by walls - levels often have decorative parts outside the playable area
+/// with incomplete frames or no walls at all.
+pub(crate) fn check_reachability<M: Map + ?Sized>(
     map: &M,
     state: &State,
 ) -> Result<Vec2d<MapCell>, SolverErr> {
-    // make sure the level is surrounded by wall
-    let mut visited = map.grid().scratchpad();
+    let grid = map.grid();
+    let mut visited = grid.scratchpad();
 
-    let mut to_visit = vec![state.player_pos];
+    let mut to_visit: Vec<_> = state.players().collect();
     while let Some(cur) = to_visit.pop() {
+        if visited[cur] {
+            continue;
+        }
         visited[cur] = true;
 
-        let (r, c) = (i32::from(cur.r), i32::from(cur.c));
-        let neighbors = [(r + 1, c), (r - 1, c), (r, c + 1), (r, c - 1)];
-        for &(nr, nc) in &neighbors {
-            // this is the only place in the solver where we need to check bounds (using signed types)
-            // everything after that will be surrounded by walls
-            if nr < 0
-                || nc < 0
-                || nr >= i32::from(map.grid().rows())
-                || nc >= i32::from(map.grid().cols())
-            {
-                // we got out of bounds without hitting a wall
-                return Err(SolverErr::IncompleteBorder);
-            }
+        // this is the only place in the solver where we need to check bounds,
+        // everything after that will be surrounded by walls
+        if cur.r == 0 || cur.c == 0 || cur.r + 1 == grid.rows() || cur.c + 1 == grid.cols() {
+            // the player could walk off the map
+            return Err(SolverErr::IncompleteBorder);
+        }
 
-            #[allow(clippy::cast_sign_loss)]
-            let new_pos = Pos::new(nr as u8, nc as u8);
-            if !visited[new_pos] && map.grid()[new_pos] != MapCell::Wall {
+        for &dir in &DIRECTIONS {
+            let new_pos = cur + dir;
+            if !visited[new_pos] && grid[new_pos] != MapCell::Wall {
                 to_visit.push(new_pos);
             }
         }
@@ -53,8 +55,153 @@ pub(crate) fn check_reachability<M: Map>(
     Ok(processed_grid)
 }
 
+/// Squares where a box would be stuck no matter where the other boxes are:
+/// corners and squares along a wall between two corners (unless there's a goal or remover).
+///
+/// Only squares reachable by any of the players are considered. Doesn't need the level
+/// to be surrounded by walls - anything outside the grid is treated as empty.
+pub(crate) fn simple_dead_squares(grid: &Vec2d<MapCell>, players: &[Pos]) -> Vec2d<bool> {
+    // ignoring boxes - they can be pushed out of the way
+    let mut reachable = grid.scratchpad();
+    let mut to_visit = players.to_vec();
+    while let Some(cur) = to_visit.pop() {
+        if reachable[cur] || grid[cur] == MapCell::Wall {
+            continue;
+        }
+        reachable[cur] = true;
+        let (r, c) = (cur.r, cur.c);
+        if r > 0 {
+            to_visit.push(Pos::new(r - 1, c));
+        }
+        if r + 1 < grid.rows() {
+            to_visit.push(Pos::new(r + 1, c));
+        }
+        if c > 0 {
+            to_visit.push(Pos::new(r, c - 1));
+        }
+        if c + 1 < grid.cols() {
+            to_visit.push(Pos::new(r, c + 1));
+        }
+    }
+
+    let rows = i32::from(grid.rows());
+    let cols = i32::from(grid.cols());
+    let cell = |r: i32, c: i32| {
+        if r < 0 || c < 0 || r >= rows || c >= cols {
+            MapCell::Empty
+        } else {
+            #[allow(clippy::cast_sign_loss)]
+            grid[Pos::new(r as Coord, c as Coord)]
+        }
+    };
+    let is_wall = |r, c| cell(r, c) == MapCell::Wall;
+    let is_floor = |r, c| cell(r, c) == MapCell::Empty;
+    let is_corner = |r, c| {
+        is_floor(r, c)
+            && (is_wall(r - 1, c) || is_wall(r + 1, c))
+            && (is_wall(r, c - 1) || is_wall(r, c + 1))
+    };
+
+    let mut dead = grid.scratchpad();
+    for pos in grid.positions() {
+        let (r, c) = (i32::from(pos.r), i32::from(pos.c));
+        if !reachable[pos] || !is_corner(r, c) {
+            continue;
+        }
+        dead[pos] = true;
+
+        // look for another corner to the right and down along a wall
+        for &(dr, dc) in &[(0, 1), (1, 0)] {
+            // the two sides perpendicular to the direction
+            for &side in &[-1, 1] {
+                let (sr, sc) = (dc * side, dr * side);
+                let mut len = 1;
+                loop {
+                    let (nr, nc) = (r + dr * len, c + dc * len);
+                    if !is_floor(nr, nc) || !is_wall(nr + sr, nc + sc) {
+                        break;
+                    }
+                    if is_corner(nr, nc) {
+                        for i in 1..len {
+                            #[allow(clippy::cast_sign_loss)]
+                            let between = Pos::new((r + dr * i) as Coord, (c + dc * i) as Coord);
+                            dead[between] = true;
+                        }
+                        break;
+                    }
+                    len += 1;
+                }
+            }
+        }
+    }
+    dead
+}
+
+/// Squares in dead-end corridors without goals or removers.
+///
+/// A box pushed into such a corridor can never get out because the player can't get behind it,
+/// so the corridor is filled from the dead end: a square is filled if 3 of its neighbors are walls
+/// or already filled. Filling stops at squares with a player or a box so those which start
+/// in a corridor can still leave. Only in `Variant::Push` - boxes can be pulled out of dead ends
+/// and in multiban, another player can wait in the corridor to push the box back out.
+pub(crate) fn dead_end_corridors(
+    grid: &Vec2d<MapCell>,
+    state: &State,
+    variant: Variant,
+) -> Vec2d<bool> {
+    let mut filled = grid.scratchpad();
+    if variant != Variant::Push {
+        return filled;
+    }
+
+    let mut occupied = grid.scratchpad();
+    for pos in state.players().chain(state.boxes.iter().copied()) {
+        occupied[pos] = true;
+    }
+
+    let mut to_visit: Vec<Pos> = grid.positions().collect();
+    while let Some(pos) = to_visit.pop() {
+        // squares on the border are walls after `check_reachability` so the neighbors exist
+        if grid[pos] != MapCell::Empty || occupied[pos] || filled[pos] {
+            continue;
+        }
+        let blocked = pos
+            .neighbors()
+            .iter()
+            .filter(|&&neighbor| grid[neighbor] == MapCell::Wall || filled[neighbor])
+            .count();
+        if blocked >= 3 {
+            filled[pos] = true;
+            to_visit.extend_from_slice(&pos.neighbors());
+        }
+    }
+    filled
+}
+
+/// The grid as boxes see it - `dead_end_corridors` turned into walls.
+///
+/// Push distances are computed on this grid so they never lead through the corridors
+/// and boxes can't be pushed into them at all.
+pub(crate) fn box_grid(grid: &Vec2d<MapCell>, state: &State, variant: Variant) -> Vec2d<MapCell> {
+    let corridors = dead_end_corridors(grid, state, variant);
+    let mut box_grid = grid.clone();
+    for pos in box_grid.positions() {
+        if corridors[pos] {
+            box_grid[pos] = MapCell::Wall;
+        }
+    }
+    box_grid
+}
+
 #[inline(never)] // this is called only once and this way it's easier to see in callgrind
-pub(crate) fn push_dists<M: Map>(map: &M) -> Vec2d<[Vec2d<Option<u16>>; 4]> {
+/// Number of pushes (or pulls in `Variant::Pull`) needed to get a box from one position to another
+/// ignoring other boxes, indexed by the start position and the direction from the player to the box.
+pub(crate) fn push_dists(
+    grid: &Vec2d<MapCell>,
+    variant: Variant,
+) -> Vec2d<[Vec2d<Option<u16>>; 4]> {
+    span!("push_dists");
+
     // I don't think distances per direction can be used as a heuristic - example:
     // Center box is pushable only from bottom but shortest solution first pushes the bottom box
     // which would lower the heuristic of the center box by 2 -> the push distance depends
@@ -72,11 +219,10 @@ pub(crate) fn push_dists<M: Map>(map: &M) -> Vec2d<[Vec2d<Option<u16>>; 4]> {
     // The only thing directions can probably prevent is pushing boxes into dead end tunnels.
 
     let mut push_dirs =
-        map.grid()
-            .scratchpad_with_default([Vec::new(), Vec::new(), Vec::new(), Vec::new()]);
+        grid.scratchpad_with_default([Vec::new(), Vec::new(), Vec::new(), Vec::new()]);
 
-    for box_pos in map.grid().positions() {
-        if map.grid()[box_pos] == MapCell::Wall {
+    for box_pos in grid.positions() {
+        if grid[box_pos] == MapCell::Wall {
             continue;
         }
 
@@ -85,13 +231,11 @@ pub(crate) fn push_dists<M: Map>(map: &M) -> Vec2d<[Vec2d<Option<u16>>; 4]> {
             // all of them have the same push dirs. `one_box_push_dirs` would have to be modified to return
             // reachable dists, not push dists.
 
-            let player_pos = box_pos - player_to_box;
-            if map.grid()[player_pos] == MapCell::Wall {
+            if grid[box_pos - player_to_box] == MapCell::Wall {
                 continue;
             }
-
             push_dirs[box_pos][player_to_box as usize] =
-                one_box_push_dirs(map, box_pos, player_pos);
+                box_dirs(grid, box_pos, player_to_box, variant);
         }
     }
 
@@ -104,49 +248,26 @@ pub(crate) fn push_dists<M: Map>(map: &M) -> Vec2d<[Vec2d<Option<u16>>; 4]> {
     // 64x64 map: 2^28 B = 256 MiB
     // 128x128 map: 2^32 B = 4 GiB
     // 256x256 map: 2^36 B = 64 GiB
-    let mut push_dists: Vec2d<[Vec2d<Option<u16>>; 4]> = map.grid().scratchpad_with_default([
-        map.grid().scratchpad(),
-        map.grid().scratchpad(),
-        map.grid().scratchpad(),
-        map.grid().scratchpad(),
+    let mut push_dists: Vec2d<[Vec2d<Option<u16>>; 4]> = grid.scratchpad_with_default([
+        grid.scratchpad(),
+        grid.scratchpad(),
+        grid.scratchpad(),
+        grid.scratchpad(),
     ]);
 
-    for box_start_pos in map.grid().positions() {
-        if map.grid()[box_start_pos] == MapCell::Wall {
+    for box_start_pos in grid.positions() {
+        if grid[box_start_pos] == MapCell::Wall {
             continue;
         }
 
         for &initial_dir in &DIRECTIONS {
-            let player_start_pos = box_start_pos - initial_dir;
-            if map.grid()[player_start_pos] == MapCell::Wall {
+            if grid[box_start_pos - initial_dir] == MapCell::Wall {
                 continue;
             }
-
-            // BFS of pushes fanning out from the box position.
-            // `visited` must be per direction because going back to the same cell from a different direction
-            // means different areas are accessible.
-            let mut visited = map.grid().scratchpad_with_default([false; 4]);
-            let mut to_visit = VecDeque::new();
-            to_visit.push_back((box_start_pos, player_start_pos, 0));
-
-            while let Some((cur_box_pos, cur_player_pos, cur_dist)) = to_visit.pop_front() {
-                let player_to_box = cur_player_pos.dir_to(cur_box_pos);
-                if visited[cur_box_pos][player_to_box as usize] {
-                    continue;
-                }
-
-                let old_dist = &mut push_dists[box_start_pos][initial_dir as usize][cur_box_pos];
-                if old_dist.is_none() {
-                    // given this is BFS, the old value, if there is any, is always better
-                    *old_dist = Some(cur_dist);
-                }
-
-                //for push_dir in Self::one_box_push_dirs(map, cur_box_pos, cur_player_pos) {
-                for &push_dir in &push_dirs[cur_box_pos][player_to_box as usize] {
-                    visited[cur_box_pos][player_to_box as usize] = true;
-                    to_visit.push_back((cur_box_pos + push_dir, cur_box_pos, cur_dist + 1));
-                }
-            }
+            push_dists[box_start_pos][initial_dir as usize] =
+                push_dists_from(grid, box_start_pos, initial_dir, variant, |pos, dir| {
+                    &push_dirs[pos][dir as usize]
+                });
         }
     }
 
@@ -163,11 +284,111 @@ pub(crate) fn push_dists<M: Map>(map: &M) -> Vec2d<[Vec2d<Option<u16>>; 4]> {
     push_dists
 }
 
+/// Memo of the push distances for the last box grid they were computed for.
+///
+/// Only the distances are memoized, they're reused when the walls of the new box grid
+/// and the variant are exactly the same and recomputed in full otherwise.
+/// The box grid has a wall wherever the level has one, where the player can't reach
+/// and in dead-end corridors no box or player starts in so moving walls, moving the player
+/// to another area or moving boxes or players into or out of those corridors invalidates it.
+/// Everything else in preprocessing is recomputed on every solve.
+#[derive(Default)]
+pub(crate) struct PushDistsMemo {
+    /// Which squares of the box grid were walls, the variant and the distances
+    #[allow(clippy::type_complexity)]
+    last: Option<(Vec2d<bool>, Variant, Vec2d<[Vec2d<Option<u16>>; 4]>)>,
+    /// The last call returned the memoized distances
+    pub(crate) reused: bool,
+}
+
+impl PushDistsMemo {
+    pub(crate) fn push_dists(
+        &mut self,
+        grid: &Vec2d<MapCell>,
+        variant: Variant,
+    ) -> &Vec2d<[Vec2d<Option<u16>>; 4]> {
+        let mut walls = grid.scratchpad();
+        for pos in grid.positions() {
+            walls[pos] = grid[pos] == MapCell::Wall;
+        }
+        self.reused = matches!(self.last, Some((ref last_walls, last_variant, _))
+            if *last_walls == walls && last_variant == variant);
+        if !self.reused {
+            self.last = Some((walls, variant, push_dists(grid, variant)));
+        }
+        &self.last.as_ref().expect("Filled above").2
+    }
+}
+
+/// Directions in which a box can be pushed (or pulled) with the player next to it,
+/// `player_to_box` is the direction from the player to the box.
+pub(crate) fn box_dirs(
+    grid: &Vec2d<MapCell>,
+    box_pos: Pos,
+    player_to_box: Dir,
+    variant: Variant,
+) -> Vec<Dir> {
+    let player_pos = box_pos - player_to_box;
+    match variant {
+        Variant::Push | Variant::Multiban => one_box_push_dirs(grid, box_pos, player_pos),
+        Variant::Pull => one_box_pull_dirs(grid, box_pos, player_pos),
+    }
+}
+
+/// Push distances from one start position and player side (the direction from the player to the box)
+/// to all positions. `push_dirs` returns the directions the box can move in from a position and side.
+pub(crate) fn push_dists_from<D: AsRef<[Dir]>>(
+    grid: &Vec2d<MapCell>,
+    box_start_pos: Pos,
+    initial_dir: Dir,
+    variant: Variant,
+    mut push_dirs: impl FnMut(Pos, Dir) -> D,
+) -> Vec2d<Option<u16>> {
+    let mut dists: Vec2d<Option<u16>> = grid.scratchpad();
+    let player_start_pos = box_start_pos - initial_dir;
+
+    // BFS of pushes fanning out from the box position.
+    // `visited` must be per direction because going back to the same cell from a different direction
+    // means different areas are accessible.
+    let mut visited = grid.scratchpad_with_default([false; 4]);
+    let mut to_visit = VecDeque::new();
+    to_visit.push_back((box_start_pos, player_start_pos, 0));
+
+    while let Some((cur_box_pos, cur_player_pos, cur_dist)) = to_visit.pop_front() {
+        let player_to_box = cur_player_pos.dir_to(cur_box_pos);
+        if visited[cur_box_pos][player_to_box as usize] {
+            continue;
+        }
+
+        let old_dist = &mut dists[cur_box_pos];
+        if old_dist.is_none() {
+            // given this is BFS, the old value, if there is any, is always better
+            *old_dist = Some(cur_dist);
+        }
+
+        for &push_dir in push_dirs(cur_box_pos, player_to_box).as_ref() {
+            visited[cur_box_pos][player_to_box as usize] = true;
+            let new_box_pos = cur_box_pos + push_dir;
+            let new_player_pos = match variant {
+                Variant::Push | Variant::Multiban => cur_box_pos,
+                Variant::Pull => new_box_pos + push_dir,
+            };
+            to_visit.push_back((new_box_pos, new_player_pos, cur_dist + 1));
+        }
+    }
+
+    dists
+}
+
 /// Finds in which directions the box is pushable
-pub(crate) fn one_box_push_dirs<M: Map>(map: &M, box_pos: Pos, player_start_pos: Pos) -> Vec<Dir> {
+pub(crate) fn one_box_push_dirs(
+    grid: &Vec2d<MapCell>,
+    box_pos: Pos,
+    player_start_pos: Pos,
+) -> Vec<Dir> {
     let mut ret = Vec::new();
 
-    let mut touched = map.grid().scratchpad();
+    let mut touched = grid.scratchpad();
     touched[player_start_pos] = true;
 
     // BFS turns out to be faster than DFS here on the levels i benched
@@ -179,7 +400,7 @@ pub(crate) fn one_box_push_dirs<M: Map>(map: &M, box_pos: Pos, player_start_pos:
             let next_pos = cur_pos + dir;
             if next_pos == box_pos {
                 // can't step on this pos (so `else if` is not taken) but can we actually push?
-                if map.grid()[next_pos + dir] != MapCell::Wall {
+                if grid[next_pos + dir] != MapCell::Wall {
                     // don't set touched here
                     // box pos can be touched multiple times - that's the whole point
                     ret.push(dir);
@@ -188,7 +409,42 @@ pub(crate) fn one_box_push_dirs<M: Map>(map: &M, box_pos: Pos, player_start_pos:
                         return ret;
                     }
                 }
-            } else if map.grid()[next_pos] != MapCell::Wall && !touched[next_pos] {
+            } else if grid[next_pos] != MapCell::Wall && !touched[next_pos] {
+                touched[next_pos] = true;
+                to_visit.push_back(next_pos);
+            }
+        }
+    }
+
+    ret
+}
+
+/// Finds in which directions the box can be pulled - the player has to reach
+/// the square next to the box and the one behind it has to be free
+pub(crate) fn one_box_pull_dirs(
+    grid: &Vec2d<MapCell>,
+    box_pos: Pos,
+    player_start_pos: Pos,
+) -> Vec<Dir> {
+    let mut ret = Vec::new();
+
+    let mut touched = grid.scratchpad();
+    touched[player_start_pos] = true;
+
+    let mut to_visit = VecDeque::new();
+    to_visit.push_back(player_start_pos);
+
+    while let Some(cur_pos) = to_visit.pop_front() {
+        for &dir in &DIRECTIONS {
+            let next_pos = cur_pos + dir;
+            if next_pos == box_pos {
+                // the player walks away from the box, dragging it along
+                let pull_dir = dir.inverse();
+                if grid[cur_pos + pull_dir] != MapCell::Wall {
+                    // every square next to the box is only reached once so there are no duplicates
+                    ret.push(pull_dir);
+                }
+            } else if grid[next_pos] != MapCell::Wall && !touched[next_pos] {
                 touched[next_pos] = true;
                 to_visit.push_back(next_pos);
             }
@@ -205,31 +461,268 @@ pub(crate) fn closest_push_dists<M: Map>(
     let mut closest_push_dists = map.grid().scratchpad();
 
     for src_pos in closest_push_dists.positions() {
-        let mut best = None;
-        for dests in &push_dists[src_pos] {
-            for dest_pos in dests.positions() {
-                if map.grid()[dest_pos] != MapCell::Goal && map.grid()[dest_pos] != MapCell::Remover
-                {
-                    continue;
-                }
+        closest_push_dists[src_pos] = push_dists[src_pos]
+            .iter()
+            .filter_map(|dests| closest_dest_dist(map.grid(), dests))
+            .min();
+    }
 
-                let cur = dests[dest_pos];
-                match best {
-                    None => best = cur,
-                    Some(best_dist) => {
-                        if let Some(cur_dist) = cur {
-                            if cur_dist < best_dist {
-                                best = cur;
-                            }
+    closest_push_dists
+}
+
+/// The shortest of the push distances from one position and player side to any goal or remover
+pub(crate) fn closest_dest_dist(grid: &Vec2d<MapCell>, dests: &Vec2d<Option<u16>>) -> Option<u16> {
+    dests
+        .positions()
+        .filter(|&pos| grid[pos] == MapCell::Goal || grid[pos] == MapCell::Remover)
+        .filter_map(|pos| dests[pos])
+        .min()
+}
+
+/// Closest push distances of a box on a square which splits the map around it into parts
+/// the player can't walk between, by the part the player is in.
+///
+/// `closest_push_dists` takes the minimum over all sides of the box. After a push, the player
+/// can be in a part from which the box is several pushes further from a goal so the heuristic
+/// would drop by more than the cost of the push. A* would then expand some states
+/// before the cheapest path to them was found and return a longer solution.
+#[derive(Debug, Clone)]
+pub(crate) struct SideDists {
+    /// The part each square is in while the box blocks its square, `u8::MAX` for walls
+    parts: Vec2d<u8>,
+    /// The closest push distance from each part, `None` if no goal or remover can be reached
+    dists: Vec<Option<u16>>,
+}
+
+impl SideDists {
+    pub(crate) fn dist(&self, player_pos: Pos) -> Option<u16> {
+        self.dists
+            .get(usize::from(self.parts[player_pos]))
+            .copied()
+            .flatten()
+    }
+}
+
+/// `SideDists` for each square which splits the map around it, `None` for the other squares.
+pub(crate) fn side_dists<M: Map + ?Sized>(
+    map: &M,
+    push_dists: &Vec2d<[Vec2d<Option<u16>>; 4]>,
+) -> Vec2d<Option<SideDists>> {
+    let grid = map.grid();
+    let mut side_dists = grid.scratchpad();
+
+    for box_pos in grid.positions() {
+        if grid[box_pos] == MapCell::Wall {
+            continue;
+        }
+
+        let mut parts = grid.scratchpad_with_default(u8::MAX);
+        let mut dists: Vec<Option<u16>> = Vec::new();
+        let mut part_cnt = 0;
+        for &dir in &DIRECTIONS {
+            let side = box_pos - dir;
+            if grid[side] == MapCell::Wall {
+                continue;
+            }
+
+            if parts[side] == u8::MAX {
+                parts[side] = part_cnt;
+                let mut to_visit = vec![side];
+                while let Some(cur) = to_visit.pop() {
+                    for &next in &cur.neighbors() {
+                        if grid[next] != MapCell::Wall && next != box_pos && parts[next] == u8::MAX
+                        {
+                            parts[next] = part_cnt;
+                            to_visit.push(next);
                         }
                     }
                 }
+                part_cnt += 1;
+                dists.push(None);
             }
+
+            let part = usize::from(parts[side]);
+            let dist = closest_dest_dist(grid, &push_dists[box_pos][dir as usize]);
+            dists[part] = match (dists[part], dist) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            };
+        }
+
+        if part_cnt > 1 {
+            side_dists[box_pos] = Some(SideDists { parts, dists });
         }
-        closest_push_dists[src_pos] = best;
     }
 
-    closest_push_dists
+    side_dists
+}
+
+/// Distances from every square to each goal or remover (in the order of `grid.positions()`),
+/// found by pulling a box backwards from the goal (pushing in `Variant::Pull`).
+///
+/// Unlike `push_dists`, this ignores whether the player can get to the other side of the box
+/// so it's a weaker bound for a single box but it's kept per goal and only needs memory
+/// proportional to the number of goals. Squares no goal can be reached from are dead.
+pub(crate) fn goal_pull_dists(grid: &Vec2d<MapCell>, variant: Variant) -> Vec<Vec2d<Option<u16>>> {
+    span!("goal_pull_dists");
+
+    let mut all_dists = Vec::new();
+    for goal in grid.positions() {
+        if grid[goal] != MapCell::Goal && grid[goal] != MapCell::Remover {
+            continue;
+        }
+
+        let mut dists = grid.scratchpad();
+        dists[goal] = Some(0);
+        let mut to_visit = VecDeque::new();
+        to_visit.push_back(goal);
+        while let Some(cur_pos) = to_visit.pop_front() {
+            let cur_dist = dists[cur_pos].expect("Only reached squares are visited");
+            // the box came to `cur_pos` from `prev_pos` by moving in `dir`
+            for &dir in &DIRECTIONS {
+                let prev_pos = cur_pos - dir;
+                // reachable squares are surrounded by walls so check them in this order
+                if grid[prev_pos] == MapCell::Wall || dists[prev_pos].is_some() {
+                    continue;
+                }
+                let player_pos = match variant {
+                    Variant::Push | Variant::Multiban => prev_pos - dir,
+                    Variant::Pull => cur_pos + dir,
+                };
+                if grid[player_pos] == MapCell::Wall {
+                    continue;
+                }
+                dists[prev_pos] = Some(cur_dist + 1);
+                to_visit.push_back(prev_pos);
+            }
+        }
+        all_dists.push(dists);
+    }
+    all_dists
+}
+
+/// Finds a set of boxes which together can reach fewer goals than there are boxes in the set
+/// (the level then can't be solved by Hall's theorem).
+/// `reachable` contains indices of goals each box can reach.
+/// Returns indices of the boxes and the goals they can reach
+/// or `None` if each box can be assigned a different goal.
+pub(crate) fn unmatchable_boxes(
+    reachable: &[Vec<usize>],
+    goal_cnt: usize,
+) -> Option<(Vec<usize>, Vec<usize>)> {
+    // Kuhn's algorithm - try to find an augmenting path from each box
+    fn augment(
+        box_index: usize,
+        reachable: &[Vec<usize>],
+        goal_to_box: &mut [Option<usize>],
+        visited: &mut [bool],
+    ) -> bool {
+        for &goal in &reachable[box_index] {
+            if visited[goal] {
+                continue;
+            }
+            visited[goal] = true;
+            let free = match goal_to_box[goal] {
+                None => true,
+                Some(other) => augment(other, reachable, goal_to_box, visited),
+            };
+            if free {
+                goal_to_box[goal] = Some(box_index);
+                return true;
+            }
+        }
+        false
+    }
+
+    let mut goal_to_box = vec![None; goal_cnt];
+    for box_index in 0..reachable.len() {
+        let mut visited = vec![false; goal_cnt];
+        if !augment(box_index, reachable, &mut goal_to_box, &mut visited) {
+            // the failed search visited exactly the goals reachable by this box
+            // and the boxes matched to them, all of those goals are taken
+            let goals: Vec<_> = (0..goal_cnt).filter(|&goal| visited[goal]).collect();
+            let mut boxes: Vec<_> = goals
+                .iter()
+                .map(|&goal| goal_to_box[goal].expect("Visited goals are matched"))
+                .collect();
+            boxes.push(box_index);
+            boxes.sort_unstable();
+            return Some((boxes, goals));
+        }
+    }
+    None
+}
+
+/// Minimum total cost of assigning each row a different column (rows must not outnumber columns),
+/// `None` marks pairs which can't be assigned. Returns `None` if there's no such assignment.
+///
+/// Hungarian algorithm, `O(rows^2 * columns)`.
+pub(crate) fn min_assignment(costs: &[Vec<Option<u16>>], cols: usize) -> Option<u32> {
+    // higher than any sum of real costs so it's only used when there's no other option
+    const FORBIDDEN: i64 = 1 << 40;
+
+    let rows = costs.len();
+    assert!(rows <= cols);
+    let cost = |r: usize, c: usize| costs[r - 1][c - 1].map_or(FORBIDDEN, i64::from);
+
+    // potentials and the row assigned to each column, all 1-based with 0 as a sentinel
+    let mut row_pot = vec![0; rows + 1];
+    let mut col_pot = vec![0; cols + 1];
+    let mut col_row = vec![0; cols + 1];
+    let mut way = vec![0; cols + 1];
+    for r in 1..=rows {
+        col_row[0] = r;
+        let mut cur_col = 0;
+        let mut min_slack = vec![i64::MAX; cols + 1];
+        let mut used = vec![false; cols + 1];
+        // find an augmenting path from the new row
+        loop {
+            used[cur_col] = true;
+            let cur_row = col_row[cur_col];
+            let mut delta = i64::MAX;
+            let mut next_col = 0;
+            for c in 1..=cols {
+                if used[c] {
+                    continue;
+                }
+                let slack = cost(cur_row, c) - row_pot[cur_row] - col_pot[c];
+                if slack < min_slack[c] {
+                    min_slack[c] = slack;
+                    way[c] = cur_col;
+                }
+                if min_slack[c] < delta {
+                    delta = min_slack[c];
+                    next_col = c;
+                }
+            }
+            for c in 0..=cols {
+                if used[c] {
+                    row_pot[col_row[c]] += delta;
+                    col_pot[c] -= delta;
+                } else {
+                    min_slack[c] -= delta;
+                }
+            }
+            cur_col = next_col;
+            if col_row[cur_col] == 0 {
+                break;
+            }
+        }
+        // flip the path
+        while cur_col != 0 {
+            let prev_col = way[cur_col];
+            col_row[cur_col] = col_row[prev_col];
+            cur_col = prev_col;
+        }
+    }
+
+    let mut total = 0;
+    for c in 1..=cols {
+        if col_row[c] != 0 {
+            total += u32::from(costs[col_row[c] - 1][c - 1]?);
+        }
+    }
+    Some(total)
 }
 
 #[cfg(test)]
@@ -238,11 +731,73 @@ mod tests {
 
     use typed_arena::Arena;
 
+    use crate::config::SolverOptions;
     use crate::level::Level;
     use crate::map::GoalMap;
-    use crate::solver::{GameLogic, PushLogic};
+    use crate::solver::{GameLogic, PruningStats, PushLogic, Scratch};
     use crate::solver::{Solver, SolverTrait, StaticData};
 
+    #[test]
+    fn simple_dead_squares() {
+        let level: Level = r"
+ ######
+##    #
+#@$ . #
+#  ####
+####
+"
+        .parse()
+        .unwrap();
+        let dead = super::simple_dead_squares(level.map().grid(), &[level.state.player_pos]);
+        assert_eq!(
+            dead.to_string(),
+            "0000000
+0011110
+0100010
+0110000
+0000000
+"
+        );
+    }
+
+    #[test]
+    fn dead_end_corridors() {
+        let level: Level = r"
+#########
+#   #####
+#@$    .#
+# ### ###
+# ###.###
+#########
+"
+        .parse()
+        .unwrap();
+        let grid = check_reachability(level.map(), &level.state).unwrap();
+        let corridors = super::dead_end_corridors(&grid, &level.state, Variant::Push);
+        assert_eq!(
+            corridors.to_string(),
+            "000000000
+000000000
+000000000
+010000000
+010000000
+000000000
+"
+        );
+        let box_grid = box_grid(&grid, &level.state, Variant::Push);
+        assert_eq!(box_grid[Pos::new(4, 1)], MapCell::Wall);
+        assert_eq!(box_grid[Pos::new(4, 5)], MapCell::Goal);
+
+        // the player and boxes can leave corridors they start in
+        let level: Level = "#######\n#@$  .#\n#######".parse().unwrap();
+        let grid = check_reachability(level.map(), &level.state).unwrap();
+        let corridors = super::dead_end_corridors(&grid, &level.state, Variant::Push);
+        assert!(corridors.positions().all(|pos| !corridors[pos]));
+
+        let corridors = super::dead_end_corridors(&grid, &level.state, Variant::Pull);
+        assert!(corridors.positions().all(|pos| !corridors[pos]));
+    }
+
     #[test]
     fn one_box_reachability() {
         use crate::data::Dir::{self, Down, Left, Right, Up};
@@ -274,53 +829,53 @@ mod tests {
         // although the function should handle all player positions,
         // in practice the player will always be next to the box
         assert_eq!(
-            hash_set(search_fn(map, center_box, center_box + Up)),
+            hash_set(search_fn(map.grid(), center_box, center_box + Up)),
             hash_set(vec![Down, Left])
         );
         assert_eq!(
-            hash_set(search_fn(map, center_box, center_box + Right)),
+            hash_set(search_fn(map.grid(), center_box, center_box + Right)),
             hash_set(vec![Down, Left])
         );
         assert_eq!(
-            hash_set(search_fn(map, center_box, center_box + Down)),
+            hash_set(search_fn(map.grid(), center_box, center_box + Down)),
             hash_set(vec![Up, Right])
         );
         assert_eq!(
-            hash_set(search_fn(map, center_box, center_box + Left)),
+            hash_set(search_fn(map.grid(), center_box, center_box + Left)),
             hash_set(vec![Up, Right])
         );
         assert_eq!(
-            hash_set(search_fn(map, left_box, left_box + Up)),
+            hash_set(search_fn(map.grid(), left_box, left_box + Up)),
             hash_set(vec![Left])
         );
         assert_eq!(
-            hash_set(search_fn(map, left_box, left_box + Right)),
+            hash_set(search_fn(map.grid(), left_box, left_box + Right)),
             hash_set(vec![Left])
         );
         assert_eq!(
-            hash_set(search_fn(map, left_box, left_box + Left)),
+            hash_set(search_fn(map.grid(), left_box, left_box + Left)),
             hash_set(vec![Right])
         );
         assert_eq!(
-            hash_set(search_fn(map, right_box, right_box + Up)),
+            hash_set(search_fn(map.grid(), right_box, right_box + Up)),
             hash_set(vec![Up, Right, Down, Left])
         );
         assert_eq!(
-            hash_set(search_fn(map, right_box, right_box + Right)),
+            hash_set(search_fn(map.grid(), right_box, right_box + Right)),
             hash_set(vec![Up, Right, Down, Left])
         );
         assert_eq!(
-            hash_set(search_fn(map, right_box, right_box + Down)),
+            hash_set(search_fn(map.grid(), right_box, right_box + Down)),
             hash_set(vec![Up, Right, Down, Left])
         );
         assert_eq!(
-            hash_set(search_fn(map, right_box, right_box + Left)),
+            hash_set(search_fn(map.grid(), right_box, right_box + Left)),
             hash_set(vec![Up, Right, Down, Left])
         );
     }
 
     #[test]
-    #[ignore] // pretty slow even in release mode
+    #[ignore = "pretty slow even in release mode"]
     fn push_distances() {
         use crate::solver::a_star::SimpleCost;
 
@@ -333,12 +888,15 @@ mod tests {
                 sd: &StaticData<GoalMap>,
                 state: &State,
                 arena: &'a Arena<State>,
-            ) -> Vec<(&'a State, Self::C, Self::C)> {
-                let mut new_states = PushLogic::expand(sd, state, arena);
-                for (new_state, _, h) in &mut new_states {
+                pruned: &mut PruningStats,
+                scratch: &mut Scratch,
+                new_states: &mut Vec<(&'a State, Self::C, Self::C)>,
+            ) {
+                let start = new_states.len();
+                PushLogic::expand(sd, state, arena, pruned, scratch, new_states);
+                for (new_state, _, h) in &mut new_states[start..] {
                     *h = SimpleCost(manhattan_heuristic(sd, new_state));
                 }
-                new_states
             }
         }
 
@@ -346,7 +904,7 @@ mod tests {
             let mut goal_dist_sum = 0;
 
             for box_pos in &state.boxes {
-                let mut min = u16::max_value();
+                let mut min = u16::MAX;
                 for goal in &sd.map.goals {
                     let dist = box_pos.dist(*goal);
                     if dist < min {
@@ -386,7 +944,7 @@ mod tests {
         let level0: Level = level0.parse().unwrap();
         let level1: Level = level1.parse().unwrap();
         for level in &[level0, level1] {
-            let push_dists = push_dists(level.goal_map());
+            let push_dists = push_dists(level.goal_map().grid(), Variant::Push);
 
             // put box on every position and try to get it to every position
             for box_pos in level.map.grid().positions() {
@@ -411,8 +969,18 @@ mod tests {
                         let mut fake_map = level.goal_map().clone();
                         fake_map.grid[goal_pos] = MapCell::Goal;
                         fake_map.goals = vec![goal_pos];
-                        let fake_solver = Solver::new_with_goals(&fake_map, &fake_state).unwrap();
-                        let moves = fake_solver.search(false, FakePushLogic).moves;
+                        let fake_solver =
+                            Solver::new_with_goals(&fake_map, &fake_state, Variant::Push).unwrap();
+                        let moves = fake_solver
+                            .search(
+                                SolverOptions::default(),
+                                FakePushLogic,
+                                None,
+                                &mut |_| true,
+                                None,
+                                &mut std::io::sink(),
+                            )
+                            .moves;
 
                         let dist_result = push_dists[box_pos][dir as usize][goal_pos];
                         let dist_expected = moves.map(|m| m.push_cnt() as u16);
@@ -438,12 +1006,14 @@ mod tests {
     None    None    None    None    None    None    None
     None    None    None    None    None    None    None
     None    None    None    None    None    None    None
-    None    None Some(3) Some(2) Some(1) Some(0)    None
+    None    None    None    None Some(1) Some(0)    None
     None    None    None    None    None    None    None
 "
         .trim_start_matches('\n');
 
-        let solver = Solver::new_with_goals(level.goal_map(), &level.state).unwrap();
+        // the left end is a dead-end corridor and the player can never get behind a box
+        // right next to it to push it out
+        let solver = Solver::new_with_goals(level.goal_map(), &level.state, Variant::Push).unwrap();
         let result = format!("{:?}", solver.sd.closest_push_dists);
         assert_eq!(result, expected);
     }
@@ -467,7 +1037,7 @@ mod tests {
 "
         .trim_start_matches('\n');
 
-        let solver = Solver::new_with_goals(level.goal_map(), &level.state).unwrap();
+        let solver = Solver::new_with_goals(level.goal_map(), &level.state, Variant::Push).unwrap();
         let result = format!("{:?}", solver.sd.closest_push_dists);
         assert_eq!(result, expected);
     }
@@ -503,8 +1073,78 @@ mod tests {
 "
         .trim_start_matches('\n');
 
-        let solver = Solver::new_with_goals(level.goal_map(), &level.state).unwrap();
+        let solver = Solver::new_with_goals(level.goal_map(), &level.state, Variant::Push).unwrap();
         let result = format!("{:?}", solver.sd.closest_push_dists);
         assert_eq!(result, expected);
     }
+
+    #[test]
+    fn matching() {
+        assert_eq!(unmatchable_boxes(&[], 0), None);
+        assert_eq!(unmatchable_boxes(&[vec![0, 1], vec![0]], 2), None);
+        // the first box has to move to the other goal
+        assert_eq!(
+            unmatchable_boxes(&[vec![0, 1], vec![0], vec![1, 2]], 3),
+            None
+        );
+        assert_eq!(
+            unmatchable_boxes(&[vec![0], vec![1, 2], vec![0]], 3),
+            Some((vec![0, 2], vec![0]))
+        );
+        assert_eq!(
+            unmatchable_boxes(&[vec![0, 1], vec![0, 1], vec![2], vec![1, 0]], 4),
+            Some((vec![0, 1, 3], vec![0, 1]))
+        );
+    }
+
+    #[test]
+    fn assignment() {
+        assert_eq!(min_assignment(&[], 0), Some(0));
+        assert_eq!(
+            min_assignment(&[vec![Some(1), Some(2)], vec![Some(1), Some(5)]], 2),
+            Some(3)
+        );
+        // the cheapest pairs can't all be used at once
+        assert_eq!(
+            min_assignment(
+                &[
+                    vec![Some(4), Some(1), Some(3)],
+                    vec![Some(2), Some(0), Some(5)],
+                    vec![Some(3), Some(2), Some(2)],
+                ],
+                3
+            ),
+            Some(5)
+        );
+        // more columns than rows
+        assert_eq!(min_assignment(&[vec![None, Some(7), Some(3)]], 3), Some(3));
+        assert_eq!(
+            min_assignment(&[vec![Some(1), None], vec![Some(1), None]], 2),
+            None
+        );
+    }
+
+    #[test]
+    fn goal_pulls() {
+        let level: Level = "########\n#@$   .#\n#  #   #\n########".parse().unwrap();
+        let grid = level.goal_map().grid();
+        let dists = goal_pull_dists(grid, Variant::Push);
+        assert_eq!(dists.len(), 1);
+        assert_eq!(dists[0][Pos::new(1, 2)], Some(4));
+        assert_eq!(dists[0][Pos::new(1, 1)], None);
+        // the box would have to be pushed up from below the map
+        assert_eq!(dists[0][Pos::new(2, 5)], None);
+
+        let level: Level = "#########\n# $@ .  #\n#########".parse().unwrap();
+        let grid = level.goal_map().grid();
+        let push = goal_pull_dists(grid, Variant::Push);
+        let pull = goal_pull_dists(grid, Variant::Pull);
+        assert_eq!(push[0][Pos::new(1, 2)], Some(3));
+        assert_eq!(pull[0][Pos::new(1, 2)], Some(3));
+        // pulling needs space for the player in front of the box, pushing behind it
+        assert_eq!(push[0][Pos::new(1, 1)], None);
+        assert_eq!(pull[0][Pos::new(1, 1)], Some(4));
+        assert_eq!(push[0][Pos::new(1, 7)], None);
+        assert_eq!(pull[0][Pos::new(1, 7)], Some(2));
+    }
 }
@@ -0,0 +1,155 @@
+//! The visited states and the states they were reached from.
+//!
+//! It's looked up for every state popped from the open list and can grow to millions of entries
+//! so the hash table only holds 32-bit indices of the states and their predecessors are kept
+//! as indices too. States are hashed once per lookup, the table only hashes them again when it grows.
+
+use std::convert::TryFrom;
+use std::hash::{Hash, Hasher};
+use std::mem;
+
+use fnv::FnvHasher; // using rustc-hash gives the same results, maybe bench again when able to solve levels with many boxes
+use hashbrown::HashTable;
+
+use crate::state::State;
+
+/// Visited states in the order they were expanded, each with the index of the state
+/// it was first reached from (the initial state points to itself)
+#[derive(Debug)]
+pub(super) struct Prevs<'a> {
+    states: Vec<&'a State>,
+    /// What the states are looked up by in maps with symmetries (see `symmetry::canonical`),
+    /// stays empty otherwise so ordinary levels don't pay for it
+    keys: Vec<&'a State>,
+    symmetric: bool,
+    prevs: Vec<u32>,
+    /// Indices into `states` by the hash of their keys
+    indices: HashTable<u32>,
+}
+
+/// Computed once per lookup and passed to the methods of `Prevs`
+pub(super) fn hash(key: &State) -> u64 {
+    let mut hasher = FnvHasher::default();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl<'a> Prevs<'a> {
+    /// `symmetric` means states are looked up by a key which can be a different state
+    pub(super) fn new(symmetric: bool) -> Self {
+        Prevs {
+            states: Vec::new(),
+            keys: Vec::new(),
+            symmetric,
+            prevs: Vec::new(),
+            indices: HashTable::new(),
+        }
+    }
+
+    fn key(&self, index: u32) -> &'a State {
+        if self.symmetric {
+            self.keys[index as usize]
+        } else {
+            self.states[index as usize]
+        }
+    }
+
+    /// The index of the state visited under `key`
+    pub(super) fn find(&self, hash: u64, key: &State) -> Option<u32> {
+        self.indices
+            .find(hash, |&index| self.key(index) == key)
+            .copied()
+    }
+
+    /// Adds a state which is not visited yet and returns its index.
+    ///
+    /// `key` is only used in symmetric maps and `prev` is `None` for the initial state.
+    pub(super) fn insert(
+        &mut self,
+        hash: u64,
+        state: &'a State,
+        key: &'a State,
+        prev: Option<u32>,
+    ) -> u32 {
+        let index = u32::try_from(self.states.len()).expect("Too many visited states");
+        self.states.push(state);
+        if self.symmetric {
+            self.keys.push(key);
+        }
+        self.prevs.push(prev.unwrap_or(index));
+
+        let (states, keys, symmetric) = (&self.states, &self.keys, self.symmetric);
+        self.indices.insert_unique(hash, index, |&index| {
+            if symmetric {
+                self::hash(keys[index as usize])
+            } else {
+                self::hash(states[index as usize])
+            }
+        });
+        index
+    }
+
+    pub(super) fn state(&self, index: u32) -> &'a State {
+        self.states[index as usize]
+    }
+
+    /// The index of the state `index` was reached from, the same index for the initial state
+    pub(super) fn prev(&self, index: u32) -> u32 {
+        self.prevs[index as usize]
+    }
+
+    /// Each visited state and the state it was reached from, in the order they were visited
+    pub(super) fn iter(&self) -> impl Iterator<Item = (&'a State, &'a State)> + '_ {
+        self.states
+            .iter()
+            .zip(&self.prevs)
+            .map(move |(&state, &prev)| (state, self.state(prev)))
+    }
+
+    /// Bytes used, not counting the states themselves which are in the arena
+    pub(super) fn memory(&self) -> usize {
+        let state_refs = self.states.capacity() + self.keys.capacity();
+        state_refs * mem::size_of::<&State>()
+            + self.prevs.capacity() * mem::size_of::<u32>()
+            // the table also stores one control byte per bucket
+            + self.indices.capacity() * (mem::size_of::<u32>() + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::data::Pos;
+
+    #[test]
+    fn lookup() {
+        let a = State::new(Pos::new(1, 1), vec![Pos::new(1, 2)]);
+        let b = State::new(Pos::new(1, 1), vec![Pos::new(1, 3)]);
+        let a_copy = a.clone();
+
+        let mut prevs = Prevs::new(false);
+        let a_index = prevs.insert(hash(&a), &a, &a, None);
+        let b_index = prevs.insert(hash(&b), &b, &b, Some(a_index));
+        assert_eq!(prevs.find(hash(&a_copy), &a_copy), Some(a_index));
+        assert_eq!(prevs.find(hash(&b), &b), Some(b_index));
+        assert_eq!(prevs.prev(a_index), a_index);
+        assert_eq!(prevs.prev(b_index), a_index);
+        assert_eq!(prevs.state(b_index), &b);
+
+        let c = State::new(Pos::new(1, 2), vec![Pos::new(1, 3)]);
+        assert_eq!(prevs.find(hash(&c), &c), None);
+    }
+
+    #[test]
+    fn symmetric_keys() {
+        let state = State::new(Pos::new(1, 3), vec![Pos::new(1, 2)]);
+        let key = State::new(Pos::new(1, 1), vec![Pos::new(1, 2)]);
+
+        let mut prevs = Prevs::new(true);
+        let index = prevs.insert(hash(&key), &state, &key, None);
+        assert_eq!(prevs.find(hash(&key), &key), Some(index));
+        assert_eq!(prevs.find(hash(&state), &state), None);
+        assert_eq!(prevs.state(index), &state);
+    }
+}
@@ -0,0 +1,134 @@
+//! Running several searches of the same level at once and keeping the first to finish.
+
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+use crate::config::{Method, SolverOptions};
+use crate::level::Level;
+
+use super::preprocessing::PushDistsMemo;
+use super::{SearchOutcome, SolverErr, SolverOk};
+
+/// The result of `Level::race`
+#[derive(Debug)]
+pub struct RaceWinner {
+    /// Index of the winning entrant
+    pub index: usize,
+    pub solver_ok: SolverOk,
+}
+
+impl Level {
+    /// Runs a search for each `(method, options)` on a separate thread and returns the first one
+    /// which finds a solution or proves there is none, the rest are stopped.
+    ///
+    /// Different methods and heuristics are fast on different levels so racing them
+    /// uses more cores to get a solution sooner. The solution is only as optimal as the winner's
+    /// method and options say. If no search finishes (all hit a limit or `stop` returned `true`),
+    /// the first entrant's result is returned. `stop` is checked as often as the progress callback
+    /// of `solve_with_progress`. Status output is turned off since the searches would interleave it.
+    ///
+    /// # Panics
+    ///
+    /// If `entrants` is empty.
+    pub fn race(
+        &self,
+        entrants: &[(Method, SolverOptions)],
+        stop: &(dyn Fn() -> bool + Sync),
+    ) -> Result<RaceWinner, SolverErr> {
+        assert!(!entrants.is_empty(), "Race needs at least one entrant");
+        span!("race", entrants = entrants.len());
+
+        let finished = AtomicBool::new(false);
+        let results: Vec<_> = thread::scope(|scope| {
+            let handles: Vec<_> = entrants
+                .iter()
+                .map(|&(method, options)| {
+                    let finished = &finished;
+                    scope.spawn(move || {
+                        let options = SolverOptions {
+                            print_status: false,
+                            status_interval: None,
+                            checkpoint: false,
+                            ..options
+                        };
+                        let mut progress = |_: &_| !finished.load(Ordering::SeqCst) && !stop();
+                        let result = self.search(
+                            method,
+                            options,
+                            None,
+                            &mut progress,
+                            None,
+                            &mut io::sink(),
+                            &mut PushDistsMemo::default(),
+                        );
+                        // only the first finished search wins, the others might also finish
+                        // before noticing they should stop
+                        let won = result.as_ref().is_ok_and(|solver_ok| {
+                            solver_ok.outcome() != SearchOutcome::LimitReached
+                                && !finished.swap(true, Ordering::SeqCst)
+                        });
+                        (won, result)
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("Search thread panicked"))
+                .collect()
+        });
+
+        let index = results.iter().position(|&(won, _)| won).unwrap_or(0);
+        let (_, result) = results.into_iter().nth(index).expect("Index is in bounds");
+        result.map(|solver_ok| RaceWinner { index, solver_ok })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::config::Heuristic;
+
+    #[test]
+    fn race() {
+        let level: Level = r"
+########
+#@ $  .#
+#  ##  #
+# $  $.#
+#.     #
+########
+"
+        .parse()
+        .unwrap();
+        let entrants = [
+            (Method::Moves, SolverOptions::default()),
+            (
+                Method::Pushes,
+                SolverOptions {
+                    heuristic: Heuristic::GoalPullDists,
+                    ..SolverOptions::default()
+                },
+            ),
+            (Method::Any, SolverOptions::default()),
+        ];
+        let winner = level.race(&entrants, &|| false).unwrap();
+        let (method, options) = entrants[winner.index];
+        assert_eq!(winner.solver_ok.metadata.method, Some(method));
+        assert_eq!(winner.solver_ok.metadata.heuristic, options.heuristic);
+        let moves = winner.solver_ok.moves.unwrap();
+        assert!(level.verify_solution(&moves).is_ok());
+
+        // nobody finishes so the first entrant's result is returned
+        let winner = level.race(&entrants, &|| true).unwrap();
+        assert_eq!(winner.index, 0);
+        assert!(winner.solver_ok.interrupted);
+
+        let invalid: Level = "####\n#@$.\n####".parse().unwrap();
+        assert_eq!(
+            invalid.race(&entrants, &|| false).unwrap_err(),
+            SolverErr::IncompleteBorder
+        );
+    }
+}
@@ -0,0 +1,223 @@
+//! Rotations and reflections which map the level onto itself.
+//!
+//! States which are images of each other under such a symmetry have the same solutions
+//! (transformed the same way) so the search only visits one of them.
+//! Most levels have no symmetries, some have one (e.g. mirrored halves)
+//! which can cut the number of visited states in half.
+
+use std::fmt::{self, Debug, Formatter};
+
+use crate::data::{Coord, Dir, MapCell, Pos, DIRECTIONS};
+use crate::map::Map;
+use crate::state::{Boxes, State};
+use crate::transform::Transform;
+use crate::vec2d::Vec2d;
+
+use super::{BoxIndex, Scratch};
+
+/// A non-identity transformation of the map which keeps every square the same
+#[derive(Clone)]
+pub(super) struct Symmetry {
+    /// Where each square ends up
+    images: Vec2d<Pos>,
+    /// Where each direction ends up, indexed like `DIRECTIONS`
+    dirs: [Dir; 4],
+}
+
+impl Debug for Symmetry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        // the images are a whole grid, the directions are enough to tell symmetries apart
+        f.debug_struct("Symmetry")
+            .field("dirs", &self.dirs)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Symmetry {
+    fn dir(&self, dir: Dir) -> Dir {
+        self.dirs[dir as usize]
+    }
+
+    fn image(&self, state: &State, player_pos: Pos) -> State {
+        let boxes: Boxes = state
+            .boxes
+            .iter()
+            .map(|&box_pos| self.images[box_pos])
+            .collect();
+        let mut image = State::new(player_pos, boxes);
+        image.last_push = state
+            .last_push
+            .map(|(pos, dir)| (self.images[pos], self.dir(dir)));
+        image
+    }
+}
+
+/// The symmetries of the squares which are not walls (goals and removers must map onto goals
+/// and removers), the walls around them don't matter.
+///
+/// Multiban states keep the players in order so they're never canonicalized.
+pub(super) fn symmetries(grid: &Vec2d<MapCell>, state: &State) -> Vec<Symmetry> {
+    if state.player_cnt() > 1 {
+        return Vec::new();
+    }
+    let inside: Vec<Pos> = grid
+        .positions()
+        .filter(|&pos| grid[pos] != MapCell::Wall)
+        .collect();
+    let (Some(min_r), Some(max_r), Some(min_c), Some(max_c)) = (
+        inside.iter().map(|pos| pos.r).min(),
+        inside.iter().map(|pos| pos.r).max(),
+        inside.iter().map(|pos| pos.c).min(),
+        inside.iter().map(|pos| pos.c).max(),
+    ) else {
+        return Vec::new();
+    };
+    let rows = usize::from(max_r - min_r) + 1;
+    let cols = usize::from(max_c - min_c) + 1;
+
+    let mut identity = grid.scratchpad_with_default(Pos::new(0, 0));
+    for pos in grid.positions() {
+        identity[pos] = pos;
+    }
+
+    let mut symmetries: Vec<Symmetry> = Vec::new();
+    // transposing only maps the bounding box onto itself if it's a square
+    for transform in Transform::all().filter(|transform| !transform.transpose || rows == cols) {
+        let mut images = identity.clone();
+        let mut symmetric = true;
+        for &pos in &inside {
+            let relative = (usize::from(pos.r - min_r), usize::from(pos.c - min_c));
+            let (r, c) = transform.apply(relative, rows, cols);
+            let image = Pos::new(min_r + r as Coord, min_c + c as Coord);
+            if grid[image] != grid[pos] {
+                symmetric = false;
+                break;
+            }
+            images[pos] = image;
+        }
+        // in a single row or column, some transformations move no squares or the same ones as others
+        let duplicate = images == identity || symmetries.iter().any(|sym| sym.images == images);
+        if symmetric && !duplicate {
+            symmetries.push(Symmetry {
+                images,
+                dirs: DIRECTIONS.map(|dir| transform.apply_dir(dir)),
+            });
+        }
+    }
+    symmetries
+}
+
+/// The smallest image of `state` under the symmetries, `None` if it's `state` itself.
+///
+/// With `normalized` set, states only keep the top-left square the player can reach
+/// (see `normalized_pos`) which isn't the image of the top-left square of the original area.
+pub(super) fn canonical<M: Map>(
+    map: &M,
+    symmetries: &[Symmetry],
+    state: &State,
+    normalized: bool,
+    scratch: &mut Scratch,
+) -> Option<State> {
+    if symmetries.is_empty() {
+        return None;
+    }
+
+    // at most 7 symmetries
+    let mut players = [state.player_pos; 8];
+    if normalized {
+        scratch.set_boxes(&state.boxes);
+        top_left_images(map, symmetries, state.player_pos, scratch, &mut players);
+        scratch.clear_boxes(&state.boxes);
+    } else {
+        for (player, symmetry) in players.iter_mut().zip(symmetries) {
+            *player = symmetry.images[state.player_pos];
+        }
+    }
+
+    let mut best: Option<State> = None;
+    for (symmetry, &player_pos) in symmetries.iter().zip(&players) {
+        let image = symmetry.image(state, player_pos);
+        if image < *best.as_ref().unwrap_or(state) {
+            best = Some(image);
+        }
+    }
+    best
+}
+
+/// For each symmetry, the top-left image of the squares reachable from `player_pos`
+/// with the boxes currently in `scratch.box_grid`
+fn top_left_images<M: Map>(
+    map: &M,
+    symmetries: &[Symmetry],
+    player_pos: Pos,
+    scratch: &mut Scratch,
+    top_lefts: &mut [Pos],
+) {
+    for (top_left, symmetry) in top_lefts.iter_mut().zip(symmetries) {
+        *top_left = symmetry.images[player_pos];
+    }
+
+    scratch.norm_reached.clear();
+    scratch.norm_reached.mark(player_pos);
+    scratch.norm_to_visit.clear();
+    scratch.norm_to_visit.push(player_pos);
+    while let Some(cur_pos) = scratch.norm_to_visit.pop() {
+        for &new_pos in &cur_pos.neighbors() {
+            if !scratch.norm_reached.mark(new_pos) {
+                continue;
+            }
+            if map.grid()[new_pos] == MapCell::Wall || scratch.box_grid[new_pos] < BoxIndex::MAX {
+                continue;
+            }
+            scratch.norm_to_visit.push(new_pos);
+            for (top_left, symmetry) in top_lefts.iter_mut().zip(symmetries) {
+                *top_left = (*top_left).min(symmetry.images[new_pos]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::level::Level;
+    use crate::solver::preprocessing;
+
+    fn symmetries_of(level: &str) -> Vec<Symmetry> {
+        let level: Level = level.parse().unwrap();
+        let grid = preprocessing::check_reachability(level.map(), &level.state).unwrap();
+        symmetries(&grid, &level.state)
+    }
+
+    fn symmetry_cnt(level: &str) -> usize {
+        symmetries_of(level).len()
+    }
+
+    #[test]
+    fn detects_symmetries() {
+        assert_eq!(symmetry_cnt("#####\n#@$.#\n#####"), 0);
+        // mirrored left to right
+        assert_eq!(symmetry_cnt("#######\n#. @ .#\n# $ $ #\n#######"), 1);
+        // a square with goals in all corners has all 7
+        assert_eq!(symmetry_cnt("#####\n#.@.#\n# $ #\n#. .#\n#####"), 7);
+        // squares outside the level don't matter
+        assert_eq!(
+            symmetry_cnt("   #######\n   #. @ .#\n#### $ $ #\n#  #######\n####"),
+            1
+        );
+    }
+
+    #[test]
+    fn directions() {
+        for symmetry in symmetries_of("#####\n#.@.#\n# $ #\n#. .#\n#####") {
+            let pos = Pos::new(2, 2);
+            for &dir in &DIRECTIONS {
+                assert_eq!(
+                    symmetry.images[pos + dir],
+                    symmetry.images[pos] + symmetry.dir(dir)
+                );
+            }
+        }
+    }
+}
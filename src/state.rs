@@ -1,16 +1,95 @@
-use crate::data::Pos;
+use smallvec::SmallVec;
+
+use crate::data::{Dir, Pos};
+
+/// Most levels have fewer boxes than this so their states don't need a separate allocation
+pub(crate) const INLINE_BOXES: usize = 16;
+
+pub(crate) type Boxes = SmallVec<[Pos; INLINE_BOXES]>;
 
 // TODO private to keep sorted?
 #[derive(Debug, Clone, PartialEq, Eq, Ord, PartialOrd, Hash)]
 pub(crate) struct State {
     pub(crate) player_pos: Pos,
-    pub(crate) boxes: Vec<Pos>,
+    /// Only in Multiban levels - the players after the first one.
+    /// Levels keep the players in their original order so moves can refer to them,
+    /// the solver sorts them so states only differ by which player is where are equal.
+    /// A boxed slice instead of a `Vec` because every state has it - it's 8 bytes smaller
+    /// (enough for `last_push` to fit in the padding) and empty ones don't allocate.
+    pub(crate) other_players: Box<[Pos]>,
+    pub(crate) boxes: Boxes,
+    /// Only with `Method::PushesBoxLines` - where the last pushed box ended up
+    /// and which way it moved, other methods leave it `None` so it doesn't split equal states
+    pub(crate) last_push: Option<(Pos, Dir)>,
 }
 
 impl State {
-    pub(crate) fn new(player_pos: Pos, mut boxes: Vec<Pos>) -> State {
+    pub(crate) fn new(player_pos: Pos, boxes: impl Into<Boxes>) -> State {
+        let mut boxes = boxes.into();
         // TODO use binary search when inserting instead (a different data structure might be even better)
         boxes.sort(); // sort to detect equal states when we reorder boxes
-        State { player_pos, boxes }
+        State {
+            player_pos,
+            other_players: Box::default(),
+            boxes,
+            last_push: None,
+        }
+    }
+
+    pub(crate) fn with_players(players: &[Pos], boxes: impl Into<Boxes>) -> State {
+        let mut state = State::new(players[0], boxes);
+        state.other_players = players[1..].into();
+        state
+    }
+
+    pub(crate) fn player_cnt(&self) -> usize {
+        1 + self.other_players.len()
+    }
+
+    pub(crate) fn players(&self) -> impl Iterator<Item = Pos> + '_ {
+        Some(self.player_pos)
+            .into_iter()
+            .chain(self.other_players.iter().copied())
+    }
+
+    pub(crate) fn player(&self, index: usize) -> Pos {
+        if index == 0 {
+            self.player_pos
+        } else {
+            self.other_players[index - 1]
+        }
+    }
+
+    /// A copy with the `index`th player moved and different boxes
+    pub(crate) fn moved(&self, index: usize, player_pos: Pos, boxes: Boxes) -> State {
+        let mut state = State::new(self.player_pos, boxes);
+        state.other_players.clone_from(&self.other_players);
+        state.last_push = self.last_push;
+        if index == 0 {
+            state.player_pos = player_pos;
+        } else {
+            state.other_players[index - 1] = player_pos;
+        }
+        state
+    }
+
+    /// Whether a player other than the `index`th one stands at `pos`
+    pub(crate) fn has_other_player(&self, index: usize, pos: Pos) -> bool {
+        // the common case of one player should be fast
+        !self.other_players.is_empty()
+            && self
+                .players()
+                .enumerate()
+                .any(|(i, player_pos)| i != index && player_pos == pos)
+    }
+
+    /// The players in row-major order so states which only differ by which player is where are equal
+    pub(crate) fn sort_players(&mut self) {
+        if !self.other_players.is_empty() {
+            let mut players: Vec<_> = self.players().collect();
+            players.sort();
+            self.player_pos = players[0];
+            self.other_players = players[1..].into();
+        }
     }
 }
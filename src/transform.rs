@@ -0,0 +1,561 @@
+//! Normalization and comparison of levels under rotation and mirroring.
+
+use crate::builder::LevelBuilder;
+use crate::config::Variant;
+use crate::data::{Coord, Dir, MapCell, Pos};
+use crate::level::Level;
+use crate::solver::{preprocessing, SolverErr};
+
+/// Level contents in a form which is easy to transform
+#[derive(Debug, Clone)]
+struct Cells {
+    /// Only the static part, `Empty` also means outside
+    grid: Vec<Vec<MapCell>>,
+    boxes: Vec<(usize, usize)>,
+    /// All players in the original order
+    players: Vec<(usize, usize)>,
+}
+
+/// One of the 8 rotations and reflections of a rectangle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Transform {
+    /// Swap rows and columns, applied first
+    pub(crate) transpose: bool,
+    pub(crate) flip_rows: bool,
+    pub(crate) flip_cols: bool,
+}
+
+impl Transform {
+    /// The identity first
+    pub(crate) fn all() -> impl Iterator<Item = Transform> {
+        (0..8).map(|i| Transform {
+            transpose: i & 4 != 0,
+            flip_rows: i & 2 != 0,
+            flip_cols: i & 1 != 0,
+        })
+    }
+
+    /// Where the square `(r, c)` of a `rows` x `cols` rectangle ends up
+    pub(crate) fn apply(self, (r, c): (usize, usize), rows: usize, cols: usize) -> (usize, usize) {
+        let (r, c, rows, cols) = if self.transpose {
+            (c, r, cols, rows)
+        } else {
+            (r, c, rows, cols)
+        };
+        let r = if self.flip_rows { rows - 1 - r } else { r };
+        let c = if self.flip_cols { cols - 1 - c } else { c };
+        (r, c)
+    }
+
+    pub(crate) fn apply_dir(self, dir: Dir) -> Dir {
+        let dir = if self.transpose {
+            match dir {
+                Dir::Up => Dir::Left,
+                Dir::Right => Dir::Down,
+                Dir::Down => Dir::Right,
+                Dir::Left => Dir::Up,
+            }
+        } else {
+            dir
+        };
+        let vertical = dir == Dir::Up || dir == Dir::Down;
+        if (vertical && self.flip_rows) || (!vertical && self.flip_cols) {
+            dir.inverse()
+        } else {
+            dir
+        }
+    }
+}
+
+/// Which way `Level::mirrored` flips a level
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Axis {
+    /// Top and bottom swap
+    Horizontal,
+    /// Left and right swap
+    Vertical,
+}
+
+impl Level {
+    /// Returns the level rotated by 90 degrees, clockwise or counterclockwise.
+    ///
+    /// Unlike `normalize`, nothing else changes - the player stays on its square
+    /// and the metadata is kept.
+    #[must_use]
+    pub fn rotated(&self, clockwise: bool) -> Level {
+        self.transformed(Transform {
+            transpose: true,
+            flip_rows: !clockwise,
+            flip_cols: clockwise,
+        })
+    }
+
+    /// Returns the level mirrored along `axis`
+    #[must_use]
+    pub fn mirrored(&self, axis: Axis) -> Level {
+        self.transformed(Transform {
+            transpose: false,
+            flip_rows: axis == Axis::Horizontal,
+            flip_cols: axis == Axis::Vertical,
+        })
+    }
+
+    /// Returns the level mirrored along the diagonal from the top left corner
+    #[must_use]
+    pub fn transposed(&self) -> Level {
+        self.transformed(Transform {
+            transpose: true,
+            flip_rows: false,
+            flip_cols: false,
+        })
+    }
+
+    fn transformed(&self, transform: Transform) -> Level {
+        let mut level = self
+            .cells()
+            .transformed(transform)
+            .build(self.variant, self.extra_goals());
+        level.metadata = self.metadata.clone();
+        level
+    }
+
+    /// The whole level, including squares the player can't reach
+    fn cells(&self) -> Cells {
+        let grid = self.map().grid();
+        let mut cells =
+            vec![vec![MapCell::Empty; usize::from(grid.cols())]; usize::from(grid.rows())];
+        for pos in grid.positions() {
+            cells[usize::from(pos.r)][usize::from(pos.c)] = grid[pos];
+        }
+        Cells {
+            grid: cells,
+            boxes: self.state.boxes.iter().map(|&b| b.into()).collect(),
+            players: self.state.players().map(Into::into).collect(),
+        }
+    }
+
+    /// Returns an equivalent level in a canonical form so identical puzzles
+    /// can be found by comparing the normalized levels' XSB:
+    /// - squares the player can't reach are removed (boxes on goals there are only decoration)
+    /// - walls which don't touch the rest of the level are removed
+    ///   (or become walls if they're enclosed by it)
+    /// - the player is moved to the top left square it can reach (unless there are more players)
+    /// - the level is rotated and/or mirrored into the orientation with the smallest XSB
+    pub fn normalize(&self) -> Result<Level, SolverErr> {
+        let cells = self.trimmed()?;
+        let best = Transform::all()
+            .map(|transform| {
+                let mut transformed = cells.transformed(transform);
+                transformed.normalize_player();
+                let level = transformed.build(self.variant, self.extra_goals());
+                let xsb = level.xsb().to_string();
+                (xsb, level)
+            })
+            .min_by(|(a, _), (b, _)| a.cmp(b))
+            .map(|(_, level)| level);
+        // the iterator is never empty
+        Ok(best.unwrap_or_else(|| self.clone()))
+    }
+
+    /// Whether the levels are the same puzzle, possibly rotated, mirrored
+    /// or with the player elsewhere in the same area.
+    ///
+    /// Invalid levels are only equivalent if they're identical.
+    /// Levels with different variants are never equivalent.
+    pub fn is_equivalent(&self, other: &Level) -> bool {
+        if self.variant != other.variant {
+            return false;
+        }
+        match (self.normalize(), other.normalize()) {
+            (Ok(a), Ok(b)) => a.xsb().to_string() == b.xsb().to_string(),
+            _ => self.xsb().to_string() == other.xsb().to_string(),
+        }
+    }
+
+    /// Returns the level without padding for display:
+    /// - squares the player can't reach are removed (boxes on goals there are only decoration)
+    /// - walls which don't touch the rest of the level are removed
+    /// - removed squares enclosed by the rest of the level become walls
+    /// - rows and columns left empty are cropped
+    ///
+    /// Unlike `normalize`, the level keeps its orientation, player position and metadata.
+    pub fn minimized(&self) -> Result<Level, SolverErr> {
+        let mut level = self.trimmed()?.build(self.variant, self.extra_goals());
+        level.metadata = self.metadata.clone();
+        Ok(level)
+    }
+
+    /// Removes unreachable squares and walls around them, then crops the level
+    fn trimmed(&self) -> Result<Cells, SolverErr> {
+        let reachable = preprocessing::check_reachability(self.map(), &self.state)?;
+        let grid = self.map().grid();
+
+        let mut keep = grid.scratchpad();
+        for pos in grid.positions() {
+            let has_box = self.state.boxes.binary_search(&pos).is_ok();
+            keep[pos] = reachable[pos] != MapCell::Wall
+                // unreachable boxes which are not on goals (and the other way around) still matter
+                || (grid[pos] != MapCell::Wall && has_box != (grid[pos] == MapCell::Goal))
+                || grid[pos] == MapCell::Remover;
+        }
+
+        let rows = usize::from(grid.rows());
+        let cols = usize::from(grid.cols());
+        let mut walls = grid.scratchpad();
+        for pos in grid.positions() {
+            let (r, c) = pos.into();
+            walls[pos] = !keep[pos]
+                && (r.saturating_sub(1)..=(r + 1).min(rows - 1)).any(|nr| {
+                    (c.saturating_sub(1)..=(c + 1).min(cols - 1))
+                        .any(|nc| keep[Pos::new(nr as Coord, nc as Coord)])
+                });
+        }
+
+        let used: Vec<(usize, usize)> = grid
+            .positions()
+            .filter(|&pos| keep[pos] || walls[pos])
+            .map(Into::into)
+            .collect();
+        let min_r = used.iter().map(|&(r, _)| r).min().unwrap_or(0);
+        let max_r = used.iter().map(|&(r, _)| r).max().unwrap_or(0);
+        let min_c = used.iter().map(|&(_, c)| c).min().unwrap_or(0);
+        let max_c = used.iter().map(|&(_, c)| c).max().unwrap_or(0);
+
+        let mut cells = vec![vec![MapCell::Empty; max_c - min_c + 1]; max_r - min_r + 1];
+        let mut outside = vec![vec![false; max_c - min_c + 1]; max_r - min_r + 1];
+        for (r, row) in cells.iter_mut().enumerate() {
+            for (c, cell) in row.iter_mut().enumerate() {
+                let pos = Pos::new((r + min_r) as Coord, (c + min_c) as Coord);
+                if walls[pos] {
+                    *cell = MapCell::Wall;
+                } else if keep[pos] {
+                    *cell = grid[pos];
+                } else {
+                    outside[r][c] = true;
+                }
+            }
+        }
+        fill_enclosed(&mut cells, &outside);
+        let boxes = self
+            .state
+            .boxes
+            .iter()
+            .filter(|&&b| keep[b])
+            .map(|&b| {
+                let (r, c) = b.into();
+                (r - min_r, c - min_c)
+            })
+            .collect();
+        let players = self
+            .state
+            .players()
+            .map(|player_pos| {
+                let (r, c) = player_pos.into();
+                (r - min_r, c - min_c)
+            })
+            .collect();
+
+        Ok(Cells {
+            grid: cells,
+            boxes,
+            players,
+        })
+    }
+}
+
+/// Turns `outside` squares which can't get to the edge through other `outside` squares into walls
+/// so holes in thick walls look like walls
+fn fill_enclosed(cells: &mut [Vec<MapCell>], outside: &[Vec<bool>]) {
+    let rows = outside.len();
+    let cols = outside.first().map_or(0, Vec::len);
+    let mut open = vec![vec![false; cols]; rows];
+    let mut to_visit: Vec<(usize, usize)> = (0..rows)
+        .flat_map(|r| (0..cols).map(move |c| (r, c)))
+        .filter(|&(r, c)| outside[r][c] && (r == 0 || c == 0 || r == rows - 1 || c == cols - 1))
+        .collect();
+    for &(r, c) in &to_visit {
+        open[r][c] = true;
+    }
+    while let Some((r, c)) = to_visit.pop() {
+        let neighbors = [
+            (r.wrapping_sub(1), c),
+            (r + 1, c),
+            (r, c.wrapping_sub(1)),
+            (r, c + 1),
+        ];
+        for &(nr, nc) in &neighbors {
+            if nr < rows && nc < cols && outside[nr][nc] && !open[nr][nc] {
+                open[nr][nc] = true;
+                to_visit.push((nr, nc));
+            }
+        }
+    }
+
+    for (r, row) in cells.iter_mut().enumerate() {
+        for (c, cell) in row.iter_mut().enumerate() {
+            if outside[r][c] && !open[r][c] {
+                *cell = MapCell::Wall;
+            }
+        }
+    }
+}
+
+impl Cells {
+    fn rows(&self) -> usize {
+        self.grid.len()
+    }
+
+    fn cols(&self) -> usize {
+        self.grid.first().map_or(0, Vec::len)
+    }
+
+    fn transformed(&self, transform: Transform) -> Cells {
+        let (rows, cols) = if transform.transpose {
+            (self.cols(), self.rows())
+        } else {
+            (self.rows(), self.cols())
+        };
+        let map = |pos| transform.apply(pos, self.rows(), self.cols());
+
+        let mut grid = vec![vec![MapCell::Empty; cols]; rows];
+        for (r, row) in self.grid.iter().enumerate() {
+            for (c, &cell) in row.iter().enumerate() {
+                let (nr, nc) = map((r, c));
+                grid[nr][nc] = cell;
+            }
+        }
+        Cells {
+            grid,
+            boxes: self.boxes.iter().copied().map(map).collect(),
+            players: self.players.iter().copied().map(map).collect(),
+        }
+    }
+
+    /// Moves the player to the first square in row-major order which it can walk to.
+    /// Multiple players block each other so they stay where they are.
+    fn normalize_player(&mut self) {
+        let [player] = *self.players.as_slice() else {
+            return;
+        };
+        let mut visited = vec![vec![false; self.cols()]; self.rows()];
+        let mut to_visit = vec![player];
+        visited[player.0][player.1] = true;
+        let mut first = player;
+        while let Some((r, c)) = to_visit.pop() {
+            first = first.min((r, c));
+            // reachable squares are surrounded by walls so there's no need to check the lower bound
+            let neighbors = [(r - 1, c), (r + 1, c), (r, c - 1), (r, c + 1)];
+            for &(nr, nc) in &neighbors {
+                if self.grid[nr][nc] != MapCell::Wall
+                    && !visited[nr][nc]
+                    && !self.boxes.contains(&(nr, nc))
+                {
+                    visited[nr][nc] = true;
+                    to_visit.push((nr, nc));
+                }
+            }
+        }
+        self.players[0] = first;
+    }
+
+    fn build(&self, variant: Variant, extra_goals: bool) -> Level {
+        let mut builder = LevelBuilder::new(self.rows(), self.cols());
+        for (r, row) in self.grid.iter().enumerate() {
+            for (c, &cell) in row.iter().enumerate() {
+                match cell {
+                    MapCell::Empty => {}
+                    MapCell::Wall => {
+                        builder.wall(r, c);
+                    }
+                    MapCell::Goal => {
+                        builder.goal(r, c);
+                    }
+                    MapCell::Remover => {
+                        builder.remover(r, c);
+                    }
+                }
+            }
+        }
+        for &(r, c) in &self.boxes {
+            builder.box_(r, c);
+        }
+        for &(r, c) in &self.players {
+            builder.player(r, c);
+        }
+        builder
+            .variant(variant)
+            .extra_goals(extra_goals)
+            .build()
+            .expect("Transforming a valid level should give a valid level")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::config::Method;
+    use crate::level::LevelMetadata;
+    use crate::Solve;
+
+    #[test]
+    fn rotating_and_mirroring() {
+        let mut level: Level = r"
+#######
+#@$ r #
+#    ##
+#####
+"
+        .parse()
+        .unwrap();
+        level.set_metadata(LevelMetadata {
+            title: Some("Turned".to_owned()),
+            ..LevelMetadata::default()
+        });
+
+        let clockwise = level.rotated(true);
+        assert_eq!(
+            clockwise.xsb().to_string(),
+            "####\n# @#\n# $#\n#  #\n# r#\n # #\n ###\n"
+        );
+        assert_eq!(clockwise.metadata().title.as_deref(), Some("Turned"));
+        assert_eq!(clockwise.player_pos(), (1, 2));
+        assert_eq!(clockwise.remover_positions(), vec![(4, 2)]);
+        assert_eq!(level.rotated(false).rotated(true), level);
+        assert_eq!(clockwise.rotated(true).rotated(true).rotated(true), level);
+
+        assert_eq!(
+            level.mirrored(Axis::Vertical).xsb().to_string(),
+            "#######\n# r $@#\n##    #\n  #####\n"
+        );
+        assert_eq!(
+            level.mirrored(Axis::Horizontal).xsb().to_string(),
+            "#####\n#    ##\n#@$ r #\n#######\n"
+        );
+        assert_eq!(level.transposed(), clockwise.mirrored(Axis::Vertical));
+
+        // the same puzzle so the solutions are the same, only rotated
+        let moves = level.solve(Method::Pushes, false).unwrap().moves.unwrap();
+        let rotated = clockwise
+            .solve(Method::Pushes, false)
+            .unwrap()
+            .moves
+            .unwrap();
+        assert_eq!(moves.to_string(), "RR");
+        assert_eq!(rotated.to_string(), "DD");
+        assert!(level.is_equivalent(&clockwise));
+    }
+
+    #[test]
+    fn normalizing() {
+        let level: Level = r"
+  #####
+  #   #   ##
+  #$@.#   ##
+  #####
+"
+        .parse()
+        .unwrap();
+        assert_eq!(
+            level.normalize().unwrap().xsb().to_string(),
+            "####\n#$@#\n#  #\n#. #\n####\n"
+        );
+
+        // decorative boxes on goals outside and unreachable areas inside are removed
+        let level: Level = r"
+#######  *
+#@$ . #
+#######
+#     #
+#######
+"
+        .parse()
+        .unwrap();
+        assert_eq!(
+            level.normalize().unwrap().xsb().to_string(),
+            "###\n# #\n#.#\n# #\n#$#\n#@#\n###\n"
+        );
+    }
+
+    #[test]
+    fn minimizing() {
+        let mut level: Level = r"
+##########
+##########
+###@ $ . #
+######## #
+#####    #
+##########
+##########
+"
+        .parse()
+        .unwrap();
+        level.set_metadata(LevelMetadata {
+            title: Some("Padded".to_owned()),
+            ..LevelMetadata::default()
+        });
+        let minimized = level.minimized().unwrap();
+        assert_eq!(
+            minimized.xsb().to_string(),
+            "########\n#@ $ . #\n###### #\n  #    #\n  ######\n"
+        );
+        assert_eq!(minimized.metadata().title.as_deref(), Some("Padded"));
+
+        // the unreachable room becomes a wall, the player keeps its place
+        let level: Level = r"
+   #######
+   #@ $ .#
+   # ### #
+   # # # #
+   # ### #
+   #     #
+   #######
+"
+        .parse()
+        .unwrap();
+        assert_eq!(
+            level.minimized().unwrap().xsb().to_string(),
+            "#######\n#@ $ .#\n# ### #\n# ### #\n# ### #\n#     #\n#######\n"
+        );
+    }
+
+    #[test]
+    fn equivalence() {
+        let level: Level = r"
+######
+#@$ .#
+#  # #
+######"
+            .parse()
+            .unwrap();
+        // mirrored with the player elsewhere in the same area
+        let mirrored: Level = r"
+######
+#. $ #
+# # @#
+######"
+            .parse()
+            .unwrap();
+        let rotated: Level = r"
+####
+# @#
+# $#
+## #
+# .#
+####"
+            .parse()
+            .unwrap();
+        assert!(level.is_equivalent(&mirrored));
+        assert!(level.is_equivalent(&rotated));
+
+        // the player is on the other side of the box
+        let different: Level = r"
+######
+# $@.#
+#  # #
+######"
+            .parse()
+            .unwrap();
+        assert!(!level.is_equivalent(&different));
+    }
+}
@@ -2,13 +2,14 @@ use std::fmt;
 use std::fmt::{Debug, Display, Formatter};
 use std::ops::{Index, IndexMut};
 
-use crate::data::{MapCell, Pos};
+use crate::data::{Coord, MapCell, Pos};
 
+/// A grid with one value per square of a level
 #[derive(Clone, PartialEq, Eq)]
-pub(crate) struct Vec2d<T> {
+pub struct Vec2d<T> {
     data: Vec<T>,
-    rows: u8,
-    cols: u8,
+    rows: Coord,
+    cols: Coord,
 }
 
 impl<T> Vec2d<T> {
@@ -28,19 +29,28 @@ impl<T> Vec2d<T> {
         }
         Vec2d {
             data,
-            rows: grid.len() as u8,
-            cols: max_cols as u8,
+            rows: grid.len() as Coord,
+            cols: max_cols as Coord,
         }
     }
 
-    pub(crate) fn rows(&self) -> u8 {
+    pub fn rows(&self) -> Coord {
         self.rows
     }
 
-    pub(crate) fn cols(&self) -> u8 {
+    pub fn cols(&self) -> Coord {
         self.cols
     }
 
+    /// The value at row `r` and column `c`, `None` if out of bounds
+    pub fn get(&self, r: usize, c: usize) -> Option<&T> {
+        if r < self.rows.into() && c < self.cols.into() {
+            Some(&self.data[r * usize::from(self.cols) + c])
+        } else {
+            None
+        }
+    }
+
     pub(crate) fn scratchpad_with_default<U>(&self, default: U) -> Vec2d<U>
     where
         U: Clone,
@@ -70,10 +80,10 @@ impl<T> Vec2d<T> {
 }
 
 pub(crate) struct Positions {
-    rows: u8,
-    cols: u8,
-    cur_r: u8,
-    cur_c: u8,
+    rows: Coord,
+    cols: Coord,
+    cur_r: Coord,
+    cur_c: Coord,
 }
 
 impl Iterator for Positions {
@@ -96,66 +106,86 @@ impl Iterator for Positions {
     }
 }
 
-impl<T: Display> Display for Vec2d<T> {
-    default fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        let data: Vec<String> = self.data.iter().map(|t| format!("{t}")).collect();
-        fmt_t(&data, self.cols.into(), f)
-    }
+/// How values are printed as part of a whole `Vec2d`.
+///
+/// Types whose values are a single character (e.g. `bool` as 0 or 1) are printed
+/// without separators, others are right-aligned to the width of the longest value.
+/// Sealed because it only exists to pick the formatting without specialization.
+pub trait CellFormat: sealed::Sealed {
+    /// Every value is printed as exactly one character
+    const COMPACT: bool = false;
+
+    fn fmt_cell(&self, debug: bool) -> String;
 }
 
-impl<T: Debug> Debug for Vec2d<T> {
-    default fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        let data: Vec<String> = self.data.iter().map(|t| format!("{t:?}")).collect();
-        fmt_t(&data, self.cols.into(), f)
-    }
+mod sealed {
+    #[allow(unreachable_pub)]
+    pub trait Sealed {}
 }
 
-fn fmt_t(data: &[String], cols: usize, f: &mut Formatter<'_>) -> fmt::Result {
-    if cols == 0 {
-        // chunk size must be >0
-        return Ok(());
-    }
-    let longest = data.iter().map(String::len).max().unwrap_or(0);
-    for row in data.chunks(cols) {
-        for cell in row {
-            write!(f, " {cell:>longest$}")?;
-        }
-        writeln!(f)?;
+impl sealed::Sealed for MapCell {}
+
+impl CellFormat for MapCell {
+    const COMPACT: bool = true;
+
+    fn fmt_cell(&self, _debug: bool) -> String {
+        self.to_string()
     }
-    Ok(())
 }
 
-impl Display for Vec2d<MapCell> {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        if self.cols == 0 {
-            // chunk size must be >0
-            return Ok(());
-        }
-        for row in self.data.chunks(self.cols.into()) {
-            for &cell in row {
-                write!(f, "{cell}")?;
-            }
-            writeln!(f)?;
-        }
-        Ok(())
+impl sealed::Sealed for bool {}
+
+impl CellFormat for bool {
+    const COMPACT: bool = true;
+
+    fn fmt_cell(&self, _debug: bool) -> String {
+        i32::from(*self).to_string()
     }
 }
 
-impl Debug for Vec2d<MapCell> {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "{self}")
+impl<T: Debug> sealed::Sealed for Option<T> {}
+
+impl<T: Debug> CellFormat for Option<T> {
+    fn fmt_cell(&self, _debug: bool) -> String {
+        format!("{self:?}")
     }
 }
 
-impl Display for Vec2d<bool> {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+macro_rules! impl_cell_format {
+    ( $( $t:ty ),* ) => {
+        $(
+            impl sealed::Sealed for $t {}
+
+            impl CellFormat for $t {
+                fn fmt_cell(&self, debug: bool) -> String {
+                    if debug {
+                        format!("{self:?}")
+                    } else {
+                        format!("{self}")
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_cell_format!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize, &str, String);
+
+impl<T: CellFormat> Vec2d<T> {
+    fn fmt_cells(&self, debug: bool, f: &mut Formatter<'_>) -> fmt::Result {
         if self.cols == 0 {
             // chunk size must be >0
             return Ok(());
         }
-        for row in self.data.chunks(self.cols.into()) {
-            for &cell in row {
-                write!(f, "{}", i32::from(cell))?;
+        let data: Vec<String> = self.data.iter().map(|t| t.fmt_cell(debug)).collect();
+        let longest = data.iter().map(String::len).max().unwrap_or(0);
+        for row in data.chunks(self.cols.into()) {
+            for cell in row {
+                if T::COMPACT {
+                    write!(f, "{cell}")?;
+                } else {
+                    write!(f, " {cell:>longest$}")?;
+                }
             }
             writeln!(f)?;
         }
@@ -163,9 +193,15 @@ impl Display for Vec2d<bool> {
     }
 }
 
-impl Debug for Vec2d<bool> {
+impl<T: CellFormat> Display for Vec2d<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.fmt_cells(false, f)
+    }
+}
+
+impl<T: CellFormat> Debug for Vec2d<T> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "{self}")
+        self.fmt_cells(true, f)
     }
 }
 
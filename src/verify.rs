@@ -0,0 +1,650 @@
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+use std::iter::Enumerate;
+use std::slice;
+
+use crate::config::Variant;
+use crate::data::{MapCell, Pos};
+use crate::level::Level;
+use crate::map::Map;
+use crate::moves::{Move, Moves};
+use crate::state::State;
+
+/// Counts of a valid solution
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SolutionInfo {
+    pub move_cnt: usize,
+    pub push_cnt: usize,
+    /// Runs of consecutive pushes of the same box in the same direction
+    /// (the player's steps between them don't matter)
+    pub box_lines: usize,
+    /// Runs of consecutive pushes of the same box, i.e. how many times the player
+    /// starts pushing a different box (including the first one)
+    pub box_changes: usize,
+}
+
+/// Where a box ended up in a solution, see `Level::box_assignment`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BoxFate {
+    /// On the goal at `(row, column)` after the last move
+    Goal((usize, usize)),
+    /// Pushed into a remover by the push with this index (starting at 0)
+    Removed { push: usize },
+}
+
+/// A box of the initial state and its fate in a solution
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BoxAssignment {
+    /// Initial position as `(row, column)`
+    pub start: (usize, usize),
+    pub fate: BoxFate,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveErr {
+    /// The player would walk into a wall
+    Wall,
+    /// The pushed box would move into a wall, another box or a player
+    Blocked,
+    /// The move is marked as a push but there is no box
+    NoBox,
+    /// The move isn't marked as a push but there is a box
+    UnmarkedPush,
+    /// The player would walk into another player (Multiban)
+    OtherPlayer,
+    /// The move is made by a player the level doesn't have
+    NoPlayer,
+}
+
+impl Display for MoveErr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match *self {
+            MoveErr::Wall => write!(f, "Player walks into a wall"),
+            MoveErr::Blocked => write!(f, "Box can't be pushed"),
+            MoveErr::NoBox => write!(f, "Push without a box"),
+            MoveErr::UnmarkedPush => write!(f, "Box pushed by a move not marked as a push"),
+            MoveErr::OtherPlayer => write!(f, "Player walks into another player"),
+            MoveErr::NoPlayer => write!(f, "No such player"),
+        }
+    }
+}
+
+impl Error for MoveErr {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerifyErr {
+    /// The move at `index` (starting at 0) can't be made
+    IllegalMove { index: usize, err: MoveErr },
+    /// All moves are legal but the level is not solved after them
+    NotSolved,
+}
+
+impl Display for VerifyErr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match *self {
+            VerifyErr::IllegalMove { index, err } => {
+                write!(f, "Illegal move {}: {err}", index + 1)
+            }
+            VerifyErr::NotSolved => write!(f, "Level is not solved after the last move"),
+        }
+    }
+}
+
+impl Error for VerifyErr {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            VerifyErr::IllegalMove { err, .. } => Some(err),
+            VerifyErr::NotSolved => None,
+        }
+    }
+}
+
+/// Returns the state after the move, moves marked as pushes are pulls in `Variant::Pull`
+pub(crate) fn apply_move(
+    map: &dyn Map,
+    state: &State,
+    variant: Variant,
+    mov: Move,
+) -> Result<State, MoveErr> {
+    let player = usize::from(mov.player);
+    if player >= state.player_cnt() {
+        return Err(MoveErr::NoPlayer);
+    }
+    let new_player_pos = state.player(player) + mov.dir;
+    if map.grid()[new_player_pos] == MapCell::Wall {
+        return Err(MoveErr::Wall);
+    }
+    if state.has_other_player(player, new_player_pos) {
+        return Err(MoveErr::OtherPlayer);
+    }
+
+    match variant {
+        Variant::Push | Variant::Multiban => apply_push(map, state, player, mov),
+        Variant::Pull => apply_pull(map, state, player, mov),
+    }
+}
+
+fn apply_push(map: &dyn Map, state: &State, player: usize, mov: Move) -> Result<State, MoveErr> {
+    let new_player_pos = state.player(player) + mov.dir;
+
+    let mut new_boxes = state.boxes.clone();
+    let box_index = new_boxes.iter().position(|&b| b == new_player_pos);
+    match (mov.is_push, box_index) {
+        (true, Some(box_index)) => {
+            let new_box_pos = new_player_pos + mov.dir;
+            if map.grid()[new_box_pos] == MapCell::Wall
+                || new_boxes.contains(&new_box_pos)
+                || state.has_other_player(player, new_box_pos)
+            {
+                return Err(MoveErr::Blocked);
+            }
+            new_boxes[box_index] = new_box_pos;
+            if map.grid()[new_box_pos] == MapCell::Remover {
+                new_boxes.remove(box_index);
+            }
+        }
+        (true, None) => return Err(MoveErr::NoBox),
+        (false, Some(_)) => return Err(MoveErr::UnmarkedPush),
+        (false, None) => {}
+    }
+
+    Ok(state.moved(player, new_player_pos, new_boxes))
+}
+
+fn apply_pull(map: &dyn Map, state: &State, player: usize, mov: Move) -> Result<State, MoveErr> {
+    let player_pos = state.player(player);
+    let new_player_pos = player_pos + mov.dir;
+    let mut new_boxes = state.boxes.clone();
+    if new_boxes.contains(&new_player_pos) {
+        return Err(MoveErr::Blocked);
+    }
+
+    // stepping away from a box without pulling it is allowed
+    if mov.is_push {
+        let behind = player_pos - mov.dir;
+        let box_index = new_boxes
+            .iter()
+            .position(|&b| b == behind)
+            .ok_or(MoveErr::NoBox)?;
+        new_boxes[box_index] = player_pos;
+        if map.grid()[player_pos] == MapCell::Remover {
+            new_boxes.remove(box_index);
+        }
+    }
+
+    Ok(state.moved(player, new_player_pos, new_boxes))
+}
+
+/// Returns the state after all moves or `VerifyErr::IllegalMove`
+pub(crate) fn apply_moves(
+    map: &dyn Map,
+    initial_state: &State,
+    variant: Variant,
+    moves: &Moves,
+) -> Result<State, VerifyErr> {
+    let mut state = initial_state.clone();
+    for (index, &mov) in moves.iter().enumerate() {
+        state = apply_move(map, &state, variant, mov)
+            .map_err(|err| VerifyErr::IllegalMove { index, err })?;
+    }
+    Ok(state)
+}
+
+/// Levels after each move, created by `Moves::iter_states`
+#[derive(Debug, Clone)]
+pub struct LevelStates<'a> {
+    level: &'a Level,
+    state: State,
+    moves: Enumerate<slice::Iter<'a, Move>>,
+    failed: bool,
+}
+
+impl<'a> LevelStates<'a> {
+    pub(crate) fn new(level: &'a Level, moves: &'a Moves) -> Self {
+        Self {
+            level,
+            state: level.state.clone(),
+            moves: moves.iter().enumerate(),
+            failed: false,
+        }
+    }
+}
+
+impl Iterator for LevelStates<'_> {
+    type Item = Result<Level, VerifyErr>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let (index, &mov) = self.moves.next()?;
+        match apply_move(self.level.map(), &self.state, self.level.variant, mov) {
+            Ok(state) => {
+                self.state = state.clone();
+                let mut level = Level::new(self.level.map.clone(), state);
+                level.variant = self.level.variant;
+                Some(Ok(level))
+            }
+            Err(err) => {
+                self.failed = true;
+                Some(Err(VerifyErr::IllegalMove { index, err }))
+            }
+        }
+    }
+}
+
+pub(crate) fn verify(
+    map: &dyn Map,
+    initial_state: &State,
+    variant: Variant,
+    moves: &Moves,
+) -> Result<SolutionInfo, VerifyErr> {
+    let mut state = initial_state.clone();
+    let mut box_lines = 0;
+    let mut box_changes = 0;
+    // where the last pushed box ended up and in which direction
+    let mut last_push = None;
+    for (index, &mov) in moves.iter().enumerate() {
+        let new_state = apply_move(map, &state, variant, mov)
+            .map_err(|err| VerifyErr::IllegalMove { index, err })?;
+        if mov.is_push {
+            let old_pos = *state
+                .boxes
+                .iter()
+                .find(|pos| !new_state.boxes.contains(pos))
+                .expect("A push moves exactly one box");
+            let same_box = last_push.is_some_and(|(pos, _)| pos == old_pos);
+            if !same_box {
+                box_changes += 1;
+            }
+            if !same_box || last_push.is_some_and(|(_, dir)| dir != mov.dir) {
+                box_lines += 1;
+            }
+            last_push = Some((old_pos + mov.dir, mov.dir));
+        }
+        state = new_state;
+    }
+
+    if !map.is_solved(&state.boxes) {
+        return Err(VerifyErr::NotSolved);
+    }
+
+    Ok(SolutionInfo {
+        move_cnt: moves.move_cnt(),
+        push_cnt: moves.push_cnt(),
+        box_lines,
+        box_changes,
+    })
+}
+
+/// Follows boxes through pushes, ids are indices into the initial state's boxes
+/// (the order of `Level::box_positions`).
+#[derive(Debug, Clone)]
+pub(crate) struct BoxTracker {
+    positions: Vec<Pos>,
+    removed: Vec<bool>,
+}
+
+impl BoxTracker {
+    pub(crate) fn new(initial_state: &State) -> Self {
+        Self {
+            positions: initial_state.boxes.to_vec(),
+            removed: vec![false; initial_state.boxes.len()],
+        }
+    }
+
+    /// The id of the box which moved between the states and whether it was removed
+    pub(crate) fn push(&mut self, state: &State, new_state: &State) -> (usize, bool) {
+        // states keep boxes sorted so they're matched by the positions which changed
+        let old_pos = state
+            .boxes
+            .iter()
+            .find(|pos| !new_state.boxes.contains(pos))
+            .expect("A push moves exactly one box");
+        let new_pos = new_state
+            .boxes
+            .iter()
+            .find(|pos| !state.boxes.contains(pos));
+        let id = (0..self.positions.len())
+            .find(|&id| !self.removed[id] && self.positions[id] == *old_pos)
+            .expect("Every remaining box is tracked");
+        match new_pos {
+            Some(&new_pos) => self.positions[id] = new_pos,
+            None => self.removed[id] = true,
+        }
+        (id, new_pos.is_none())
+    }
+}
+
+/// Follows each box through the moves, in the order of `Level::box_positions`
+pub(crate) fn box_assignment(
+    map: &dyn Map,
+    initial_state: &State,
+    variant: Variant,
+    moves: &Moves,
+) -> Result<Vec<BoxAssignment>, VerifyErr> {
+    let mut tracker = BoxTracker::new(initial_state);
+    let mut fates = vec![None; initial_state.boxes.len()];
+    let mut state = initial_state.clone();
+    let mut push = 0;
+    for (index, &mov) in moves.iter().enumerate() {
+        let new_state = apply_move(map, &state, variant, mov)
+            .map_err(|err| VerifyErr::IllegalMove { index, err })?;
+        if mov.is_push {
+            let (id, removed) = tracker.push(&state, &new_state);
+            if removed {
+                fates[id] = Some(BoxFate::Removed { push });
+            }
+            push += 1;
+        }
+        state = new_state;
+    }
+
+    if !map.is_solved(&state.boxes) {
+        return Err(VerifyErr::NotSolved);
+    }
+
+    Ok(initial_state
+        .boxes
+        .iter()
+        .zip(tracker.positions)
+        .zip(fates)
+        .map(|((&start, end), fate)| BoxAssignment {
+            start: start.into(),
+            fate: fate.unwrap_or_else(|| BoxFate::Goal(end.into())),
+        })
+        .collect())
+}
+
+/// The id of the pushed box (see `BoxTracker`) and the push for every push of the moves
+pub(crate) fn push_sequence(
+    map: &dyn Map,
+    initial_state: &State,
+    variant: Variant,
+    moves: &Moves,
+) -> Result<Vec<(usize, Move)>, VerifyErr> {
+    let mut tracker = BoxTracker::new(initial_state);
+    let mut pushes = Vec::new();
+    let mut state = initial_state.clone();
+    for (index, &mov) in moves.iter().enumerate() {
+        let new_state = apply_move(map, &state, variant, mov)
+            .map_err(|err| VerifyErr::IllegalMove { index, err })?;
+        if mov.is_push {
+            let (id, _) = tracker.push(&state, &new_state);
+            pushes.push((id, mov));
+        }
+        state = new_state;
+    }
+    Ok(pushes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verifying() {
+        let level: Level = r"
+#######
+#@ $ .#
+#  $ .#
+#######"
+            .parse()
+            .unwrap();
+        let verify = |lurd| level.verify_solution(&Moves::from_lurd(lurd).unwrap());
+
+        assert_eq!(
+            verify("rRRlldRR"),
+            Ok(SolutionInfo {
+                move_cnt: 8,
+                push_cnt: 4,
+                box_lines: 2,
+                box_changes: 2,
+            })
+        );
+        assert_eq!(verify("rRR"), Err(VerifyErr::NotSolved));
+        assert_eq!(
+            verify("u"),
+            Err(VerifyErr::IllegalMove {
+                index: 0,
+                err: MoveErr::Wall,
+            })
+        );
+        assert_eq!(
+            verify("rrr"),
+            Err(VerifyErr::IllegalMove {
+                index: 1,
+                err: MoveErr::UnmarkedPush,
+            })
+        );
+        assert_eq!(
+            verify("R"),
+            Err(VerifyErr::IllegalMove {
+                index: 0,
+                err: MoveErr::NoBox,
+            })
+        );
+        assert_eq!(
+            verify("rRRR"),
+            Err(VerifyErr::IllegalMove {
+                index: 3,
+                err: MoveErr::Blocked,
+            })
+        );
+    }
+
+    #[test]
+    fn box_lines() {
+        let level: Level = r"
+#######
+#     #
+#@$   #
+#    .#
+#######"
+            .parse()
+            .unwrap();
+        let info = |lurd| {
+            let info = level
+                .verify_solution(&Moves::from_lurd(lurd).unwrap())
+                .unwrap();
+            (info.box_lines, info.box_changes)
+        };
+
+        // turning the same box starts a new line
+        assert_eq!(info("RRRurD"), (2, 1));
+        // walking around doesn't break a line
+        assert_eq!(info("RRduRurD"), (2, 1));
+    }
+
+    #[test]
+    fn assigning_boxes() {
+        let level: Level = r"
+#######
+#@ $ .#
+#  $ .#
+#######"
+            .parse()
+            .unwrap();
+        let moves = Moves::from_lurd("rRRlldRR").unwrap();
+        assert_eq!(
+            level.box_assignment(&moves).unwrap(),
+            [
+                BoxAssignment {
+                    start: (1, 3),
+                    fate: BoxFate::Goal((1, 5)),
+                },
+                BoxAssignment {
+                    start: (2, 3),
+                    fate: BoxFate::Goal((2, 5)),
+                },
+            ]
+        );
+        let moves = Moves::from_lurd("rRR").unwrap();
+        assert_eq!(level.box_assignment(&moves), Err(VerifyErr::NotSolved));
+
+        let level: Level = "########\n#r$@ $.#\n########".parse().unwrap();
+        let moves = Moves::from_lurd("LrrR").unwrap();
+        assert_eq!(
+            level.box_assignment(&moves).unwrap(),
+            [
+                BoxAssignment {
+                    start: (1, 2),
+                    fate: BoxFate::Removed { push: 0 },
+                },
+                BoxAssignment {
+                    start: (1, 5),
+                    fate: BoxFate::Goal((1, 6)),
+                },
+            ]
+        );
+        let assignment = level.box_assignment(&moves).unwrap();
+        assert_eq!(
+            level.format_assignment(&assignment).to_string(),
+            "################\n## ra @   b  b##\n################\na: removed by push 1\n"
+        );
+    }
+
+    #[test]
+    fn verifying_remover() {
+        let level: Level = r"
+######
+#@$ r#
+######"
+            .parse()
+            .unwrap();
+        let moves = Moves::from_lurd("RR").unwrap();
+        assert_eq!(
+            level.verify_solution(&moves),
+            Ok(SolutionInfo {
+                move_cnt: 2,
+                push_cnt: 2,
+                box_lines: 1,
+                box_changes: 1,
+            })
+        );
+    }
+
+    #[test]
+    fn verifying_remover_custom() {
+        let level: Level = r"
+<><><><><><>
+<>P B    R<>
+<><><><><><>"
+            .parse()
+            .unwrap();
+        let moves = Moves::from_lurd("RR").unwrap();
+        assert_eq!(
+            level.verify_solution(&moves),
+            Ok(SolutionInfo {
+                move_cnt: 2,
+                push_cnt: 2,
+                box_lines: 1,
+                box_changes: 1,
+            })
+        );
+    }
+
+    #[test]
+    fn verifying_pull() {
+        let mut level: Level = r"
+#######
+# $@. #
+#######"
+            .parse()
+            .unwrap();
+        let verify = |level: &Level, lurd| level.verify_solution(&Moves::from_lurd(lurd).unwrap());
+
+        assert_eq!(
+            verify(&level, "RR"),
+            Err(VerifyErr::IllegalMove {
+                index: 0,
+                err: MoveErr::NoBox,
+            })
+        );
+
+        level.set_variant(Variant::Pull);
+        assert_eq!(
+            verify(&level, "RR"),
+            Ok(SolutionInfo {
+                move_cnt: 2,
+                push_cnt: 2,
+                box_lines: 1,
+                box_changes: 1,
+            })
+        );
+        // pulling is optional
+        assert_eq!(verify(&level, "r"), Err(VerifyErr::NotSolved));
+        assert_eq!(
+            verify(&level, "L"),
+            Err(VerifyErr::IllegalMove {
+                index: 0,
+                err: MoveErr::Blocked,
+            })
+        );
+        assert_eq!(
+            verify(&level, "rrR"),
+            Err(VerifyErr::IllegalMove {
+                index: 2,
+                err: MoveErr::Wall,
+            })
+        );
+        assert_eq!(
+            verify(&level, "rR"),
+            Err(VerifyErr::IllegalMove {
+                index: 1,
+                err: MoveErr::NoBox,
+            })
+        );
+
+        let mut level: Level = "######\n# r@$#\n######".parse().unwrap();
+        level.set_variant(Variant::Pull);
+        assert_eq!(
+            verify(&level, "LL"),
+            Ok(SolutionInfo {
+                move_cnt: 2,
+                push_cnt: 2,
+                box_lines: 1,
+                box_changes: 1,
+            })
+        );
+    }
+
+    #[test]
+    fn verifying_multiban() {
+        let level =
+            Level::parse_variant("#######\n#@$ @.#\n### ###\n  ###", Variant::Multiban).unwrap();
+        let verify = |lurd| level.verify_solution(&Moves::from_lurd(lurd).unwrap());
+
+        assert_eq!(
+            verify("[2]ld[1]RRR"),
+            Ok(SolutionInfo {
+                move_cnt: 5,
+                push_cnt: 3,
+                box_lines: 1,
+                box_changes: 1,
+            })
+        );
+        assert_eq!(
+            verify("RR"),
+            Err(VerifyErr::IllegalMove {
+                index: 1,
+                err: MoveErr::Blocked,
+            })
+        );
+        assert_eq!(
+            verify("[2]ld[1]RR[2]u"),
+            Err(VerifyErr::IllegalMove {
+                index: 4,
+                err: MoveErr::OtherPlayer,
+            })
+        );
+        assert_eq!(
+            verify("[3]l"),
+            Err(VerifyErr::IllegalMove {
+                index: 0,
+                err: MoveErr::NoPlayer,
+            })
+        );
+    }
+}
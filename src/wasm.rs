@@ -0,0 +1,80 @@
+//! Exports for using the solver from JS, e.g. in web-based Sokoban players.
+
+use js_sys::Function;
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use crate::config::{Method, SolverOptions};
+use crate::level::Level;
+use crate::moves::Moves;
+use crate::solver::Stats;
+use crate::Solve;
+
+#[derive(Debug, Serialize)]
+struct SolveResult {
+    /// LURD, `None` if there's no solution or the search was interrupted
+    solution: Option<String>,
+    moves: Option<usize>,
+    pushes: Option<usize>,
+    created: i32,
+    visited: i32,
+    interrupted: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct Progress {
+    created: i32,
+    visited: i32,
+}
+
+/// Solves a level given in the XSB format.
+///
+/// `method` is one of `moves-pushes`, `moves`, `pushes-moves`, `pushes` or `any`.
+///
+/// `progress` is called periodically with an object containing the number
+/// of `created` and `visited` states so far, returning `false` from it stops the search.
+///
+/// Returns an object with the `solution` in LURD (or `null`), its `moves` and `pushes`,
+/// the number of `created` and `visited` states and whether the search was `interrupted`.
+/// Throws an error message if the level or method is invalid.
+// wasm_bindgen needs owned JS values
+#[allow(clippy::needless_pass_by_value)]
+#[wasm_bindgen]
+pub fn solve(
+    level_xsb: &str,
+    method: &str,
+    progress: Option<Function>,
+) -> Result<JsValue, JsValue> {
+    let level: Level = level_xsb.parse().map_err(|err| error(&err))?;
+    let method: Method = method.parse().map_err(|err| error(&err))?;
+
+    let mut progress = |stats: &Stats| match progress {
+        None => true,
+        Some(ref progress) => {
+            let stats = Progress {
+                created: stats.total_created(),
+                visited: stats.total_unique_visited(),
+            };
+            let stats = serde_wasm_bindgen::to_value(&stats).unwrap_or(JsValue::UNDEFINED);
+            // only an explicit `false` stops the search, exceptions are ignored
+            progress.call1(&JsValue::NULL, &stats).ok() != Some(JsValue::FALSE)
+        }
+    };
+    let solver_ok = level
+        .solve_with_progress(method, &SolverOptions::default(), &mut progress)
+        .map_err(|err| error(&err))?;
+
+    let result = SolveResult {
+        solution: solver_ok.moves.as_ref().map(ToString::to_string),
+        moves: solver_ok.moves.as_ref().map(Moves::move_cnt),
+        pushes: solver_ok.moves.as_ref().map(Moves::push_cnt),
+        created: solver_ok.stats.total_created(),
+        visited: solver_ok.stats.total_unique_visited(),
+        interrupted: solver_ok.interrupted,
+    };
+    serde_wasm_bindgen::to_value(&result).map_err(Into::into)
+}
+
+fn error(err: &dyn ToString) -> JsValue {
+    JsValue::from_str(&err.to_string())
+}
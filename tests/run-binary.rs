@@ -1,6 +1,58 @@
 use assert_cmd::prelude::*;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
+/// A directory for the files of one test, deleted when the test ends.
+///
+/// Named after the test and the process so tests running at the same time don't share it.
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(test: &str) -> Self {
+        let path =
+            std::env::temp_dir().join(format!("sokoban-solver-{}-{}", test, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+
+    fn path(&self) -> &Path {
+        &self.0
+    }
+
+    fn join(&self, file: &str) -> PathBuf {
+        self.0.join(file)
+    }
+
+    /// Creates the file and returns its path
+    fn write(&self, file: &str, contents: &str) -> PathBuf {
+        let path = self.join(file);
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Memory usage depends on the platform so replace it with a placeholder
+fn replace_memory(stdout: &[u8]) -> String {
+    let stdout = String::from_utf8_lossy(stdout);
+    let mut replaced = String::new();
+    for line in stdout.lines() {
+        if line.starts_with("Approximate memory used:") {
+            replaced.push_str("<memory>\n");
+        } else if !line.starts_with("Peak RSS:") {
+            replaced.push_str(line);
+            replaced.push('\n');
+        }
+    }
+    replaced
+}
+
 #[test]
 fn run_xsb_pushes() {
     let output = r"Solving levels/custom/02-one-way.txt...
@@ -64,18 +116,39 @@ Depth          Created        Unique         Duplicates     Unknown (not reached
 2:             1              1              0              0
 3:             1              1              0              0
 
+Pruned: 0 (dead squares: 0, removers: 0, max depth: 0, near duplicates: 0)
+<memory>
+
 UUU
 Moves: 3
 Pushes: 3
+Box lines: 1
+Box changes: 1
 ";
 
-    Command::cargo_bin(env!("CARGO_PKG_NAME"))
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))
         .unwrap()
+        .arg("solve")
+        .arg("--no-cache")
+        .arg("levels/custom/02-one-way.txt")
+        .assert()
+        .success()
+        .stderr("");
+    assert_eq!(replace_memory(&assert.get_output().stdout), output);
+}
+
+#[test]
+fn run_without_subcommand() {
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .arg("--no-cache")
+        .arg("-p")
         .arg("levels/custom/02-one-way.txt")
         .assert()
         .success()
-        .stdout(output)
         .stderr("");
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("UUU\nMoves: 3\nPushes: 3\n"));
 }
 
 #[test]
@@ -121,20 +194,27 @@ Depth          Created        Unique         Duplicates     Unknown (not reached
 2:             1              1              0              0
 3:             1              1              0              0
 
+Pruned: 0 (dead squares: 0, removers: 0, max depth: 0, near duplicates: 0)
+<memory>
+
 rRR
 Moves: 3
 Pushes: 2
+Box lines: 1
+Box changes: 1
 ";
 
-    Command::cargo_bin(env!("CARGO_PKG_NAME"))
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))
         .unwrap()
+        .arg("solve")
+        .arg("--no-cache")
         .arg("--moves")
         .arg("--custom")
         .arg("levels/custom/02-one-way-xsb.txt")
         .assert()
         .success()
-        .stdout(output)
         .stderr("");
+    assert_eq!(replace_memory(&assert.get_output().stdout), output);
 }
 
 #[test]
@@ -145,6 +225,8 @@ fn run_bad_formatting_args() {
 
     Command::cargo_bin(env!("CARGO_PKG_NAME"))
         .unwrap()
+        .arg("solve")
+        .arg("--no-cache")
         .arg("--custom")
         .arg("--xsb")
         .arg("levels/custom/02-one-way-xsb.txt")
@@ -152,3 +234,599 @@ fn run_bad_formatting_args() {
         .failure()
         .stdout("");
 }
+
+#[test]
+fn run_verify() {
+    let dir = TempDir::new("run-verify");
+    let valid = dir.write("valid.txt", "UUU\n");
+    let invalid = dir.write("invalid.txt", "UUD");
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .arg("verify")
+        .arg("levels/custom/02-one-way.txt")
+        .arg(&valid)
+        .assert()
+        .success()
+        .stdout("Valid solution\nMoves: 3\nPushes: 3\nBox lines: 1\nBox changes: 1\n")
+        .stderr("");
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .arg("verify")
+        .arg("levels/custom/02-one-way.txt")
+        .arg(&invalid)
+        .assert()
+        .failure()
+        .stdout("Invalid solution: Illegal move 3: Push without a box\n");
+}
+
+#[test]
+fn run_verify_solutions() {
+    let dir = TempDir::new("run-verify-solutions");
+    let db = dir.write(
+        "db.sok",
+        "#####\n#@$.#\n#####\nSolution: R\n\n######\n#@$ .#\n######\nSolution (3/2): RR\n",
+    );
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .arg("verify-solutions")
+        .arg(&db)
+        .assert()
+        .failure()
+        .stdout(format!(
+            "{db} level 1 Solution: 1 moves, 1 pushes, 1 box lines, 1 box changes\n\
+             {db} level 2 Solution (3/2): 2 moves, 2 pushes, 1 box lines, 1 box changes \
+             (claims 3 moves, 2 pushes)\n\
+             Checked 2 solutions of 2 levels, 1 problems\n",
+            db = db.display()
+        ))
+        .stderr("");
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .arg("verify-solutions")
+        .arg("--level")
+        .arg("1")
+        .arg(&db)
+        .assert()
+        .success();
+}
+
+#[test]
+fn run_invalid_level() {
+    let dir = TempDir::new("run-invalid-level");
+    let level = dir.write("level.txt", "#####\n#@X.#\n#####\n");
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .arg("solve")
+        .arg(&level)
+        .assert()
+        .failure()
+        .stderr("Can't load level: Invalid character 'X' at line 2, column 3:\n#@X.#\n  ^\n");
+}
+
+#[test]
+fn run_optimize() {
+    let dir = TempDir::new("run-optimize");
+    let solution = dir.write("solution.txt", "UUduU\n");
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .arg("optimize")
+        .arg("levels/custom/02-one-way.txt")
+        .arg(&solution)
+        .assert()
+        .success()
+        .stdout(
+            "Optimizing levels/custom/02-one-way.txt...\nUUU\nMoves: 3 (was 5)\nPushes: 3 (was 3)\n",
+        )
+        .stderr("");
+}
+
+#[test]
+fn run_cache() {
+    let dir = TempDir::new("run-cache");
+
+    let run = || {
+        let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .unwrap()
+            .env("SOKOBAN_SOLVER_CACHE", dir.path())
+            .arg("solve")
+            .arg("levels/custom/02-one-way.txt")
+            .assert()
+            .success()
+            .stderr("");
+        String::from_utf8_lossy(&assert.get_output().stdout).into_owned()
+    };
+    let solved = run();
+    assert!(!solved.contains("Using cached result"));
+    let cached = run();
+    assert!(cached.contains("Using cached result"));
+    assert!(cached.contains("UUU\nMoves: 3\nPushes: 3\n"));
+}
+
+#[test]
+fn run_race() {
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .arg("solve")
+        .arg("--race")
+        .arg("--pushes")
+        .arg("levels/custom/02-one-way.txt")
+        .assert()
+        .success()
+        .stderr("");
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout).into_owned();
+    // which search wins depends on the scheduling but all find the only solution
+    assert!(stdout.contains("Race won by "));
+    assert!(stdout.contains("UUU\nMoves: 3\nPushes: 3\n"));
+}
+
+#[test]
+fn run_portfolio() {
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .arg("solve")
+        .arg("--portfolio")
+        .arg("levels/custom/02-one-way.txt")
+        .assert()
+        .success()
+        .stderr("");
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout).into_owned();
+    assert!(stdout.contains("Finished by stage 1 of 3: any (fast), at most 10000 states\n"));
+    assert!(stdout.contains("UUU\nMoves: 3\nPushes: 3\n"));
+}
+
+#[test]
+fn run_tie_break() {
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .arg("solve")
+        .arg("--pushes")
+        .arg("--tie-break")
+        .arg("shallower")
+        .arg("levels/custom/02-one-way.txt")
+        .assert()
+        .success()
+        .stderr("");
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout).into_owned();
+    assert!(stdout.contains("UUU\nMoves: 3\nPushes: 3\n"));
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .arg("solve")
+        .arg("--tie-break")
+        .arg("sideways")
+        .arg("levels/custom/02-one-way.txt")
+        .assert()
+        .failure();
+}
+
+#[test]
+fn run_checkpoint() {
+    let dir = TempDir::new("run-checkpoint");
+    let checkpoint = dir.join("checkpoint.json.zst");
+
+    // zero timeout stops before visiting the first state
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .arg("solve")
+        .arg("--no-cache")
+        .arg("--pushes")
+        .arg("--timeout")
+        .arg("0")
+        .arg("--checkpoint")
+        .arg(&checkpoint)
+        .arg("levels/custom/02-one-way.txt")
+        .assert()
+        .success()
+        .stderr("");
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout).into_owned();
+    assert!(stdout.contains("Search interrupted\n"));
+    assert!(stdout.contains("Saved checkpoint to "));
+    assert!(checkpoint.exists());
+
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .arg("solve")
+        .arg("--resume")
+        .arg(&checkpoint)
+        .arg("levels/custom/02-one-way.txt")
+        .assert()
+        .success()
+        .stderr("");
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout).into_owned();
+    assert!(stdout.contains("UUU\nMoves: 3\nPushes: 3\n"));
+
+    // a checkpoint only works for the level it was saved for
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .arg("solve")
+        .arg("--resume")
+        .arg(&checkpoint)
+        .arg("levels/custom/01-simplest-custom.txt")
+        .assert()
+        .failure();
+}
+
+#[test]
+fn run_pull_variant() {
+    let dir = TempDir::new("run-pull-variant");
+    let level = dir.write("level.txt", "#######\n# $@. #\n#######\n");
+
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .arg("solve")
+        .arg("--no-cache")
+        .arg("--variant")
+        .arg("pull")
+        .arg(&level)
+        .assert()
+        .success()
+        .stderr("");
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout).into_owned();
+    assert!(stdout.contains("RR\nMoves: 2\nPushes: 2\n"));
+
+    // pushing can't solve it
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .arg("solve")
+        .arg("--no-cache")
+        .arg(&level)
+        .assert()
+        .success()
+        .stderr("");
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout).into_owned();
+    assert!(!stdout.contains("RR\n"));
+}
+
+#[test]
+fn run_any_profile() {
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .arg("solve")
+        .arg("--no-cache")
+        .arg("--any-profile")
+        .arg("fast")
+        .arg("levels/custom/02-one-way.txt")
+        .assert()
+        .success()
+        .stderr("");
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout).into_owned();
+    assert!(stdout.contains("Moves: 3\nPushes: 3\n"));
+
+    // profiles only apply to --any
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .arg("solve")
+        .arg("--no-cache")
+        .arg("--pushes")
+        .arg("--any-profile")
+        .arg("fast")
+        .arg("levels/custom/02-one-way.txt")
+        .assert()
+        .failure()
+        .stdout("");
+}
+
+#[test]
+fn run_custom_remover() {
+    let dir = TempDir::new("run-custom-remover");
+    let solution = dir.write("solution.txt", "U\n");
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .arg("verify")
+        .arg("levels/custom/remover-01-simplest-custom.txt")
+        .arg(&solution)
+        .assert()
+        .success()
+        .stdout("Valid solution\nMoves: 1\nPushes: 1\nBox lines: 1\nBox changes: 1\n")
+        .stderr("");
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .arg("fmt")
+        .arg("levels/custom/remover-01-simplest-custom.txt")
+        .assert()
+        .success()
+        .stdout("###\n#r#\n#$#\n#@#\n###\n")
+        .stderr("");
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .arg("fmt")
+        .arg("--custom")
+        .arg("levels/custom/remover-01-simplest-custom.txt")
+        .assert()
+        .success()
+        .stdout(std::fs::read_to_string("levels/custom/remover-01-simplest-custom.txt").unwrap())
+        .stderr("");
+}
+
+#[test]
+fn run_fmt() {
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .arg("fmt")
+        .arg("--rle")
+        .arg("levels/custom/02-one-way.txt")
+        .assert()
+        .success()
+        .stdout("3#|#.#|#-#|#-#|#$#|#@#|3#\n")
+        .stderr("");
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .arg("fmt")
+        .arg("--input-format")
+        .arg("custom")
+        .arg("--rle")
+        .arg("levels/custom/02-one-way.txt")
+        .assert()
+        .success()
+        .stdout("3#|#.#|#-#|#-#|#$#|#@#|3#\n");
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .arg("fmt")
+        .arg("--input-format")
+        .arg("xsb")
+        .arg("levels/custom/02-one-way.txt")
+        .assert()
+        .failure()
+        .stderr("Can't load level: Invalid character '<' at line 1, column 1:\n<><><>\n^\n");
+}
+
+#[test]
+fn run_fmt_rotate() {
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .arg("fmt")
+        .arg("--rotate")
+        .arg("90")
+        .arg("levels/custom/02-one-way.txt")
+        .assert()
+        .success()
+        .stdout("#######\n#@$  .#\n#######\n")
+        .stderr("");
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .arg("fmt")
+        .arg("--rotate")
+        .arg("270")
+        .arg("--mirror")
+        .arg("horizontal")
+        .arg("levels/custom/02-one-way.txt")
+        .assert()
+        .success()
+        .stdout("#######\n#.  $@#\n#######\n")
+        .stderr("");
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .arg("fmt")
+        .arg("--rotate")
+        .arg("45")
+        .arg("levels/custom/02-one-way.txt")
+        .assert()
+        .failure();
+}
+
+#[test]
+fn run_level_with_title() {
+    let dir = TempDir::new("run-level-with-title");
+    let level = dir.write(
+        "titled.xsb",
+        "; One step\n#####\n#@$.#\n#####\nAuthor: Someone\n",
+    );
+
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .arg("solve")
+        .arg("--no-cache")
+        .arg("--save-solutions")
+        .arg(dir.path())
+        .arg(&level)
+        .assert()
+        .success()
+        .stderr("");
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout).into_owned();
+    assert!(stdout.starts_with(&format!("Solving {} (One step)...\n", level.display())));
+    let sok = std::fs::read_to_string(dir.join("titled.sok")).unwrap();
+    assert!(sok.starts_with("#####\n#@$.#\n#####\nTitle: One step\nAuthor: Someone\n"));
+}
+
+#[test]
+fn run_summary() {
+    let dir = TempDir::new("run-summary");
+    let csv = dir.join("summary.csv");
+
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .arg("solve")
+        .arg("--no-cache")
+        .arg("--summary-csv")
+        .arg(&csv)
+        .arg("levels/custom/02-one-way.txt")
+        .arg("levels/custom/01-simplest-custom.txt")
+        .assert()
+        .success()
+        .stderr("");
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout).into_owned();
+    assert!(stdout.contains("\nSolved 2 of 2 levels in "));
+
+    // time and memory are not deterministic
+    let csv = std::fs::read_to_string(csv).unwrap();
+    let rows: Vec<_> = csv
+        .lines()
+        .map(|line| line.rsplitn(3, ',').nth(2).unwrap())
+        .collect();
+    assert_eq!(
+        rows,
+        [
+            "level,solved,moves,pushes,created,visited",
+            r#""levels/custom/02-one-way.txt",true,3,3,4,4"#,
+            r#""levels/custom/01-simplest-custom.txt",true,1,1,2,2"#,
+        ]
+    );
+}
+
+#[test]
+fn run_stats_csv() {
+    let dir = TempDir::new("run-stats-csv");
+    let csv = dir.join("stats.csv");
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .arg("solve")
+        .arg("--stats-csv")
+        .arg(&csv)
+        .arg("levels/custom/02-one-way.txt")
+        .arg("levels/custom/01-simplest-custom.txt")
+        .assert()
+        .success()
+        .stderr("");
+
+    let csv = std::fs::read_to_string(csv).unwrap();
+    assert_eq!(
+        csv,
+        r#"level,depth,created,unique,duplicates,unknown
+"levels/custom/02-one-way.txt",0,1,1,0,0
+"levels/custom/02-one-way.txt",1,1,1,0,0
+"levels/custom/02-one-way.txt",2,1,1,0,0
+"levels/custom/02-one-way.txt",3,1,1,0,0
+"levels/custom/02-one-way.txt",total,4,4,0,0
+"levels/custom/01-simplest-custom.txt",0,1,1,0,0
+"levels/custom/01-simplest-custom.txt",1,1,1,0,0
+"levels/custom/01-simplest-custom.txt",total,2,2,0,0
+"#
+    );
+}
+
+#[test]
+fn run_record_compare() {
+    let dir = TempDir::new("run-record-compare");
+    let record = |file: &str, levels: &[&str]| {
+        let path = dir.join(file);
+        Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .unwrap()
+            .arg("solve")
+            .arg("--no-cache")
+            .arg("--pushes")
+            .arg("--record")
+            .arg(&path)
+            .args(levels)
+            .assert()
+            .success()
+            .stderr("");
+        path
+    };
+    let old = record("old.json", &["levels/custom/01-simplest-custom.txt"]);
+    let same = record("same.json", &["levels/custom/01-simplest-custom.txt"]);
+    let more = record(
+        "more.json",
+        &[
+            "levels/custom/01-simplest-custom.txt",
+            "levels/custom/02-one-way.txt",
+        ],
+    );
+
+    let compare = |new: &std::path::Path| {
+        Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .unwrap()
+            .arg("compare")
+            .arg(&old)
+            .arg(new)
+            .assert()
+    };
+    compare(&same)
+        .success()
+        .stdout("No changes in 1 levels\n")
+        .stderr("");
+    compare(&more)
+        .failure()
+        .stdout("levels/custom/02-one-way.txt: added\n")
+        .stderr("");
+}
+
+#[test]
+fn run_analyze_json() {
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .arg("analyze")
+        .arg("--json")
+        .arg("levels/custom/02-one-way.txt")
+        .assert()
+        .success()
+        .stdout(
+            r#"{"name":"levels/custom/02-one-way.txt","rows":7,"cols":3,"playable_squares":5,"walls":16,"boxes":1,"goals":1,"dead_squares":1,"tunnels":5,"rooms":0,"articulation_squares":3,"lower_bound_pushes":3,"lower_bound_moves":3,"boxes_on_goals":0}
+"#,
+        )
+        .stderr("");
+}
+
+#[test]
+#[cfg(not(feature = "letslogic"))]
+fn run_letslogic_without_feature() {
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .arg("solve")
+        .arg("--letslogic")
+        .arg("levels/custom/01-simplest-custom.txt")
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr("--letslogic needs a build with the letslogic feature\n");
+}
+
+#[test]
+fn run_serve() {
+    let input = r######"{"id": 1, "level": "#####\n#@$.#\n#####", "method": "moves"}
+{"id": 2, "level": "#####\n#@$.#\n#####", "method": "fast"}
+"######;
+    let output = r#"{"type":"progress","id":1,"created":1,"visited":1}
+{"type":"progress","id":1,"created":2,"visited":2}
+{"type":"result","id":1,"solved":true,"solution":"R","moves":1,"pushes":1,"created":2,"visited":2,"timed_out":false}
+{"type":"error","id":2,"message":"Unknown method: fast"}
+"#;
+
+    // std Command has no way to pass stdin
+    assert_cmd::Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .arg("serve")
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout(output)
+        .stderr("");
+}
+
+#[test]
+fn run_serve_bad_timeout() {
+    let input = r######"{"id": 1, "level": "#####\n#@$.#\n#####", "timeout": -1}
+{"id": 2, "level": "#####\n#@$.#\n#####", "timeout": 1e300}
+{"id": 3, "level": "#####\n#@$.#\n#####", "method": "pushes"}
+"######;
+
+    let assert = assert_cmd::Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .arg("serve")
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stderr("");
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout).into_owned();
+    let lines: Vec<_> = stdout.lines().collect();
+    assert!(lines[0].starts_with(r#"{"type":"error","id":1,"message":"Invalid timeout: "#));
+    assert!(lines[1].starts_with(r#"{"type":"error","id":2,"message":"Invalid timeout: "#));
+    // the server keeps going after bad requests
+    assert!(lines
+        .last()
+        .unwrap()
+        .starts_with(r#"{"type":"result","id":3,"solved":true"#));
+}